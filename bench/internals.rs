@@ -7,14 +7,18 @@ use starknet_rs::{
     business_logic::{
         fact_state::in_memory_state_reader::InMemoryStateReader,
         state::{cached_state::CachedState, state_api::State},
-        transaction::objects::{
-            internal_declare::InternalDeclare, internal_deploy::InternalDeploy,
-            internal_deploy_account::InternalDeployAccount,
-            internal_invoke_function::InternalInvokeFunction,
+        transaction::{
+            account_transaction::AccountTransaction,
+            objects::{
+                internal_declare::InternalDeclare, internal_deploy::InternalDeploy,
+                internal_deploy_account::InternalDeployAccount,
+                internal_invoke_function::InternalInvokeFunction,
+            },
+            signer::StarkSigner,
         },
     },
     core::contract_address::starknet_contract_address::compute_class_hash,
-    definitions::general_config::StarknetChainId,
+    definitions::{constants::TRANSACTION_VERSION, general_config::StarknetChainId},
     public::abi::VALIDATE_ENTRY_POINT_SELECTOR,
     services::api::contract_class::ContractClass,
     utils::Address,
@@ -33,10 +37,7 @@ lazy_static! {
     static ref CONTRACT_ADDRESS: Address = Address(felt_str!(
         "3577223136242220508961486249701638158054969090851914040041358274796489907314"
     ));
-    static ref SIGNATURE: Vec<Felt252> = vec![
-        felt_str!("3233776396904427614006684968846859029149676045084089832563834729503047027074"),
-        felt_str!("707039245213420890976709143988743108543645298941971188668773816813012281203"),
-    ];
+    static ref SIGNER: StarkSigner = StarkSigner::new(felt_str!("1234567890"));
 }
 
 // This function just executes the given function. This adds a stack level
@@ -77,16 +78,15 @@ fn deploy_account() {
             "2669425616857739096022668060305620640217901643963991674344872184515580705509"
         ));
         let class_hash = *CLASS_HASH;
-        let signature = SIGNATURE.clone();
         scope(|| {
             // new consumes more execution time than raw struct instantiation
-            let internal_deploy_account = InternalDeployAccount::new(
+            let internal_deploy_account = InternalDeployAccount::new_signed(
                 class_hash,
                 0,
                 0,
                 Felt252::zero(),
                 vec![],
-                signature,
+                &SIGNER,
                 salt,
                 StarknetChainId::TestNet,
             )
@@ -182,16 +182,16 @@ fn invoke() {
         let mut state_copy = state.clone();
         let address = CONTRACT_ADDRESS.clone();
         let selector = VALIDATE_ENTRY_POINT_SELECTOR.clone();
-        let signature = SIGNATURE.clone();
         let calldata = vec![address.0.clone(), selector.clone(), Felt252::zero()];
         scope(|| {
             // new consumes more execution time than raw struct instantiation
-            let internal_invoke = InternalInvokeFunction::new(
+            let internal_invoke = InternalInvokeFunction::new_signed(
                 address,
                 selector,
                 0,
+                TRANSACTION_VERSION.into(),
                 calldata,
-                signature,
+                &SIGNER,
                 StarknetChainId::TestNet.to_felt(),
                 Some(Felt252::zero()),
             )
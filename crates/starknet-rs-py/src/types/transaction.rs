@@ -13,8 +13,8 @@ pub struct PyTransaction {
 #[pymethods]
 impl PyTransaction {
     #[getter]
-    fn contract_hash(&self) -> ClassHash {
-        self.inner.contract_hash()
+    fn contract_hash(&self) -> Option<ClassHash> {
+        self.inner.class_hash()
     }
 
     #[getter]
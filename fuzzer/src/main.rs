@@ -25,6 +25,7 @@ use starknet_rs::{
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
+    sync::Arc,
 };
 
 use std::fs;
@@ -118,7 +119,7 @@ fn main() {
             let address = Address(1111.into());
             let class_hash = [1; 32];
 
-            contract_class_cache.insert(class_hash, contract_class);
+            contract_class_cache.insert(class_hash, Arc::new(contract_class));
             let mut state_reader = InMemoryStateReader::default();
             state_reader
                 .address_to_class_hash_mut()
@@ -180,6 +181,10 @@ fn main() {
                 class_hash: Some(class_hash),
                 storage_read_values: vec![Felt252::from_bytes_be(data_to_ascii(data).as_bytes())],
                 accessed_storage_keys: expected_accessed_storage_keys,
+                storage_writes: HashMap::from([(
+                    expected_key,
+                    Felt252::from_bytes_be(data_to_ascii(data).as_bytes()),
+                )]),
                 ..Default::default()
             };
 
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use felt::{felt_str, Felt252};
 use num_traits::Zero;
@@ -23,9 +23,9 @@ static ALLOC: MiMalloc = MiMalloc;
 
 lazy_static! {
     // include_str! doesn't seem to work in CI
-    static ref CONTRACT_CLASS: ContractClass = ContractClass::try_from(PathBuf::from(
+    static ref CONTRACT_CLASS: Arc<ContractClass> = Arc::new(ContractClass::try_from(PathBuf::from(
         "starknet_programs/first_contract.json",
-    )).unwrap();
+    )).unwrap());
 
     static ref CONTRACT_PATH: PathBuf = PathBuf::from("starknet_programs/first_contract.json");
 
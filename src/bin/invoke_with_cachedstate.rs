@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use felt::{felt_str, Felt252};
 use num_traits::Zero;
@@ -7,9 +7,15 @@ use starknet_rs::{
     business_logic::{
         fact_state::in_memory_state_reader::InMemoryStateReader,
         state::{cached_state::CachedState, state_api_objects::BlockInfo},
-        transaction::objects::internal_invoke_function::InternalInvokeFunction,
+        transaction::{
+            account_transaction::AccountTransaction,
+            objects::internal_invoke_function::InternalInvokeFunction,
+        },
+    },
+    definitions::{
+        constants::TRANSACTION_VERSION,
+        general_config::{StarknetChainId, StarknetGeneralConfig, StarknetOsConfig},
     },
-    definitions::general_config::{StarknetChainId, StarknetGeneralConfig, StarknetOsConfig},
     services::api::contract_class::ContractClass,
     utils::Address,
 };
@@ -25,9 +31,9 @@ static ALLOC: MiMalloc = MiMalloc;
 
 lazy_static! {
     // include_str! doesn't seem to work in CI
-    static ref CONTRACT_CLASS: ContractClass = ContractClass::try_from(PathBuf::from(
+    static ref CONTRACT_CLASS: Arc<ContractClass> = Arc::new(ContractClass::try_from(PathBuf::from(
         "starknet_programs/first_contract.json",
-    )).unwrap();
+    )).unwrap());
 
     static ref CONTRACT_PATH: PathBuf = PathBuf::from("starknet_programs/first_contract.json");
 
@@ -56,6 +62,7 @@ fn main() {
             CONTRACT_ADDRESS.clone(),
             INCREASE_BALANCE_SELECTOR.clone(),
             2,
+            TRANSACTION_VERSION.into(),
             vec![1000.into()],
             vec![],
             StarknetChainId::TestNet.to_felt(),
@@ -69,6 +76,7 @@ fn main() {
             CONTRACT_ADDRESS.clone(),
             GET_BALANCE_SELECTOR.clone(),
             2,
+            TRANSACTION_VERSION.into(),
             vec![],
             vec![],
             StarknetChainId::TestNet.to_felt(),
@@ -118,7 +126,7 @@ pub fn new_starknet_general_config_for_testing() -> StarknetGeneralConfig {
         0,
         Default::default(),
         1_000_000,
-        0,
+        1_000_000,
         BlockInfo::default(),
     )
 }
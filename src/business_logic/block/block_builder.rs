@@ -0,0 +1,359 @@
+use super::{
+    error::BlockError,
+    objects::{Block, RejectedTransaction, TransactionReceipt},
+};
+use crate::{
+    business_logic::{
+        execution::profiler::Profiler,
+        fact_state::state::StateDiff,
+        state::{
+            cached_state::CachedState,
+            state_api::{State, StateReader},
+            state_api_objects::BlockInfo,
+        },
+        transaction::{
+            error::TransactionError, fee::calculate_tx_l1_gas_usage, transactions::Transaction,
+        },
+    },
+    definitions::general_config::StarknetGeneralConfig,
+};
+
+/// Executes transactions one after the other into a block.
+///
+/// A transaction that fails is rejected: it's left out of the block and its changes are
+/// discarded. Invoke transactions whose execution fails once validated are reverted instead, and
+/// included in the block.
+///
+/// The state diff of each transaction is computed from a checkpoint taken right before it, so
+/// checkpoints left open on the state don't leak earlier changes into it.
+#[derive(Debug)]
+pub struct BlockBuilder {
+    general_config: StarknetGeneralConfig,
+    /// Profiles the entry points run by the transactions of the block when set.
+    profiler: Option<Profiler>,
+    receipts: Vec<TransactionReceipt>,
+    rejected_transactions: Vec<RejectedTransaction>,
+    state_diff: StateDiff,
+}
+
+impl BlockBuilder {
+    /// Starts the block described by `block_info`, which must follow the last block of
+    /// `general_config`.
+    ///
+    /// The transactions are executed with `general_config` and `block_info`. Once the block is
+    /// built, it's up to the caller to set `block_info` as the last block of its config.
+    pub fn new(
+        general_config: &StarknetGeneralConfig,
+        block_info: BlockInfo,
+    ) -> Result<Self, BlockError> {
+        general_config
+            .block_info
+            .validate_legal_progress(&block_info)?;
+
+        let mut general_config = general_config.clone();
+        general_config.block_info = block_info;
+        Ok(BlockBuilder {
+            general_config,
+            profiler: None,
+            receipts: Vec::new(),
+            rejected_transactions: Vec::new(),
+            state_diff: StateDiff::default(),
+        })
+    }
+
+    /// Profiles the entry points run by the transactions added from now on into `profiler`.
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Executes `tx` on `state` and adds it to the block, or to the rejected transactions if it
+    /// fails.
+    pub fn add_transaction<T: StateReader + Clone + Default>(
+        &mut self,
+        state: &mut CachedState<T>,
+        tx: &Transaction,
+    ) -> Result<(), BlockError> {
+        let outcome = execute_transaction(state, &self.general_config, self.profiler(), tx)?;
+        self.add_outcome(tx, outcome)
+    }
+
+    /// Adds a transaction executed with [`execute_transaction`] to the block.
+    pub(super) fn add_outcome(
+        &mut self,
+        tx: &Transaction,
+        outcome: TransactionOutcome,
+    ) -> Result<(), BlockError> {
+        match outcome {
+            Ok((mut receipt, state_diff)) => {
+                receipt.transaction_index = self.receipts.len();
+                self.state_diff = self.state_diff.squash(state_diff)?;
+                self.receipts.push(receipt);
+            }
+            Err(error) => self.rejected_transactions.push(RejectedTransaction {
+                transaction_hash: tx.hash().clone(),
+                error,
+            }),
+        }
+        Ok(())
+    }
+
+    /// The config the transactions of the block are executed with.
+    pub(super) fn general_config(&self) -> &StarknetGeneralConfig {
+        &self.general_config
+    }
+
+    pub(super) fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn build(self) -> Block {
+        Block {
+            l1_gas_consumed: self
+                .receipts
+                .iter()
+                .map(|receipt| receipt.l1_gas_consumed)
+                .sum(),
+            total_fee: self.receipts.iter().map(|receipt| receipt.actual_fee).sum(),
+            block_info: self.general_config.block_info,
+            receipts: self.receipts,
+            rejected_transactions: self.rejected_transactions,
+            state_diff: self.state_diff,
+        }
+    }
+}
+
+/// Executes `transactions` in order on `state` into the block described by `block_info`,
+/// profiling them into `profiler` if given. See [`BlockBuilder`].
+pub fn build_block<T: StateReader + Clone + Default>(
+    state: &mut CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    block_info: BlockInfo,
+    transactions: &[Transaction],
+    profiler: Option<&Profiler>,
+) -> Result<Block, BlockError> {
+    let mut block_builder = BlockBuilder::new(general_config, block_info)?;
+    if let Some(profiler) = profiler {
+        block_builder.set_profiler(profiler.clone());
+    }
+    for tx in transactions {
+        block_builder.add_transaction(state, tx)?;
+    }
+    Ok(block_builder.build())
+}
+
+/// What executing a transaction gave: its receipt and state diff, or why it was rejected.
+pub(super) type TransactionOutcome = Result<(TransactionReceipt, StateDiff), TransactionError>;
+
+/// Executes `tx` on `state`, keeping its changes only if it isn't rejected. The index of the
+/// receipt is set once it's added to a block.
+pub(super) fn execute_transaction<T: StateReader + Clone + Default>(
+    state: &mut CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    profiler: Option<&Profiler>,
+    tx: &Transaction,
+) -> Result<TransactionOutcome, BlockError> {
+    let checkpoint = state.checkpoint();
+    let outcome = execute_and_diff(state, general_config, profiler, tx);
+    if outcome.is_ok() {
+        state.commit(checkpoint)?;
+    } else {
+        state.revert_to(checkpoint)?;
+    }
+    Ok(outcome)
+}
+
+fn execute_and_diff<T: StateReader + Clone + Default>(
+    state: &mut CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    profiler: Option<&Profiler>,
+    tx: &Transaction,
+) -> TransactionOutcome {
+    let execution_info = tx.execute_with_profiler(state, general_config, profiler)?;
+
+    let receipt = TransactionReceipt {
+        transaction_hash: tx.hash().clone(),
+        transaction_index: 0,
+        tx_type: tx.tx_type(),
+        actual_fee: execution_info.actual_fee,
+        l1_gas_consumed: calculate_tx_l1_gas_usage(
+            &execution_info.actual_resources,
+            general_config,
+        )?,
+        events: execution_info.get_sorted_events()?,
+        l2_to_l1_messages: execution_info.get_sorted_l2_to_l1_messages()?,
+        execution_info,
+    };
+    // The checkpoint taken before the transaction is still open, so this is its diff alone.
+    Ok((receipt, state.actual_state_diff()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            block::test_utils::{deploy, fibonacci_class, fibonacci_state, next_block_info},
+            fact_state::in_memory_state_reader::InMemoryStateReader,
+            transaction::objects::internal_declare_v2::InternalDeclareV2,
+        },
+        core::contract_address::starknet_contract_address::{
+            compute_class_hash, compute_compiled_class_hash,
+        },
+        definitions::{
+            constants::DECLARE_VERSION, general_config::StarknetChainId,
+            transaction_type::TransactionType,
+        },
+        services::api::{
+            contract_class::ContractClass,
+            sierra_contract_class::{CasmContractClass, SierraContractClass},
+        },
+        starkware_utils::starkware_errors::StarkwareError,
+        utils::Address,
+    };
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+    use felt::Felt252;
+    use num_traits::Zero;
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    #[test]
+    fn build_block_rejects_failed_transactions() {
+        let (contract_class, class_hash) = fibonacci_class();
+        let mut state = fibonacci_state();
+
+        // The second deploy is rejected, the contract is already deployed at its address.
+        let transactions = [
+            deploy(&contract_class, 1),
+            deploy(&contract_class, 1),
+            deploy(&contract_class, 2),
+        ];
+        let block = build_block(
+            &mut state,
+            &StarknetGeneralConfig::default(),
+            next_block_info(),
+            &transactions,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(block.block_info, next_block_info());
+        assert_eq!(block.receipts.len(), 2);
+        assert_eq!(block.receipts[0].transaction_hash, *transactions[0].hash());
+        assert_eq!(block.receipts[1].transaction_hash, *transactions[2].hash());
+        assert_eq!(block.receipts[1].transaction_index, 1);
+        assert_eq!(block.receipts[1].tx_type, TransactionType::Deploy);
+        assert!(!block.receipts[1].is_reverted());
+        assert_eq!(block.events().count(), 0);
+
+        assert_eq!(block.rejected_transactions.len(), 1);
+        assert_eq!(
+            block.rejected_transactions[0].transaction_hash,
+            *transactions[1].hash()
+        );
+
+        let deployed_contracts: HashMap<_, _> = [&transactions[0], &transactions[2]]
+            .into_iter()
+            .map(|tx| (tx.contract_address(), class_hash))
+            .collect();
+        assert_eq!(
+            block.state_diff.address_to_class_hash(),
+            &deployed_contracts
+        );
+
+        let next_block_info = block.next_block_info(5);
+        assert_eq!(next_block_info.block_number, 2);
+        assert_eq!(next_block_info.block_timestamp, 5);
+    }
+
+    #[test]
+    fn build_block_with_a_declare_v2() {
+        let account_class = ContractClass::try_from(PathBuf::from(
+            "starknet_programs/account_without_validation.json",
+        ))
+        .unwrap();
+        let account_class_hash = compute_class_hash(&account_class).unwrap().to_be_bytes();
+        let account = Address(1.into());
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(account.clone(), account_class_hash);
+        state_reader
+            .address_to_nonce_mut()
+            .insert(account.clone(), Felt252::zero());
+        let mut state = CachedState::new(
+            state_reader,
+            Some(HashMap::from([(
+                account_class_hash,
+                Arc::new(account_class),
+            )])),
+        );
+
+        let casm_contract_class = CasmContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.casm",
+        ))
+        .unwrap();
+        let compiled_class_hash = compute_compiled_class_hash(&casm_contract_class).unwrap();
+        let declare = InternalDeclareV2::new(
+            SierraContractClass::try_from(&PathBuf::from(
+                "starknet_programs/cairo1/minimal_contract.sierra",
+            ))
+            .unwrap(),
+            casm_contract_class,
+            compiled_class_hash.clone(),
+            StarknetChainId::TestNet.to_felt(),
+            account.clone(),
+            0,
+            DECLARE_VERSION,
+            Vec::new(),
+            Felt252::zero(),
+        )
+        .unwrap();
+        let class_hash = declare.class_hash;
+
+        let block = build_block(
+            &mut state,
+            &StarknetGeneralConfig::default(),
+            next_block_info(),
+            &[Transaction::DeclareV2(declare)],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(block.receipts.len(), 1);
+        assert_eq!(block.receipts[0].tx_type, TransactionType::Declare);
+        assert_eq!(
+            block.state_diff.class_hash_to_compiled_class_hash(),
+            &HashMap::from([(class_hash, compiled_class_hash.to_be_bytes())])
+        );
+        assert_eq!(
+            block.state_diff.address_to_nonce(),
+            &HashMap::from([(account, Felt252::new(1))])
+        );
+    }
+
+    #[test]
+    fn block_must_follow_the_last_block() {
+        let general_config = StarknetGeneralConfig::default();
+
+        assert_matches!(
+            BlockBuilder::new(
+                &general_config,
+                BlockInfo {
+                    block_number: 2,
+                    ..next_block_info()
+                }
+            ),
+            Err(BlockError::Starkware(StarkwareError::InvalidBlockNumber))
+        );
+        assert_matches!(
+            BlockBuilder::new(
+                &general_config,
+                BlockInfo {
+                    block_timestamp: 0,
+                    ..next_block_info()
+                }
+            ),
+            Err(BlockError::Starkware(StarkwareError::InvalidBlockTimestamp))
+        );
+    }
+}
@@ -0,0 +1,16 @@
+use crate::{
+    core::errors::{state_errors::StateError, syscall_handler_errors::SyscallHandlerError},
+    starkware_utils::starkware_errors::StarkwareError,
+};
+use thiserror::Error;
+
+/// Errors building a block. Failing transactions aren't errors, they're rejected.
+#[derive(Debug, Error)]
+pub enum BlockError {
+    #[error(transparent)]
+    Starkware(#[from] StarkwareError),
+    #[error(transparent)]
+    State(#[from] StateError),
+    #[error(transparent)]
+    Syscall(#[from] SyscallHandlerError),
+}
@@ -0,0 +1,6 @@
+pub mod block_builder;
+pub mod error;
+pub mod objects;
+pub mod parallel_executor;
+#[cfg(test)]
+mod test_utils;
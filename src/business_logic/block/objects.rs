@@ -0,0 +1,82 @@
+use crate::{
+    business_logic::{
+        execution::objects::{Event, L2toL1MessageInfo, TransactionExecutionInfo},
+        fact_state::state::StateDiff,
+        state::state_api_objects::BlockInfo,
+        transaction::error::TransactionError,
+    },
+    definitions::transaction_type::TransactionType,
+};
+use felt::Felt252;
+
+/// A transaction included in a block, with what its execution did.
+#[derive(Debug)]
+pub struct TransactionReceipt {
+    pub transaction_hash: Felt252,
+    /// The position of the transaction in the block.
+    pub transaction_index: usize,
+    pub tx_type: TransactionType,
+    pub actual_fee: u64,
+    /// The L1 gas the transaction was charged for.
+    pub l1_gas_consumed: u64,
+    /// The events emitted by the transaction, in the order they were emitted.
+    pub events: Vec<Event>,
+    /// The messages sent to L1 by the transaction, in the order they were sent.
+    pub l2_to_l1_messages: Vec<L2toL1MessageInfo>,
+    pub execution_info: TransactionExecutionInfo,
+}
+
+impl TransactionReceipt {
+    /// Whether the execution of the transaction was reverted. A reverted transaction is still
+    /// included in the block, it bumps the nonce of its account and pays its fee.
+    pub fn is_reverted(&self) -> bool {
+        self.execution_info.is_reverted()
+    }
+}
+
+/// A transaction left out of a block because it failed. It made no changes to the state.
+#[derive(Debug)]
+pub struct RejectedTransaction {
+    pub transaction_hash: Felt252,
+    pub error: TransactionError,
+}
+
+/// The outcome of executing a batch of transactions as a block.
+#[derive(Debug)]
+pub struct Block {
+    /// The info the transactions of the block were executed with.
+    pub block_info: BlockInfo,
+    /// The receipts of the transactions included in the block, in execution order.
+    pub receipts: Vec<TransactionReceipt>,
+    pub rejected_transactions: Vec<RejectedTransaction>,
+    /// The changes the block made to the state.
+    pub state_diff: StateDiff,
+    /// The L1 gas consumed by the transactions of the block.
+    pub l1_gas_consumed: u64,
+    /// The fees paid by the transactions of the block.
+    pub total_fee: u64,
+}
+
+impl Block {
+    /// The events emitted in the block, in order.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.receipts.iter().flat_map(|receipt| &receipt.events)
+    }
+
+    /// The messages sent to L1 in the block, in order.
+    pub fn l2_to_l1_messages(&self) -> impl Iterator<Item = &L2toL1MessageInfo> {
+        self.receipts
+            .iter()
+            .flat_map(|receipt| &receipt.l2_to_l1_messages)
+    }
+
+    /// The info of the block following this one, started at `block_timestamp`. The gas price,
+    /// sequencer address and StarkNet version stay the same.
+    pub fn next_block_info(&self, block_timestamp: u64) -> BlockInfo {
+        BlockInfo {
+            block_number: self.block_info.block_number + 1,
+            block_timestamp,
+            ..self.block_info.clone()
+        }
+    }
+}
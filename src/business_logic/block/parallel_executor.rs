@@ -0,0 +1,753 @@
+use super::{
+    block_builder::{execute_transaction, BlockBuilder, TransactionOutcome},
+    error::BlockError,
+    objects::Block,
+};
+use crate::{
+    business_logic::{
+        block::objects::TransactionReceipt,
+        execution::{
+            objects::TransactionExecutionInfo,
+            profiler::{ExecutionProfile, Profiler},
+        },
+        fact_state::state::StateDiff,
+        state::{
+            cached_state::{CacheEntries, CachedState},
+            state_accesses::StateAccesses,
+            state_api::{State, StateReader},
+            state_api_objects::BlockInfo,
+            state_cache::StorageEntry,
+        },
+        transaction::transactions::Transaction,
+    },
+    core::errors::state_errors::StateError,
+    definitions::general_config::StarknetGeneralConfig,
+    utils::{get_erc20_balance_var_addresses, Address},
+};
+use felt::Felt252;
+use num_traits::One;
+use std::{panic, thread};
+
+/// A transaction executed on its own against the state before the block.
+struct SpeculativeExecution {
+    outcome: TransactionOutcome,
+    accesses: StateAccesses,
+    /// The cache entries of the accessed keys once the transaction was executed.
+    entries: CacheEntries,
+    /// Whether the fee transfer was the only access to the balance of the sequencer. If so, the
+    /// balance is left out of `accesses` and the fee is added to it when the transaction is
+    /// committed.
+    defers_fee: bool,
+    /// The profile of the execution, if the block is profiled. It's added to the profile of the
+    /// block if the execution is committed, executions done again are profiled then.
+    profile: Option<ExecutionProfile>,
+}
+
+/// The fee token balance of the sequencer, that every fee transfer reads and updates.
+///
+/// Adding fees to it commutes, so transactions that only access it through their fee transfer
+/// don't conflict with each other: their fee is added to the balance when they're committed.
+struct SequencerBalance {
+    sequencer_address: Address,
+    low: StorageEntry,
+    high: StorageEntry,
+}
+
+impl SequencerBalance {
+    fn new(general_config: &StarknetGeneralConfig) -> Result<Self, BlockError> {
+        let fee_token_address = &general_config.starknet_os_config.fee_token_address;
+        let sequencer_address = general_config.block_info.sequencer_address.clone();
+        let (low_key, high_key) = get_erc20_balance_var_addresses(&sequencer_address)?;
+        Ok(SequencerBalance {
+            sequencer_address,
+            low: (fee_token_address.clone(), low_key),
+            high: (fee_token_address.clone(), high_key),
+        })
+    }
+
+    fn keys(&self) -> [&StorageEntry; 2] {
+        [&self.low, &self.high]
+    }
+
+    /// The low and high halves of the balance in `state`.
+    fn read<T: StateReader + Clone>(
+        &self,
+        state: &mut CachedState<T>,
+    ) -> Result<(Felt252, Felt252), StateError> {
+        Ok((
+            state.get_storage_at(&self.low)?,
+            state.get_storage_at(&self.high)?,
+        ))
+    }
+
+    /// Whether the balance was only accessed by the fee transfer of `tx`, which found it at
+    /// `balance` and added the fee to its low half alone. The accesses of reverted executions are
+    /// unknown, so their transactions never defer their fee.
+    ///
+    /// The fee token is expected to read the balance of the sender, then the one of the
+    /// recipient. A fee transfer that reads them otherwise, or that carries into the high half,
+    /// can't be replayed on another balance, so its transaction doesn't defer its fee.
+    fn only_accessed_by_fee_transfer(
+        &self,
+        tx: &Transaction,
+        execution_info: &TransactionExecutionInfo,
+        balance: &(Felt252, Felt252),
+    ) -> bool {
+        let fee_transfer_info = match &execution_info.fee_transfer_info {
+            Some(fee_transfer_info) => fee_transfer_info,
+            None => return false,
+        };
+        let (low, high) = balance;
+        fee_transfer_info.storage_read_values.get(2..4) == Some(&[low.clone(), high.clone()][..])
+            && fee_transfer_info
+                .storage_writes
+                .get(&self.high.1)
+                .map_or(true, |new_high| new_high == high)
+            && !execution_info.is_reverted()
+            && tx.sender_address() != Some(&self.sequencer_address)
+            && [&execution_info.validate_info, &execution_info.call_info]
+                .into_iter()
+                .flatten()
+                .all(|call_info| {
+                    let visited = call_info.clone().get_visited_storage_entries();
+                    self.keys().into_iter().all(|key| !visited.contains(key))
+                })
+    }
+
+    /// Whether the fee deferred by `execution`, if any, can be added to the balance in `state`.
+    /// It can't if it carries into the high half of the balance: the execution didn't write it,
+    /// so its resources and fee didn't count it, and the transaction has to be executed again.
+    fn can_add_fee<T: StateReader + Clone>(
+        &self,
+        state: &mut CachedState<T>,
+        execution: &SpeculativeExecution,
+    ) -> Result<bool, StateError> {
+        match (&execution.outcome, execution.defers_fee) {
+            (Ok((receipt, _)), true) => {
+                let low = state.get_storage_at(&self.low)?;
+                Ok(low + Felt252::from(receipt.actual_fee) < half_bound())
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// Adds the fee of a transaction that deferred it to the balance in `state`, and updates
+    /// the receipt and state diff of the transaction to what executing it on `state` gives. The
+    /// fee must not carry into the high half of the balance, see [`Self::can_add_fee`].
+    fn add_fee<T: StateReader + Clone + Default>(
+        &self,
+        state: &mut CachedState<T>,
+        receipt: &mut TransactionReceipt,
+        state_diff: &mut StateDiff,
+    ) -> Result<(), StateError> {
+        let (low, high) = self.read(state)?;
+        let new_low = low.clone() + Felt252::from(receipt.actual_fee);
+        let new_high = high.clone();
+
+        if let Some(fee_transfer_info) = receipt.execution_info.fee_transfer_info.as_mut() {
+            fee_transfer_info.storage_read_values[2..4]
+                .clone_from_slice(&[low.clone(), high.clone()]);
+            fee_transfer_info
+                .storage_writes
+                .insert(self.low.1, new_low.clone());
+            fee_transfer_info
+                .storage_writes
+                .insert(self.high.1, new_high.clone());
+        }
+
+        for (key, value, new_value) in [(&self.low, low, new_low), (&self.high, high, new_high)] {
+            state.set_storage_at(key, new_value.clone());
+
+            let (address, storage_key) = key;
+            let storage_updates = state_diff
+                .storage_updates
+                .entry(address.clone())
+                .or_default();
+            if new_value != value {
+                storage_updates.insert(*storage_key, new_value);
+            } else {
+                storage_updates.remove(storage_key);
+            }
+        }
+        state_diff
+            .storage_updates
+            .retain(|_, storage_updates| !storage_updates.is_empty());
+        Ok(())
+    }
+}
+
+/// The bound of each half of a Uint256.
+fn half_bound() -> Felt252 {
+    Felt252::from(u128::MAX) + Felt252::one()
+}
+
+/// Executes `transactions` into the block described by `block_info`, like
+/// [`build_block`](super::block_builder::build_block), but on `n_threads` threads.
+///
+/// Every transaction is first executed against `state` as it was before the block, recording the
+/// keys it reads and writes. The transactions are then added to the block in order: a transaction
+/// that accessed a key written by an earlier transaction of the block, or wrote a key an earlier
+/// one accessed, is executed again on the updated state. The others are added as they were
+/// executed. The fee transfers to the sequencer don't make transactions conflict, each fee is
+/// added to the balance of the sequencer as its transaction is added. The block and `state` end
+/// up the same as if the transactions were executed one after the other, and so does the profile
+/// of `profiler`, if given.
+pub fn build_block_parallel<T: StateReader + Clone + Default + Send + Sync>(
+    state: &mut CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    block_info: BlockInfo,
+    transactions: &[Transaction],
+    profiler: Option<&Profiler>,
+    n_threads: usize,
+) -> Result<Block, BlockError> {
+    commit_executions(
+        state,
+        general_config,
+        block_info,
+        transactions,
+        profiler,
+        n_threads,
+    )
+    .map(|(block, _)| block)
+}
+
+/// Builds the block like [`build_block_parallel`], also returning how many transactions had to
+/// be executed again.
+fn commit_executions<T: StateReader + Clone + Default + Send + Sync>(
+    state: &mut CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    block_info: BlockInfo,
+    transactions: &[Transaction],
+    profiler: Option<&Profiler>,
+    n_threads: usize,
+) -> Result<(Block, usize), BlockError> {
+    let mut block_builder = BlockBuilder::new(general_config, block_info)?;
+    if let Some(profiler) = profiler {
+        block_builder.set_profiler(profiler.clone());
+    }
+    let sequencer_balance = SequencerBalance::new(block_builder.general_config())?;
+    let executions = execute_speculatively(
+        state,
+        block_builder.general_config(),
+        &sequencer_balance,
+        transactions,
+        profiler.is_some(),
+        n_threads,
+    );
+
+    let mut block_accesses = StateAccesses::default();
+    let mut n_executed_again = 0;
+    for (tx, execution) in transactions.iter().zip(executions) {
+        let (outcome, accesses) = match execution {
+            Ok(execution)
+                if !execution.accesses.conflicts_with(&block_accesses)
+                    && sequencer_balance.can_add_fee(state, &execution)? =>
+            {
+                state.set_cache_entries(&execution.accesses, &execution.entries);
+                if let (Some(profiler), Some(profile)) = (profiler, &execution.profile) {
+                    profiler.merge(profile);
+                }
+                let mut outcome = execution.outcome;
+                let mut accesses = execution.accesses;
+                if let (true, Ok((receipt, state_diff))) = (execution.defers_fee, &mut outcome) {
+                    sequencer_balance.add_fee(state, receipt, state_diff)?;
+                    // Later transactions that access the balance other than through their fee
+                    // transfer conflict with this one.
+                    accesses
+                        .writes
+                        .storage
+                        .extend(sequencer_balance.keys().into_iter().cloned());
+                }
+                (outcome, accesses)
+            }
+            // Failing to execute the transaction speculatively isn't an error unless it also
+            // fails on the updated state.
+            _ => {
+                n_executed_again += 1;
+                state.track_accesses();
+                let outcome = execute_transaction(
+                    state,
+                    block_builder.general_config(),
+                    block_builder.profiler(),
+                    tx,
+                );
+                let accesses = state.take_accesses();
+                (outcome?, accesses)
+            }
+        };
+        // The changes of rejected transactions are undone, only what they read matters.
+        let accesses = match &outcome {
+            Ok(_) => accesses,
+            Err(_) => accesses.into_reads(),
+        };
+        block_builder.add_outcome(tx, outcome)?;
+        block_accesses.extend(accesses);
+    }
+    Ok((block_builder.build(), n_executed_again))
+}
+
+/// Executes each of `transactions` against `pre_state`, returning the executions in order. Each
+/// execution is profiled on its own if `profile` is set.
+fn execute_speculatively<T: StateReader + Clone + Default + Send + Sync>(
+    pre_state: &CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    sequencer_balance: &SequencerBalance,
+    transactions: &[Transaction],
+    profile: bool,
+    n_threads: usize,
+) -> Vec<Result<SpeculativeExecution, BlockError>> {
+    let n_threads = n_threads.max(1);
+    let mut executions: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..n_threads)
+            .map(|thread_index| {
+                scope.spawn(move || {
+                    // Each thread executes its transactions on its own copy of the state, undoing
+                    // the changes of one transaction before executing the next.
+                    let mut tx_state = pre_state.clone();
+                    transactions
+                        .iter()
+                        .enumerate()
+                        .skip(thread_index)
+                        .step_by(n_threads)
+                        .map(|(index, tx)| {
+                            let execution = execute_speculatively_one(
+                                pre_state,
+                                &mut tx_state,
+                                general_config,
+                                sequencer_balance,
+                                profile,
+                                tx,
+                            );
+                            (index, execution)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|err| panic::resume_unwind(err))
+            })
+            .collect()
+    });
+    executions.sort_by_key(|(index, _)| *index);
+    executions
+        .into_iter()
+        .map(|(_, execution)| execution)
+        .collect()
+}
+
+fn execute_speculatively_one<T: StateReader + Clone + Default>(
+    pre_state: &CachedState<T>,
+    tx_state: &mut CachedState<T>,
+    general_config: &StarknetGeneralConfig,
+    sequencer_balance: &SequencerBalance,
+    profile: bool,
+    tx: &Transaction,
+) -> Result<SpeculativeExecution, BlockError> {
+    // The execution is profiled on its own, it only counts in the profile of the block once
+    // committed.
+    let profiler = profile.then(Profiler::new);
+
+    tx_state.track_accesses();
+    let outcome = execute_transaction(tx_state, general_config, profiler.as_ref(), tx);
+    let mut accesses = tx_state.take_accesses();
+    let entries = tx_state.cache_entries(&accesses);
+    tx_state.set_cache_entries(&accesses, &pre_state.cache_entries(&accesses));
+    let outcome = outcome?;
+    if outcome.is_err() {
+        // The changes of a rejected transaction are undone, it only conflicts through its reads.
+        accesses = accesses.into_reads();
+    }
+
+    let defers_fee = match &outcome {
+        Ok((receipt, _)) => {
+            let balance = sequencer_balance.read(tx_state)?;
+            sequencer_balance.only_accessed_by_fee_transfer(tx, &receipt.execution_info, &balance)
+        }
+        Err(_) => false,
+    };
+    if defers_fee {
+        for key in sequencer_balance.keys() {
+            accesses.reads.storage.remove(key);
+            accesses.writes.storage.remove(key);
+        }
+    }
+
+    Ok(SpeculativeExecution {
+        outcome,
+        accesses,
+        entries,
+        defers_fee,
+        profile: profiler.map(|profiler| profiler.profile()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            block::{
+                block_builder::build_block,
+                test_utils::{deploy, fibonacci_class, fibonacci_state, next_block_info},
+            },
+            fact_state::in_memory_state_reader::InMemoryStateReader,
+            transaction::objects::internal_invoke_function::InternalInvokeFunction,
+        },
+        core::contract_address::starknet_contract_address::compute_class_hash,
+        definitions::{
+            constants::{EXECUTE_ENTRY_POINT_SELECTOR, TRANSACTION_VERSION},
+            general_config::StarknetChainId,
+        },
+        services::api::contract_class::ContractClass,
+        utils::calculate_sn_keccak,
+    };
+    use coverage_helper::test;
+    use num_traits::Zero;
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    const FIBONACCI_ADDRESS: u64 = 100;
+    const SEQUENCER_ADDRESS: u64 = 1000;
+
+    /// A state with the fee token, the fibonacci contract and `n_accounts` funded accounts at
+    /// addresses 1 to `n_accounts`, and a config that charges fees.
+    fn fee_paying_state(
+        n_accounts: u64,
+    ) -> (CachedState<InMemoryStateReader>, StarknetGeneralConfig) {
+        let mut general_config = StarknetGeneralConfig::default();
+        general_config.starknet_os_config.gas_price = 1;
+        let fee_token_address = general_config.starknet_os_config.fee_token_address.clone();
+
+        let account_class = ContractClass::try_from(PathBuf::from(
+            "starknet_programs/account_without_validation.json",
+        ))
+        .unwrap();
+        let fee_token_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/ERC20.json")).unwrap();
+        let (fibonacci_class, _) = fibonacci_class();
+
+        let mut state_reader = InMemoryStateReader::default();
+        let mut deploy_contract = |address: Address, contract_class: ContractClass| {
+            let class_hash = compute_class_hash(&contract_class).unwrap().to_be_bytes();
+            state_reader
+                .address_to_class_hash_mut()
+                .insert(address.clone(), class_hash);
+            state_reader
+                .address_to_nonce_mut()
+                .insert(address, Felt252::zero());
+            state_reader
+                .class_hash_to_contract_class_mut()
+                .insert(class_hash, Arc::new(contract_class));
+        };
+        deploy_contract(fee_token_address.clone(), fee_token_class);
+        deploy_contract(Address(FIBONACCI_ADDRESS.into()), fibonacci_class);
+        for account in 1..=n_accounts {
+            deploy_contract(Address(account.into()), account_class.clone());
+        }
+        for account in 1..=n_accounts {
+            let (balance_key, _) =
+                get_erc20_balance_var_addresses(&Address(account.into())).unwrap();
+            state_reader.address_to_storage_mut().insert(
+                (fee_token_address.clone(), balance_key),
+                Felt252::new(10_u64.pow(12)),
+            );
+        }
+
+        (
+            CachedState::new(state_reader, Some(HashMap::new())),
+            general_config,
+        )
+    }
+
+    /// Calls the fibonacci contract from the account at `account`, paying the fee.
+    fn invoke_fibonacci(account: u64) -> Transaction {
+        invoke_fibonacci_with_max_fee(account, 1_000_000_000)
+    }
+
+    fn invoke_fibonacci_with_max_fee(account: u64, max_fee: u64) -> Transaction {
+        Transaction::InvokeFunction(
+            InternalInvokeFunction::new(
+                Address(account.into()),
+                EXECUTE_ENTRY_POINT_SELECTOR.clone(),
+                max_fee,
+                TRANSACTION_VERSION.into(),
+                vec![
+                    FIBONACCI_ADDRESS.into(),
+                    Felt252::from_bytes_be(&calculate_sn_keccak(b"fib")),
+                    3.into(),
+                    1.into(),
+                    1.into(),
+                    10.into(),
+                ],
+                Vec::new(),
+                StarknetChainId::TestNet.to_felt(),
+                Some(Felt252::zero()),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn assert_same_blocks(
+        (parallel_block, parallel_state): (&Block, &CachedState<InMemoryStateReader>),
+        (sequential_block, sequential_state): (&Block, &CachedState<InMemoryStateReader>),
+    ) {
+        assert_eq!(
+            parallel_block.receipts.len(),
+            sequential_block.receipts.len()
+        );
+        for (parallel, sequential) in parallel_block
+            .receipts
+            .iter()
+            .zip(&sequential_block.receipts)
+        {
+            assert_eq!(parallel.transaction_hash, sequential.transaction_hash);
+            assert_eq!(parallel.transaction_index, sequential.transaction_index);
+            assert_eq!(parallel.execution_info, sequential.execution_info);
+        }
+        assert_eq!(parallel_block.state_diff, sequential_block.state_diff);
+        assert_eq!(parallel_state.cache(), sequential_state.cache());
+        assert_eq!(
+            parallel_state.contract_classes(),
+            sequential_state.contract_classes()
+        );
+    }
+
+    #[test]
+    fn build_block_parallel_matches_sequential_execution() {
+        let (contract_class, _) = fibonacci_class();
+        let state = fibonacci_state();
+
+        // The second deploy conflicts with the first one and is executed again, to be rejected.
+        let transactions = [
+            deploy(&contract_class, 1),
+            deploy(&contract_class, 1),
+            deploy(&contract_class, 2),
+        ];
+
+        let mut sequential_state = state.clone();
+        let sequential_block = build_block(
+            &mut sequential_state,
+            &StarknetGeneralConfig::default(),
+            next_block_info(),
+            &transactions,
+            None,
+        )
+        .unwrap();
+
+        for n_threads in [0, 1, 2, 4] {
+            let mut parallel_state = state.clone();
+            let (parallel_block, n_executed_again) = commit_executions(
+                &mut parallel_state,
+                &StarknetGeneralConfig::default(),
+                next_block_info(),
+                &transactions,
+                None,
+                n_threads,
+            )
+            .unwrap();
+
+            assert_eq!(n_executed_again, 1);
+            assert_eq!(
+                parallel_block.rejected_transactions[0].transaction_hash,
+                *transactions[1].hash()
+            );
+            assert_same_blocks(
+                (&parallel_block, &parallel_state),
+                (&sequential_block, &sequential_state),
+            );
+        }
+    }
+
+    #[test]
+    fn fee_transfers_dont_conflict() {
+        let (state, general_config) = fee_paying_state(4);
+        let block_info = BlockInfo {
+            sequencer_address: Address(SEQUENCER_ADDRESS.into()),
+            ..next_block_info()
+        };
+        let transactions: Vec<_> = (1..=4).map(invoke_fibonacci).collect();
+
+        let mut sequential_state = state.clone();
+        let sequential_block = build_block(
+            &mut sequential_state,
+            &general_config,
+            block_info.clone(),
+            &transactions,
+            None,
+        )
+        .unwrap();
+        assert!(sequential_block.rejected_transactions.is_empty());
+        assert!(sequential_block
+            .receipts
+            .iter()
+            .all(|receipt| receipt.actual_fee > 0));
+
+        let mut parallel_state = state.clone();
+        let (parallel_block, n_executed_again) = commit_executions(
+            &mut parallel_state,
+            &general_config,
+            block_info,
+            &transactions,
+            None,
+            4,
+        )
+        .unwrap();
+
+        // Every transaction pays the sequencer, yet none of them had to be executed again.
+        assert_eq!(n_executed_again, 0);
+        assert_same_blocks(
+            (&parallel_block, &parallel_state),
+            (&sequential_block, &sequential_state),
+        );
+    }
+
+    #[test]
+    fn fee_carrying_into_the_high_half_is_executed_again() {
+        let (mut state, general_config) = fee_paying_state(2);
+        let block_info = BlockInfo {
+            sequencer_address: Address(SEQUENCER_ADDRESS.into()),
+            ..next_block_info()
+        };
+        let transactions = [invoke_fibonacci(1), invoke_fibonacci(2)];
+        let fee = build_block(
+            &mut state.clone(),
+            &general_config,
+            block_info.clone(),
+            &transactions[..1],
+            None,
+        )
+        .unwrap()
+        .receipts[0]
+            .actual_fee;
+
+        // Each fee alone fits in the low half of the balance of the sequencer, but the second
+        // one carries into the high half once the first one is added.
+        let (balance_key, _) =
+            get_erc20_balance_var_addresses(&Address(SEQUENCER_ADDRESS.into())).unwrap();
+        state.state_reader.address_to_storage_mut().insert(
+            (
+                general_config.starknet_os_config.fee_token_address.clone(),
+                balance_key,
+            ),
+            half_bound() - Felt252::from(fee) - Felt252::one(),
+        );
+
+        let mut sequential_state = state.clone();
+        let sequential_block = build_block(
+            &mut sequential_state,
+            &general_config,
+            block_info.clone(),
+            &transactions,
+            None,
+        )
+        .unwrap();
+        // The carry writes the high half, which the second transaction pays for.
+        assert!(sequential_block.receipts[1].actual_fee > fee);
+
+        let mut parallel_state = state.clone();
+        let (parallel_block, n_executed_again) = commit_executions(
+            &mut parallel_state,
+            &general_config,
+            block_info,
+            &transactions,
+            None,
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(n_executed_again, 1);
+        assert_same_blocks(
+            (&parallel_block, &parallel_state),
+            (&sequential_block, &sequential_state),
+        );
+    }
+
+    #[test]
+    fn rejected_transactions_only_conflict_through_their_reads() {
+        let (state, general_config) = fee_paying_state(1);
+        // The max fee is too low, so the transactions are rejected after bumping the nonce of the
+        // account and running the call. The second one reads the nonce the first one wrote.
+        let transactions = [
+            invoke_fibonacci_with_max_fee(1, 1),
+            invoke_fibonacci_with_max_fee(1, 1),
+        ];
+
+        let mut sequential_state = state.clone();
+        let sequential_block = build_block(
+            &mut sequential_state,
+            &general_config,
+            next_block_info(),
+            &transactions,
+            None,
+        )
+        .unwrap();
+        assert_eq!(sequential_block.rejected_transactions.len(), 2);
+
+        let mut parallel_state = state.clone();
+        let (parallel_block, n_executed_again) = commit_executions(
+            &mut parallel_state,
+            &general_config,
+            next_block_info(),
+            &transactions,
+            None,
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(n_executed_again, 0);
+        assert_eq!(parallel_block.rejected_transactions.len(), 2);
+        assert_same_blocks(
+            (&parallel_block, &parallel_state),
+            (&sequential_block, &sequential_state),
+        );
+    }
+
+    #[test]
+    fn only_committed_executions_are_profiled() {
+        let (state, general_config) = fee_paying_state(2);
+        // The sequencer pays its own fee, so its transaction accesses its balance and conflicts
+        // with the fee transfer of the first one.
+        let block_info = BlockInfo {
+            sequencer_address: Address(1.into()),
+            ..next_block_info()
+        };
+        let transactions = [invoke_fibonacci(2), invoke_fibonacci(1)];
+
+        let sequential_profiler = Profiler::new();
+        build_block(
+            &mut state.clone(),
+            &general_config,
+            block_info.clone(),
+            &transactions,
+            Some(&sequential_profiler),
+        )
+        .unwrap();
+
+        let parallel_profiler = Profiler::new();
+        let (parallel_block, n_executed_again) = commit_executions(
+            &mut state.clone(),
+            &general_config,
+            block_info,
+            &transactions,
+            Some(&parallel_profiler),
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(n_executed_again, 1);
+        assert_eq!(parallel_block.receipts.len(), 2);
+        let sequential_profile = sequential_profiler.profile();
+        let parallel_profile = parallel_profiler.profile();
+        assert!(!sequential_profile.entry_points().is_empty());
+        assert_eq!(
+            parallel_profile.entry_points(),
+            sequential_profile.entry_points()
+        );
+        assert_eq!(parallel_profile.stacks(), sequential_profile.stacks());
+    }
+}
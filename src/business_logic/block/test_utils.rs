@@ -0,0 +1,52 @@
+use crate::{
+    business_logic::{
+        fact_state::in_memory_state_reader::InMemoryStateReader,
+        state::{cached_state::CachedState, state_api_objects::BlockInfo},
+        transaction::{objects::internal_deploy::InternalDeploy, transactions::Transaction},
+    },
+    core::contract_address::starknet_contract_address::compute_class_hash,
+    definitions::general_config::StarknetChainId,
+    services::api::contract_class::ContractClass,
+    utils::{Address, ClassHash},
+};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+/// The fibonacci contract class, with its hash.
+pub(super) fn fibonacci_class() -> (ContractClass, ClassHash) {
+    let contract_class =
+        ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap();
+    let class_hash = compute_class_hash(&contract_class).unwrap().to_be_bytes();
+    (contract_class, class_hash)
+}
+
+/// An empty state where the fibonacci contract class is declared.
+pub(super) fn fibonacci_state() -> CachedState<InMemoryStateReader> {
+    let (contract_class, class_hash) = fibonacci_class();
+    CachedState::new(
+        InMemoryStateReader::default(),
+        Some(HashMap::from([(class_hash, Arc::new(contract_class))])),
+    )
+}
+
+/// Deploys `contract_class` without constructor calldata, at the address given by `salt`.
+pub(super) fn deploy(contract_class: &ContractClass, salt: u64) -> Transaction {
+    Transaction::Deploy(
+        InternalDeploy::new(
+            Address(salt.into()),
+            contract_class.clone(),
+            Vec::new(),
+            StarknetChainId::TestNet.to_felt(),
+            0,
+        )
+        .unwrap(),
+    )
+}
+
+/// The block following the default last block.
+pub(super) fn next_block_info() -> BlockInfo {
+    BlockInfo {
+        block_number: 1,
+        block_timestamp: 1,
+        ..Default::default()
+    }
+}
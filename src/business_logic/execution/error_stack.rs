@@ -0,0 +1,216 @@
+use crate::{
+    business_logic::transaction::error::TransactionError,
+    utils::{encode_felt, Address, ClassHash},
+};
+use cairo_rs::{
+    serde::deserialize_program::Location,
+    vm::errors::{cairo_run_errors::CairoRunError, vm_exception::VmException},
+};
+use felt::Felt252;
+use std::{error::Error, fmt};
+
+/// An entry point whose execution failed, either by itself or because a call it made failed.
+#[derive(Debug)]
+pub struct ErrorFrame {
+    pub contract_address: Address,
+    pub class_hash: ClassHash,
+    pub entry_point_selector: Felt252,
+    /// The offset of the instruction the execution failed at in the program, unless the failure
+    /// isn't tied to an instruction.
+    pub pc: Option<usize>,
+    /// Where the instruction at `pc` is in the Cairo source, if the program has debug info.
+    pub location: Option<Location>,
+    /// The `Error message: ...` of the `with_attr error_message` blocks around the instruction.
+    pub error_attr_value: Option<String>,
+    /// The Cairo traceback of the instruction, from the entry point to the failing function.
+    pub traceback: Option<String>,
+}
+
+impl ErrorFrame {
+    /// The frame of the entry point `entry_point_selector` of `contract_address`, whose run
+    /// failed with `error`.
+    pub(crate) fn new(
+        contract_address: Address,
+        class_hash: ClassHash,
+        entry_point_selector: Felt252,
+        error: &TransactionError,
+    ) -> Self {
+        let vm_exception = match error {
+            TransactionError::CairoRunner(CairoRunError::VmException(vm_exception)) => {
+                Some(vm_exception)
+            }
+            _ => None,
+        };
+
+        ErrorFrame {
+            contract_address,
+            class_hash,
+            entry_point_selector,
+            pc: vm_exception.map(|vm_exception| vm_exception.pc),
+            location: vm_exception.and_then(|vm_exception| vm_exception.inst_location.clone()),
+            error_attr_value: vm_exception
+                .and_then(|vm_exception| vm_exception.error_attr_value.clone()),
+            traceback: vm_exception.and_then(|vm_exception| vm_exception.traceback.clone()),
+        }
+    }
+}
+
+/// The entry points a failure unwound through, from the one called by the transaction to the one
+/// that failed first, with the error that made it fail.
+///
+/// Displays like a cairo-lang error: for each frame, the contract and selector, the pc and source
+/// location of the failing instruction and the Cairo traceback.
+#[derive(Debug)]
+pub struct ErrorStack {
+    pub frames: Vec<ErrorFrame>,
+    pub error: Box<TransactionError>,
+}
+
+impl ErrorStack {
+    /// The stack of `error` with `frame` on top: `error` made the entry point of `frame` fail.
+    pub(crate) fn push(frame: ErrorFrame, error: TransactionError) -> Self {
+        match error {
+            TransactionError::EntryPointFailed(mut error_stack) => {
+                error_stack.frames.insert(0, frame);
+                error_stack
+            }
+            error => ErrorStack {
+                frames: vec![frame],
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// The error the innermost entry point failed with, e.g. the VM exception of a failed
+    /// assertion or the [`OutOfResources`](TransactionError::OutOfResources) of a syscall.
+    pub fn root_cause(&self) -> &TransactionError {
+        &self.error
+    }
+}
+
+impl Error for ErrorStack {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl fmt::Display for ErrorStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, frame) in self.frames.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(
+                f,
+                "Error in the called contract ({}, selector {}):",
+                encode_felt(&frame.contract_address.0),
+                encode_felt(&frame.entry_point_selector),
+            )?;
+
+            if let Some(pc) = frame.pc {
+                let message = format!("Error at pc=0:{pc}:");
+                match &frame.location {
+                    Some(location) => writeln!(
+                        f,
+                        "{}:{}:{}: {message}",
+                        location.input_file.filename, location.start_line, location.start_col
+                    )?,
+                    None => writeln!(f, "{message}")?,
+                }
+            }
+            // The innermost frame is where the error happened.
+            if index + 1 == self.frames.len() {
+                match self.error.as_ref() {
+                    TransactionError::CairoRunner(CairoRunError::VmException(VmException {
+                        inner_exc,
+                        ..
+                    })) => writeln!(f, "{inner_exc}")?,
+                    error => writeln!(f, "{error}")?,
+                }
+            }
+            if let Some(error_attr_value) = &frame.error_attr_value {
+                write!(f, "{error_attr_value}")?;
+            }
+            if let Some(traceback) = &frame.traceback {
+                write!(f, "{traceback}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use cairo_rs::{
+        serde::deserialize_program::InputFile, vm::errors::vm_errors::VirtualMachineError,
+    };
+    use coverage_helper::test;
+
+    #[test]
+    fn error_stack_displays_every_frame() {
+        let failed_call_error =
+            TransactionError::CairoRunner(CairoRunError::VmException(VmException {
+                pc: 5,
+                inst_location: Some(Location {
+                    end_line: 12,
+                    end_col: 20,
+                    input_file: InputFile {
+                        filename: "contracts/callee.cairo".to_string(),
+                    },
+                    parent_location: None,
+                    start_line: 12,
+                    start_col: 5,
+                }),
+                inner_exc: VirtualMachineError::EndOfProgram(3),
+                error_attr_value: Some("Error message: value must be positive\n".to_string()),
+                traceback: None,
+            }));
+        let failed_call_frame =
+            ErrorFrame::new(Address(2.into()), [2; 32], 3.into(), &failed_call_error);
+        let caller_frame = ErrorFrame {
+            contract_address: Address(1.into()),
+            class_hash: [1; 32],
+            entry_point_selector: 4.into(),
+            pc: Some(35),
+            location: None,
+            error_attr_value: None,
+            traceback: Some(
+                "Cairo traceback (most recent call last):\nUnknown location (pc=0:20)\n"
+                    .to_string(),
+            ),
+        };
+
+        let error_stack = ErrorStack::push(
+            caller_frame,
+            TransactionError::EntryPointFailed(ErrorStack::push(
+                failed_call_frame,
+                failed_call_error,
+            )),
+        );
+
+        assert_eq!(error_stack.frames.len(), 2);
+        assert_eq!(error_stack.frames[0].contract_address, Address(1.into()));
+        assert_matches!(
+            error_stack.root_cause(),
+            TransactionError::CairoRunner(CairoRunError::VmException(VmException { pc: 5, .. }))
+        );
+        assert!(error_stack.source().is_some());
+        assert_eq!(
+            error_stack.to_string(),
+            format!(
+                "Error in the called contract (0x1, selector 0x4):\n\
+                 Error at pc=0:35:\n\
+                 Cairo traceback (most recent call last):\n\
+                 Unknown location (pc=0:20)\n\
+                 \n\
+                 Error in the called contract (0x2, selector 0x3):\n\
+                 contracts/callee.cairo:12:5: Error at pc=0:5:\n\
+                 {}\n\
+                 Error message: value must be positive\n",
+                VirtualMachineError::EndOfProgram(3)
+            )
+        );
+    }
+}
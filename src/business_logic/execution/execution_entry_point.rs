@@ -1,6 +1,10 @@
 use crate::{
     business_logic::{
-        execution::objects::{CallInfo, CallType, TransactionExecutionContext},
+        execution::{
+            error_stack::{ErrorFrame, ErrorStack},
+            objects::{CallInfo, CallType, TransactionExecutionContext},
+            profiler::{EntryPointId, Profiler},
+        },
         fact_state::state::ExecutionResourcesManager,
         state::state_api::State,
         state::state_api::StateReader,
@@ -13,7 +17,9 @@ use crate::{
     definitions::{constants::DEFAULT_ENTRY_POINT_SELECTOR, general_config::StarknetGeneralConfig},
     services::api::contract_class::{ContractClass, ContractEntryPoint, EntryPointType},
     starknet_runner::runner::StarknetRunner,
-    utils::{get_deployed_address_class_hash_at_address, validate_contract_deployed, Address},
+    utils::{
+        get_deployed_address_class_hash_at_address, validate_contract_deployed, Address, ClassHash,
+    },
 };
 use cairo_rs::{
     types::relocatable::{MaybeRelocatable, Relocatable},
@@ -85,6 +91,14 @@ impl ExecutionEntryPoint {
         // Update resources usage (for bouncer).
         resources_manager.cairo_usage =
             &resources_manager.cairo_usage + &runner.get_execution_resources()?;
+        // The syscall handler counted the syscalls of the run, and of the calls it made, on a
+        // copy of the manager.
+        resources_manager.syscall_counter = runner
+            .hint_processor
+            .syscall_handler
+            .resources_manager
+            .syscall_counter
+            .clone();
 
         let retdata = runner.get_return_values()?;
         self.build_call_info::<T>(
@@ -100,10 +114,11 @@ impl ExecutionEntryPoint {
     /// self.contract_address.
     /// Returns the corresponding CairoFunctionRunner and BusinessLogicSysCallHandler in order to
     /// retrieve the execution information.
+    /// If the run fails, the resources it used until then are added to the resources manager.
     fn run<'a, T>(
         &self,
         state: &'a mut T,
-        resources_manager: &ExecutionResourcesManager,
+        resources_manager: &mut ExecutionResourcesManager,
         general_config: &StarknetGeneralConfig,
         tx_execution_context: &TransactionExecutionContext,
     ) -> Result<StarknetRunner<BusinessLogicSyscallHandler<'a, T>>, TransactionError>
@@ -118,9 +133,14 @@ impl ExecutionEntryPoint {
 
         // fetch selected entry point
         let entry_point = self.get_selected_entry_point(&contract_class, class_hash)?;
-        // create starknet runner
+        // The calls made by the entry point are profiled as called from it.
+        let profiler = resources_manager
+            .profiler
+            .as_ref()
+            .map(|profiler| profiler.enter(&self.entry_point_id()));
 
-        let mut vm = VirtualMachine::new(false);
+        // create starknet runner
+        let mut vm = VirtualMachine::new(profiler.is_some());
         let mut cairo_runner = CairoRunner::new(contract_class.program(), "all", false)?;
         cairo_runner.initialize_function_runner(&mut vm)?;
 
@@ -143,7 +163,10 @@ impl ExecutionEntryPoint {
         let syscall_handler = BusinessLogicSyscallHandler::new(
             tx_execution_context.clone(),
             state,
-            resources_manager.clone(),
+            ExecutionResourcesManager {
+                profiler: profiler.clone(),
+                ..resources_manager.clone()
+            },
             self.caller_address.clone(),
             self.contract_address.clone(),
             general_config.clone(),
@@ -170,7 +193,20 @@ impl ExecutionEntryPoint {
         let entrypoint = entry_point.offset;
 
         // cairo runner entry point
-        runner.run_from_entrypoint(entrypoint, &entry_point_args)?;
+        if let Err(error) = runner.run_from_entrypoint(
+            entrypoint,
+            &entry_point_args,
+            &tx_execution_context.run_resources,
+        ) {
+            return Err(self.entry_point_failed(
+                &mut runner,
+                resources_manager,
+                class_hash,
+                &contract_class,
+                profiler.as_ref(),
+                error,
+            )?);
+        }
         runner.validate_and_process_os_context(os_context)?;
 
         // When execution starts the stack holds entry_points_args + [ret_fp, ret_pc].
@@ -184,9 +220,79 @@ impl ExecutionEntryPoint {
             .vm
             .mark_address_range_as_accessed(args_ptr, entry_point_args.len())?;
 
+        if let Some(profiler) = &profiler {
+            profiler.record(
+                self.entry_point_id(),
+                &runner.get_execution_resources()?,
+                contract_class.program(),
+                &runner.vm,
+            );
+        }
+
         Ok(runner)
     }
 
+    /// The error of a run of `contract_class` that failed with `error`: the frame of this entry point on
+    /// top of the failed call that made it fail, if any. The steps and syscalls run until the
+    /// failure are charged, and the steps profiled, like those of a run that ended.
+    fn entry_point_failed<T>(
+        &self,
+        runner: &mut StarknetRunner<BusinessLogicSyscallHandler<T>>,
+        resources_manager: &mut ExecutionResourcesManager,
+        class_hash: ClassHash,
+        contract_class: &ContractClass,
+        profiler: Option<&Profiler>,
+        error: TransactionError,
+    ) -> Result<TransactionError, TransactionError>
+    where
+        T: Default + State + StateReader,
+    {
+        let frame = ErrorFrame::new(
+            self.contract_address.clone(),
+            class_hash,
+            self.entry_point_selector.clone(),
+            &error,
+        );
+        let resources = runner.get_failed_execution_resources()?;
+        if let Some(profiler) = profiler {
+            profiler.record(
+                self.entry_point_id(),
+                &resources,
+                contract_class.program(),
+                &runner.vm,
+            );
+        }
+        resources_manager.cairo_usage = &resources_manager.cairo_usage + &resources;
+        // The OS still runs the syscalls made before the failure.
+        resources_manager.syscall_counter = runner
+            .hint_processor
+            .syscall_handler
+            .resources_manager
+            .syscall_counter
+            .clone();
+        // A failed call made by the entry point is what made it fail.
+        let error = runner
+            .hint_processor
+            .syscall_handler
+            .failed_call
+            .take()
+            .unwrap_or(error);
+        // The transaction ran out of steps, whichever call it happened in.
+        if matches!(error, TransactionError::OutOfResources) {
+            return Ok(error);
+        }
+        Ok(TransactionError::EntryPointFailed(ErrorStack::push(
+            frame, error,
+        )))
+    }
+
+    fn entry_point_id(&self) -> EntryPointId {
+        EntryPointId {
+            contract_address: self.contract_address.clone(),
+            entry_point_selector: self.entry_point_selector.clone(),
+        }
+    }
+
     /// Returns the entry point with selector corresponding with self.entry_point_selector, or the
     /// default if there is one and the requested one is not found.
     fn get_selected_entry_point(
@@ -249,6 +355,7 @@ impl ExecutionEntryPoint {
             l2_to_l1_messages: syscall_handler.l2_to_l1_messages,
             storage_read_values: syscall_handler.starknet_storage_state.read_values,
             accessed_storage_keys: syscall_handler.starknet_storage_state.accessed_keys,
+            storage_writes: syscall_handler.starknet_storage_state.written_values,
             internal_calls: syscall_handler.internal_calls,
         })
     }
@@ -285,20 +392,22 @@ mod tests {
     use crate::business_logic::{
         execution::execution_entry_point::ExecutionEntryPoint, state::cached_state::CachedState,
     };
-    use crate::definitions::constants::TRANSACTION_VERSION;
+    use crate::definitions::constants::{EXECUTE_ENTRY_POINT_SELECTOR, TRANSACTION_VERSION};
     use crate::utils::calculate_sn_keccak;
     use crate::{
         business_logic::{
             execution::objects::{CallInfo, CallType, TransactionExecutionContext},
             fact_state::state::ExecutionResourcesManager,
+            transaction::error::TransactionError,
         },
         definitions::general_config::StarknetGeneralConfig,
         services::api::contract_class::{ContractClass, EntryPointType},
         utils::Address,
     };
+    use assert_matches::assert_matches;
     use cairo_rs::with_std::collections::HashMap;
     use felt::Felt252;
-    use std::path::Path;
+    use std::{path::Path, sync::Arc};
     #[test]
     fn test_execution_entrypoint() {
         let contract_path = "starknet_programs/fibonacci.json";
@@ -353,7 +462,7 @@ mod tests {
             .insert((contract_address.clone(), [0; 32]), Felt252::new(1));
 
         let mut contract_class_cache = HashMap::new();
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         let mut state = CachedState::new(state_reader, Some(contract_class_cache));
 
@@ -399,4 +508,145 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn execution_entrypoint_takes_its_steps_from_the_context() {
+        let contract_class = ContractClass::try_from(
+            <str as AsRef<Path>>::as_ref("starknet_programs/fibonacci.json").to_path_buf(),
+        )
+        .unwrap();
+        let contract_address = Address(1111.into());
+        let class_hash = [1; 32];
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash
+            .insert(contract_address.clone(), class_hash);
+        let mut state = CachedState::new(
+            state_reader,
+            Some(HashMap::from([(class_hash, Arc::new(contract_class))])),
+        );
+
+        let entry_point = ExecutionEntryPoint::new(
+            contract_address,
+            [1.into(), 1.into(), 10.into()].to_vec(),
+            Felt252::from_bytes_be(&calculate_sn_keccak(b"fib")),
+            Address(0.into()),
+            EntryPointType::External,
+            CallType::Delegate.into(),
+            class_hash.into(),
+        );
+        let general_config = StarknetGeneralConfig::default();
+        let tx_execution_context = |n_steps| {
+            TransactionExecutionContext::create_for_testing(
+                Address(0.into()),
+                10,
+                0.into(),
+                n_steps,
+                TRANSACTION_VERSION,
+            )
+        };
+
+        let short_context = tx_execution_context(10);
+        let mut resources_manager = ExecutionResourcesManager::default();
+        assert_matches!(
+            entry_point.execute(
+                &mut state,
+                &general_config,
+                &mut resources_manager,
+                &short_context,
+            ),
+            Err(TransactionError::OutOfResources)
+        );
+        assert_eq!(short_context.run_resources().n_steps(), 0);
+        // The failed run is charged its 10 steps and the one it ran out of steps at.
+        assert_eq!(resources_manager.cairo_usage.n_steps, 11);
+
+        let context = tx_execution_context(1000);
+        let mut resources_manager = ExecutionResourcesManager::default();
+        entry_point
+            .execute(
+                &mut state,
+                &general_config,
+                &mut resources_manager,
+                &context,
+            )
+            .unwrap();
+        assert_eq!(
+            context.run_resources().n_steps(),
+            1000 - resources_manager.cairo_usage.n_steps as u64
+        );
+    }
+
+    #[test]
+    fn nested_call_out_of_steps() {
+        let account_class = ContractClass::try_from(
+            <str as AsRef<Path>>::as_ref("starknet_programs/account_without_validation.json")
+                .to_path_buf(),
+        )
+        .unwrap();
+        let fibonacci_class = ContractClass::try_from(
+            <str as AsRef<Path>>::as_ref("starknet_programs/fibonacci.json").to_path_buf(),
+        )
+        .unwrap();
+        let account_address = Address(1.into());
+        let fibonacci_address = Address(1111.into());
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash
+            .insert(account_address.clone(), [1; 32]);
+        state_reader
+            .address_to_class_hash
+            .insert(fibonacci_address.clone(), [2; 32]);
+        let mut state = CachedState::new(
+            state_reader,
+            Some(HashMap::from([
+                ([1; 32], Arc::new(account_class)),
+                ([2; 32], Arc::new(fibonacci_class)),
+            ])),
+        );
+
+        // The account calls fib(1, 1, 1000), which runs out of the steps of the context.
+        let entry_point = ExecutionEntryPoint::new(
+            account_address,
+            vec![
+                fibonacci_address.0,
+                Felt252::from_bytes_be(&calculate_sn_keccak(b"fib")),
+                3.into(),
+                1.into(),
+                1.into(),
+                1000.into(),
+            ],
+            EXECUTE_ENTRY_POINT_SELECTOR.clone(),
+            Address(0.into()),
+            EntryPointType::External,
+            CallType::Call.into(),
+            None,
+        );
+        let tx_execution_context = TransactionExecutionContext::create_for_testing(
+            Address(0.into()),
+            10,
+            0.into(),
+            1000,
+            TRANSACTION_VERSION,
+        );
+
+        let mut resources_manager = ExecutionResourcesManager::default();
+        assert_matches!(
+            entry_point.execute(
+                &mut state,
+                &StarknetGeneralConfig::default(),
+                &mut resources_manager,
+                &tx_execution_context,
+            ),
+            Err(TransactionError::OutOfResources)
+        );
+        assert_eq!(tx_execution_context.run_resources().n_steps(), 0);
+        // The call made before running out of steps is still counted.
+        assert_eq!(
+            resources_manager.get_syscall_counter("call_contract"),
+            Some(1)
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use super::objects::L2toL1MessageInfo;
+use crate::business_logic::state::state_api_objects::StateChanges;
 use crate::definitions::constants::*;
 use crate::services::eth_definitions::eth_gas_constans::*;
 
@@ -10,20 +11,17 @@ use crate::services::eth_definitions::eth_gas_constans::*;
 /// l1_handler_payload_size should be an int if and only if we calculate the gas usage of an
 /// InternalInvokeFunction of type L1 handler. Otherwise the payload size is irrelevant, and should
 /// be None.
-/// n_deployments is the number of the contracts deployed by the transaction.
+/// state_changes are the changes the transaction made to the state.
 
 pub fn calculate_tx_gas_usage(
     l2_to_l1_messages: Vec<L2toL1MessageInfo>,
-    n_modified_contracts: usize,
-    n_storage_changes: usize,
+    state_changes: &StateChanges,
     l1_handler_payload_size: Option<usize>,
-    n_deployments: usize,
 ) -> usize {
     let residual_message_segment_length =
         get_message_segment_lenght(&l2_to_l1_messages, l1_handler_payload_size);
 
-    let residual_onchain_data_segment_length =
-        get_onchain_data_segment_length(n_modified_contracts, n_storage_changes, n_deployments);
+    let residual_onchain_data_segment_length = get_onchain_data_segment_length(state_changes);
 
     let n_l2_to_l1_messages = l2_to_l1_messages.len();
     let n_l1_to_l2_messages = match l1_handler_payload_size {
@@ -87,15 +85,15 @@ pub fn get_message_segment_lenght(
 /// Returns the number of felts added to the output data availability segment as a result of adding
 /// a transaction to a batch. Note that constant cells - such as the one that holds the number of
 /// modified contracts - are not counted.
-/// This segment consists of deployment info (of contracts deployed by the transaction) and
-/// storage updates.
-
-pub fn get_onchain_data_segment_length(
-    n_modified_contracts: usize,
-    n_storage_changes: usize,
-    n_deployments: usize,
-) -> usize {
-    n_modified_contracts * 2 + n_storage_changes * 2 + n_deployments * DEPLOYMENT_INFO_SIZE
+/// This segment consists of the modified contracts (address and nonce), their storage updates,
+/// their class hash updates, deployments included, and the compiled class hashes of the declared
+/// Cairo 1 classes.
+
+pub fn get_onchain_data_segment_length(state_changes: &StateChanges) -> usize {
+    state_changes.n_modified_contracts * 2
+        + state_changes.n_storage_updates * 2
+        + state_changes.n_class_hash_updates * CLASS_UPDATE_SIZE
+        + state_changes.n_compiled_class_hash_updates * 2
 }
 
 /// Returns the cost of ConsumedMessageToL2 event emissions caused by an L1 handler with the given
@@ -224,9 +222,18 @@ mod test {
         let message1 = L2toL1MessageInfo::new(ord_ev1, Address(1234.into()));
         let message2 = L2toL1MessageInfo::new(ord_ev2, Address(1235.into()));
 
+        let state_changes = StateChanges {
+            n_modified_contracts: 2,
+            n_storage_updates: 2,
+            n_class_hash_updates: 1,
+            n_deployments: 1,
+            n_declared_classes: 0,
+            n_compiled_class_hash_updates: 0,
+        };
+
         assert_eq!(
-            calculate_tx_gas_usage(vec![message1, message2], 2, 2, Some(2), 1),
-            77051
+            calculate_tx_gas_usage(vec![message1, message2], &state_changes, Some(2)),
+            76439
         )
     }
 }
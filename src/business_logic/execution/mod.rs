@@ -1,4 +1,7 @@
+pub mod error_stack;
 pub mod execution_entry_point;
 pub mod gas_usage;
 pub mod objects;
 pub mod os_usage;
+pub mod profiler;
+pub mod trace;
@@ -2,11 +2,12 @@ use crate::{
     business_logic::{state::state_cache::StorageEntry, transaction::error::TransactionError},
     core::errors::syscall_handler_errors::SyscallHandlerError,
     definitions::{
-        constants::CONSTRUCTOR_ENTRY_POINT_SELECTOR, general_config::StarknetChainId,
+        constants::{CONSTRUCTOR_ENTRY_POINT_SELECTOR, DEFAULT_INVOKE_TX_MAX_N_STEPS},
+        general_config::StarknetChainId,
         transaction_type::TransactionType,
     },
     services::api::contract_class::EntryPointType,
-    utils::{get_big_int, get_integer, get_relocatable, Address, ClassHash},
+    utils::{get_big_int, get_integer, get_relocatable, Address, ClassHash, StorageKey},
 };
 use cairo_rs::{
     types::relocatable::{MaybeRelocatable, Relocatable},
@@ -15,7 +16,10 @@ use cairo_rs::{
 use felt::Felt252;
 use getset::Getters;
 use num_traits::{ToPrimitive, Zero};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CallType {
@@ -43,6 +47,9 @@ pub struct CallInfo {
     pub l2_to_l1_messages: Vec<OrderedL2ToL1Message>,
     pub storage_read_values: Vec<Felt252>,
     pub accessed_storage_keys: HashSet<ClassHash>,
+    /// The values the call wrote to the storage of its contract, the last one of each key. The
+    /// writes of the calls it made are in their own call info.
+    pub storage_writes: HashMap<StorageKey, Felt252>,
     pub internal_calls: Vec<CallInfo>,
 }
 
@@ -75,6 +82,7 @@ impl CallInfo {
             l2_to_l1_messages: Vec::new(),
             storage_read_values: Vec::new(),
             accessed_storage_keys: HashSet::new(),
+            storage_writes: HashMap::new(),
             internal_calls: Vec::new(),
         }
     }
@@ -201,6 +209,7 @@ impl Default for CallInfo {
             entry_point_selector: None,
             l2_to_l1_messages: Vec::new(),
             accessed_storage_keys: HashSet::new(),
+            storage_writes: HashMap::new(),
             calldata: Vec::new(),
             execution_resources: ExecutionResources {
                 n_steps: 0,
@@ -253,7 +262,9 @@ impl Event {
 #[derive(Clone, Debug, Default, Getters)]
 pub struct TransactionExecutionContext {
     pub(crate) n_emitted_events: u64,
-    pub(crate) version: u64,
+    /// The version the transaction was signed with, which includes the query offset of query
+    /// transactions.
+    pub(crate) version: Felt252,
     pub(crate) account_contract_address: Address,
     pub(crate) max_fee: u64,
     pub(crate) transaction_hash: Felt252,
@@ -261,7 +272,8 @@ pub struct TransactionExecutionContext {
     #[get = "pub"]
     pub(crate) nonce: Felt252,
     pub(crate) n_sent_messages: usize,
-    pub(crate) _n_steps: u64,
+    #[get = "pub"]
+    pub(crate) run_resources: RunResources,
 }
 
 impl TransactionExecutionContext {
@@ -281,9 +293,9 @@ impl TransactionExecutionContext {
             nonce,
             signature,
             transaction_hash,
-            version,
+            version: version.into(),
             n_sent_messages: 0,
-            _n_steps: n_steps,
+            run_resources: RunResources::new(n_steps),
         }
     }
 
@@ -296,21 +308,68 @@ impl TransactionExecutionContext {
     ) -> Self {
         TransactionExecutionContext {
             n_emitted_events: 0,
-            version,
+            version: version.into(),
             account_contract_address,
             max_fee: 0,
             transaction_hash: Felt252::zero(),
             signature: Vec::new(),
             nonce,
             n_sent_messages: 0,
-            _n_steps: n_steps,
+            run_resources: RunResources::new(n_steps),
+        }
+    }
+}
+
+/// The Cairo steps left to run the entry points of a transaction.
+///
+/// Clones share the steps left, so the calls an entry point makes draw from the same budget as
+/// the entry point itself.
+#[derive(Clone, Debug)]
+pub struct RunResources {
+    n_steps: Arc<Mutex<u64>>,
+}
+
+impl Default for RunResources {
+    /// As many steps as an invoke transaction gets with the default config.
+    fn default() -> Self {
+        RunResources::new(DEFAULT_INVOKE_TX_MAX_N_STEPS)
+    }
+}
+
+impl RunResources {
+    pub fn new(n_steps: u64) -> Self {
+        RunResources {
+            n_steps: Arc::new(Mutex::new(n_steps)),
+        }
+    }
+
+    pub fn n_steps(&self) -> u64 {
+        *self.lock()
+    }
+
+    /// Takes `n_steps` from the steps left. If there aren't enough, no steps are left afterwards.
+    pub(crate) fn consume(&self, n_steps: u64) -> Result<(), TransactionError> {
+        let mut steps_left = self.lock();
+        match steps_left.checked_sub(n_steps) {
+            Some(remaining) => {
+                *steps_left = remaining;
+                Ok(())
+            }
+            None => {
+                *steps_left = 0;
+                Err(TransactionError::OutOfResources)
+            }
         }
     }
+
+    fn lock(&self) -> MutexGuard<'_, u64> {
+        self.n_steps.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct TxInfoStruct {
-    pub(crate) version: usize,
+    pub(crate) version: Felt252,
     pub(crate) account_contract_address: Address,
     pub(crate) max_fee: u64,
     pub(crate) signature_len: usize,
@@ -327,7 +386,7 @@ impl TxInfoStruct {
         chain_id: StarknetChainId,
     ) -> TxInfoStruct {
         TxInfoStruct {
-            version: tx.version as usize,
+            version: tx.version,
             account_contract_address: tx.account_contract_address,
             max_fee: tx.max_fee,
             signature_len: tx.signature.len(),
@@ -340,7 +399,7 @@ impl TxInfoStruct {
 
     pub(crate) fn to_vec(&self) -> Vec<MaybeRelocatable> {
         vec![
-            MaybeRelocatable::from(Felt252::new(self.version)),
+            MaybeRelocatable::from(&self.version),
             MaybeRelocatable::from(&self.account_contract_address.0),
             MaybeRelocatable::from(Felt252::new(self.max_fee)),
             MaybeRelocatable::from(Felt252::new(self.signature_len)),
@@ -355,7 +414,7 @@ impl TxInfoStruct {
         vm: &VirtualMachine,
         tx_info_ptr: Relocatable,
     ) -> Result<TxInfoStruct, SyscallHandlerError> {
-        let version = get_integer(vm, tx_info_ptr)?;
+        let version = get_big_int(vm, tx_info_ptr)?;
 
         let account_contract_address = Address(get_big_int(vm, &tx_info_ptr + 1)?);
         let max_fee = get_big_int(vm, &tx_info_ptr + 2)?
@@ -388,6 +447,9 @@ pub struct TransactionExecutionInfo {
     pub actual_fee: u64,
     pub actual_resources: HashMap<String, usize>,
     pub tx_type: Option<TransactionType>,
+    /// Why the execution was reverted, if it was. A reverted transaction keeps no state changes
+    /// besides the nonce increment and the fee transfer.
+    pub revert_error: Option<String>,
 }
 
 impl TransactionExecutionInfo {
@@ -406,6 +468,7 @@ impl TransactionExecutionInfo {
             actual_fee,
             actual_resources,
             tx_type,
+            revert_error: None,
         }
     }
 
@@ -444,6 +507,7 @@ impl TransactionExecutionInfo {
             actual_fee: 0,
             actual_resources: HashMap::new(),
             tx_type,
+            revert_error: None,
         }
     }
 
@@ -460,9 +524,33 @@ impl TransactionExecutionInfo {
             actual_fee: 0,
             actual_resources,
             tx_type,
+            revert_error: None,
+        }
+    }
+
+    /// Same as `create_concurrent_stage_execution_info`, for an execution that failed and whose
+    /// changes were discarded.
+    pub fn create_reverted_execution_info(
+        validate_info: Option<CallInfo>,
+        revert_error: String,
+        actual_resources: HashMap<String, usize>,
+        tx_type: Option<TransactionType>,
+    ) -> Self {
+        TransactionExecutionInfo {
+            revert_error: Some(revert_error),
+            ..Self::create_concurrent_stage_execution_info(
+                validate_info,
+                None,
+                actual_resources,
+                tx_type,
+            )
         }
     }
 
+    pub fn is_reverted(&self) -> bool {
+        self.revert_error.is_some()
+    }
+
     pub fn from_concurrent_state_execution_info(
         concurrent_execution_info: TransactionExecutionInfo,
         actual_fee: u64,
@@ -543,9 +631,9 @@ impl Default for OrderedL2ToL1Message {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct L2toL1MessageInfo {
-    pub(crate) from_address: Address,
-    pub(crate) to_address: Address,
-    pub(crate) payload: Vec<Felt252>,
+    pub from_address: Address,
+    pub to_address: Address,
+    pub payload: Vec<Felt252>,
 }
 
 impl L2toL1MessageInfo {
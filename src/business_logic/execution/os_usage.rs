@@ -9,12 +9,179 @@ use crate::{
 
 #[derive(Debug, Clone)]
 pub struct OsResources {
-    _execute_syscalls: HashMap<String, ExecutionResources>,
+    /// The resources the OS runs for each syscall.
+    execute_syscalls: HashMap<String, ExecutionResources>,
+    /// The resources the OS runs for each type of transaction, besides its syscalls.
     execute_txs_inner: HashMap<TransactionType, ExecutionResources>,
 }
 
 impl Default for OsResources {
     fn default() -> Self {
+        // Taken from the os_resources.json of cairo-lang.
+        let execute_syscalls: HashMap<String, ExecutionResources> = HashMap::from([
+            (
+                "call_contract".to_string(),
+                ExecutionResources {
+                    n_steps: 690,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([(
+                        "range_check_builtin".to_string(),
+                        20,
+                    )]),
+                },
+            ),
+            (
+                "delegate_call".to_string(),
+                ExecutionResources {
+                    n_steps: 712,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([(
+                        "range_check_builtin".to_string(),
+                        19,
+                    )]),
+                },
+            ),
+            (
+                "delegate_l1_handler".to_string(),
+                ExecutionResources {
+                    n_steps: 691,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([(
+                        "range_check_builtin".to_string(),
+                        15,
+                    )]),
+                },
+            ),
+            (
+                "deploy".to_string(),
+                ExecutionResources {
+                    n_steps: 936,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([
+                        ("pedersen_builtin".to_string(), 7),
+                        ("range_check_builtin".to_string(), 18),
+                    ]),
+                },
+            ),
+            (
+                "emit_event".to_string(),
+                ExecutionResources {
+                    n_steps: 19,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_block_number".to_string(),
+                ExecutionResources {
+                    n_steps: 40,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_block_timestamp".to_string(),
+                ExecutionResources {
+                    n_steps: 38,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_caller_address".to_string(),
+                ExecutionResources {
+                    n_steps: 32,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_contract_address".to_string(),
+                ExecutionResources {
+                    n_steps: 36,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_sequencer_address".to_string(),
+                ExecutionResources {
+                    n_steps: 34,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_tx_info".to_string(),
+                ExecutionResources {
+                    n_steps: 29,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "get_tx_signature".to_string(),
+                ExecutionResources {
+                    n_steps: 44,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "library_call".to_string(),
+                ExecutionResources {
+                    n_steps: 679,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([(
+                        "range_check_builtin".to_string(),
+                        20,
+                    )]),
+                },
+            ),
+            (
+                "library_call_l1_handler".to_string(),
+                ExecutionResources {
+                    n_steps: 656,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([(
+                        "range_check_builtin".to_string(),
+                        14,
+                    )]),
+                },
+            ),
+            (
+                "replace_class".to_string(),
+                ExecutionResources {
+                    n_steps: 73,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "send_message_to_l1".to_string(),
+                ExecutionResources {
+                    n_steps: 84,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "storage_read".to_string(),
+                ExecutionResources {
+                    n_steps: 44,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                "storage_write".to_string(),
+                ExecutionResources {
+                    n_steps: 46,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+        ]);
         let execute_txs_inner: HashMap<TransactionType, ExecutionResources> = HashMap::from([
             (
                 TransactionType::InvokeFunction,
@@ -57,24 +224,115 @@ impl Default for OsResources {
                     ]),
                 },
             ),
+            // Like DEPLOY, INITIALIZE_BLOCK_INFO is listed with no resources in the
+            // execute_txs_inner table of cairo-lang's os_resources.json: the OS initializes the
+            // block info once per block, outside of the transactions.
+            (
+                TransactionType::InitializeBlockInfo,
+                ExecutionResources {
+                    n_steps: 0,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::new(),
+                },
+            ),
+            (
+                TransactionType::L1Handler,
+                ExecutionResources {
+                    n_steps: 1068,
+                    n_memory_holes: 0,
+                    builtin_instance_counter: HashMap::from([
+                        ("pedersen_builtin".to_string(), 11),
+                        ("range_check_builtin".to_string(), 16),
+                    ]),
+                },
+            ),
         ]);
 
         OsResources {
-            _execute_syscalls: HashMap::new(),
+            execute_syscalls,
             execute_txs_inner,
         }
     }
 }
 
+/// The resources the OS runs for a transaction of type `tx_type` that made the syscalls counted
+/// in `syscall_counter`.
 pub fn get_additional_os_resources(
-    _syscall_counter: HashMap<String, u64>,
+    syscall_counter: HashMap<String, u64>,
     tx_type: &TransactionType,
 ) -> Result<ExecutionResources, TransactionError> {
     let os_resources = OsResources::default();
 
-    Ok(os_resources
+    let mut additional_resources = os_resources
         .execute_txs_inner
         .get(tx_type)
         .ok_or_else(|| TransactionError::NoneTransactionType(*tx_type, os_resources.clone()))?
-        .clone())
+        .clone();
+    for (syscall_name, n_calls) in syscall_counter {
+        let syscall_resources = os_resources
+            .execute_syscalls
+            .get(&syscall_name)
+            .ok_or_else(|| TransactionError::NoneSyscall(syscall_name.clone()))?;
+        additional_resources =
+            &additional_resources + &scale_resources(syscall_resources, n_calls as usize);
+    }
+    Ok(additional_resources)
+}
+
+fn scale_resources(resources: &ExecutionResources, factor: usize) -> ExecutionResources {
+    ExecutionResources {
+        n_steps: resources.n_steps * factor,
+        n_memory_holes: resources.n_memory_holes * factor,
+        builtin_instance_counter: resources
+            .builtin_instance_counter
+            .iter()
+            .map(|(builtin, count)| (builtin.clone(), count * factor))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+
+    #[test]
+    fn additional_os_resources_include_the_syscalls() {
+        let syscall_counter = HashMap::from([
+            ("call_contract".to_string(), 2),
+            ("storage_write".to_string(), 1),
+            ("get_tx_info".to_string(), 0),
+        ]);
+
+        let resources =
+            get_additional_os_resources(syscall_counter, &TransactionType::InvokeFunction).unwrap();
+
+        assert_eq!(resources.n_steps, 2839 + 2 * 690 + 46);
+        assert_eq!(resources.n_memory_holes, 0);
+        assert_eq!(
+            resources.builtin_instance_counter,
+            HashMap::from([
+                ("pedersen_builtin".to_string(), 16),
+                ("range_check_builtin".to_string(), 70 + 2 * 20),
+            ])
+        );
+
+        assert_matches!(
+            get_additional_os_resources(
+                HashMap::from([("unknown".to_string(), 1)]),
+                &TransactionType::InitializeBlockInfo
+            ),
+            Err(TransactionError::NoneSyscall(syscall)) if syscall == "unknown"
+        );
+        assert_eq!(
+            get_additional_os_resources(HashMap::new(), &TransactionType::InitializeBlockInfo)
+                .unwrap(),
+            ExecutionResources {
+                n_steps: 0,
+                n_memory_holes: 0,
+                builtin_instance_counter: HashMap::new(),
+            }
+        );
+    }
 }
@@ -0,0 +1,513 @@
+use crate::utils::{encode_felt, Address};
+use cairo_rs::{
+    types::{program::Program, relocatable::Relocatable},
+    vm::{runners::cairo_runner::ExecutionResources, vm_core::VirtualMachine},
+};
+use felt::Felt252;
+use getset::Getters;
+use num_traits::ToPrimitive;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// An entry point of a deployed contract.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EntryPointId {
+    pub contract_address: Address,
+    pub entry_point_selector: Felt252,
+}
+
+impl EntryPointId {
+    /// The name of the entry point in the stacks of a profile.
+    fn frame_name(&self) -> String {
+        format!(
+            "{}:{}",
+            encode_felt(&self.contract_address.0),
+            encode_felt(&self.entry_point_selector)
+        )
+    }
+}
+
+/// Where the resources of the profiled entry point runs went.
+#[derive(Clone, Debug, Default, Getters)]
+#[getset(get = "pub")]
+pub struct ExecutionProfile {
+    /// The resources each entry point used, not counting the calls it made, summed over its runs.
+    entry_points: HashMap<EntryPointId, ExecutionResources>,
+    /// The resources used in each stack of frames, outermost first. Entry points are named
+    /// `<contract address>:<selector>`, functions by their full Cairo name.
+    ///
+    /// A stack is charged the steps run in its innermost function, the builtin instances those
+    /// steps used and the memory holes they left in the execution segment, the cells they moved
+    /// `ap` past without any instruction accessing them.
+    stacks: HashMap<Vec<String>, ExecutionResources>,
+}
+
+impl ExecutionProfile {
+    /// The steps of the stacks in the folded format of flamegraph tools: one
+    /// `frame;frame;... steps` line per stack.
+    pub fn to_folded(&self) -> String {
+        self.to_folded_by(|resources| resources.n_steps)
+    }
+
+    /// The stacks in the folded format of flamegraph tools, weighted by `weight`, e.g. the
+    /// instances of a builtin they used. Stacks that weigh nothing are left out.
+    pub fn to_folded_by(&self, weight: impl Fn(&ExecutionResources) -> usize) -> String {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(stack, resources)| (stack, weight(resources)))
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(stack, weight)| format!("{} {weight}\n", stack.join(";")))
+            .collect();
+        lines.sort();
+        lines.concat()
+    }
+
+    fn add(&mut self, entry_point: EntryPointId, resources: &ExecutionResources) {
+        let entry_point_resources = self.entry_points.entry(entry_point).or_default();
+        *entry_point_resources = &*entry_point_resources + resources;
+    }
+
+    fn add_stack(&mut self, stack: Vec<String>, resources: &ExecutionResources) {
+        let stack_resources = self.stacks.entry(stack).or_default();
+        *stack_resources = &*stack_resources + resources;
+    }
+}
+
+/// Profiles the entry points run with a resources manager that has it.
+///
+/// Clones record into the same profile, so the calls an entry point makes are profiled along with
+/// it. Runs that fail are profiled up to where they failed. Running entry points to trace them
+/// makes them slower, so profiling is off unless the manager is built with
+/// [`ExecutionResourcesManager::with_profiler`](crate::business_logic::fact_state::state::ExecutionResourcesManager::with_profiler),
+/// or a transaction is executed with a profiler.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    profile: Arc<Mutex<ExecutionProfile>>,
+    /// The entry points the next one is called from, outermost first.
+    call_stack: Vec<String>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What was profiled so far.
+    pub fn profile(&self) -> ExecutionProfile {
+        self.lock().clone()
+    }
+
+    /// The profiler of the calls made by `entry_point`.
+    pub(crate) fn enter(&self, entry_point: &EntryPointId) -> Profiler {
+        let mut call_stack = self.call_stack.clone();
+        call_stack.push(entry_point.frame_name());
+        Profiler {
+            profile: Arc::clone(&self.profile),
+            call_stack,
+        }
+    }
+
+    /// Records a run of `entry_point`, whose own usage was `resources`, attributing the steps of
+    /// the `vm` trace, and the builtins and memory holes they used, to the functions of `program`
+    /// they ran in. `self` must be the profiler returned by [`enter`](Profiler::enter) for the
+    /// entry point.
+    pub(crate) fn record(
+        &self,
+        entry_point: EntryPointId,
+        resources: &ExecutionResources,
+        program: &Program,
+        vm: &VirtualMachine,
+    ) {
+        let trace = vm.get_trace().map(Vec::as_slice).unwrap_or_default();
+        let function_names = FunctionNames::new(program);
+        let operands: Vec<_> = trace
+            .iter()
+            .map(|entry| operand_addresses(vm, entry.pc, entry.ap, entry.fp))
+            .collect();
+        let accessed: HashSet<Relocatable> = operands.iter().flatten().flatten().copied().collect();
+        // Cells past the last accessed one of their segment aren't part of it.
+        let mut segment_ends: HashMap<isize, usize> = HashMap::new();
+        for address in &accessed {
+            let end = segment_ends.entry(address.segment_index).or_default();
+            *end = (*end).max(address.offset + 1);
+        }
+        // The builtin name and cells per instance of each builtin segment.
+        let builtins: HashMap<isize, (String, usize)> = vm
+            .get_builtin_runners()
+            .iter()
+            .map(|runner| {
+                (
+                    runner.base() as isize,
+                    (
+                        format!("{}_builtin", runner.name()),
+                        runner.cells_per_instance() as usize,
+                    ),
+                )
+            })
+            .collect();
+
+        let mut caller_stacks: HashMap<Relocatable, Vec<String>> = HashMap::new();
+        let mut used_instances: HashSet<(isize, usize)> = HashSet::new();
+        let mut stacks: HashMap<Vec<String>, ExecutionResources> = HashMap::new();
+        for (index, entry) in trace.iter().enumerate() {
+            let mut stack = self.call_stack.clone();
+            stack.extend(
+                caller_stacks
+                    .entry(entry.fp)
+                    .or_insert_with(|| function_names.caller_stack(vm, entry.fp, &entry.pc))
+                    .iter()
+                    .cloned(),
+            );
+            stack.push(function_names.name(entry.pc.offset));
+            let stack_resources = stacks.entry(stack).or_default();
+            stack_resources.n_steps += 1;
+
+            // A builtin instance is used by the first step that accesses one of its cells.
+            for address in operands[index].iter().flatten() {
+                if let Some((name, cells_per_instance)) = builtins.get(&address.segment_index) {
+                    let instance = (address.segment_index, address.offset / cells_per_instance);
+                    if used_instances.insert(instance) {
+                        *stack_resources
+                            .builtin_instance_counter
+                            .entry(name.clone())
+                            .or_default() += 1;
+                    }
+                }
+            }
+
+            if let Some(next_entry) = trace.get(index + 1) {
+                if next_entry.ap.segment_index == entry.ap.segment_index {
+                    let segment_end = segment_ends
+                        .get(&entry.ap.segment_index)
+                        .copied()
+                        .unwrap_or_default();
+                    stack_resources.n_memory_holes += (entry.ap.offset
+                        ..next_entry.ap.offset.min(segment_end))
+                        .filter(|offset| {
+                            !accessed
+                                .contains(&Relocatable::from((entry.ap.segment_index, *offset)))
+                        })
+                        .count();
+                }
+            }
+        }
+
+        let mut profile = self.lock();
+        profile.add(entry_point, resources);
+        for (stack, resources) in stacks {
+            profile.add_stack(stack, &resources);
+        }
+    }
+
+    /// Adds `profile`, recorded by another profiler, to this one.
+    pub(crate) fn merge(&self, profile: &ExecutionProfile) {
+        let mut own_profile = self.lock();
+        for (entry_point, resources) in &profile.entry_points {
+            own_profile.add(entry_point.clone(), resources);
+        }
+        for (stack, resources) in &profile.stacks {
+            own_profile.add_stack(stack.clone(), resources);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ExecutionProfile> {
+        self.profile.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The addresses of the dst, op0 and op1 operands of the instruction at `pc`, run with `ap` and
+/// `fp`, which the VM marks as accessed. `None` if the instruction or op0 can't be read.
+///
+/// An instruction is encoded as the three 16-bit biased offsets of its operands followed by its
+/// flags, of which the first five tell the registers the operands are relative to.
+fn operand_addresses(
+    vm: &VirtualMachine,
+    pc: Relocatable,
+    ap: Relocatable,
+    fp: Relocatable,
+) -> Option<[Relocatable; 3]> {
+    let encoded = vm.get_integer(&pc).ok()?.to_u64()?;
+    let offset = |shift: u32| ((encoded >> shift) & 0xffff) as isize - (1 << 15);
+    let flag = |bit: u32| (encoded >> (48 + bit)) & 1 == 1;
+    let register = |is_fp: bool| if is_fp { fp } else { ap };
+
+    let dst = offset_address(register(flag(0)), offset(0))?;
+    let op0 = offset_address(register(flag(1)), offset(16))?;
+    let op1_base = match (flag(2), flag(3), flag(4)) {
+        (true, _, _) => pc,
+        (_, true, _) => fp,
+        (_, _, true) => ap,
+        _ => vm.get_relocatable(&op0).ok()?,
+    };
+    let op1 = offset_address(op1_base, offset(32))?;
+    Some([dst, op0, op1])
+}
+
+fn offset_address(base: Relocatable, offset: isize) -> Option<Relocatable> {
+    let offset = base.offset.checked_add_signed(offset)?;
+    Some(Relocatable::from((base.segment_index, offset)))
+}
+
+/// The functions of a program, by the pc they start at.
+struct FunctionNames(Vec<(usize, String)>);
+
+impl FunctionNames {
+    fn new(program: &Program) -> Self {
+        let mut functions: Vec<_> = program
+            .identifiers
+            .iter()
+            .filter(|(_, identifier)| identifier.type_.as_deref() == Some("function"))
+            .filter_map(|(name, identifier)| Some((identifier.pc?, name.clone())))
+            .collect();
+        functions.sort();
+        FunctionNames(functions)
+    }
+
+    /// The name of the function the instruction at `pc` belongs to.
+    fn name(&self, pc: usize) -> String {
+        match self.0.partition_point(|(start, _)| *start <= pc) {
+            0 => format!("pc=0:{pc}"),
+            index => self.0[index - 1].1.clone(),
+        }
+    }
+
+    /// The functions that called the one whose frame starts at `fp`, outermost first, up to the
+    /// function the entry point started with.
+    ///
+    /// Each frame starts after the fp and the pc to return to in the caller, so the stack is
+    /// walked back from those until returning would leave the program segment of `pc`.
+    fn caller_stack(
+        &self,
+        vm: &VirtualMachine,
+        mut fp: Relocatable,
+        pc: &Relocatable,
+    ) -> Vec<String> {
+        let mut stack = Vec::new();
+        while fp.offset >= 2 {
+            let return_fp =
+                vm.get_relocatable(&Relocatable::from((fp.segment_index, fp.offset - 2)));
+            let return_pc =
+                vm.get_relocatable(&Relocatable::from((fp.segment_index, fp.offset - 1)));
+            match (return_fp, return_pc) {
+                (Ok(return_fp), Ok(return_pc))
+                    if return_pc.segment_index == pc.segment_index && return_pc.offset > 0 =>
+                {
+                    // The call instruction is right before the pc returned to.
+                    stack.push(self.name(return_pc.offset - 1));
+                    fp = return_fp;
+                }
+                _ => break,
+            }
+        }
+        stack.reverse();
+        stack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            execution::{
+                execution_entry_point::ExecutionEntryPoint,
+                objects::{CallType, TransactionExecutionContext},
+            },
+            fact_state::{
+                in_memory_state_reader::InMemoryStateReader, state::ExecutionResourcesManager,
+            },
+            state::cached_state::CachedState,
+            transaction::error::TransactionError,
+        },
+        definitions::{constants::TRANSACTION_VERSION, general_config::StarknetGeneralConfig},
+        services::api::contract_class::{ContractClass, EntryPointType},
+        utils::calculate_sn_keccak,
+    };
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+    use std::path::PathBuf;
+
+    /// Runs `function` of the contract of `contract_path` with `calldata` and at most `n_steps`
+    /// steps, profiling it. Returns the result of the run, the profile, the id of the entry point
+    /// and the resources it used.
+    fn run_profiled(
+        contract_path: &str,
+        function: &str,
+        calldata: Vec<Felt252>,
+        n_steps: u64,
+    ) -> (
+        Result<(), TransactionError>,
+        ExecutionProfile,
+        EntryPointId,
+        ExecutionResources,
+    ) {
+        let contract_class = ContractClass::try_from(PathBuf::from(contract_path)).unwrap();
+        let contract_address = Address(1111.into());
+        let class_hash = [1; 32];
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash
+            .insert(contract_address.clone(), class_hash);
+        let mut state = CachedState::new(
+            state_reader,
+            Some(HashMap::from([(class_hash, Arc::new(contract_class))])),
+        );
+
+        let entry_point_selector =
+            Felt252::from_bytes_be(&calculate_sn_keccak(function.as_bytes()));
+        let entry_point = ExecutionEntryPoint::new(
+            contract_address.clone(),
+            calldata,
+            entry_point_selector.clone(),
+            Address(0.into()),
+            EntryPointType::External,
+            CallType::Delegate.into(),
+            class_hash.into(),
+        );
+
+        let profiler = Profiler::new();
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(Some(&profiler));
+        let result = entry_point
+            .execute(
+                &mut state,
+                &StarknetGeneralConfig::default(),
+                &mut resources_manager,
+                &TransactionExecutionContext::create_for_testing(
+                    Address(0.into()),
+                    10,
+                    0.into(),
+                    n_steps,
+                    TRANSACTION_VERSION,
+                ),
+            )
+            .map(|_| ());
+
+        let entry_point_id = EntryPointId {
+            contract_address,
+            entry_point_selector,
+        };
+        (
+            result,
+            profiler.profile(),
+            entry_point_id,
+            resources_manager.cairo_usage,
+        )
+    }
+
+    /// The resources of all the stacks of `profile`.
+    fn stacks_usage(profile: &ExecutionProfile) -> ExecutionResources {
+        profile
+            .stacks()
+            .values()
+            .fold(ExecutionResources::default(), |usage, resources| {
+                &usage + resources
+            })
+    }
+
+    #[test]
+    fn profile_attributes_steps_to_entry_points_and_functions() {
+        let (result, profile, entry_point_id, cairo_usage) = run_profiled(
+            "starknet_programs/fibonacci.json",
+            "fib",
+            [1.into(), 1.into(), 10.into()].to_vec(),
+            StarknetGeneralConfig::default().invoke_tx_max_n_steps(),
+        );
+        result.unwrap();
+
+        assert_eq!(profile.entry_points()[&entry_point_id], cairo_usage);
+        assert_eq!(stacks_usage(&profile).n_steps, cairo_usage.n_steps);
+
+        // Every stack starts at the entry point, and fib(1, 1, 10) calls itself down to n = 0.
+        let entry_point_frame = entry_point_id.frame_name();
+        assert!(profile
+            .stacks()
+            .keys()
+            .all(|stack| stack[0] == entry_point_frame));
+        let deepest_fib_calls = profile
+            .stacks()
+            .keys()
+            .map(|stack| {
+                stack
+                    .iter()
+                    .filter(|frame| *frame == "__main__.fib")
+                    .count()
+            })
+            .max();
+        assert_eq!(deepest_fib_calls, Some(11));
+
+        let folded = profile.to_folded();
+        assert_eq!(folded.lines().count(), profile.stacks().len());
+        assert!(folded
+            .lines()
+            .all(|line| line.starts_with(&format!("{entry_point_frame};"))));
+    }
+
+    #[test]
+    fn profile_attributes_builtins_and_memory_holes_to_functions() {
+        let (result, profile, entry_point_id, cairo_usage) = run_profiled(
+            "starknet_programs/amm.json",
+            "init_pool",
+            [10000.into(), 10000.into()].to_vec(),
+            StarknetGeneralConfig::default().invoke_tx_max_n_steps(),
+        );
+        result.unwrap();
+        assert_eq!(profile.entry_points()[&entry_point_id], cairo_usage);
+
+        // Every builtin instance the run used is used by one of its steps.
+        let stacks_usage = stacks_usage(&profile);
+        let used_builtins = |resources: &ExecutionResources| {
+            resources
+                .builtin_instance_counter
+                .clone()
+                .into_iter()
+                .filter(|(_, n_instances)| *n_instances > 0)
+                .collect::<HashMap<_, _>>()
+        };
+        assert!(used_builtins(&cairo_usage).contains_key("pedersen_builtin"));
+        assert_eq!(used_builtins(&stacks_usage), used_builtins(&cairo_usage));
+        // The range checks of init_pool are done by the math functions it calls.
+        assert!(profile.stacks().iter().any(|(stack, resources)| {
+            stack.last().map(String::as_str) == Some("starkware.cairo.common.math.assert_nn")
+                && resources
+                    .builtin_instance_counter
+                    .get("range_check_builtin")
+                    > Some(&0)
+        }));
+
+        // Only the holes of the execution segment are attributed to the steps that left them.
+        assert!(stacks_usage.n_memory_holes <= cairo_usage.n_memory_holes);
+
+        assert_eq!(
+            profile
+                .to_folded_by(|resources| resources
+                    .builtin_instance_counter
+                    .get("pedersen_builtin")
+                    .copied()
+                    .unwrap_or_default())
+                .lines()
+                .map(|line| line.rsplit(' ').next().unwrap().parse::<usize>().unwrap())
+                .sum::<usize>(),
+            cairo_usage.builtin_instance_counter["pedersen_builtin"]
+        );
+    }
+
+    #[test]
+    fn profile_includes_failed_runs() {
+        let (result, profile, entry_point_id, cairo_usage) = run_profiled(
+            "starknet_programs/fibonacci.json",
+            "fib",
+            [1.into(), 1.into(), 1000.into()].to_vec(),
+            100,
+        );
+        assert_matches!(result, Err(TransactionError::OutOfResources));
+
+        // The steps run until the failure are profiled.
+        assert!(cairo_usage.n_steps > 0);
+        assert_eq!(profile.entry_points()[&entry_point_id], cairo_usage);
+        assert_eq!(stacks_usage(&profile).n_steps, cairo_usage.n_steps);
+    }
+}
@@ -0,0 +1,380 @@
+use super::objects::{
+    CallInfo, CallType, OrderedEvent, OrderedL2ToL1Message, TransactionExecutionInfo,
+};
+use crate::{
+    definitions::transaction_type::TransactionType,
+    services::api::contract_class::EntryPointType,
+    utils::{encode_felt, encode_hash},
+};
+use cairo_rs::vm::runners::cairo_runner::ExecutionResources;
+use felt::Felt252;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The calls made by a transaction, as a tree for each stage of its execution: validation,
+/// execution (the constructor of a deploy account, or the handler of an L1 handler) and fee
+/// transfer. Each node is the [`CallInfo`] of a call, with the calls it made in `internal_calls`.
+///
+/// Serializes to the result of the JSON-RPC `starknet_traceTransaction` method. Besides the fields
+/// of the spec, each invocation has its code address, storage read values, accessed storage keys,
+/// storage writes and execution resources.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(into = "TransactionTraceJson")]
+pub struct TransactionTrace {
+    pub tx_type: Option<TransactionType>,
+    pub validate_invocation: Option<CallInfo>,
+    pub execute_invocation: Option<CallInfo>,
+    pub fee_transfer_invocation: Option<CallInfo>,
+    /// Why the execution was reverted, if it was. Reverted executions have no invocation.
+    pub revert_error: Option<String>,
+}
+
+impl TransactionTrace {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+impl From<TransactionExecutionInfo> for TransactionTrace {
+    fn from(execution_info: TransactionExecutionInfo) -> Self {
+        TransactionTrace {
+            tx_type: execution_info.tx_type,
+            validate_invocation: execution_info.validate_info,
+            execute_invocation: execution_info.call_info,
+            fee_transfer_invocation: execution_info.fee_transfer_info,
+            revert_error: execution_info.revert_error,
+        }
+    }
+}
+
+// ---------------------------------
+//  JSON-RPC representation
+// ---------------------------------
+
+/// A `TRANSACTION_TRACE`. The key of the execute invocation depends on the transaction type.
+#[derive(Serialize)]
+struct TransactionTraceJson {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    tx_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validate_invocation: Option<FunctionInvocationJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execute_invocation: Option<ExecuteInvocationJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    constructor_invocation: Option<FunctionInvocationJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_invocation: Option<FunctionInvocationJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_transfer_invocation: Option<FunctionInvocationJson>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ExecuteInvocationJson {
+    Invocation(FunctionInvocationJson),
+    Reverted { revert_reason: String },
+}
+
+#[derive(Serialize)]
+struct FunctionInvocationJson {
+    contract_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry_point_selector: Option<String>,
+    calldata: Vec<String>,
+    caller_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry_point_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    call_type: Option<&'static str>,
+    result: Vec<String>,
+    calls: Vec<FunctionInvocationJson>,
+    events: Vec<EventJson>,
+    messages: Vec<MessageJson>,
+    storage_read_values: Vec<String>,
+    accessed_storage_keys: Vec<String>,
+    storage_writes: Vec<StorageWriteJson>,
+    execution_resources: BTreeMap<String, usize>,
+}
+
+/// The last value an invocation wrote to a key of the storage of its contract.
+#[derive(Serialize)]
+struct StorageWriteJson {
+    key: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct EventJson {
+    order: u64,
+    keys: Vec<String>,
+    data: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct MessageJson {
+    order: usize,
+    to_address: String,
+    payload: Vec<String>,
+}
+
+impl From<TransactionTrace> for TransactionTraceJson {
+    fn from(trace: TransactionTrace) -> Self {
+        let execute_invocation = match trace.revert_error {
+            Some(revert_reason) => Some(ExecuteInvocationJson::Reverted { revert_reason }),
+            None => trace
+                .execute_invocation
+                .as_ref()
+                .map(|call_info| ExecuteInvocationJson::Invocation(call_info.into())),
+        };
+
+        let mut trace_json = TransactionTraceJson {
+            tx_type: trace.tx_type.map(tx_type_name),
+            validate_invocation: trace.validate_invocation.as_ref().map(Into::into),
+            execute_invocation: None,
+            constructor_invocation: None,
+            function_invocation: None,
+            fee_transfer_invocation: trace.fee_transfer_invocation.as_ref().map(Into::into),
+        };
+        match (trace.tx_type, execute_invocation) {
+            (
+                Some(TransactionType::DeployAccount),
+                Some(ExecuteInvocationJson::Invocation(invocation)),
+            ) => trace_json.constructor_invocation = Some(invocation),
+            (
+                Some(TransactionType::L1Handler),
+                Some(ExecuteInvocationJson::Invocation(invocation)),
+            ) => trace_json.function_invocation = Some(invocation),
+            (_, execute_invocation) => trace_json.execute_invocation = execute_invocation,
+        }
+        trace_json
+    }
+}
+
+impl From<&CallInfo> for FunctionInvocationJson {
+    fn from(call_info: &CallInfo) -> Self {
+        let mut accessed_storage_keys: Vec<_> = call_info.accessed_storage_keys.iter().collect();
+        accessed_storage_keys.sort();
+        let mut storage_writes: Vec<_> = call_info.storage_writes.iter().collect();
+        storage_writes.sort_by_key(|(key, _)| *key);
+
+        FunctionInvocationJson {
+            contract_address: encode_felt(&call_info.contract_address.0),
+            entry_point_selector: call_info.entry_point_selector.as_ref().map(encode_felt),
+            calldata: encode_felts(&call_info.calldata),
+            caller_address: encode_felt(&call_info.caller_address.0),
+            code_address: call_info
+                .code_address
+                .as_ref()
+                .map(|address| encode_felt(&address.0)),
+            class_hash: call_info.class_hash.as_ref().map(encode_hash),
+            entry_point_type: call_info.entry_point_type.map(entry_point_type_name),
+            call_type: call_info.call_type.as_ref().map(call_type_name),
+            result: encode_felts(&call_info.retdata),
+            calls: call_info.internal_calls.iter().map(Into::into).collect(),
+            events: call_info.events.iter().map(Into::into).collect(),
+            messages: call_info.l2_to_l1_messages.iter().map(Into::into).collect(),
+            storage_read_values: encode_felts(&call_info.storage_read_values),
+            accessed_storage_keys: accessed_storage_keys.into_iter().map(encode_hash).collect(),
+            storage_writes: storage_writes
+                .into_iter()
+                .map(|(key, value)| StorageWriteJson {
+                    key: encode_hash(key),
+                    value: encode_felt(value),
+                })
+                .collect(),
+            execution_resources: encode_resources(&call_info.execution_resources),
+        }
+    }
+}
+
+impl From<&OrderedEvent> for EventJson {
+    fn from(event: &OrderedEvent) -> Self {
+        EventJson {
+            order: event.order,
+            keys: encode_felts(&event.keys),
+            data: encode_felts(&event.data),
+        }
+    }
+}
+
+impl From<&OrderedL2ToL1Message> for MessageJson {
+    fn from(message: &OrderedL2ToL1Message) -> Self {
+        MessageJson {
+            order: message.order,
+            to_address: encode_felt(&message.to_address.0),
+            payload: encode_felts(&message.payload),
+        }
+    }
+}
+
+fn tx_type_name(tx_type: TransactionType) -> &'static str {
+    match tx_type {
+        TransactionType::Declare => "DECLARE",
+        TransactionType::Deploy => "DEPLOY",
+        TransactionType::DeployAccount => "DEPLOY_ACCOUNT",
+        TransactionType::InitializeBlockInfo => "INITIALIZE_BLOCK_INFO",
+        TransactionType::InvokeFunction => "INVOKE",
+        TransactionType::L1Handler => "L1_HANDLER",
+    }
+}
+
+fn entry_point_type_name(entry_point_type: EntryPointType) -> &'static str {
+    match entry_point_type {
+        EntryPointType::External => "EXTERNAL",
+        EntryPointType::L1Handler => "L1_HANDLER",
+        EntryPointType::Constructor => "CONSTRUCTOR",
+    }
+}
+
+fn call_type_name(call_type: &CallType) -> &'static str {
+    match call_type {
+        CallType::Call => "CALL",
+        CallType::Delegate => "LIBRARY_CALL",
+    }
+}
+
+/// The `EXECUTION_RESOURCES` of an invocation: its steps, memory holes and the applications of
+/// each builtin, like `range_check_builtin_applications`.
+fn encode_resources(resources: &ExecutionResources) -> BTreeMap<String, usize> {
+    let mut resources_json: BTreeMap<_, _> = resources
+        .builtin_instance_counter
+        .iter()
+        .map(|(builtin, count)| (format!("{builtin}_applications"), *count))
+        .collect();
+    resources_json.insert("steps".to_string(), resources.n_steps);
+    resources_json.insert("memory_holes".to_string(), resources.n_memory_holes);
+    resources_json
+}
+
+fn encode_felts(values: &[Felt252]) -> Vec<String> {
+    values.iter().map(encode_felt).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Address;
+    use coverage_helper::test;
+    use serde_json::json;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn trace_serializes_like_starknet_trace_transaction() {
+        let inner_call = CallInfo {
+            caller_address: Address(1.into()),
+            call_type: Some(CallType::Delegate),
+            contract_address: Address(1.into()),
+            code_address: Some(Address(2.into())),
+            class_hash: Some([2; 32]),
+            entry_point_selector: Some(3.into()),
+            entry_point_type: Some(EntryPointType::External),
+            retdata: vec![4.into()],
+            events: vec![OrderedEvent::new(0, vec![5.into()], vec![6.into()])],
+            l2_to_l1_messages: vec![OrderedL2ToL1Message::new(
+                0,
+                Address(7.into()),
+                vec![8.into()],
+            )],
+            storage_read_values: vec![9.into()],
+            accessed_storage_keys: HashSet::from([[10; 32], [15; 32]]),
+            storage_writes: HashMap::from([([15; 32], 16.into()), ([10; 32], 17.into())]),
+            execution_resources: ExecutionResources {
+                n_steps: 11,
+                n_memory_holes: 0,
+                builtin_instance_counter: HashMap::from([("range_check_builtin".to_string(), 12)]),
+            },
+            ..Default::default()
+        };
+        let execution_info = TransactionExecutionInfo {
+            call_info: Some(CallInfo {
+                caller_address: Address(0.into()),
+                call_type: Some(CallType::Call),
+                contract_address: Address(1.into()),
+                entry_point_selector: Some(13.into()),
+                entry_point_type: Some(EntryPointType::External),
+                calldata: vec![14.into()],
+                internal_calls: vec![inner_call.clone()],
+                ..Default::default()
+            }),
+            tx_type: Some(TransactionType::InvokeFunction),
+            ..Default::default()
+        };
+
+        let trace = TransactionTrace::from(execution_info);
+        assert_eq!(
+            trace.execute_invocation.as_ref().unwrap().internal_calls,
+            vec![inner_call]
+        );
+
+        let empty_resources = json!({ "memory_holes": 0, "steps": 0 });
+        let class_hash = encode_hash(&[2; 32]);
+        let (read_key, written_key) = (encode_hash(&[10; 32]), encode_hash(&[15; 32]));
+        assert_eq!(
+            serde_json::to_value(&trace).unwrap(),
+            json!({
+                "type": "INVOKE",
+                "execute_invocation": {
+                    "contract_address": "0x1",
+                    "entry_point_selector": "0xd",
+                    "calldata": ["0xe"],
+                    "caller_address": "0x0",
+                    "entry_point_type": "EXTERNAL",
+                    "call_type": "CALL",
+                    "result": [],
+                    "calls": [{
+                        "contract_address": "0x1",
+                        "entry_point_selector": "0x3",
+                        "calldata": [],
+                        "caller_address": "0x1",
+                        "code_address": "0x2",
+                        "class_hash": class_hash,
+                        "entry_point_type": "EXTERNAL",
+                        "call_type": "LIBRARY_CALL",
+                        "result": ["0x4"],
+                        "calls": [],
+                        "events": [{ "order": 0, "keys": ["0x5"], "data": ["0x6"] }],
+                        "messages": [{ "order": 0, "to_address": "0x7", "payload": ["0x8"] }],
+                        "storage_read_values": ["0x9"],
+                        "accessed_storage_keys": [read_key, written_key],
+                        "storage_writes": [
+                            { "key": read_key, "value": "0x11" },
+                            { "key": written_key, "value": "0x10" },
+                        ],
+                        "execution_resources": {
+                            "memory_holes": 0,
+                            "range_check_builtin_applications": 12,
+                            "steps": 11,
+                        },
+                    }],
+                    "events": [],
+                    "messages": [],
+                    "storage_read_values": [],
+                    "accessed_storage_keys": [],
+                    "storage_writes": [],
+                    "execution_resources": empty_resources,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn reverted_trace_has_a_revert_reason() {
+        let trace = TransactionTrace::from(TransactionExecutionInfo {
+            tx_type: Some(TransactionType::InvokeFunction),
+            revert_error: Some("Entry point not found".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            serde_json::to_value(&trace).unwrap(),
+            json!({
+                "type": "INVOKE",
+                "execute_invocation": { "revert_reason": "Entry point not found" },
+            })
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::utils::ClassHash;
+use felt::Felt252;
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The state of a single contract: the class it's an instance of, its nonce and its storage.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ContractState {
+    pub class_hash: ClassHash,
+    pub nonce: Felt252,
+    // JSON maps only allow string keys, so the storage is kept as a list of (key, value) pairs.
+    #[serde(with = "storage_serde")]
+    pub storage: HashMap<[u8; 32], Felt252>,
+}
+
+impl ContractState {
+    pub fn new(class_hash: ClassHash, nonce: Felt252, storage: HashMap<[u8; 32], Felt252>) -> Self {
+        Self {
+            class_hash,
+            nonce,
+            storage,
+        }
+    }
+
+    /// A contract state that was never written to: no class, zero nonce and empty storage.
+    pub fn empty() -> Self {
+        Self::new([0; 32], Felt252::zero(), HashMap::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.class_hash == [0; 32] && self.nonce.is_zero() && self.storage.is_empty()
+    }
+}
+
+mod storage_serde {
+    use felt::Felt252;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        storage: &HashMap<[u8; 32], Felt252>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<_> = storage.iter().collect();
+        entries.sort_by_key(|(key, _)| **key);
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<[u8; 32], Felt252>, D::Error> {
+        Ok(Vec::<([u8; 32], Felt252)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coverage_helper::test;
+
+    #[test]
+    fn serde_roundtrip() {
+        let contract_state = ContractState::new(
+            [1; 32],
+            Felt252::new(3),
+            HashMap::from([([2; 32], Felt252::new(5)), ([0; 32], Felt252::new(7))]),
+        );
+
+        let serialized = serde_json::to_string(&contract_state).unwrap();
+        let deserialized: ContractState = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized, contract_state);
+    }
+
+    #[test]
+    fn empty_contract_state() {
+        assert!(ContractState::empty().is_empty());
+        assert!(!ContractState::new([1; 32], Felt252::zero(), HashMap::new()).is_empty());
+    }
+}
@@ -1,12 +1,15 @@
+use super::{contract_state::ContractState, state::StateDiff};
 use crate::{
-    business_logic::state::{state_api::StateReader, state_cache::StorageEntry},
+    business_logic::state::{
+        state_api::StateReader, state_api_objects::StateChanges, state_cache::StorageEntry,
+    },
     core::errors::state_errors::StateError,
-    services::api::contract_class::ContractClass,
-    utils::{Address, ClassHash},
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    utils::{Address, ClassHash, CompiledClassHash},
 };
 use felt::Felt252;
 use getset::{Getters, MutGetters};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 #[derive(Clone, Debug, Default, MutGetters, Getters, PartialEq)]
 pub struct InMemoryStateReader {
@@ -17,7 +20,11 @@ pub struct InMemoryStateReader {
     #[getset(get_mut = "pub")]
     pub address_to_storage: HashMap<StorageEntry, Felt252>,
     #[getset(get_mut = "pub")]
-    pub class_hash_to_contract_class: HashMap<ClassHash, ContractClass>,
+    pub class_hash_to_contract_class: HashMap<ClassHash, Arc<ContractClass>>,
+    #[getset(get_mut = "pub")]
+    pub class_hash_to_compiled_class_hash: HashMap<ClassHash, CompiledClassHash>,
+    #[getset(get_mut = "pub")]
+    pub compiled_class_hash_to_compiled_class: HashMap<CompiledClassHash, Arc<CasmContractClass>>,
 }
 
 impl InMemoryStateReader {
@@ -25,19 +32,80 @@ impl InMemoryStateReader {
         address_to_class_hash: HashMap<Address, ClassHash>,
         address_to_nonce: HashMap<Address, Felt252>,
         address_to_storage: HashMap<StorageEntry, Felt252>,
-        class_hash_to_contract_class: HashMap<ClassHash, ContractClass>,
+        class_hash_to_contract_class: HashMap<ClassHash, Arc<ContractClass>>,
     ) -> Self {
         Self {
             address_to_class_hash,
             address_to_nonce,
             address_to_storage,
             class_hash_to_contract_class,
+            class_hash_to_compiled_class_hash: HashMap::new(),
+            compiled_class_hash_to_compiled_class: HashMap::new(),
+        }
+    }
+
+    /// Groups the class hash, nonce and storage of every contract into its [`ContractState`].
+    pub fn contract_states(&self) -> HashMap<Address, ContractState> {
+        let mut contract_states: HashMap<Address, ContractState> = HashMap::new();
+
+        for (address, class_hash) in self.address_to_class_hash.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_default()
+                .class_hash = *class_hash;
+        }
+        for (address, nonce) in self.address_to_nonce.iter() {
+            contract_states.entry(address.clone()).or_default().nonce = nonce.clone();
+        }
+        for ((address, key), value) in self.address_to_storage.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_default()
+                .storage
+                .insert(*key, value.clone());
+        }
+
+        contract_states
+    }
+
+    /// Writes the changes of `state_diff` on top of the stored state.
+    ///
+    /// State diffs only carry the hashes of the declared classes, so their definitions must be
+    /// added to `class_hash_to_contract_class` beforehand. Nothing is written otherwise.
+    pub fn apply_state_diff(&mut self, state_diff: &StateDiff) -> Result<(), StateError> {
+        if state_diff
+            .declared_classes
+            .iter()
+            .any(|class_hash| !self.class_hash_to_contract_class.contains_key(class_hash))
+        {
+            return Err(StateError::MissingClassHash());
+        }
+
+        self.address_to_class_hash
+            .extend(state_diff.address_to_class_hash.clone());
+        self.address_to_class_hash
+            .extend(state_diff.replaced_classes.clone());
+        self.class_hash_to_compiled_class_hash
+            .extend(state_diff.class_hash_to_compiled_class_hash.clone());
+        self.address_to_nonce
+            .extend(state_diff.address_to_nonce.clone());
+        for (address, storage_updates) in state_diff.storage_updates.iter() {
+            self.address_to_storage.extend(
+                storage_updates
+                    .iter()
+                    .map(|(key, value)| ((address.clone(), *key), value.clone())),
+            );
         }
+
+        Ok(())
     }
 }
 
 impl StateReader for InMemoryStateReader {
-    fn get_contract_class(&mut self, class_hash: &ClassHash) -> Result<ContractClass, StateError> {
+    fn get_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Arc<ContractClass>, StateError> {
         let contract_class = self
             .class_hash_to_contract_class
             .get(class_hash)
@@ -71,8 +139,30 @@ impl StateReader for InMemoryStateReader {
         storage.cloned()
     }
 
-    fn count_actual_storage_changes(&mut self) -> (usize, usize) {
-        todo!()
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StateError> {
+        Ok(self
+            .class_hash_to_compiled_class_hash
+            .get(class_hash)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn get_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<Arc<CasmContractClass>, StateError> {
+        self.compiled_class_hash_to_compiled_class
+            .get(compiled_class_hash)
+            .cloned()
+            .ok_or(StateError::MissingCompiledClass(*compiled_class_hash))
+    }
+
+    fn count_actual_state_changes(&mut self) -> StateChanges {
+        // Everything stored here is already committed, changes are tracked by the cache on top.
+        StateChanges::default()
     }
 }
 
@@ -141,16 +231,15 @@ mod tests {
 
         state_reader
             .class_hash_to_contract_class
-            .insert([0; 32], contract_class.clone());
+            .insert([0; 32], Arc::new(contract_class.clone()));
         assert_eq!(
             state_reader.get_contract_class(&contract_class_key),
-            Ok(contract_class)
+            Ok(Arc::new(contract_class))
         )
     }
 
     #[test]
-    #[should_panic]
-    fn count_actual_storage_changes_is_a_wip() {
+    fn count_actual_state_changes_is_empty() {
         let mut state_reader = InMemoryStateReader::new(
             HashMap::new(),
             HashMap::new(),
@@ -158,6 +247,9 @@ mod tests {
             HashMap::new(),
         );
 
-        state_reader.count_actual_storage_changes();
+        assert_eq!(
+            state_reader.count_actual_state_changes(),
+            StateChanges::default()
+        );
     }
 }
@@ -1,2 +1,8 @@
+pub mod contract_state;
 pub mod in_memory_state_reader;
+pub mod persistent_state_reader;
+pub mod rpc_state_reader;
 pub mod state;
+pub mod state_commitment;
+pub mod state_update;
+pub mod storage_state_reader;
@@ -0,0 +1,527 @@
+use crate::{
+    business_logic::state::{
+        cached_state::{CachedState, CompiledClassCache, ContractClassCache, ContractClassSources},
+        state_api::StateReader,
+        state_api_objects::StateChanges,
+        state_cache::{StateCache, StorageEntry},
+    },
+    core::errors::state_errors::StateError,
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    starknet_storage::errors::storage_errors::StorageError,
+    utils::{
+        decode_felt, decode_hash, encode_felt, encode_hash, Address, ClassHash, CompiledClassHash,
+    },
+};
+use felt::Felt252;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const STATE_FILE_NAME: &str = "state.json";
+const CLASSES_DIR_NAME: &str = "classes";
+const COMPILED_CLASSES_DIR_NAME: &str = "compiled_classes";
+
+/// On-disk representation of the contract states. Every value is hex encoded and the maps are
+/// ordered so the file stays readable and stable between commits.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct PersistedState {
+    class_hashes: BTreeMap<String, String>,
+    nonces: BTreeMap<String, String>,
+    storage: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    compiled_class_hashes: BTreeMap<String, String>,
+}
+
+/// A [`StateReader`] backed by a directory on disk.
+///
+/// The directory holds a `state.json` file with the class hash, nonce and storage of every
+/// contract and the compiled class hashes of the Cairo 1 classes, a `classes` directory with one
+/// JSON file per declared contract class, named after its class hash, and a `compiled_classes`
+/// directory with the CASM classes, named after their compiled class hash. Classes are loaded
+/// lazily the first time they are requested.
+///
+/// The default reader is an empty state with no directory, it can be read from but not persisted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PersistentStateReader {
+    path: Option<PathBuf>,
+    address_to_class_hash: HashMap<Address, ClassHash>,
+    address_to_nonce: HashMap<Address, Felt252>,
+    address_to_storage: HashMap<StorageEntry, Felt252>,
+    class_hash_to_compiled_class_hash: HashMap<ClassHash, CompiledClassHash>,
+    class_hash_to_contract_class: HashMap<ClassHash, Arc<ContractClass>>,
+    compiled_class_hash_to_compiled_class: HashMap<CompiledClassHash, Arc<CasmContractClass>>,
+}
+
+impl PersistentStateReader {
+    /// Opens the state stored at `path`, creating an empty one if the directory doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StateError> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(path.join(CLASSES_DIR_NAME)).map_err(StorageError::from)?;
+        fs::create_dir_all(path.join(COMPILED_CLASSES_DIR_NAME)).map_err(StorageError::from)?;
+
+        let state_file = path.join(STATE_FILE_NAME);
+        let persisted_state: PersistedState = if state_file.exists() {
+            let bytes = fs::read(&state_file).map_err(StorageError::from)?;
+            serde_json::from_slice(&bytes).map_err(StorageError::from)?
+        } else {
+            PersistedState::default()
+        };
+
+        let mut address_to_class_hash = HashMap::new();
+        for (address, class_hash) in persisted_state.class_hashes {
+            let address = decode_felt(&address).map_err(StorageError::SerdeError)?;
+            let class_hash = decode_hash(&class_hash).map_err(StorageError::SerdeError)?;
+            address_to_class_hash.insert(Address(address), class_hash);
+        }
+
+        let mut address_to_nonce = HashMap::new();
+        for (address, nonce) in persisted_state.nonces {
+            let address = decode_felt(&address).map_err(StorageError::SerdeError)?;
+            let nonce = decode_felt(&nonce).map_err(StorageError::SerdeError)?;
+            address_to_nonce.insert(Address(address), nonce);
+        }
+
+        let mut address_to_storage = HashMap::new();
+        for (address, contract_storage) in persisted_state.storage {
+            let address = Address(decode_felt(&address).map_err(StorageError::SerdeError)?);
+            for (key, value) in contract_storage {
+                let key = decode_hash(&key).map_err(StorageError::SerdeError)?;
+                let value = decode_felt(&value).map_err(StorageError::SerdeError)?;
+                address_to_storage.insert((address.clone(), key), value);
+            }
+        }
+
+        let mut class_hash_to_compiled_class_hash = HashMap::new();
+        for (class_hash, compiled_class_hash) in persisted_state.compiled_class_hashes {
+            class_hash_to_compiled_class_hash.insert(
+                decode_hash(&class_hash).map_err(StorageError::SerdeError)?,
+                decode_hash(&compiled_class_hash).map_err(StorageError::SerdeError)?,
+            );
+        }
+
+        Ok(Self {
+            path: Some(path),
+            address_to_class_hash,
+            address_to_nonce,
+            address_to_storage,
+            class_hash_to_compiled_class_hash,
+            class_hash_to_contract_class: HashMap::new(),
+            compiled_class_hash_to_compiled_class: HashMap::new(),
+        })
+    }
+
+    /// The directory the state is stored in, if it was opened from one.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Applies the writes of `cache` and stores the given contract and compiled classes, then
+    /// flushes everything to disk. The contract classes that aren't stored yet are written as
+    /// their JSON in `contract_class_sources`.
+    pub fn persist(
+        &mut self,
+        cache: &StateCache,
+        contract_classes: &ContractClassCache,
+        contract_class_sources: &ContractClassSources,
+        compiled_classes: &CompiledClassCache,
+    ) -> Result<(), StateError> {
+        // Fail before applying anything if there's nowhere to persist it to.
+        self.dir()?;
+        for (class_hash, contract_class) in contract_classes {
+            self.write_contract_class(
+                class_hash,
+                contract_class,
+                contract_class_sources.get(class_hash),
+            )?;
+        }
+        for (compiled_class_hash, casm_class) in compiled_classes {
+            self.write_compiled_class(compiled_class_hash, casm_class)?;
+        }
+
+        self.address_to_class_hash
+            .extend(cache.class_hash_writes.clone());
+        self.address_to_nonce.extend(cache.nonce_writes.clone());
+        self.address_to_storage.extend(cache.storage_writes.clone());
+        self.class_hash_to_compiled_class_hash
+            .extend(cache.compiled_class_hash_writes.clone());
+
+        self.flush()
+    }
+
+    fn dir(&self) -> Result<&Path, StorageError> {
+        self.path().ok_or(StorageError::MissingStateDirectory)
+    }
+
+    fn class_path(&self, class_hash: &ClassHash) -> Option<PathBuf> {
+        let path = self.path()?;
+        Some(
+            path.join(CLASSES_DIR_NAME)
+                .join(format!("{}.json", encode_hash(class_hash))),
+        )
+    }
+
+    fn write_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+        contract_class: &Arc<ContractClass>,
+        source: Option<&Arc<str>>,
+    ) -> Result<(), StorageError> {
+        // Classes are immutable once declared, there's no point in writing them twice.
+        let class_path = self
+            .class_path(class_hash)
+            .ok_or(StorageError::MissingStateDirectory)?;
+        if !class_path.exists() {
+            let source = source.ok_or_else(|| {
+                StorageError::UnserializableContractClass(encode_hash(class_hash))
+            })?;
+            write_atomically(&class_path, source.as_bytes())?;
+        }
+
+        self.class_hash_to_contract_class
+            .insert(*class_hash, contract_class.clone());
+        Ok(())
+    }
+
+    fn compiled_class_path(&self, compiled_class_hash: &CompiledClassHash) -> Option<PathBuf> {
+        let path = self.path()?;
+        Some(
+            path.join(COMPILED_CLASSES_DIR_NAME)
+                .join(format!("{}.json", encode_hash(compiled_class_hash))),
+        )
+    }
+
+    fn write_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+        casm_class: &Arc<CasmContractClass>,
+    ) -> Result<(), StorageError> {
+        let compiled_class_path = self
+            .compiled_class_path(compiled_class_hash)
+            .ok_or(StorageError::MissingStateDirectory)?;
+        if !compiled_class_path.exists() {
+            write_atomically(
+                &compiled_class_path,
+                &serde_json::to_vec(casm_class.as_ref())?,
+            )?;
+        }
+
+        self.compiled_class_hash_to_compiled_class
+            .insert(*compiled_class_hash, casm_class.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), StateError> {
+        let mut persisted_state = PersistedState::default();
+
+        for (address, class_hash) in self.address_to_class_hash.iter() {
+            persisted_state
+                .class_hashes
+                .insert(encode_felt(&address.0), encode_hash(class_hash));
+        }
+        for (address, nonce) in self.address_to_nonce.iter() {
+            persisted_state
+                .nonces
+                .insert(encode_felt(&address.0), encode_felt(nonce));
+        }
+        for ((address, key), value) in self.address_to_storage.iter() {
+            persisted_state
+                .storage
+                .entry(encode_felt(&address.0))
+                .or_default()
+                .insert(encode_hash(key), encode_felt(value));
+        }
+
+        for (class_hash, compiled_class_hash) in self.class_hash_to_compiled_class_hash.iter() {
+            persisted_state
+                .compiled_class_hashes
+                .insert(encode_hash(class_hash), encode_hash(compiled_class_hash));
+        }
+
+        let bytes = serde_json::to_vec_pretty(&persisted_state).map_err(StorageError::from)?;
+        write_atomically(&self.dir()?.join(STATE_FILE_NAME), &bytes)?;
+        Ok(())
+    }
+}
+
+impl StateReader for PersistentStateReader {
+    fn get_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Arc<ContractClass>, StateError> {
+        if let Some(contract_class) = self.class_hash_to_contract_class.get(class_hash) {
+            return Ok(contract_class.clone());
+        }
+
+        let class_path = match self.class_path(class_hash) {
+            Some(class_path) if class_path.exists() => class_path,
+            _ => return Err(StateError::MissingClassHash()),
+        };
+        let source_json = fs::read_to_string(class_path).map_err(StorageError::from)?;
+        let contract_class = ContractClass::try_from(source_json.as_str())
+            .map_err(|error| StorageError::SerdeError(error.to_string()))?;
+        contract_class.validate()?;
+        let contract_class = Arc::new(contract_class);
+
+        self.class_hash_to_contract_class
+            .insert(*class_hash, contract_class.clone());
+        Ok(contract_class)
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: &Address) -> Result<ClassHash, StateError> {
+        self.address_to_class_hash
+            .get(contract_address)
+            .cloned()
+            .ok_or_else(|| StateError::NoneContractState(contract_address.clone()))
+    }
+
+    fn get_nonce_at(&mut self, contract_address: &Address) -> Result<Felt252, StateError> {
+        self.address_to_nonce
+            .get(contract_address)
+            .cloned()
+            .ok_or_else(|| StateError::NoneContractState(contract_address.clone()))
+    }
+
+    fn get_storage_at(&mut self, storage_entry: &StorageEntry) -> Result<Felt252, StateError> {
+        self.address_to_storage
+            .get(storage_entry)
+            .cloned()
+            .ok_or_else(|| StateError::NoneStorage(storage_entry.clone()))
+    }
+
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StateError> {
+        Ok(self
+            .class_hash_to_compiled_class_hash
+            .get(class_hash)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn get_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<Arc<CasmContractClass>, StateError> {
+        if let Some(casm_class) = self
+            .compiled_class_hash_to_compiled_class
+            .get(compiled_class_hash)
+        {
+            return Ok(casm_class.clone());
+        }
+
+        let compiled_class_path = match self.compiled_class_path(compiled_class_hash) {
+            Some(compiled_class_path) if compiled_class_path.exists() => compiled_class_path,
+            _ => return Err(StateError::MissingCompiledClass(*compiled_class_hash)),
+        };
+        let bytes = fs::read(compiled_class_path).map_err(StorageError::from)?;
+        let casm_class: CasmContractClass =
+            serde_json::from_slice(&bytes).map_err(StorageError::from)?;
+        let casm_class = Arc::new(casm_class);
+
+        self.compiled_class_hash_to_compiled_class
+            .insert(*compiled_class_hash, casm_class.clone());
+        Ok(casm_class)
+    }
+
+    fn count_actual_state_changes(&mut self) -> StateChanges {
+        // Everything stored here is already committed, changes are tracked by the cache on top.
+        StateChanges::default()
+    }
+}
+
+impl CachedState<PersistentStateReader> {
+    /// Persists the writes accumulated in the cache, along with the contract and compiled
+    /// classes declared since the last time, and starts over with an empty cache and no class
+    /// declared. Open checkpoints are discarded.
+    ///
+    /// The classes declared on this state are written as the JSON given to
+    /// `set_contract_class_source`, so declaring one without it makes this fail.
+    pub fn persist(&mut self) -> Result<(), StateError> {
+        let (contract_classes, compiled_classes) = self.classes_to_persist();
+        self.state_reader.persist(
+            &self.cache,
+            &contract_classes,
+            &self.contract_class_sources,
+            &compiled_classes,
+        )?;
+        self.clear_updates();
+        Ok(())
+    }
+}
+
+/// Writes to a temporary file first and then renames it, so a crash mid-write never leaves
+/// a truncated file behind.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), StorageError> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::state::state_api::State,
+        services::api::contract_class::ContractClassWithSource,
+    };
+    use coverage_helper::test;
+
+    fn temp_state_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "starknet_rs_persistent_state_{name}_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn open_empty_directory() {
+        let path = temp_state_dir("open_empty_directory");
+        let mut state_reader = PersistentStateReader::open(&path).unwrap();
+        let address = Address(1.into());
+
+        assert_eq!(
+            state_reader.get_class_hash_at(&address),
+            Err(StateError::NoneContractState(address.clone()))
+        );
+        assert_eq!(
+            state_reader.get_storage_at(&(address.clone(), [1; 32])),
+            Err(StateError::NoneStorage((address, [1; 32])))
+        );
+        assert_eq!(
+            state_reader.get_contract_class(&[1; 32]),
+            Err(StateError::MissingClassHash())
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn persist_and_reopen() {
+        let path = temp_state_dir("persist_and_reopen");
+        let ContractClassWithSource {
+            contract_class,
+            source,
+        } = ContractClassWithSource::try_from(&PathBuf::from("starknet_programs/fibonacci.json"))
+            .unwrap();
+        let class_hash = [3; 32];
+        let address = Address(1111.into());
+        let storage_entry = (address.clone(), [7; 32]);
+
+        let mut state = CachedState::new(
+            PersistentStateReader::open(&path).unwrap(),
+            Some(HashMap::new()),
+        );
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+        state.set_contract_class_source(&class_hash, source.unwrap());
+        state.deploy_contract(address.clone(), class_hash).unwrap();
+        state.increment_nonce(&address).unwrap();
+        state.set_storage_at(&storage_entry, Felt252::new(42));
+        let casm_class = CasmContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.casm",
+        ))
+        .unwrap();
+        state.set_compiled_class_hash(&[4; 32], &[5; 32]).unwrap();
+        state
+            .set_compiled_class(&[5; 32], casm_class.clone())
+            .unwrap();
+        state.persist().unwrap();
+
+        assert_eq!(state.cache(), &StateCache::default());
+        assert!(state.declared_classes().is_empty());
+        assert_eq!(state.count_actual_state_changes(), StateChanges::default());
+        assert_eq!(state.get_storage_at(&storage_entry), Ok(Felt252::new(42)));
+
+        let mut state_reader = PersistentStateReader::open(&path).unwrap();
+        assert_eq!(state_reader.get_class_hash_at(&address), Ok(class_hash));
+        assert_eq!(state_reader.get_nonce_at(&address), Ok(Felt252::new(1)));
+        assert_eq!(
+            state_reader.get_storage_at(&storage_entry),
+            Ok(Felt252::new(42))
+        );
+        assert_eq!(
+            state_reader.get_contract_class(&class_hash),
+            Ok(Arc::new(contract_class))
+        );
+        assert_eq!(state_reader.get_compiled_class_hash(&[4; 32]), Ok([5; 32]));
+        assert_eq!(state_reader.get_compiled_class_hash(&[5; 32]), Ok([0; 32]));
+        assert_eq!(
+            state_reader.get_compiled_class(&[5; 32]),
+            Ok(Arc::new(casm_class))
+        );
+        assert_eq!(
+            state_reader.get_compiled_class(&[4; 32]),
+            Err(StateError::MissingCompiledClass([4; 32]))
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn persist_class_without_source_fails() {
+        let path = temp_state_dir("persist_class_without_source_fails");
+        let contract_class = ContractClass::new(Default::default(), HashMap::new(), None).unwrap();
+
+        let mut state_reader = PersistentStateReader::open(&path).unwrap();
+        let contract_classes = HashMap::from([([5; 32], Arc::new(contract_class))]);
+
+        assert_eq!(
+            state_reader.persist(
+                &StateCache::default(),
+                &contract_classes,
+                &HashMap::new(),
+                &HashMap::new()
+            ),
+            Err(StateError::Storage(
+                StorageError::UnserializableContractClass(encode_hash(&[5; 32]))
+            ))
+        );
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn persist_only_declared_classes() {
+        let path = temp_state_dir("persist_only_declared_classes");
+        let contract_class = ContractClass::new(Default::default(), HashMap::new(), None).unwrap();
+
+        // The class is cached without being declared on this state, so it needs no source.
+        let mut state = CachedState::new(
+            PersistentStateReader::open(&path).unwrap(),
+            Some(HashMap::from([([5; 32], Arc::new(contract_class))])),
+        );
+        state.set_storage_at(&(Address(1.into()), [1; 32]), Felt252::new(1));
+        state.persist().unwrap();
+
+        assert!(!path
+            .join(CLASSES_DIR_NAME)
+            .join(format!("{}.json", encode_hash(&[5; 32])))
+            .exists());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn default_state_cant_be_persisted() {
+        let mut state = CachedState::new(PersistentStateReader::default(), Some(HashMap::new()));
+
+        assert_eq!(
+            state.get_contract_class(&[1; 32]),
+            Err(StateError::MissingClassHash())
+        );
+        state.set_storage_at(&(Address(1.into()), [1; 32]), Felt252::new(1));
+        assert_eq!(
+            state.persist(),
+            Err(StateError::Storage(StorageError::MissingStateDirectory))
+        );
+    }
+}
@@ -0,0 +1,629 @@
+use crate::{
+    business_logic::state::{
+        state_api::StateReader, state_api_objects::StateChanges, state_cache::StorageEntry,
+    },
+    core::errors::state_errors::StateError,
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    utils::{
+        decode_felt, decode_hash, encode_felt, encode_hash, Address, ClassHash, CompiledClassHash,
+    },
+};
+use actix_web::rt::System;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use felt::Felt252;
+use flate2::read::GzDecoder;
+use getset::Getters;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+// JSON-RPC error codes of the Starknet specification.
+const CONTRACT_NOT_FOUND: i64 = 20;
+const CLASS_HASH_NOT_FOUND: i64 = 28;
+
+// Contract classes easily exceed the default 2MB limit of awc.
+const MAX_RESPONSE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The block the state is read at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockId {
+    Number(u64),
+    Hash(Felt252),
+}
+
+impl BlockId {
+    fn to_json(&self) -> Value {
+        match self {
+            BlockId::Number(number) => json!({ "block_number": number }),
+            BlockId::Hash(hash) => json!({ "block_hash": encode_felt(hash) }),
+        }
+    }
+}
+
+/// A request body, and where to send the node's response to it.
+type RpcRequest = (Value, mpsc::Sender<Result<Value, String>>);
+
+/// Posts requests to a node from a thread that runs an actix runtime, which the awc client needs.
+/// Having its own thread also makes it work when called from inside a running runtime, like the
+/// devnet server.
+///
+/// The thread and its client are shared by the clones of the client, and stop once they are all
+/// dropped.
+#[derive(Clone, Debug)]
+struct RpcClient {
+    requests: mpsc::SyncSender<RpcRequest>,
+}
+
+impl RpcClient {
+    fn new(url: String) -> Self {
+        let (requests, receiver) = mpsc::sync_channel::<RpcRequest>(0);
+        thread::spawn(move || {
+            System::new().block_on(async move {
+                let client = awc::Client::new();
+                while let Ok((body, response_sender)) = receiver.recv() {
+                    let response = async {
+                        let mut response = client
+                            .post(&url)
+                            .send_json(&body)
+                            .await
+                            .map_err(|error| error.to_string())?;
+                        response
+                            .json::<Value>()
+                            .limit(MAX_RESPONSE_SIZE)
+                            .await
+                            .map_err(|error| error.to_string())
+                    }
+                    .await;
+                    // The requester only goes away if it panicked.
+                    let _ = response_sender.send(response);
+                }
+            })
+        });
+
+        RpcClient { requests }
+    }
+
+    /// Posts `body` and waits for the response.
+    fn post(&self, body: Value) -> Result<Value, String> {
+        let (response_sender, response) = mpsc::channel();
+        self.requests
+            .send((body, response_sender))
+            .map_err(|_| String::from("the RPC client thread stopped"))?;
+        response
+            .recv()
+            .map_err(|_| String::from("the RPC client thread stopped"))?
+    }
+}
+
+/// The values read from the node. The block is pinned, so they never change.
+#[derive(Debug, Default)]
+struct RpcCache {
+    class_hashes: HashMap<Address, ClassHash>,
+    nonces: HashMap<Address, Felt252>,
+    storage: HashMap<StorageEntry, Felt252>,
+    /// Only holds Cairo 0 classes, the Sierra ones can't be parsed as a [`ContractClass`].
+    contract_classes: HashMap<ClassHash, Arc<ContractClass>>,
+    compiled_class_hashes: HashMap<ClassHash, CompiledClassHash>,
+    /// The Cairo 1 classes, whose compiled class hash the node doesn't expose.
+    sierra_class_hashes: HashSet<ClassHash>,
+}
+
+/// A [`StateReader`] that forks the state of a Starknet node at a pinned block.
+///
+/// Values are fetched through the node's JSON-RPC API the first time they are read, and kept
+/// in a local cache afterwards, so each of them is only requested once. The cache is shared by
+/// the clones of the reader, although clones reading the same value at the same time may both
+/// request it.
+#[derive(Clone, Debug, Getters)]
+pub struct RpcStateReader {
+    #[getset(get = "pub")]
+    url: String,
+    #[getset(get = "pub")]
+    block_id: BlockId,
+    client: RpcClient,
+    cache: Arc<Mutex<RpcCache>>,
+}
+
+impl RpcStateReader {
+    pub fn new(url: &str, block_id: BlockId) -> Self {
+        Self {
+            url: url.to_string(),
+            block_id,
+            client: RpcClient::new(url.to_string()),
+            cache: Arc::new(Mutex::new(RpcCache::default())),
+        }
+    }
+
+    // The lock is never held while requesting the node, and a panic while holding it can't leave
+    // the cache inconsistent, so a poisoned lock is still safe to use.
+    fn cache(&self) -> MutexGuard<'_, RpcCache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sends a JSON-RPC request and returns its result. Errors returned by the node come back
+    /// with their code, so callers can tell missing values apart from failures.
+    fn request(&self, method: &str, params: Value) -> Result<Value, (Option<i64>, String)> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": method,
+            "params": params,
+        });
+        let response = self.client.post(body).map_err(|error| (None, error))?;
+
+        if let Some(error) = response.get("error") {
+            return Err((
+                error.get("code").and_then(Value::as_i64),
+                error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            ));
+        }
+        response
+            .get("result")
+            .cloned()
+            .ok_or_else(|| (None, format!("{method} returned no result")))
+    }
+
+    fn request_felt(&self, method: &str, params: Value) -> Result<Felt252, (Option<i64>, String)> {
+        let result = self.request(method, params)?;
+        result
+            .as_str()
+            .and_then(|value| decode_felt(value).ok())
+            .ok_or_else(|| (None, format!("{method} returned an invalid felt: {result}")))
+    }
+}
+
+/// Builds a contract class from a `DEPRECATED_CONTRACT_CLASS` RPC object, whose program is
+/// gzipped and base64 encoded.
+fn parse_rpc_contract_class(rpc_class: &Value) -> Result<ContractClass, StateError> {
+    let encoded_program = rpc_class
+        .get("program")
+        .and_then(Value::as_str)
+        .ok_or_else(|| StateError::Rpc(String::from("contract class without program")))?;
+    let compressed_program = STANDARD
+        .decode(encoded_program)
+        .map_err(|error| StateError::Rpc(error.to_string()))?;
+    let mut program = String::new();
+    GzDecoder::new(compressed_program.as_slice())
+        .read_to_string(&mut program)
+        .map_err(|error| StateError::Rpc(error.to_string()))?;
+    let program: Value =
+        serde_json::from_str(&program).map_err(|error| StateError::Rpc(error.to_string()))?;
+
+    let contract_class = json!({
+        "program": program,
+        "entry_points_by_type": rpc_class.get("entry_points_by_type"),
+        "abi": rpc_class.get("abi").cloned().unwrap_or_else(|| json!([])),
+    });
+    ContractClass::try_from(contract_class.to_string().as_str())
+        .map_err(|error| StateError::Rpc(error.to_string()))
+}
+
+impl StateReader for RpcStateReader {
+    fn get_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Arc<ContractClass>, StateError> {
+        if let Some(contract_class) = self.cache().contract_classes.get(class_hash) {
+            return Ok(contract_class.clone());
+        }
+
+        let params = json!({
+            "block_id": self.block_id.to_json(),
+            "class_hash": encode_hash(class_hash),
+        });
+        let contract_class = match self.request("starknet_getClass", params) {
+            Ok(rpc_class) => parse_rpc_contract_class(&rpc_class)?,
+            Err((Some(CLASS_HASH_NOT_FOUND), _)) => return Err(StateError::MissingClassHash()),
+            Err((_, message)) => return Err(StateError::Rpc(message)),
+        };
+        contract_class.validate()?;
+        let contract_class = Arc::new(contract_class);
+
+        self.cache()
+            .contract_classes
+            .insert(*class_hash, contract_class.clone());
+        Ok(contract_class)
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: &Address) -> Result<ClassHash, StateError> {
+        if let Some(class_hash) = self.cache().class_hashes.get(contract_address) {
+            return Ok(*class_hash);
+        }
+
+        let params = json!({
+            "block_id": self.block_id.to_json(),
+            "contract_address": encode_felt(&contract_address.0),
+        });
+        let class_hash = match self.request("starknet_getClassHashAt", params) {
+            Ok(Value::String(class_hash)) => decode_hash(&class_hash).map_err(StateError::Rpc)?,
+            Ok(result) => return Err(StateError::Rpc(format!("invalid class hash: {result}"))),
+            Err((Some(CONTRACT_NOT_FOUND), _)) => {
+                return Err(StateError::NoneContractState(contract_address.clone()))
+            }
+            Err((_, message)) => return Err(StateError::Rpc(message)),
+        };
+
+        self.cache()
+            .class_hashes
+            .insert(contract_address.clone(), class_hash);
+        Ok(class_hash)
+    }
+
+    fn get_nonce_at(&mut self, contract_address: &Address) -> Result<Felt252, StateError> {
+        if let Some(nonce) = self.cache().nonces.get(contract_address) {
+            return Ok(nonce.clone());
+        }
+
+        let params = json!({
+            "block_id": self.block_id.to_json(),
+            "contract_address": encode_felt(&contract_address.0),
+        });
+        let nonce = match self.request_felt("starknet_getNonce", params) {
+            Ok(nonce) => nonce,
+            Err((Some(CONTRACT_NOT_FOUND), _)) => {
+                return Err(StateError::NoneContractState(contract_address.clone()))
+            }
+            Err((_, message)) => return Err(StateError::Rpc(message)),
+        };
+
+        self.cache()
+            .nonces
+            .insert(contract_address.clone(), nonce.clone());
+        Ok(nonce)
+    }
+
+    fn get_storage_at(&mut self, storage_entry: &StorageEntry) -> Result<Felt252, StateError> {
+        if let Some(value) = self.cache().storage.get(storage_entry) {
+            return Ok(value.clone());
+        }
+
+        let (address, key) = storage_entry;
+        let params = json!({
+            "contract_address": encode_felt(&address.0),
+            "key": encode_hash(key),
+            "block_id": self.block_id.to_json(),
+        });
+        let value = match self.request_felt("starknet_getStorageAt", params) {
+            Ok(value) => value,
+            Err((Some(CONTRACT_NOT_FOUND), _)) => {
+                return Err(StateError::NoneStorage(storage_entry.clone()))
+            }
+            Err((_, message)) => return Err(StateError::Rpc(message)),
+        };
+
+        self.cache()
+            .storage
+            .insert(storage_entry.clone(), value.clone());
+        Ok(value)
+    }
+
+    /// The JSON-RPC API doesn't expose compiled class hashes. Classes the node doesn't know
+    /// and Cairo 0 classes have none, but declared Cairo 1 classes fail with
+    /// [`StateError::UnknownCompiledClassHash`].
+    ///
+    /// Telling them apart takes fetching the class, unless it was read already. A Cairo 0 class
+    /// fetched here is kept for [`StateReader::get_contract_class`].
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StateError> {
+        {
+            let cache = self.cache();
+            if let Some(compiled_class_hash) = cache.compiled_class_hashes.get(class_hash) {
+                return Ok(*compiled_class_hash);
+            }
+            if cache.contract_classes.contains_key(class_hash) {
+                return Ok([0; 32]);
+            }
+            if cache.sierra_class_hashes.contains(class_hash) {
+                return Err(StateError::UnknownCompiledClassHash(*class_hash));
+            }
+        }
+
+        let params = json!({
+            "block_id": self.block_id.to_json(),
+            "class_hash": encode_hash(class_hash),
+        });
+        match self.request("starknet_getClass", params) {
+            Ok(rpc_class) if rpc_class.get("sierra_program").is_some() => {
+                self.cache().sierra_class_hashes.insert(*class_hash);
+                return Err(StateError::UnknownCompiledClassHash(*class_hash));
+            }
+            Ok(rpc_class) => {
+                let contract_class = parse_rpc_contract_class(&rpc_class)?;
+                contract_class.validate()?;
+                self.cache()
+                    .contract_classes
+                    .insert(*class_hash, Arc::new(contract_class));
+            }
+            Err((Some(CLASS_HASH_NOT_FOUND), _)) => {}
+            Err((_, message)) => return Err(StateError::Rpc(message)),
+        }
+
+        self.cache()
+            .compiled_class_hashes
+            .insert(*class_hash, [0; 32]);
+        Ok([0; 32])
+    }
+
+    // The JSON-RPC API only serves Sierra classes, their CASM has to be set on the state on top.
+    fn get_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<Arc<CasmContractClass>, StateError> {
+        Err(StateError::MissingCompiledClass(*compiled_class_hash))
+    }
+
+    fn count_actual_state_changes(&mut self) -> StateChanges {
+        // The forked state is read only, changes are tracked by the cache on top.
+        StateChanges::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::state::{cached_state::CachedState, state_api::State};
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use coverage_helper::test;
+    use flate2::{write::GzEncoder, Compression};
+    use std::{
+        io::Write,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+    };
+
+    const CONTRACT_ADDRESS: u64 = 0x1234;
+
+    fn rpc_contract_class() -> Value {
+        let contract_class: Value =
+            serde_json::from_str(include_str!("../../../starknet_programs/fibonacci.json"))
+                .unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(contract_class["program"].to_string().as_bytes())
+            .unwrap();
+        json!({
+            "program": STANDARD.encode(encoder.finish().unwrap()),
+            "entry_points_by_type": contract_class["entry_points_by_type"],
+            "abi": contract_class["abi"],
+        })
+    }
+
+    async fn handle_request(
+        request: web::Json<Value>,
+        request_count: web::Data<Arc<AtomicUsize>>,
+    ) -> HttpResponse {
+        request_count.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(request["params"]["block_id"], json!({ "block_number": 7 }));
+
+        let address = encode_felt(&Felt252::from(CONTRACT_ADDRESS));
+        let known_contract = request["params"]["contract_address"] == json!(address);
+        let response = match request["method"].as_str().unwrap() {
+            "starknet_getClassHashAt" if known_contract => json!({ "result": "0x1" }),
+            "starknet_getNonce" if known_contract => json!({ "result": "0x5" }),
+            "starknet_getStorageAt" if known_contract => json!({ "result": "0x2a" }),
+            "starknet_getClass"
+                if request["params"]["class_hash"] == json!(encode_hash(&[1; 32])) =>
+            {
+                json!({ "result": rpc_contract_class() })
+            }
+            "starknet_getClass"
+                if request["params"]["class_hash"] == json!(encode_hash(&[3; 32])) =>
+            {
+                json!({
+                    "result": {
+                        "sierra_program": [],
+                        "contract_class_version": "0.1.0",
+                        "entry_points_by_type": {},
+                        "abi": "[]",
+                    }
+                })
+            }
+            "starknet_getClass" => json!({
+                "error": { "code": CLASS_HASH_NOT_FOUND, "message": "Class hash not found" }
+            }),
+            _ => json!({
+                "error": { "code": CONTRACT_NOT_FOUND, "message": "Contract not found" }
+            }),
+        };
+
+        HttpResponse::Ok().json(response)
+    }
+
+    /// Starts a mock JSON-RPC node on a free port, returning its url and the count of requests
+    /// it served.
+    fn mock_rpc_node() -> (String, Arc<AtomicUsize>) {
+        let request_count = Arc::new(AtomicUsize::new(0));
+        let server_request_count = request_count.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            System::new().block_on(async move {
+                let server = HttpServer::new(move || {
+                    App::new()
+                        .app_data(web::Data::new(server_request_count.clone()))
+                        .app_data(web::JsonConfig::default().limit(MAX_RESPONSE_SIZE))
+                        .route("/", web::post().to(handle_request))
+                })
+                .workers(1)
+                .bind(("127.0.0.1", 0))
+                .unwrap();
+                sender.send(server.addrs()[0]).unwrap();
+                server.run().await
+            })
+        });
+
+        (
+            format!("http://{}", receiver.recv().unwrap()),
+            request_count,
+        )
+    }
+
+    #[test]
+    fn values_are_fetched_once() {
+        let (url, request_count) = mock_rpc_node();
+        let mut state_reader = RpcStateReader::new(&url, BlockId::Number(7));
+        let address = Address(CONTRACT_ADDRESS.into());
+        let storage_entry = (address.clone(), Felt252::new(3).to_be_bytes());
+
+        let mut class_hash = [0; 32];
+        class_hash[31] = 1;
+        assert_eq!(state_reader.get_class_hash_at(&address), Ok(class_hash));
+        assert_eq!(state_reader.get_nonce_at(&address), Ok(Felt252::new(5)));
+        assert_eq!(
+            state_reader.get_storage_at(&storage_entry),
+            Ok(Felt252::new(42))
+        );
+        assert_eq!(request_count.load(Ordering::SeqCst), 3);
+
+        assert_eq!(state_reader.get_class_hash_at(&address), Ok(class_hash));
+        assert_eq!(state_reader.get_nonce_at(&address), Ok(Felt252::new(5)));
+        assert_eq!(
+            state_reader.get_storage_at(&storage_entry),
+            Ok(Felt252::new(42))
+        );
+        assert_eq!(request_count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn missing_values() {
+        let (url, _) = mock_rpc_node();
+        let mut state_reader = RpcStateReader::new(&url, BlockId::Number(7));
+        let address = Address(1.into());
+
+        assert_eq!(
+            state_reader.get_class_hash_at(&address),
+            Err(StateError::NoneContractState(address.clone()))
+        );
+        assert_eq!(
+            state_reader.get_storage_at(&(address.clone(), [0; 32])),
+            Err(StateError::NoneStorage((address, [0; 32])))
+        );
+        assert_eq!(
+            state_reader.get_contract_class(&[2; 32]),
+            Err(StateError::MissingClassHash())
+        );
+    }
+
+    #[test]
+    fn fetch_contract_class() {
+        let (url, request_count) = mock_rpc_node();
+        let mut state_reader = RpcStateReader::new(&url, BlockId::Number(7));
+        let contract_class = Arc::new(
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap(),
+        );
+
+        assert_eq!(
+            state_reader.get_contract_class(&[1; 32]),
+            Ok(contract_class.clone())
+        );
+        assert_eq!(
+            state_reader.get_contract_class(&[1; 32]),
+            Ok(contract_class)
+        );
+        assert_eq!(request_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn compiled_class_hashes() {
+        let (url, request_count) = mock_rpc_node();
+        let mut state_reader = RpcStateReader::new(&url, BlockId::Number(7));
+
+        // Cairo 0 classes, and classes that aren't declared, have no compiled class.
+        assert_eq!(state_reader.get_compiled_class_hash(&[1; 32]), Ok([0; 32]));
+        assert_eq!(state_reader.get_compiled_class_hash(&[2; 32]), Ok([0; 32]));
+        assert!(state_reader.get_contract_class(&[1; 32]).is_ok());
+        assert_eq!(state_reader.get_compiled_class_hash(&[1; 32]), Ok([0; 32]));
+        assert_eq!(state_reader.get_compiled_class_hash(&[2; 32]), Ok([0; 32]));
+        assert_eq!(request_count.load(Ordering::SeqCst), 2);
+
+        for _ in 0..2 {
+            assert_eq!(
+                state_reader.get_compiled_class_hash(&[3; 32]),
+                Err(StateError::UnknownCompiledClassHash([3; 32]))
+            );
+        }
+        assert_eq!(request_count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn compiled_class_hash_of_read_class() {
+        let (url, request_count) = mock_rpc_node();
+        let mut state_reader = RpcStateReader::new(&url, BlockId::Number(7));
+
+        // A class that was read is a Cairo 0 one, there's no need to fetch it again.
+        assert!(state_reader.get_contract_class(&[1; 32]).is_ok());
+        assert_eq!(state_reader.get_compiled_class_hash(&[1; 32]), Ok([0; 32]));
+        assert_eq!(request_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn clones_request_from_other_threads() {
+        let (url, request_count) = mock_rpc_node();
+        let state_reader = RpcStateReader::new(&url, BlockId::Number(7));
+        let address = Address(CONTRACT_ADDRESS.into());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mut state_reader = state_reader.clone();
+                let address = address.clone();
+                thread::spawn(move || state_reader.get_nonce_at(&address))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Ok(Felt252::new(5)));
+        }
+        let n_requests = request_count.load(Ordering::SeqCst);
+        assert!((1..=4).contains(&n_requests));
+
+        // The clones filled the cache of the reader they were cloned from.
+        assert_eq!(
+            state_reader.clone().get_nonce_at(&address),
+            Ok(Felt252::new(5))
+        );
+        assert_eq!(request_count.load(Ordering::SeqCst), n_requests);
+    }
+
+    #[test]
+    fn cached_state_on_top_of_forked_state() {
+        let (url, _) = mock_rpc_node();
+        let address = Address(CONTRACT_ADDRESS.into());
+        let storage_entry = (address.clone(), Felt252::new(3).to_be_bytes());
+
+        let mut state = CachedState::new(
+            RpcStateReader::new(&url, BlockId::Number(7)),
+            Some(HashMap::new()),
+        );
+        state.set_storage_at(&storage_entry, Felt252::new(43));
+
+        assert_eq!(state.get_storage_at(&storage_entry), Ok(Felt252::new(43)));
+        assert_eq!(state.get_nonce_at(&address), Ok(Felt252::new(5)));
+        assert_eq!(
+            state.state_reader().clone().get_storage_at(&storage_entry),
+            Ok(Felt252::new(42))
+        );
+    }
+
+    #[test]
+    fn unreachable_node() {
+        let mut state_reader = RpcStateReader::new("http://127.0.0.1:1", BlockId::Number(7));
+
+        assert_matches!(
+            state_reader.get_nonce_at(&Address(1.into())),
+            Err(StateError::Rpc(_))
+        );
+    }
+}
@@ -1,20 +1,31 @@
+use super::state_update::StateDiffJson;
 use crate::{
-    business_logic::state::{cached_state::CachedState, state_api::StateReader},
+    business_logic::{
+        execution::profiler::Profiler,
+        state::{
+            cached_state::{CachedState, UNINITIALIZED_CLASS_HASH},
+            state_api::StateReader,
+        },
+    },
     core::errors::state_errors::StateError,
     starkware_utils::starkware_errors::StarkwareError,
     utils::{
         get_keys, subtract_mappings, to_cache_state_storage_mapping, to_state_diff_storage_mapping,
-        Address, ClassHash,
+        Address, ClassHash, CompiledClassHash,
     },
 };
 use cairo_rs::vm::runners::cairo_runner::ExecutionResources;
 use felt::Felt252;
-use std::collections::HashMap;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Debug, Default)]
 pub struct ExecutionResourcesManager {
     pub(crate) syscall_counter: HashMap<String, u64>,
     pub(crate) cairo_usage: ExecutionResources,
+    /// Profiles the entry points run with the manager when set.
+    pub(crate) profiler: Option<Profiler>,
 }
 
 impl ExecutionResourcesManager {
@@ -26,13 +37,23 @@ impl ExecutionResourcesManager {
         ExecutionResourcesManager {
             syscall_counter,
             cairo_usage,
+            profiler: None,
         }
     }
 
-    pub fn increment_syscall_counter(&mut self, syscall_name: &str, amount: u64) -> Option<()> {
-        self.syscall_counter
-            .get_mut(syscall_name)
-            .map(|val| *val += amount)
+    /// An empty manager that profiles the entry points run with it into `profiler`, if given.
+    pub fn with_profiler(profiler: Option<&Profiler>) -> Self {
+        ExecutionResourcesManager {
+            profiler: profiler.cloned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn increment_syscall_counter(&mut self, syscall_name: &str, amount: u64) {
+        *self
+            .syscall_counter
+            .entry(syscall_name.to_string())
+            .or_default() += amount;
     }
 
     pub fn get_syscall_counter(&self, syscall_name: &str) -> Option<u64> {
@@ -42,14 +63,48 @@ impl ExecutionResourcesManager {
     }
 }
 
-#[derive(Default, Clone, PartialEq, Debug)]
+/// The changes a block (or any sequence of transactions) makes to the state: the contracts
+/// deployed or whose class was replaced, the classes declared, and the updated nonces and storage
+/// values.
+///
+/// Serializes to and from the `state_diff` object of the feeder gateway `state_update`.
+#[derive(Default, Clone, PartialEq, Eq, Debug, Getters, Serialize, Deserialize)]
+#[serde(into = "StateDiffJson", try_from = "StateDiffJson")]
+#[getset(get = "pub")]
 pub struct StateDiff {
+    /// The contracts deployed, with their class.
     pub(crate) address_to_class_hash: HashMap<Address, ClassHash>,
+    /// The contracts that were already deployed and had their class replaced, with the new one.
+    pub(crate) replaced_classes: HashMap<Address, ClassHash>,
     pub(crate) address_to_nonce: HashMap<Address, Felt252>,
-    pub(crate) storage_updates: HashMap<Felt252, HashMap<ClassHash, Address>>,
+    pub(crate) storage_updates: HashMap<Address, HashMap<[u8; 32], Felt252>>,
+    /// The Cairo 0 classes declared.
+    pub(crate) declared_classes: HashSet<ClassHash>,
+    /// The Cairo 1 classes declared, with the hash of their compiled class.
+    pub(crate) class_hash_to_compiled_class_hash: HashMap<ClassHash, CompiledClassHash>,
 }
 
 impl StateDiff {
+    pub fn new(
+        address_to_class_hash: HashMap<Address, ClassHash>,
+        replaced_classes: HashMap<Address, ClassHash>,
+        address_to_nonce: HashMap<Address, Felt252>,
+        storage_updates: HashMap<Address, HashMap<[u8; 32], Felt252>>,
+        declared_classes: HashSet<ClassHash>,
+        class_hash_to_compiled_class_hash: HashMap<ClassHash, CompiledClassHash>,
+    ) -> Self {
+        StateDiff {
+            address_to_class_hash,
+            replaced_classes,
+            address_to_nonce,
+            storage_updates,
+            declared_classes,
+            class_hash_to_compiled_class_hash,
+        }
+    }
+
+    /// Builds the diff between the cached state and its state reader. The declared classes are
+    /// the ones set on the cached state.
     pub fn from_cached_state<T>(cached_state: CachedState<T>) -> Result<Self, StateError>
     where
         T: StateReader + Clone,
@@ -66,15 +121,33 @@ impl StateDiff {
         let address_to_nonce =
             subtract_mappings(state_cache.nonce_writes, state_cache.nonce_initial_values);
 
-        let address_to_class_hash = subtract_mappings(
+        // Contracts that had a class before the writes had it replaced, the rest were deployed.
+        let (replaced_classes, address_to_class_hash) = subtract_mappings(
             state_cache.class_hash_writes,
-            state_cache.class_hash_initial_values,
+            state_cache.class_hash_initial_values.clone(),
+        )
+        .into_iter()
+        .partition(|(address, _)| {
+            state_cache
+                .class_hash_initial_values
+                .get(address)
+                .map_or(false, |class_hash| class_hash != UNINITIALIZED_CLASS_HASH)
+        });
+
+        let declared_classes = cached_state.declared_classes;
+
+        let class_hash_to_compiled_class_hash = subtract_mappings(
+            state_cache.compiled_class_hash_writes,
+            state_cache.compiled_class_hash_initial_values,
         );
 
         Ok(StateDiff {
             address_to_class_hash,
+            replaced_classes,
             address_to_nonce,
             storage_updates,
+            declared_classes,
+            class_hash_to_compiled_class_hash,
         })
     }
 
@@ -84,9 +157,11 @@ impl StateDiff {
     {
         let mut cache_state = CachedState::new(state_reader, None);
         let cache_storage_mapping = to_cache_state_storage_mapping(self.storage_updates.clone());
+        let mut address_to_class_hash = self.address_to_class_hash.clone();
+        address_to_class_hash.extend(self.replaced_classes.clone());
 
         cache_state.cache_mut().set_initial_values(
-            &self.address_to_class_hash,
+            &address_to_class_hash,
             &self.address_to_nonce,
             &cache_storage_mapping,
         )?;
@@ -96,18 +171,29 @@ impl StateDiff {
     pub fn squash(&mut self, other: StateDiff) -> Result<Self, StarkwareError> {
         self.address_to_class_hash
             .extend(other.address_to_class_hash);
+        for (address, class_hash) in other.replaced_classes {
+            match self.address_to_class_hash.get_mut(&address) {
+                // Replacing the class of a contract deployed in this diff deploys it with the new
+                // class.
+                Some(deployed_class_hash) => *deployed_class_hash = class_hash,
+                None => {
+                    self.replaced_classes.insert(address, class_hash);
+                }
+            }
+        }
         let address_to_class_hash = self.address_to_class_hash.clone();
+        let replaced_classes = self.replaced_classes.clone();
 
         self.address_to_nonce.extend(other.address_to_nonce);
         let address_to_nonce = self.address_to_nonce.clone();
 
         let mut storage_updates = HashMap::new();
 
-        let addresses: Vec<Felt252> =
+        let addresses: Vec<Address> =
             get_keys(self.storage_updates.clone(), other.storage_updates.clone());
 
         for address in addresses {
-            let default: HashMap<ClassHash, Address> = HashMap::new();
+            let default: HashMap<[u8; 32], Felt252> = HashMap::new();
             let mut map_a = self
                 .storage_updates
                 .get(&address)
@@ -122,10 +208,20 @@ impl StateDiff {
             storage_updates.insert(address, map_a.clone());
         }
 
+        self.declared_classes.extend(other.declared_classes);
+        let declared_classes = self.declared_classes.clone();
+
+        self.class_hash_to_compiled_class_hash
+            .extend(other.class_hash_to_compiled_class_hash);
+        let class_hash_to_compiled_class_hash = self.class_hash_to_compiled_class_hash.clone();
+
         Ok(StateDiff {
             address_to_class_hash,
+            replaced_classes,
             address_to_nonce,
             storage_updates,
+            declared_classes,
+            class_hash_to_compiled_class_hash,
         })
     }
 }
@@ -133,7 +229,11 @@ impl StateDiff {
 #[cfg(test)]
 mod test {
     use coverage_helper::test;
-    use std::collections::HashMap;
+    use std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+        sync::Arc,
+    };
 
     use super::StateDiff;
     use crate::{
@@ -141,10 +241,11 @@ mod test {
             fact_state::in_memory_state_reader::InMemoryStateReader,
             state::{
                 cached_state::{CachedState, ContractClassCache},
-                state_api::StateReader,
+                state_api::{State, StateReader},
                 state_cache::{StateCache, StorageEntry},
             },
         },
+        services::api::contract_class::ContractClass,
         utils::Address,
     };
     use felt::Felt252;
@@ -171,6 +272,106 @@ mod test {
         assert_eq!(0, diff.storage_updates.len());
     }
 
+    #[test]
+    fn test_from_cached_state_with_updates() {
+        let mut state_reader = InMemoryStateReader::default();
+
+        let contract_address = Address(32123.into());
+        let known_class_hash = [1; 32];
+        let declared_class_hash = [2; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap();
+
+        state_reader
+            .class_hash_to_contract_class
+            .insert(known_class_hash, Arc::new(contract_class.clone()));
+
+        let mut cached_state = CachedState::new(state_reader, Some(HashMap::new()));
+        cached_state.get_contract_class(&known_class_hash).unwrap();
+        cached_state
+            .set_contract_class(&declared_class_hash, &contract_class)
+            .unwrap();
+        cached_state
+            .deploy_contract(contract_address.clone(), known_class_hash)
+            .unwrap();
+        cached_state.set_storage_at(&(contract_address.clone(), [3; 32]), Felt252::new(4));
+        cached_state
+            .set_compiled_class_hash(&[5; 32], &[6; 32])
+            .unwrap();
+
+        let diff = StateDiff::from_cached_state(cached_state).unwrap();
+
+        assert_eq!(
+            diff.address_to_class_hash,
+            HashMap::from([(contract_address.clone(), known_class_hash)])
+        );
+        assert_eq!(
+            diff.storage_updates,
+            HashMap::from([(
+                contract_address,
+                HashMap::from([([3; 32], Felt252::new(4))])
+            )])
+        );
+        assert_eq!(diff.declared_classes, HashSet::from([declared_class_hash]));
+        assert_eq!(
+            diff.class_hash_to_compiled_class_hash,
+            HashMap::from([([5; 32], [6; 32])])
+        );
+    }
+
+    #[test]
+    fn state_diff_keeps_replaced_classes_apart_from_deployments() {
+        let replaced_address = Address(1.into());
+        let deployed_address = Address(2.into());
+        let cache = StateCache::new(
+            HashMap::from([
+                (replaced_address.clone(), [1; 32]),
+                (deployed_address.clone(), [0; 32]),
+            ]),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::from([
+                (replaced_address.clone(), [2; 32]),
+                (deployed_address.clone(), [3; 32]),
+            ]),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        let cached_state = CachedState::new_for_testing(
+            InMemoryStateReader::default(),
+            Some(ContractClassCache::new()),
+            cache,
+        );
+
+        let mut diff = StateDiff::from_cached_state(cached_state).unwrap();
+        assert_eq!(
+            diff.address_to_class_hash,
+            HashMap::from([(deployed_address.clone(), [3; 32])])
+        );
+        assert_eq!(
+            diff.replaced_classes,
+            HashMap::from([(replaced_address.clone(), [2; 32])])
+        );
+
+        // Replacing the class of a contract deployed earlier in the diff changes its deployment.
+        let replacements = StateDiff {
+            replaced_classes: HashMap::from([
+                (replaced_address.clone(), [4; 32]),
+                (deployed_address.clone(), [5; 32]),
+            ]),
+            ..Default::default()
+        };
+        let squashed = diff.squash(replacements).unwrap();
+        assert_eq!(
+            squashed.address_to_class_hash,
+            HashMap::from([(deployed_address, [5; 32])])
+        );
+        assert_eq!(
+            squashed.replaced_classes,
+            HashMap::from([(replaced_address, [4; 32])])
+        );
+    }
+
     #[test]
     fn execution_resources_manager_should_start_with_zero_syscall_counter() {
         let execution_resources_manager = super::ExecutionResourcesManager::new(
@@ -195,9 +396,7 @@ mod test {
             Default::default(),
         );
 
-        execution_resources_manager
-            .increment_syscall_counter("syscall1", 1)
-            .unwrap();
+        execution_resources_manager.increment_syscall_counter("syscall1", 1);
 
         assert_eq!(
             execution_resources_manager.get_syscall_counter("syscall1"),
@@ -207,6 +406,13 @@ mod test {
             execution_resources_manager.get_syscall_counter("syscall2"),
             Some(0)
         );
+
+        // Syscalls the manager wasn't created with are counted too.
+        execution_resources_manager.increment_syscall_counter("syscall3", 2);
+        assert_eq!(
+            execution_resources_manager.get_syscall_counter("syscall3"),
+            Some(2)
+        );
     }
 
     #[test]
@@ -0,0 +1,344 @@
+use super::{
+    contract_state::ContractState, in_memory_state_reader::InMemoryStateReader, state::StateDiff,
+};
+use crate::{
+    business_logic::state::cached_state::CachedState,
+    definitions::general_config::StarknetGeneralConfig,
+    starkware_utils::{
+        patricia_tree::{pedersen, verify_proof, PatriciaTree, ProofNode},
+        starkware_errors::StarkwareError,
+    },
+    utils::{Address, ClassHash},
+};
+use felt::Felt252;
+use num_traits::Zero;
+use std::collections::HashMap;
+
+/// Appended to the contract state hash so the format can be changed in the future.
+const CONTRACT_STATE_HASH_VERSION: u64 = 0;
+
+/// Calculates `H(H(H(class_hash, storage_root), nonce), CONTRACT_STATE_HASH_VERSION)`.
+pub fn calculate_contract_state_hash(
+    class_hash: &ClassHash,
+    storage_root: &Felt252,
+    nonce: &Felt252,
+) -> Result<Felt252, StarkwareError> {
+    let hash = pedersen(&Felt252::from_bytes_be(class_hash), storage_root)?;
+    let hash = pedersen(&hash, nonce)?;
+    pedersen(&hash, &Felt252::from(CONTRACT_STATE_HASH_VERSION))
+}
+
+/// Proves a storage value against the global state root: first the contract state hash against
+/// the global tree, then the value against the contract's storage root.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StorageProof {
+    pub class_hash: ClassHash,
+    pub nonce: Felt252,
+    pub storage_root: Felt252,
+    pub contract_proof: Vec<ProofNode>,
+    pub storage_proof: Vec<ProofNode>,
+}
+
+impl StorageProof {
+    pub fn verify(
+        &self,
+        global_root: &Felt252,
+        storage_entry: (&Address, &[u8; 32]),
+        value: &Felt252,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<bool, StarkwareError> {
+        let (address, key) = storage_entry;
+        let contract_state_hash = match self.class_hash == [0; 32]
+            && self.nonce.is_zero()
+            && self.storage_root.is_zero()
+        {
+            true => Felt252::zero(),
+            false => {
+                calculate_contract_state_hash(&self.class_hash, &self.storage_root, &self.nonce)?
+            }
+        };
+
+        Ok(verify_proof(
+            global_root,
+            general_config.global_state_commitment_tree_height(),
+            &address.0,
+            &contract_state_hash,
+            &self.contract_proof,
+        )? && verify_proof(
+            &self.storage_root,
+            general_config.contract_storage_commitment_tree_height(),
+            &Felt252::from_bytes_be(key),
+            value,
+            &self.storage_proof,
+        )?)
+    }
+}
+
+/// The Patricia trees of the whole state: one per contract storage, plus the global tree with
+/// the contract state hashes as leaves.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateCommitment {
+    contract_states: HashMap<Address, ContractState>,
+    storage_trees: HashMap<Address, PatriciaTree>,
+    global_tree: PatriciaTree,
+}
+
+impl StateCommitment {
+    pub fn new(
+        contract_states: HashMap<Address, ContractState>,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<Self, StarkwareError> {
+        let mut storage_trees = HashMap::new();
+        let mut contract_state_hashes = Vec::new();
+
+        for (address, contract_state) in contract_states.iter() {
+            let storage_tree = PatriciaTree::new(
+                general_config.contract_storage_commitment_tree_height(),
+                contract_state
+                    .storage
+                    .iter()
+                    .map(|(key, value)| (Felt252::from_bytes_be(key), value.clone())),
+            )?;
+
+            if !(contract_state.class_hash == [0; 32]
+                && contract_state.nonce.is_zero()
+                && storage_tree.root().is_zero())
+            {
+                contract_state_hashes.push((
+                    address.0.clone(),
+                    calculate_contract_state_hash(
+                        &contract_state.class_hash,
+                        &storage_tree.root(),
+                        &contract_state.nonce,
+                    )?,
+                ));
+            }
+            storage_trees.insert(address.clone(), storage_tree);
+        }
+
+        let global_tree = PatriciaTree::new(
+            general_config.global_state_commitment_tree_height(),
+            contract_state_hashes,
+        )?;
+
+        Ok(Self {
+            contract_states,
+            storage_trees,
+            global_tree,
+        })
+    }
+
+    /// Builds the commitment of the state resulting from applying the cache writes on top of
+    /// the state reader.
+    pub fn from_cached_state(
+        state: &CachedState<InMemoryStateReader>,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<Self, StarkwareError> {
+        let mut contract_states = state.state_reader().contract_states();
+        let cache = state.cache();
+
+        for (address, class_hash) in cache.class_hash_writes.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_insert_with(ContractState::empty)
+                .class_hash = *class_hash;
+        }
+        for (address, nonce) in cache.nonce_writes.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_insert_with(ContractState::empty)
+                .nonce = nonce.clone();
+        }
+        for ((address, key), value) in cache.storage_writes.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_insert_with(ContractState::empty)
+                .storage
+                .insert(*key, value.clone());
+        }
+
+        Self::new(contract_states, general_config)
+    }
+
+    /// Builds the commitment of the state resulting from applying `state_diff` on top of
+    /// `contract_states`.
+    pub fn from_state_diff(
+        mut contract_states: HashMap<Address, ContractState>,
+        state_diff: &StateDiff,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<Self, StarkwareError> {
+        for (address, class_hash) in state_diff
+            .address_to_class_hash
+            .iter()
+            .chain(state_diff.replaced_classes.iter())
+        {
+            contract_states
+                .entry(address.clone())
+                .or_insert_with(ContractState::empty)
+                .class_hash = *class_hash;
+        }
+        for (address, nonce) in state_diff.address_to_nonce.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_insert_with(ContractState::empty)
+                .nonce = nonce.clone();
+        }
+        for (address, storage_updates) in state_diff.storage_updates.iter() {
+            contract_states
+                .entry(address.clone())
+                .or_insert_with(ContractState::empty)
+                .storage
+                .extend(storage_updates.clone());
+        }
+
+        Self::new(contract_states, general_config)
+    }
+
+    /// The global state root.
+    pub fn root(&self) -> Felt252 {
+        self.global_tree.root()
+    }
+
+    pub fn storage_root(&self, address: &Address) -> Felt252 {
+        self.storage_trees
+            .get(address)
+            .map(PatriciaTree::root)
+            .unwrap_or_else(Felt252::zero)
+    }
+
+    pub fn storage_proof(
+        &self,
+        address: &Address,
+        key: &[u8; 32],
+    ) -> Result<StorageProof, StarkwareError> {
+        let contract_state = self
+            .contract_states
+            .get(address)
+            .cloned()
+            .unwrap_or_else(ContractState::empty);
+        let storage_proof = match self.storage_trees.get(address) {
+            Some(storage_tree) => storage_tree.proof(&Felt252::from_bytes_be(key))?,
+            None => Vec::new(),
+        };
+
+        Ok(StorageProof {
+            class_hash: contract_state.class_hash,
+            nonce: contract_state.nonce,
+            storage_root: self.storage_root(address),
+            contract_proof: self.global_tree.proof(&address.0)?,
+            storage_proof,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::state::state_api::State;
+    use coverage_helper::test;
+
+    #[test]
+    fn empty_state_has_zero_root() {
+        let general_config = StarknetGeneralConfig::default();
+        let commitment = StateCommitment::new(
+            HashMap::from([(Address(1.into()), ContractState::empty())]),
+            &general_config,
+        )
+        .unwrap();
+
+        assert_eq!(commitment.root(), Felt252::zero());
+    }
+
+    #[test]
+    fn cached_state_and_state_diff_roots_match() {
+        let general_config = StarknetGeneralConfig::default();
+        let address = Address(1111.into());
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash
+            .insert(address.clone(), [1; 32]);
+        state_reader
+            .address_to_nonce
+            .insert(address.clone(), Felt252::zero());
+        let initial_contract_states = state_reader.contract_states();
+
+        let mut state = CachedState::new(state_reader, None);
+        state.increment_nonce(&address).unwrap();
+        state.set_storage_at(&(address.clone(), [2; 32]), Felt252::new(5));
+        state
+            .deploy_contract(Address(2222.into()), [3; 32])
+            .unwrap();
+
+        let from_cached_state =
+            StateCommitment::from_cached_state(&state, &general_config).unwrap();
+        let from_state_diff = StateCommitment::from_state_diff(
+            initial_contract_states,
+            &StateDiff::from_cached_state(state).unwrap(),
+            &general_config,
+        )
+        .unwrap();
+
+        assert_ne!(from_cached_state.root(), Felt252::zero());
+        assert_eq!(from_cached_state.root(), from_state_diff.root());
+        assert_eq!(
+            from_cached_state.storage_root(&address),
+            PatriciaTree::new(251, [(Felt252::from_bytes_be(&[2; 32]), Felt252::new(5))])
+                .unwrap()
+                .root()
+        );
+    }
+
+    #[test]
+    fn storage_proofs() {
+        let general_config = StarknetGeneralConfig::default();
+        let address = Address(1111.into());
+        let contract_state = ContractState::new(
+            [1; 32],
+            Felt252::new(2),
+            HashMap::from([([3; 32], Felt252::new(4)), ([5; 32], Felt252::new(6))]),
+        );
+        let commitment = StateCommitment::new(
+            HashMap::from([
+                (address.clone(), contract_state),
+                (
+                    Address(2222.into()),
+                    ContractState::new([7; 32], Felt252::zero(), HashMap::new()),
+                ),
+            ]),
+            &general_config,
+        )
+        .unwrap();
+
+        let proof = commitment.storage_proof(&address, &[3; 32]).unwrap();
+        assert_eq!(
+            proof.verify(
+                &commitment.root(),
+                (&address, &[3; 32]),
+                &Felt252::new(4),
+                &general_config
+            ),
+            Ok(true)
+        );
+        assert_eq!(
+            proof.verify(
+                &commitment.root(),
+                (&address, &[3; 32]),
+                &Felt252::new(5),
+                &general_config
+            ),
+            Ok(false)
+        );
+
+        let proof = commitment.storage_proof(&address, &[4; 32]).unwrap();
+        assert_eq!(
+            proof.verify(
+                &commitment.root(),
+                (&address, &[4; 32]),
+                &Felt252::zero(),
+                &general_config
+            ),
+            Ok(true)
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use super::state::StateDiff;
+use crate::utils::{decode_felt, decode_hash, encode_felt, encode_hash, Address, ClassHash};
+use felt::Felt252;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// A block state update, as returned by the feeder gateway `get_state_update` endpoint.
+///
+/// Pending blocks have neither a block hash nor a new root yet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "StateUpdateJson", try_from = "StateUpdateJson")]
+pub struct StateUpdate {
+    pub block_hash: Option<Felt252>,
+    pub new_root: Option<Felt252>,
+    pub old_root: Felt252,
+    pub state_diff: StateDiff,
+}
+
+impl StateUpdate {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+// ---------------------------------
+//  Gateway JSON representation
+// ---------------------------------
+
+#[derive(Clone, Deserialize, Serialize)]
+struct StateUpdateJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    new_root: Option<String>,
+    old_root: String,
+    state_diff: StateDiff,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct StorageDiffJson {
+    key: String,
+    value: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct DeployedContractJson {
+    address: String,
+    class_hash: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct DeclaredClassJson {
+    class_hash: String,
+    compiled_class_hash: String,
+}
+
+/// The `state_diff` object of a gateway state update. Every value is a `0x` prefixed hex string.
+///
+/// `old_declared_contracts` are the Cairo 0 classes declared, `declared_classes` the Cairo 1 ones.
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct StateDiffJson {
+    #[serde(default)]
+    storage_diffs: BTreeMap<String, Vec<StorageDiffJson>>,
+    #[serde(default)]
+    nonces: BTreeMap<String, String>,
+    #[serde(default)]
+    deployed_contracts: Vec<DeployedContractJson>,
+    #[serde(default)]
+    old_declared_contracts: Vec<String>,
+    #[serde(default)]
+    declared_classes: Vec<DeclaredClassJson>,
+    #[serde(default)]
+    replaced_classes: Vec<DeployedContractJson>,
+}
+
+impl From<StateDiff> for StateDiffJson {
+    fn from(state_diff: StateDiff) -> Self {
+        let storage_diffs = state_diff
+            .storage_updates
+            .iter()
+            .map(|(address, storage)| {
+                let mut storage: Vec<_> = storage.iter().collect();
+                storage.sort_by_key(|(key, _)| **key);
+                let storage = storage
+                    .into_iter()
+                    .map(|(key, value)| StorageDiffJson {
+                        key: encode_hash(key),
+                        value: encode_felt(value),
+                    })
+                    .collect();
+                (encode_felt(&address.0), storage)
+            })
+            .collect();
+
+        let nonces = state_diff
+            .address_to_nonce
+            .iter()
+            .map(|(address, nonce)| (encode_felt(&address.0), encode_felt(nonce)))
+            .collect();
+
+        let deployed_contracts = encode_contract_classes(&state_diff.address_to_class_hash);
+        let replaced_classes = encode_contract_classes(&state_diff.replaced_classes);
+
+        let mut old_declared_contracts: Vec<_> = state_diff.declared_classes.iter().collect();
+        old_declared_contracts.sort();
+        let old_declared_contracts = old_declared_contracts
+            .into_iter()
+            .map(encode_hash)
+            .collect();
+
+        let mut declared_classes: Vec<_> = state_diff
+            .class_hash_to_compiled_class_hash
+            .iter()
+            .collect();
+        declared_classes.sort();
+        let declared_classes = declared_classes
+            .into_iter()
+            .map(|(class_hash, compiled_class_hash)| DeclaredClassJson {
+                class_hash: encode_hash(class_hash),
+                compiled_class_hash: encode_hash(compiled_class_hash),
+            })
+            .collect();
+
+        StateDiffJson {
+            storage_diffs,
+            nonces,
+            deployed_contracts,
+            old_declared_contracts,
+            declared_classes,
+            replaced_classes,
+        }
+    }
+}
+
+impl TryFrom<StateDiffJson> for StateDiff {
+    type Error = String;
+
+    fn try_from(json: StateDiffJson) -> Result<Self, Self::Error> {
+        let mut storage_updates = HashMap::new();
+        for (address, storage_diffs) in json.storage_diffs {
+            let mut storage = HashMap::new();
+            for StorageDiffJson { key, value } in storage_diffs {
+                storage.insert(decode_hash(&key)?, decode_felt(&value)?);
+            }
+            storage_updates.insert(Address(decode_felt(&address)?), storage);
+        }
+
+        let mut address_to_nonce = HashMap::new();
+        for (address, nonce) in json.nonces {
+            address_to_nonce.insert(Address(decode_felt(&address)?), decode_felt(&nonce)?);
+        }
+
+        let address_to_class_hash = decode_contract_classes(json.deployed_contracts)?;
+        let replaced_classes = decode_contract_classes(json.replaced_classes)?;
+
+        let declared_classes = json
+            .old_declared_contracts
+            .iter()
+            .map(String::as_str)
+            .map(decode_hash)
+            .collect::<Result<_, _>>()?;
+
+        let mut class_hash_to_compiled_class_hash = HashMap::new();
+        for DeclaredClassJson {
+            class_hash,
+            compiled_class_hash,
+        } in json.declared_classes
+        {
+            class_hash_to_compiled_class_hash.insert(
+                decode_hash(&class_hash)?,
+                decode_hash(&compiled_class_hash)?,
+            );
+        }
+
+        Ok(StateDiff::new(
+            address_to_class_hash,
+            replaced_classes,
+            address_to_nonce,
+            storage_updates,
+            declared_classes,
+            class_hash_to_compiled_class_hash,
+        ))
+    }
+}
+
+impl From<StateUpdate> for StateUpdateJson {
+    fn from(state_update: StateUpdate) -> Self {
+        StateUpdateJson {
+            block_hash: state_update.block_hash.as_ref().map(encode_felt),
+            new_root: state_update.new_root.as_ref().map(encode_felt),
+            old_root: encode_felt(&state_update.old_root),
+            state_diff: state_update.state_diff,
+        }
+    }
+}
+
+impl TryFrom<StateUpdateJson> for StateUpdate {
+    type Error = String;
+
+    fn try_from(json: StateUpdateJson) -> Result<Self, Self::Error> {
+        Ok(StateUpdate {
+            block_hash: json.block_hash.as_deref().map(decode_felt).transpose()?,
+            new_root: json.new_root.as_deref().map(decode_felt).transpose()?,
+            old_root: decode_felt(&json.old_root)?,
+            state_diff: json.state_diff,
+        })
+    }
+}
+
+fn encode_contract_classes(
+    contract_classes: &HashMap<Address, ClassHash>,
+) -> Vec<DeployedContractJson> {
+    let mut contract_classes: Vec<_> = contract_classes.iter().collect();
+    contract_classes.sort_by_key(|(address, _)| address.0.to_biguint());
+    contract_classes
+        .into_iter()
+        .map(|(address, class_hash)| DeployedContractJson {
+            address: encode_felt(&address.0),
+            class_hash: encode_hash(class_hash),
+        })
+        .collect()
+}
+
+fn decode_contract_classes(
+    contract_classes: Vec<DeployedContractJson>,
+) -> Result<HashMap<Address, ClassHash>, String> {
+    contract_classes
+        .into_iter()
+        .map(
+            |DeployedContractJson {
+                 address,
+                 class_hash,
+             }| Ok((Address(decode_felt(&address)?), decode_hash(&class_hash)?)),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business_logic::fact_state::in_memory_state_reader::InMemoryStateReader;
+    use coverage_helper::test;
+    use std::collections::HashSet;
+
+    const STATE_UPDATE: &str = r#"{
+        "block_hash": "0x47c3637b57c2b079b93c61539950c17e868a28f46cdef28f88521067f21e943",
+        "new_root": "0x21870ba80540e7831fb21c591ee93481f5ae1bb71ff85a86ddd465be4eddee6",
+        "old_root": "0x0",
+        "state_diff": {
+            "storage_diffs": {
+                "0x735596016a37ee972c42adef6a3cf628c19bb3794369c65d2c82ba034aecf2c": [
+                    {"key": "0x5", "value": "0x64"},
+                    {"key": "0x2", "value": "0x0"}
+                ]
+            },
+            "nonces": {"0x1": "0x3"},
+            "deployed_contracts": [
+                {
+                    "address": "0x735596016a37ee972c42adef6a3cf628c19bb3794369c65d2c82ba034aecf2c",
+                    "class_hash": "0x10455c752b86932ce552f2b0fe81a880746649b9aee7e0d842bf3f52378f9f8"
+                }
+            ],
+            "old_declared_contracts": [
+                "0x10455c752b86932ce552f2b0fe81a880746649b9aee7e0d842bf3f52378f9f8"
+            ],
+            "declared_classes": [
+                {"class_hash": "0x3", "compiled_class_hash": "0x4"}
+            ],
+            "replaced_classes": [{"address": "0x1", "class_hash": "0x2"}]
+        }
+    }"#;
+
+    #[test]
+    fn parse_gateway_state_update() {
+        let state_update = StateUpdate::from_json(STATE_UPDATE).unwrap();
+        let state_diff = &state_update.state_diff;
+
+        let address = Address(
+            decode_felt("0x735596016a37ee972c42adef6a3cf628c19bb3794369c65d2c82ba034aecf2c")
+                .unwrap(),
+        );
+        let class_hash =
+            decode_hash("0x10455c752b86932ce552f2b0fe81a880746649b9aee7e0d842bf3f52378f9f8")
+                .unwrap();
+
+        assert_eq!(state_update.old_root, Felt252::new(0));
+        assert_eq!(
+            state_diff.address_to_class_hash,
+            HashMap::from([(address.clone(), class_hash)])
+        );
+        assert_eq!(
+            state_diff.replaced_classes,
+            HashMap::from([(Address(1.into()), Felt252::new(2).to_be_bytes())])
+        );
+        assert_eq!(
+            state_diff.address_to_nonce[&Address(1.into())],
+            Felt252::new(3)
+        );
+        assert_eq!(
+            state_diff.storage_updates[&address],
+            HashMap::from([
+                (Felt252::new(5).to_be_bytes(), Felt252::new(100)),
+                (Felt252::new(2).to_be_bytes(), Felt252::new(0)),
+            ])
+        );
+        assert_eq!(state_diff.declared_classes, HashSet::from([class_hash]));
+        assert_eq!(
+            state_diff.class_hash_to_compiled_class_hash,
+            HashMap::from([(Felt252::new(3).to_be_bytes(), Felt252::new(4).to_be_bytes())])
+        );
+    }
+
+    #[test]
+    fn state_update_roundtrip() {
+        let state_update = StateUpdate::from_json(STATE_UPDATE).unwrap();
+        let json = state_update.to_json().unwrap();
+
+        assert_eq!(StateUpdate::from_json(&json).unwrap(), state_update);
+    }
+
+    #[test]
+    fn invalid_felt_fails_to_parse() {
+        let json = r#"{"old_root": "0xzz", "state_diff": {}}"#;
+
+        assert!(StateUpdate::from_json(json).is_err());
+    }
+
+    #[test]
+    fn apply_state_update_to_state_reader() {
+        let state_update = StateUpdate::from_json(STATE_UPDATE).unwrap();
+        let mut state_reader = InMemoryStateReader::default();
+
+        // The declared class definition isn't part of the state update.
+        assert!(state_reader
+            .apply_state_diff(&state_update.state_diff)
+            .is_err());
+        assert_eq!(state_reader, InMemoryStateReader::default());
+
+        let mut state_diff = state_update.state_diff;
+        state_diff.declared_classes.clear();
+        state_reader.apply_state_diff(&state_diff).unwrap();
+
+        assert_eq!(
+            state_reader.address_to_nonce,
+            HashMap::from([(Address(1.into()), Felt252::new(3))])
+        );
+        assert_eq!(state_reader.address_to_class_hash.len(), 2);
+        assert_eq!(state_reader.address_to_storage.len(), 2);
+    }
+}
@@ -0,0 +1,357 @@
+use super::contract_state::ContractState;
+use crate::{
+    business_logic::state::{
+        cached_state::{CachedState, CompiledClassCache, ContractClassCache, ContractClassSources},
+        state_api::StateReader,
+        state_api_objects::StateChanges,
+        state_cache::{StateCache, StorageEntry},
+    },
+    core::errors::state_errors::StateError,
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    starknet_storage::{
+        errors::storage_errors::StorageError,
+        storage::{Prefix, Storage},
+    },
+    utils::{encode_hash, Address, ClassHash, CompiledClassHash},
+};
+use felt::Felt252;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
+
+/// A [`StateReader`] on top of any [`Storage`].
+///
+/// Contract states are stored under `Prefix::ContractState`, keyed by contract address, and
+/// contract classes under `Prefix::ContractClass`, keyed by class hash. The compiled class
+/// hashes of Cairo 1 classes are stored under `Prefix::CompiledClassHash`, and their CASM
+/// classes under `Prefix::CompiledClass`, keyed by compiled class hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageStateReader<S: Storage> {
+    storage: S,
+}
+
+impl<S: Storage> StorageStateReader<S> {
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Writes the cache writes and the given contract and compiled classes into the storage as a
+    /// single batch. The contract classes that aren't stored yet are written as their JSON in
+    /// `contract_class_sources`.
+    pub fn persist(
+        &mut self,
+        cache: &StateCache,
+        contract_classes: &ContractClassCache,
+        contract_class_sources: &ContractClassSources,
+        compiled_classes: &CompiledClassCache,
+    ) -> Result<(), StateError> {
+        let mut contract_states = HashMap::new();
+
+        for (address, class_hash) in cache.class_hash_writes.iter() {
+            self.updated_contract_state(&mut contract_states, address)?
+                .class_hash = *class_hash;
+        }
+        for (address, nonce) in cache.nonce_writes.iter() {
+            self.updated_contract_state(&mut contract_states, address)?
+                .nonce = nonce.clone();
+        }
+        for ((address, key), value) in cache.storage_writes.iter() {
+            self.updated_contract_state(&mut contract_states, address)?
+                .storage
+                .insert(*key, value.clone());
+        }
+
+        let mut batch = Vec::new();
+        for class_hash in contract_classes.keys() {
+            let key = (Prefix::ContractClass, *class_hash);
+            if self.storage.get_value(&key).is_some() {
+                continue;
+            }
+            let source = contract_class_sources.get(class_hash).ok_or_else(|| {
+                StorageError::UnserializableContractClass(encode_hash(class_hash))
+            })?;
+            batch.push((key, source.as_bytes().to_vec()));
+        }
+        for (compiled_class_hash, casm_class) in compiled_classes.iter() {
+            let key = (Prefix::CompiledClass, *compiled_class_hash);
+            if self.storage.get_value(&key).is_some() {
+                continue;
+            }
+            batch.push((
+                key,
+                serde_json::to_vec(casm_class.as_ref()).map_err(StorageError::from)?,
+            ));
+        }
+        for (class_hash, compiled_class_hash) in cache.compiled_class_hash_writes.iter() {
+            batch.push((
+                (Prefix::CompiledClassHash, *class_hash),
+                compiled_class_hash.to_vec(),
+            ));
+        }
+        for (address, contract_state) in contract_states {
+            batch.push((
+                (Prefix::ContractState, address.0.to_be_bytes()),
+                serde_json::to_vec(&contract_state).map_err(StorageError::from)?,
+            ));
+        }
+
+        Ok(self.storage.write_batch(batch)?)
+    }
+
+    fn get_contract_state(&self, address: &Address) -> Result<ContractState, StateError> {
+        match self.storage.get_contract_state(&address.0.to_be_bytes()) {
+            Err(StorageError::ErrorFetchingData) => {
+                Err(StateError::NoneContractState(address.clone()))
+            }
+            result => Ok(result?),
+        }
+    }
+
+    fn updated_contract_state<'a>(
+        &self,
+        contract_states: &'a mut HashMap<Address, ContractState>,
+        address: &Address,
+    ) -> Result<&'a mut ContractState, StateError> {
+        Ok(match contract_states.entry(address.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match self.get_contract_state(address) {
+                Err(StateError::NoneContractState(_)) => entry.insert(ContractState::empty()),
+                contract_state => entry.insert(contract_state?),
+            },
+        })
+    }
+}
+
+impl<S: Storage> StateReader for StorageStateReader<S> {
+    fn get_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Arc<ContractClass>, StateError> {
+        let contract_class = match self.storage.get_contract_class(class_hash) {
+            Err(StorageError::ErrorFetchingData) => return Err(StateError::MissingClassHash()),
+            contract_class => contract_class?,
+        };
+        contract_class.validate()?;
+        Ok(Arc::new(contract_class))
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: &Address) -> Result<ClassHash, StateError> {
+        Ok(self.get_contract_state(contract_address)?.class_hash)
+    }
+
+    fn get_nonce_at(&mut self, contract_address: &Address) -> Result<Felt252, StateError> {
+        Ok(self.get_contract_state(contract_address)?.nonce)
+    }
+
+    fn get_storage_at(&mut self, storage_entry: &StorageEntry) -> Result<Felt252, StateError> {
+        let (address, key) = storage_entry;
+        let contract_state = match self.get_contract_state(address) {
+            Err(StateError::NoneContractState(_)) => {
+                return Err(StateError::NoneStorage(storage_entry.clone()))
+            }
+            contract_state => contract_state?,
+        };
+        contract_state
+            .storage
+            .get(key)
+            .cloned()
+            .ok_or_else(|| StateError::NoneStorage(storage_entry.clone()))
+    }
+
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StateError> {
+        match self.storage.get_compiled_class_hash(class_hash) {
+            Err(StorageError::ErrorFetchingData) => Ok([0; 32]),
+            compiled_class_hash => Ok(compiled_class_hash?),
+        }
+    }
+
+    fn get_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<Arc<CasmContractClass>, StateError> {
+        match self.storage.get_compiled_class(compiled_class_hash) {
+            Err(StorageError::ErrorFetchingData) => {
+                Err(StateError::MissingCompiledClass(*compiled_class_hash))
+            }
+            casm_class => Ok(Arc::new(casm_class?)),
+        }
+    }
+
+    fn count_actual_state_changes(&mut self) -> StateChanges {
+        // Everything stored here is already committed, changes are tracked by the cache on top.
+        StateChanges::default()
+    }
+}
+
+impl<S: Storage + Clone> CachedState<StorageStateReader<S>> {
+    /// Writes the accumulated cache, and the contract and compiled classes declared since the
+    /// last time, into the underlying storage and starts over with an empty cache and no class
+    /// declared. Open checkpoints are discarded.
+    ///
+    /// The classes declared on this state are written as the JSON given to
+    /// `set_contract_class_source`, so declaring one without it makes this fail.
+    pub fn persist(&mut self) -> Result<(), StateError> {
+        let (contract_classes, compiled_classes) = self.classes_to_persist();
+        self.state_reader.persist(
+            &self.cache,
+            &contract_classes,
+            &self.contract_class_sources,
+            &compiled_classes,
+        )?;
+        self.clear_updates();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::state::state_api::State,
+        services::api::contract_class::ContractClassWithSource,
+        starknet_storage::dict_storage::DictStorage,
+    };
+    use coverage_helper::test;
+    use std::path::PathBuf;
+
+    #[test]
+    fn missing_values() {
+        let mut state_reader = StorageStateReader::new(DictStorage::new());
+        let address = Address(1.into());
+
+        assert_eq!(
+            state_reader.get_class_hash_at(&address),
+            Err(StateError::NoneContractState(address.clone()))
+        );
+        assert_eq!(
+            state_reader.get_nonce_at(&address),
+            Err(StateError::NoneContractState(address.clone()))
+        );
+        assert_eq!(
+            state_reader.get_storage_at(&(address.clone(), [0; 32])),
+            Err(StateError::NoneStorage((address, [0; 32])))
+        );
+        assert_eq!(
+            state_reader.get_contract_class(&[0; 32]),
+            Err(StateError::MissingClassHash())
+        );
+        assert_eq!(state_reader.get_compiled_class_hash(&[0; 32]), Ok([0; 32]));
+        assert_eq!(
+            state_reader.get_compiled_class(&[0; 32]),
+            Err(StateError::MissingCompiledClass([0; 32]))
+        );
+    }
+
+    #[test]
+    fn persist_cached_state() {
+        let ContractClassWithSource {
+            contract_class,
+            source,
+        } = ContractClassWithSource::try_from(&PathBuf::from("starknet_programs/fibonacci.json"))
+            .unwrap();
+        let class_hash = [1; 32];
+        let address = Address(32.into());
+        let storage_entry = (address.clone(), [2; 32]);
+
+        let mut state = CachedState::new(
+            StorageStateReader::new(DictStorage::new()),
+            Some(HashMap::new()),
+        );
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+        state.set_contract_class_source(&class_hash, source.unwrap());
+        state.deploy_contract(address.clone(), class_hash).unwrap();
+        state.set_storage_at(&storage_entry, Felt252::new(9));
+        let casm_class = CasmContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.casm",
+        ))
+        .unwrap();
+        state.set_compiled_class_hash(&[3; 32], &[4; 32]).unwrap();
+        state
+            .set_compiled_class(&[4; 32], casm_class.clone())
+            .unwrap();
+        state.persist().unwrap();
+        assert!(state.declared_classes().is_empty());
+        assert_eq!(state.count_actual_state_changes(), StateChanges::default());
+
+        let mut state_reader = state.state_reader().clone();
+        assert_eq!(state_reader.get_class_hash_at(&address), Ok(class_hash));
+        assert_eq!(state_reader.get_nonce_at(&address), Ok(Felt252::new(0)));
+        assert_eq!(
+            state_reader.get_storage_at(&storage_entry),
+            Ok(Felt252::new(9))
+        );
+        assert_eq!(
+            state_reader.get_contract_class(&class_hash),
+            Ok(Arc::new(contract_class))
+        );
+        assert_eq!(state_reader.get_compiled_class_hash(&[3; 32]), Ok([4; 32]));
+        assert_eq!(
+            state_reader.get_compiled_class(&[4; 32]),
+            Ok(Arc::new(casm_class))
+        );
+
+        // Persisting again only touches the written fields of the contract.
+        state.increment_nonce(&address).unwrap();
+        state.persist().unwrap();
+        assert_eq!(
+            state
+                .state_reader()
+                .storage()
+                .get_contract_state(&address.0.to_be_bytes()),
+            Ok(ContractState::new(
+                class_hash,
+                Felt252::new(1),
+                HashMap::from([([2; 32], Felt252::new(9))])
+            ))
+        );
+    }
+
+    #[test]
+    fn persist_only_declared_classes() {
+        let contract_class = ContractClass::new(Default::default(), HashMap::new(), None).unwrap();
+
+        // The class is cached without being declared on this state, so it needs no source.
+        let mut state = CachedState::new(
+            StorageStateReader::new(DictStorage::new()),
+            Some(HashMap::from([([5; 32], Arc::new(contract_class))])),
+        );
+        state.set_storage_at(&(Address(1.into()), [1; 32]), Felt252::new(1));
+        state.persist().unwrap();
+
+        assert!(state
+            .state_reader()
+            .storage()
+            .get_value(&(Prefix::ContractClass, [5; 32]))
+            .is_none());
+    }
+
+    #[test]
+    fn corrupted_contract_state() {
+        let address = Address(1.into());
+        let mut storage = DictStorage::new();
+        storage
+            .set_value(
+                &(Prefix::ContractState, address.0.to_be_bytes()),
+                b"{".to_vec(),
+            )
+            .unwrap();
+
+        let mut state_reader = StorageStateReader::new(storage);
+        assert_eq!(
+            state_reader.get_class_hash_at(&address),
+            Err(StateError::Storage(StorageError::SerdeError(String::from(
+                "EOF while parsing an object at line 1 column 1"
+            ))))
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod block;
 pub mod execution;
 pub mod fact_state;
 pub mod state;
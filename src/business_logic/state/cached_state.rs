@@ -1,20 +1,33 @@
 use super::{
+    contract_class_cache::SharedContractClassCache,
+    state_accesses::{AccessedKeys, StateAccesses},
     state_api::{State, StateReader},
+    state_api_objects::StateChanges,
     state_cache::{StateCache, StorageEntry},
+    state_journal::{Checkpoint, JournalEntry, StateJournal},
 };
 use crate::{
+    business_logic::fact_state::state::StateDiff,
     core::errors::state_errors::StateError,
-    services::api::contract_class::ContractClass,
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
     starknet_storage::errors::storage_errors::StorageError,
-    utils::{subtract_mappings, Address, ClassHash},
+    utils::{Address, ClassHash, CompiledClassHash},
 };
 use felt::Felt252;
 use getset::{Getters, MutGetters};
 use num_traits::Zero;
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
 
 // K: class_hash V: ContractClass
-pub type ContractClassCache = HashMap<ClassHash, ContractClass>;
+pub type ContractClassCache = HashMap<ClassHash, Arc<ContractClass>>;
+// K: class_hash V: the JSON the class was loaded from
+pub type ContractClassSources = HashMap<ClassHash, Arc<str>>;
+// K: compiled_class_hash V: CasmContractClass
+pub type CompiledClassCache = HashMap<CompiledClassHash, Arc<CasmContractClass>>;
 
 pub const UNINITIALIZED_CLASS_HASH: &ClassHash = b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
 
@@ -26,6 +39,19 @@ pub struct CachedState<T: StateReader + Clone> {
     pub(crate) cache: StateCache,
     #[get = "pub"]
     pub(crate) contract_classes: Option<ContractClassCache>,
+    #[get = "pub"]
+    pub(crate) shared_contract_classes: Option<SharedContractClassCache>,
+    #[get = "pub"]
+    pub(crate) contract_class_sources: ContractClassSources,
+    /// The classes set with [`State::set_contract_class`], as opposed to the ones read from the
+    /// state reader.
+    #[get = "pub"]
+    pub(crate) declared_classes: HashSet<ClassHash>,
+    /// The CASM classes of the Cairo 1 classes, by compiled class hash.
+    #[get = "pub"]
+    pub(crate) compiled_classes: CompiledClassCache,
+    pub(crate) journal: StateJournal,
+    pub(crate) accesses: Option<StateAccesses>,
 }
 
 impl<T: StateReader + Clone> CachedState<T> {
@@ -33,7 +59,13 @@ impl<T: StateReader + Clone> CachedState<T> {
         Self {
             cache: StateCache::default(),
             contract_classes: contract_class_cache,
+            shared_contract_classes: None,
+            contract_class_sources: HashMap::new(),
+            declared_classes: HashSet::new(),
+            compiled_classes: HashMap::new(),
             state_reader,
+            journal: StateJournal::default(),
+            accesses: None,
         }
     }
 
@@ -45,7 +77,13 @@ impl<T: StateReader + Clone> CachedState<T> {
         Self {
             cache,
             contract_classes,
+            shared_contract_classes: None,
+            contract_class_sources: HashMap::new(),
+            declared_classes: HashSet::new(),
+            compiled_classes: HashMap::new(),
             state_reader,
+            journal: StateJournal::default(),
+            accesses: None,
         }
     }
 
@@ -60,6 +98,19 @@ impl<T: StateReader + Clone> CachedState<T> {
         Ok(())
     }
 
+    /// Looks up the classes missing from this state's cache in `shared_contract_classes` before
+    /// going to the state reader, and adds the ones read from the state reader to it.
+    pub fn set_shared_contract_classes(
+        &mut self,
+        shared_contract_classes: SharedContractClassCache,
+    ) -> Result<(), StateError> {
+        if self.shared_contract_classes.is_some() {
+            return Err(StateError::AssignedContractClassCache);
+        }
+        self.shared_contract_classes = Some(shared_contract_classes);
+        Ok(())
+    }
+
     pub(crate) fn get_contract_classes(&self) -> Result<&ContractClassCache, StateError> {
         self.contract_classes
             .as_ref()
@@ -77,22 +128,255 @@ impl<T: StateReader + Clone> CachedState<T> {
         copied_state.apply(self);
         copied_state
     }
+
+    /// The contract classes declared on this state and the CASM classes of the compiled class
+    /// hashes it set, i.e. the classes a state reader persisting its updates doesn't have yet.
+    pub(crate) fn classes_to_persist(&self) -> (ContractClassCache, CompiledClassCache) {
+        let contract_classes = self
+            .declared_classes
+            .iter()
+            .filter_map(|class_hash| {
+                let contract_class = self.contract_classes.as_ref()?.get(class_hash)?;
+                Some((*class_hash, contract_class.clone()))
+            })
+            .collect();
+        let compiled_classes = self
+            .cache
+            .compiled_class_hash_writes
+            .values()
+            .filter_map(|compiled_class_hash| {
+                let casm_class = self.compiled_classes.get(compiled_class_hash)?;
+                Some((*compiled_class_hash, casm_class.clone()))
+            })
+            .collect();
+        (contract_classes, compiled_classes)
+    }
+
+    /// Starts over on top of the state reader once the updates of this state were persisted into
+    /// it. The classes stay cached, but none of them counts as declared anymore.
+    pub(crate) fn clear_updates(&mut self) {
+        self.cache = StateCache::default();
+        self.declared_classes.clear();
+        self.contract_class_sources.clear();
+        self.journal = StateJournal::default();
+    }
+
+    fn write_class_hash(&mut self, address: Address, class_hash: ClassHash) {
+        self.track(|accesses| accesses.writes.class_hashes.insert(address.clone()));
+        let previous = self
+            .cache
+            .class_hash_writes
+            .insert(address.clone(), class_hash);
+        self.journal
+            .record(JournalEntry::ClassHash(address, previous));
+    }
+
+    fn write_contract_class(
+        &mut self,
+        class_hash: ClassHash,
+        contract_class: Arc<ContractClass>,
+    ) -> Result<(), StateError> {
+        let previous = self
+            .contract_classes
+            .as_mut()
+            .ok_or(StateError::MissingContractClassCache)?
+            .insert(class_hash, contract_class);
+        self.journal
+            .record(JournalEntry::ContractClass(class_hash, previous));
+        Ok(())
+    }
+
+    fn write_compiled_class(
+        &mut self,
+        compiled_class_hash: CompiledClassHash,
+        casm_class: Arc<CasmContractClass>,
+    ) {
+        let previous = self
+            .compiled_classes
+            .insert(compiled_class_hash, casm_class);
+        self.journal
+            .record(JournalEntry::CompiledClass(compiled_class_hash, previous));
+    }
+
+    fn write_nonce(&mut self, address: Address, nonce: Felt252) {
+        self.track(|accesses| accesses.writes.nonces.insert(address.clone()));
+        let previous = self.cache.nonce_writes.insert(address.clone(), nonce);
+        self.journal.record(JournalEntry::Nonce(address, previous));
+    }
+
+    fn track(&mut self, record: impl FnOnce(&mut StateAccesses) -> bool) {
+        if let Some(accesses) = self.accesses.as_mut() {
+            record(accesses);
+        }
+    }
+
+    /// Starts recording the keys read and written, until [`take_accesses`](Self::take_accesses).
+    pub(crate) fn track_accesses(&mut self) {
+        self.accesses = Some(StateAccesses::default());
+    }
+
+    /// Stops recording accesses, returning the ones recorded.
+    pub(crate) fn take_accesses(&mut self) -> StateAccesses {
+        self.accesses.take().unwrap_or_default()
+    }
+
+    /// The cache entries (writes, values read, contract classes, whether they were declared and
+    /// compiled classes) of the keys in `accesses`.
+    pub(crate) fn cache_entries(&self, accesses: &StateAccesses) -> CacheEntries {
+        let keys = accesses.keys();
+        let mut entries = CacheEntries::default();
+        copy_cache_entries(&mut entries.cache, &self.cache, &keys);
+        if let Some(contract_classes) = self.contract_classes.as_ref() {
+            copy_entries(
+                &mut entries.contract_classes,
+                contract_classes,
+                &keys.contract_classes,
+            );
+        }
+        copy_members(
+            &mut entries.declared_classes,
+            &self.declared_classes,
+            &keys.contract_classes,
+        );
+        copy_entries(
+            &mut entries.compiled_classes,
+            &self.compiled_classes,
+            &keys.compiled_classes,
+        );
+        entries
+    }
+
+    /// Sets the cache entries of the keys in `accesses` to `entries`, taken with
+    /// [`cache_entries`](Self::cache_entries) for the same accesses. Keys without an entry there
+    /// are removed from the cache.
+    pub(crate) fn set_cache_entries(&mut self, accesses: &StateAccesses, entries: &CacheEntries) {
+        let keys = accesses.keys();
+        copy_cache_entries(&mut self.cache, &entries.cache, &keys);
+        if let Some(contract_classes) = self.contract_classes.as_mut() {
+            copy_entries(
+                contract_classes,
+                &entries.contract_classes,
+                &keys.contract_classes,
+            );
+        }
+        copy_members(
+            &mut self.declared_classes,
+            &entries.declared_classes,
+            &keys.contract_classes,
+        );
+        copy_entries(
+            &mut self.compiled_classes,
+            &entries.compiled_classes,
+            &keys.compiled_classes,
+        );
+    }
+}
+
+/// Part of the cache of a [`CachedState`], see [`CachedState::cache_entries`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CacheEntries {
+    cache: StateCache,
+    contract_classes: ContractClassCache,
+    declared_classes: HashSet<ClassHash>,
+    compiled_classes: CompiledClassCache,
+}
+
+fn copy_cache_entries(to: &mut StateCache, from: &StateCache, keys: &AccessedKeys) {
+    copy_entries(
+        &mut to.class_hash_initial_values,
+        &from.class_hash_initial_values,
+        &keys.class_hashes,
+    );
+    copy_entries(
+        &mut to.class_hash_writes,
+        &from.class_hash_writes,
+        &keys.class_hashes,
+    );
+    copy_entries(
+        &mut to.nonce_initial_values,
+        &from.nonce_initial_values,
+        &keys.nonces,
+    );
+    copy_entries(&mut to.nonce_writes, &from.nonce_writes, &keys.nonces);
+    copy_entries(
+        &mut to.storage_initial_values,
+        &from.storage_initial_values,
+        &keys.storage,
+    );
+    copy_entries(&mut to.storage_writes, &from.storage_writes, &keys.storage);
+    copy_entries(
+        &mut to.compiled_class_hash_initial_values,
+        &from.compiled_class_hash_initial_values,
+        &keys.compiled_class_hashes,
+    );
+    copy_entries(
+        &mut to.compiled_class_hash_writes,
+        &from.compiled_class_hash_writes,
+        &keys.compiled_class_hashes,
+    );
+}
+
+/// Sets the values of `keys` in `to` to the ones in `from`, removing the keys `from` doesn't
+/// have.
+fn copy_entries<K: Clone + Eq + Hash, V: Clone>(
+    to: &mut HashMap<K, V>,
+    from: &HashMap<K, V>,
+    keys: &HashSet<K>,
+) {
+    for key in keys {
+        restore(to, key.clone(), from.get(key).cloned());
+    }
+}
+
+/// Makes `keys` members of `to` if they are members of `from`, and removes them otherwise.
+fn copy_members<K: Clone + Eq + Hash>(to: &mut HashSet<K>, from: &HashSet<K>, keys: &HashSet<K>) {
+    for key in keys {
+        if from.contains(key) {
+            to.insert(key.clone());
+        } else {
+            to.remove(key);
+        }
+    }
+}
+
+fn restore<K: Eq + Hash, V>(map: &mut HashMap<K, V>, key: K, previous: Option<V>) {
+    match previous {
+        Some(value) => map.insert(key, value),
+        None => map.remove(&key),
+    };
 }
 
 impl<T: StateReader + Clone> StateReader for CachedState<T> {
-    fn get_contract_class(&mut self, class_hash: &ClassHash) -> Result<ContractClass, StateError> {
-        if !self.get_contract_classes()?.contains_key(class_hash) {
-            let contract_class = self.state_reader.get_contract_class(class_hash)?;
-            self.set_contract_class(class_hash, &contract_class)?;
+    fn get_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Arc<ContractClass>, StateError> {
+        self.track(|accesses| accesses.reads.contract_classes.insert(*class_hash));
+        if let Some(contract_class) = self.get_contract_classes()?.get(class_hash) {
+            return Ok(contract_class.clone());
         }
-        Ok(self
-            .get_contract_classes()?
-            .get(class_hash)
-            .ok_or(StateError::MissingContractClassCache)?
-            .to_owned())
+
+        let shared_contract_class = self
+            .shared_contract_classes
+            .as_ref()
+            .and_then(|shared_contract_classes| shared_contract_classes.get(class_hash));
+        let contract_class = match shared_contract_class {
+            Some(contract_class) => contract_class,
+            None => {
+                let contract_class = self.state_reader.get_contract_class(class_hash)?;
+                if let Some(shared_contract_classes) = self.shared_contract_classes.as_ref() {
+                    shared_contract_classes.insert(*class_hash, contract_class.clone());
+                }
+                contract_class
+            }
+        };
+
+        self.write_contract_class(*class_hash, contract_class.clone())?;
+        Ok(contract_class)
     }
 
     fn get_class_hash_at(&mut self, contract_address: &Address) -> Result<ClassHash, StateError> {
+        self.track(|accesses| accesses.reads.class_hashes.insert(contract_address.clone()));
         if self.cache.get_class_hash(contract_address).is_none() {
             let class_hash = match self.state_reader.get_class_hash_at(contract_address) {
                 Ok(x) => x,
@@ -111,6 +395,7 @@ impl<T: StateReader + Clone> StateReader for CachedState<T> {
     }
 
     fn get_nonce_at(&mut self, contract_address: &Address) -> Result<Felt252, StateError> {
+        self.track(|accesses| accesses.reads.nonces.insert(contract_address.clone()));
         if self.cache.get_nonce(contract_address).is_none() {
             let nonce = self.state_reader.get_nonce_at(contract_address)?;
             self.cache
@@ -124,6 +409,7 @@ impl<T: StateReader + Clone> StateReader for CachedState<T> {
     }
 
     fn get_storage_at(&mut self, storage_entry: &StorageEntry) -> Result<Felt252, StateError> {
+        self.track(|accesses| accesses.reads.storage.insert(storage_entry.clone()));
         if self.cache.get_storage(storage_entry).is_none() {
             let value = match self.state_reader.get_storage_at(storage_entry) {
                 Ok(x) => x,
@@ -147,30 +433,276 @@ impl<T: StateReader + Clone> StateReader for CachedState<T> {
             .cloned()
     }
 
-    fn count_actual_storage_changes(&mut self) -> (usize, usize) {
-        let storage_updates = subtract_mappings(
-            self.cache.storage_writes.clone(),
-            self.cache.storage_initial_values.clone(),
-        );
-        let modified_contracts = storage_updates.keys().map(|k| k.0.clone()).len();
-        (modified_contracts, storage_updates.len())
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StateError> {
+        self.track(|accesses| accesses.reads.compiled_class_hashes.insert(*class_hash));
+        if self.cache.get_compiled_class_hash(class_hash).is_none() {
+            let compiled_class_hash = self.state_reader.get_compiled_class_hash(class_hash)?;
+            self.cache
+                .compiled_class_hash_initial_values
+                .insert(*class_hash, compiled_class_hash);
+        }
+
+        self.cache
+            .get_compiled_class_hash(class_hash)
+            .ok_or(StateError::MissingClassHash())
+            .cloned()
+    }
+
+    fn get_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<Arc<CasmContractClass>, StateError> {
+        self.track(|accesses| accesses.reads.compiled_classes.insert(*compiled_class_hash));
+        if let Some(casm_class) = self.compiled_classes.get(compiled_class_hash) {
+            return Ok(casm_class.clone());
+        }
+
+        let casm_class = self.state_reader.get_compiled_class(compiled_class_hash)?;
+        self.write_compiled_class(*compiled_class_hash, casm_class.clone());
+        Ok(casm_class)
+    }
+
+    /// Counts the changes since the innermost open checkpoint, so a transaction executed inside a
+    /// checkpoint only counts its own changes, even if outer checkpoints are open. Without an open
+    /// checkpoint, every write in the cache is counted.
+    fn count_actual_state_changes(&mut self) -> StateChanges {
+        self.updates().count()
+    }
+}
+
+/// The keys whose value changed since the innermost open checkpoint, or since the state was
+/// created if there is none, mapped to their original value.
+struct StateUpdates {
+    class_hashes: HashMap<Address, ClassHash>,
+    nonces: HashMap<Address, Felt252>,
+    storage: HashMap<StorageEntry, Felt252>,
+    compiled_class_hashes: HashMap<ClassHash, CompiledClassHash>,
+    /// The classes declared in that time.
+    declared_classes: Vec<ClassHash>,
+}
+
+impl StateUpdates {
+    fn count(&self) -> StateChanges {
+        let n_modified_contracts = self
+            .class_hashes
+            .keys()
+            .chain(self.nonces.keys())
+            .chain(self.storage.keys().map(|(address, _)| address))
+            .collect::<HashSet<_>>()
+            .len();
+
+        StateChanges {
+            n_modified_contracts,
+            n_storage_updates: self.storage.len(),
+            n_class_hash_updates: self.class_hashes.len(),
+            n_deployments: self
+                .class_hashes
+                .values()
+                .filter(|&class_hash| class_hash == UNINITIALIZED_CLASS_HASH)
+                .count(),
+            n_declared_classes: self.declared_classes.len(),
+            n_compiled_class_hash_updates: self.compiled_class_hashes.len(),
+        }
+    }
+}
+
+impl<T: StateReader + Clone> CachedState<T> {
+    /// The changes made since the innermost open checkpoint, or since the state was created if
+    /// there is none, as a [`StateDiff`].
+    ///
+    /// Taking a checkpoint right before a transaction gives the diff of that transaction alone.
+    pub fn actual_state_diff(&mut self) -> StateDiff {
+        let updates = self.updates();
+
+        let mut storage_updates: HashMap<Address, HashMap<[u8; 32], Felt252>> = HashMap::new();
+        for (address, key) in updates.storage.into_keys() {
+            let value = self.cache.storage_writes[&(address.clone(), key)].clone();
+            storage_updates
+                .entry(address)
+                .or_default()
+                .insert(key, value);
+        }
+
+        // Contracts that had a class before the changes had it replaced, the rest were deployed.
+        let (replaced_classes, address_to_class_hash) = updates
+            .class_hashes
+            .into_iter()
+            .map(|(address, original_class_hash)| {
+                let class_hash = self.cache.class_hash_writes[&address];
+                (address, class_hash, original_class_hash)
+            })
+            .partition::<Vec<_>, _>(|(_, _, original_class_hash)| {
+                original_class_hash != UNINITIALIZED_CLASS_HASH
+            });
+
+        StateDiff::new(
+            address_to_class_hash
+                .into_iter()
+                .map(|(address, class_hash, _)| (address, class_hash))
+                .collect(),
+            replaced_classes
+                .into_iter()
+                .map(|(address, class_hash, _)| (address, class_hash))
+                .collect(),
+            updates
+                .nonces
+                .into_keys()
+                .map(|address| {
+                    let nonce = self.cache.nonce_writes[&address].clone();
+                    (address, nonce)
+                })
+                .collect(),
+            storage_updates,
+            updates.declared_classes.into_iter().collect(),
+            updates
+                .compiled_class_hashes
+                .into_keys()
+                .map(|class_hash| {
+                    let compiled_class_hash = self.cache.compiled_class_hash_writes[&class_hash];
+                    (class_hash, compiled_class_hash)
+                })
+                .collect(),
+        )
+    }
+
+    fn updates(&self) -> StateUpdates {
+        let (class_hashes, nonces, storage, compiled_class_hashes, declared_classes) =
+            match self.journal.innermost_entries() {
+                None => (
+                    None,
+                    None,
+                    None,
+                    None,
+                    self.declared_classes.iter().copied().collect(),
+                ),
+                Some(entries) => {
+                    let mut class_hashes = HashMap::new();
+                    let mut nonces = HashMap::new();
+                    let mut storage = HashMap::new();
+                    let mut compiled_class_hashes = HashMap::new();
+                    let mut declared_classes = Vec::new();
+                    // Only the first write to a key since the checkpoint replaced its original value.
+                    for entry in entries {
+                        match entry {
+                            JournalEntry::ClassHash(address, previous) => {
+                                class_hashes.entry(address.clone()).or_insert(*previous);
+                            }
+                            JournalEntry::Nonce(address, previous) => {
+                                nonces
+                                    .entry(address.clone())
+                                    .or_insert_with(|| previous.clone());
+                            }
+                            JournalEntry::Storage(storage_entry, previous) => {
+                                storage
+                                    .entry(storage_entry.clone())
+                                    .or_insert_with(|| previous.clone());
+                            }
+                            JournalEntry::CompiledClassHash(class_hash, previous) => {
+                                compiled_class_hashes
+                                    .entry(*class_hash)
+                                    .or_insert(*previous);
+                            }
+                            // Caching a class doesn't change the state.
+                            JournalEntry::ContractClass(..) | JournalEntry::CompiledClass(..) => {}
+                            JournalEntry::DeclaredClass(class_hash) => {
+                                declared_classes.push(*class_hash);
+                            }
+                        }
+                    }
+                    (
+                        Some(class_hashes),
+                        Some(nonces),
+                        Some(storage),
+                        Some(compiled_class_hashes),
+                        declared_classes,
+                    )
+                }
+            };
+
+        StateUpdates {
+            class_hashes: updated_keys(
+                &self.cache.class_hash_writes,
+                &self.cache.class_hash_initial_values,
+                class_hashes,
+                UNINITIALIZED_CLASS_HASH,
+            ),
+            nonces: updated_keys(
+                &self.cache.nonce_writes,
+                &self.cache.nonce_initial_values,
+                nonces,
+                &Felt252::zero(),
+            ),
+            storage: updated_keys(
+                &self.cache.storage_writes,
+                &self.cache.storage_initial_values,
+                storage,
+                &Felt252::zero(),
+            ),
+            compiled_class_hashes: updated_keys(
+                &self.cache.compiled_class_hash_writes,
+                &self.cache.compiled_class_hash_initial_values,
+                compiled_class_hashes,
+                UNINITIALIZED_CLASS_HASH,
+            ),
+            declared_classes,
+        }
     }
 }
 
+/// Returns the written keys whose value differs from their original one, mapped to it.
+///
+/// The original value is the one replaced by the first write in `window`, if given. Otherwise,
+/// or if there was no such write, it's the value read from the state reader, or `default` if it
+/// was never read.
+fn updated_keys<K: Clone + Eq + Hash, V: Clone + PartialEq>(
+    writes: &HashMap<K, V>,
+    initial_values: &HashMap<K, V>,
+    window: Option<HashMap<K, Option<V>>>,
+    default: &V,
+) -> HashMap<K, V> {
+    let previous_writes: Vec<(K, Option<V>)> = match window {
+        Some(window) => window.into_iter().collect(),
+        None => writes.keys().map(|key| (key.clone(), None)).collect(),
+    };
+
+    previous_writes
+        .into_iter()
+        .filter_map(|(key, previous)| {
+            let original = previous
+                .or_else(|| initial_values.get(&key).cloned())
+                .unwrap_or_else(|| default.clone());
+            match writes.get(&key) {
+                Some(value) if *value != original => Some((key, original)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 impl<T: StateReader + Clone> State for CachedState<T> {
     fn set_contract_class(
         &mut self,
         class_hash: &ClassHash,
         contract_class: &ContractClass,
     ) -> Result<(), StateError> {
-        self.contract_classes
-            .as_mut()
-            .ok_or(StateError::MissingContractClassCache)?
-            .insert(*class_hash, contract_class.clone());
-
+        self.track(|accesses| accesses.writes.contract_classes.insert(*class_hash));
+        self.write_contract_class(*class_hash, Arc::new(contract_class.clone()))?;
+        if self.declared_classes.insert(*class_hash) {
+            self.journal
+                .record(JournalEntry::DeclaredClass(*class_hash));
+        }
         Ok(())
     }
 
+    // Sources aren't journaled: the JSON of a class hash never changes, and it's only written
+    // along with a class that is in the state.
+    fn set_contract_class_source(&mut self, class_hash: &ClassHash, source: Arc<str>) {
+        self.contract_class_sources.insert(*class_hash, source);
+    }
+
     fn deploy_contract(
         &mut self,
         deploy_contract_address: Address,
@@ -192,27 +724,118 @@ impl<T: StateReader + Clone> State for CachedState<T> {
             _ => {}
         }
 
-        self.cache
-            .class_hash_writes_mut()
-            .insert(deploy_contract_address.clone(), class_hash);
-        self.cache
-            .nonce_writes_mut()
-            .insert(deploy_contract_address, Felt252::zero());
+        self.write_class_hash(deploy_contract_address.clone(), class_hash);
+        self.write_nonce(deploy_contract_address, Felt252::zero());
         Ok(())
     }
 
     fn increment_nonce(&mut self, contract_address: &Address) -> Result<(), StateError> {
         let new_nonce = &self.get_nonce_at(contract_address)? + 1;
-        self.cache
-            .nonce_writes
-            .insert(contract_address.clone(), new_nonce);
+        self.write_nonce(contract_address.clone(), new_nonce);
         Ok(())
     }
 
     fn set_storage_at(&mut self, storage_entry: &StorageEntry, value: Felt252) {
-        self.cache
+        self.track(|accesses| accesses.writes.storage.insert(storage_entry.clone()));
+        let previous = self
+            .cache
             .storage_writes
             .insert(storage_entry.clone(), value);
+        self.journal
+            .record(JournalEntry::Storage(storage_entry.clone(), previous));
+    }
+
+    fn set_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<(), StateError> {
+        self.track(|accesses| accesses.writes.compiled_class_hashes.insert(*class_hash));
+        let previous = self
+            .cache
+            .compiled_class_hash_writes
+            .insert(*class_hash, *compiled_class_hash);
+        self.journal
+            .record(JournalEntry::CompiledClassHash(*class_hash, previous));
+        Ok(())
+    }
+
+    fn set_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+        casm_class: CasmContractClass,
+    ) -> Result<(), StateError> {
+        self.track(|accesses| {
+            accesses
+                .writes
+                .compiled_classes
+                .insert(*compiled_class_hash)
+        });
+        self.write_compiled_class(*compiled_class_hash, Arc::new(casm_class));
+        Ok(())
+    }
+
+    fn count_actual_state_changes_with(
+        &mut self,
+        nonces: &[Address],
+        storage: &[StorageEntry],
+    ) -> StateChanges {
+        let mut updates = self.updates();
+        // The original value doesn't matter for counting, only whether the key is there.
+        for address in nonces {
+            updates
+                .nonces
+                .entry(address.clone())
+                .or_insert_with(Felt252::zero);
+        }
+        for storage_entry in storage {
+            updates
+                .storage
+                .entry(storage_entry.clone())
+                .or_insert_with(Felt252::zero);
+        }
+        updates.count()
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.journal.checkpoint()
+    }
+
+    fn revert_to(&mut self, checkpoint: Checkpoint) -> Result<(), StateError> {
+        for entry in self.journal.revert_to(checkpoint)? {
+            match entry {
+                JournalEntry::ClassHash(address, previous) => {
+                    restore(&mut self.cache.class_hash_writes, address, previous)
+                }
+                JournalEntry::Nonce(address, previous) => {
+                    restore(&mut self.cache.nonce_writes, address, previous)
+                }
+                JournalEntry::Storage(storage_entry, previous) => {
+                    restore(&mut self.cache.storage_writes, storage_entry, previous)
+                }
+                JournalEntry::CompiledClassHash(class_hash, previous) => restore(
+                    &mut self.cache.compiled_class_hash_writes,
+                    class_hash,
+                    previous,
+                ),
+                JournalEntry::ContractClass(class_hash, previous) => {
+                    if let Some(contract_classes) = self.contract_classes.as_mut() {
+                        restore(contract_classes, class_hash, previous)
+                    }
+                }
+                JournalEntry::CompiledClass(compiled_class_hash, previous) => {
+                    restore(&mut self.compiled_classes, compiled_class_hash, previous)
+                }
+                JournalEntry::DeclaredClass(class_hash) => {
+                    self.declared_classes.remove(&class_hash);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self, checkpoint: Checkpoint) -> Result<(), StateError> {
+        self.journal.commit(checkpoint)
     }
 }
 
@@ -289,7 +912,7 @@ mod tests {
 
         state_reader
             .class_hash_to_contract_class
-            .insert([0; 32], contract_class);
+            .insert([0; 32], Arc::new(contract_class));
 
         let mut cached_state = CachedState::new(state_reader, None);
 
@@ -302,6 +925,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn shared_contract_classes() {
+        let contract_class = ContractClass::new(Program::default(), HashMap::new(), None).unwrap();
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .class_hash_to_contract_class
+            .insert([1; 32], Arc::new(contract_class));
+        let shared_contract_classes = SharedContractClassCache::new(8);
+
+        let mut cached_state = CachedState::new(state_reader, Some(HashMap::new()));
+        cached_state
+            .set_shared_contract_classes(shared_contract_classes.clone())
+            .unwrap();
+        let loaded_class = cached_state.get_contract_class(&[1; 32]).unwrap();
+        assert!(Arc::ptr_eq(
+            &loaded_class,
+            &cached_state.get_contract_class(&[1; 32]).unwrap()
+        ));
+
+        // A state whose reader doesn't have the class still finds it in the shared cache.
+        let mut other_state =
+            CachedState::new(InMemoryStateReader::default(), Some(HashMap::new()));
+        assert_eq!(
+            other_state.get_contract_class(&[1; 32]),
+            Err(StateError::MissingClassHash())
+        );
+        other_state
+            .set_shared_contract_classes(shared_contract_classes.clone())
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            &loaded_class,
+            &other_state.get_contract_class(&[1; 32]).unwrap()
+        ));
+        assert_eq!(
+            other_state.set_shared_contract_classes(shared_contract_classes),
+            Err(StateError::AssignedContractClassCache)
+        );
+    }
+
     #[test]
     fn cached_state_storage_test() {
         let mut cached_state = CachedState::new(
@@ -442,4 +1104,330 @@ mod tests {
             StateError::ContractAddressUnavailable(contract_address)
         );
     }
+
+    #[test]
+    fn revert_to_checkpoint() {
+        let mut cached_state =
+            CachedState::new(InMemoryStateReader::default(), Some(HashMap::new()));
+        let address = Address(42.into());
+        let storage_entry = (address.clone(), [1; 32]);
+
+        cached_state.set_storage_at(&storage_entry, Felt252::new(1));
+        let cache_before = cached_state.cache().clone();
+
+        let checkpoint = cached_state.checkpoint();
+        cached_state.set_storage_at(&storage_entry, Felt252::new(2));
+        cached_state
+            .deploy_contract(address.clone(), [10; 32])
+            .unwrap();
+        cached_state.increment_nonce(&address).unwrap();
+        cached_state
+            .set_contract_class(
+                &[10; 32],
+                &ContractClass::new(Program::default(), HashMap::new(), None).unwrap(),
+            )
+            .unwrap();
+        cached_state.revert_to(checkpoint).unwrap();
+
+        assert_eq!(
+            cached_state.cache().class_hash_writes,
+            cache_before.class_hash_writes
+        );
+        assert_eq!(cached_state.cache().nonce_writes, cache_before.nonce_writes);
+        assert_eq!(
+            cached_state.cache().storage_writes,
+            cache_before.storage_writes
+        );
+        assert_eq!(cached_state.contract_classes(), &Some(HashMap::new()));
+        assert!(cached_state.declared_classes().is_empty());
+        assert_eq!(
+            cached_state.get_storage_at(&storage_entry),
+            Ok(Felt252::new(1))
+        );
+        assert_eq!(
+            cached_state.revert_to(checkpoint),
+            Err(StateError::InvalidCheckpoint(0))
+        );
+    }
+
+    #[test]
+    fn nested_checkpoints() {
+        let mut cached_state = CachedState::new(InMemoryStateReader::default(), None);
+        let storage_entry = (Address(42.into()), [1; 32]);
+
+        let outer = cached_state.checkpoint();
+        cached_state.set_storage_at(&storage_entry, Felt252::new(1));
+
+        // Committing the inner checkpoint keeps its writes in the outer one.
+        let inner = cached_state.checkpoint();
+        cached_state.set_storage_at(&storage_entry, Felt252::new(2));
+        cached_state.commit(inner).unwrap();
+        assert_eq!(
+            cached_state.get_storage_at(&storage_entry),
+            Ok(Felt252::new(2))
+        );
+
+        // Reverting the inner checkpoint only discards the writes done after it.
+        let inner = cached_state.checkpoint();
+        cached_state.set_storage_at(&storage_entry, Felt252::new(3));
+        cached_state.revert_to(inner).unwrap();
+        assert_eq!(
+            cached_state.get_storage_at(&storage_entry),
+            Ok(Felt252::new(2))
+        );
+
+        // Reverting the outer checkpoint also discards the committed inner writes.
+        let unclosed = cached_state.checkpoint();
+        cached_state.set_storage_at(&storage_entry, Felt252::new(4));
+        cached_state.revert_to(outer).unwrap();
+        assert!(cached_state.cache().storage_writes().is_empty());
+        assert!(cached_state.commit(unclosed).is_err());
+    }
+
+    #[test]
+    fn changes_are_counted_since_the_innermost_checkpoint() {
+        let mut cached_state = CachedState::new(InMemoryStateReader::default(), None);
+        let address = Address(42.into());
+
+        let outer = cached_state.checkpoint();
+        cached_state.set_storage_at(&(address.clone(), [1; 32]), Felt252::new(1));
+
+        // The writes done before the inner checkpoint belong to an earlier transaction.
+        let inner = cached_state.checkpoint();
+        cached_state.set_storage_at(&(address.clone(), [2; 32]), Felt252::new(2));
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_storage_updates,
+            1
+        );
+        assert_eq!(
+            cached_state.actual_state_diff().storage_updates(),
+            &HashMap::from([(address, HashMap::from([([2; 32], Felt252::new(2))]))])
+        );
+
+        cached_state.commit(inner).unwrap();
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_storage_updates,
+            2
+        );
+        cached_state.commit(outer).unwrap();
+    }
+
+    #[test]
+    fn only_classes_set_on_the_state_are_declared() {
+        let contract_class = ContractClass::new(Program::default(), HashMap::new(), None).unwrap();
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .class_hash_to_contract_class
+            .insert([1; 32], Arc::new(contract_class.clone()));
+        let mut cached_state = CachedState::new(state_reader, Some(HashMap::new()));
+
+        // Reading a class caches it without declaring it.
+        cached_state.get_contract_class(&[1; 32]).unwrap();
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_declared_classes,
+            0
+        );
+
+        let checkpoint = cached_state.checkpoint();
+        cached_state
+            .set_contract_class(&[2; 32], &contract_class)
+            .unwrap();
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_declared_classes,
+            1
+        );
+        assert_eq!(
+            cached_state.actual_state_diff().declared_classes(),
+            &HashSet::from([[2; 32]])
+        );
+
+        // Setting it again in a new checkpoint doesn't declare it again.
+        cached_state.commit(checkpoint).unwrap();
+        cached_state.checkpoint();
+        cached_state
+            .set_contract_class(&[2; 32], &contract_class)
+            .unwrap();
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_declared_classes,
+            0
+        );
+    }
+
+    #[test]
+    fn count_actual_state_changes() {
+        let address_1 = Address(1.into());
+        let address_2 = Address(2.into());
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash
+            .insert(address_1.clone(), [2; 32]);
+        state_reader
+            .address_to_nonce
+            .insert(address_1.clone(), Felt252::zero());
+        state_reader
+            .address_to_storage
+            .insert((address_1.clone(), [1; 32]), Felt252::new(5));
+        let mut cached_state = CachedState::new(state_reader, Some(HashMap::new()));
+
+        // Writing back the value read isn't a change.
+        let value = cached_state
+            .get_storage_at(&(address_1.clone(), [1; 32]))
+            .unwrap();
+        cached_state.set_storage_at(&(address_1.clone(), [1; 32]), value);
+        cached_state.set_storage_at(&(address_1.clone(), [2; 32]), Felt252::new(7));
+        cached_state.increment_nonce(&address_1).unwrap();
+        cached_state
+            .deploy_contract(address_2.clone(), [3; 32])
+            .unwrap();
+        cached_state
+            .set_contract_class(
+                &[3; 32],
+                &ContractClass::new(Program::default(), HashMap::new(), None).unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            cached_state.count_actual_state_changes(),
+            StateChanges {
+                n_modified_contracts: 2,
+                n_storage_updates: 1,
+                n_class_hash_updates: 1,
+                n_deployments: 1,
+                n_declared_classes: 1,
+                n_compiled_class_hash_updates: 0,
+            }
+        );
+
+        // Inside a checkpoint only the changes done since it are counted.
+        let checkpoint = cached_state.checkpoint();
+        cached_state.set_storage_at(&(address_1.clone(), [2; 32]), Felt252::new(8));
+        cached_state.set_storage_at(&(address_2, [1; 32]), Felt252::zero());
+        cached_state.increment_nonce(&address_1).unwrap();
+
+        assert_eq!(
+            cached_state.count_actual_state_changes(),
+            StateChanges {
+                n_modified_contracts: 1,
+                n_storage_updates: 1,
+                ..Default::default()
+            }
+        );
+
+        // Writing back the value from before the checkpoint undoes the change.
+        cached_state.set_storage_at(&(address_1, [2; 32]), Felt252::new(7));
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_storage_updates,
+            0
+        );
+
+        cached_state.commit(checkpoint).unwrap();
+        assert_eq!(
+            cached_state.count_actual_state_changes().n_storage_updates,
+            1
+        );
+    }
+
+    #[test]
+    fn count_actual_state_changes_with_pending_writes() {
+        let address_1 = Address(1.into());
+        let address_2 = Address(2.into());
+        let mut cached_state = CachedState::new(InMemoryStateReader::default(), None);
+        cached_state.set_storage_at(&(address_1.clone(), [1; 32]), Felt252::new(7));
+
+        // Pending writes to keys that already changed aren't counted twice.
+        assert_eq!(
+            cached_state.count_actual_state_changes_with(
+                &[address_1.clone()],
+                &[(address_1.clone(), [1; 32]), (address_2, [1; 32])]
+            ),
+            StateChanges {
+                n_modified_contracts: 2,
+                n_storage_updates: 2,
+                ..Default::default()
+            }
+        );
+        // Nothing is written.
+        assert!(cached_state.cache().nonce_writes.is_empty());
+        assert_eq!(cached_state.cache().storage_writes().len(), 1);
+    }
+
+    #[test]
+    fn actual_state_diff() {
+        let address = Address(1.into());
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash
+            .insert(address.clone(), [2; 32]);
+        state_reader
+            .address_to_nonce
+            .insert(address.clone(), Felt252::zero());
+        let mut cached_state = CachedState::new(state_reader, Some(HashMap::new()));
+
+        cached_state.set_storage_at(&(address.clone(), [1; 32]), Felt252::new(7));
+        cached_state.increment_nonce(&address).unwrap();
+
+        let checkpoint = cached_state.checkpoint();
+        cached_state.set_storage_at(&(address.clone(), [2; 32]), Felt252::new(8));
+        cached_state
+            .deploy_contract(Address(2.into()), [3; 32])
+            .unwrap();
+        cached_state
+            .set_contract_class(
+                &[3; 32],
+                &ContractClass::new(Program::default(), HashMap::new(), None).unwrap(),
+            )
+            .unwrap();
+        cached_state
+            .set_compiled_class_hash(&[4; 32], &[5; 32])
+            .unwrap();
+
+        // Only the changes since the checkpoint are in the diff.
+        assert_eq!(
+            cached_state.actual_state_diff(),
+            StateDiff::new(
+                HashMap::from([(Address(2.into()), [3; 32])]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::from([(address.clone(), HashMap::from([([2; 32], Felt252::new(8))]))]),
+                HashSet::from([[3; 32]]),
+                HashMap::from([([4; 32], [5; 32])]),
+            )
+        );
+
+        cached_state.commit(checkpoint).unwrap();
+        let state_diff = cached_state.actual_state_diff();
+        assert_eq!(
+            state_diff.address_to_nonce(),
+            &HashMap::from([(address.clone(), Felt252::new(1))])
+        );
+        assert_eq!(state_diff.storage_updates()[&address].len(), 2);
+    }
+
+    #[test]
+    fn compiled_class_hashes() {
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .class_hash_to_compiled_class_hash
+            .insert([1; 32], [2; 32]);
+        let mut cached_state = CachedState::new(state_reader, None);
+
+        assert_eq!(cached_state.get_compiled_class_hash(&[1; 32]), Ok([2; 32]));
+        assert_eq!(cached_state.get_compiled_class_hash(&[3; 32]), Ok([0; 32]));
+
+        let checkpoint = cached_state.checkpoint();
+        cached_state
+            .set_compiled_class_hash(&[3; 32], &[4; 32])
+            .unwrap();
+        assert_eq!(cached_state.get_compiled_class_hash(&[3; 32]), Ok([4; 32]));
+        assert_eq!(
+            cached_state
+                .count_actual_state_changes()
+                .n_compiled_class_hash_updates,
+            1
+        );
+
+        cached_state.revert_to(checkpoint).unwrap();
+        assert_eq!(cached_state.get_compiled_class_hash(&[3; 32]), Ok([0; 32]));
+        assert!(cached_state.cache().compiled_class_hash_writes().is_empty());
+    }
 }
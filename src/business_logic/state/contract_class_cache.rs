@@ -0,0 +1,182 @@
+use crate::{services::api::contract_class::ContractClass, utils::ClassHash};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+#[derive(Default)]
+struct LruClasses {
+    // K: class_hash V: (contract class, last use)
+    classes: HashMap<ClassHash, (Arc<ContractClass>, u64)>,
+    // K: last use V: class_hash. The first entry is the least recently used class.
+    uses: BTreeMap<u64, ClassHash>,
+    clock: u64,
+}
+
+impl LruClasses {
+    fn touch(&mut self, class_hash: &ClassHash) -> Option<Arc<ContractClass>> {
+        self.clock += 1;
+        let (contract_class, last_use) = self.classes.get_mut(class_hash)?;
+        self.uses.remove(last_use);
+        self.uses.insert(self.clock, *class_hash);
+        *last_use = self.clock;
+        Some(contract_class.clone())
+    }
+}
+
+/// A contract class cache that can be shared between states and threads, holding up to
+/// `capacity` classes. Once full, the least recently used class is evicted.
+///
+/// Cloning the cache is cheap and the clones share their contents, so every state created from
+/// the same cache benefits from the classes any of them has loaded.
+#[derive(Clone)]
+pub struct SharedContractClassCache {
+    capacity: usize,
+    classes: Arc<Mutex<LruClasses>>,
+}
+
+impl SharedContractClassCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            classes: Arc::new(Mutex::new(LruClasses::default())),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, class_hash: &ClassHash) -> bool {
+        self.lock().classes.contains_key(class_hash)
+    }
+
+    /// Returns the class and marks it as the most recently used one.
+    pub fn get(&self, class_hash: &ClassHash) -> Option<Arc<ContractClass>> {
+        self.lock().touch(class_hash)
+    }
+
+    pub fn insert(&self, class_hash: ClassHash, contract_class: Arc<ContractClass>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut lru = self.lock();
+        if lru.touch(&class_hash).is_some() {
+            if let Some((cached_class, _)) = lru.classes.get_mut(&class_hash) {
+                *cached_class = contract_class;
+            }
+            return;
+        }
+
+        if lru.classes.len() >= self.capacity {
+            if let Some((_, evicted)) = lru.uses.pop_first() {
+                lru.classes.remove(&evicted);
+            }
+        }
+        lru.clock += 1;
+        let last_use = lru.clock;
+        lru.uses.insert(last_use, class_hash);
+        lru.classes.insert(class_hash, (contract_class, last_use));
+    }
+
+    // A panic while holding the lock can't leave the cache inconsistent, so a poisoned lock is
+    // still safe to use.
+    fn lock(&self) -> MutexGuard<'_, LruClasses> {
+        self.classes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for SharedContractClassCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedContractClassCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Two caches are equal if they share their contents.
+impl PartialEq for SharedContractClassCache {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.classes, &other.classes)
+    }
+}
+
+impl Eq for SharedContractClassCache {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coverage_helper::test;
+    use std::{path::PathBuf, thread};
+
+    fn contract_class() -> Arc<ContractClass> {
+        Arc::new(
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap(),
+        )
+    }
+
+    #[test]
+    fn evicts_least_recently_used_class() {
+        let cache = SharedContractClassCache::new(2);
+        let contract_class = contract_class();
+
+        cache.insert([1; 32], contract_class.clone());
+        cache.insert([2; 32], contract_class.clone());
+        assert!(cache.get(&[1; 32]).is_some());
+        cache.insert([3; 32], contract_class.clone());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&[1; 32]));
+        assert!(!cache.contains(&[2; 32]));
+        assert!(cache.contains(&[3; 32]));
+    }
+
+    #[test]
+    fn returns_shared_handles() {
+        let cache = SharedContractClassCache::new(1);
+        let contract_class = contract_class();
+        cache.insert([1; 32], contract_class.clone());
+
+        assert!(Arc::ptr_eq(&cache.get(&[1; 32]).unwrap(), &contract_class));
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let cache = SharedContractClassCache::new(0);
+        cache.insert([1; 32], contract_class());
+
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn clones_share_contents_across_threads() {
+        let cache = SharedContractClassCache::new(4);
+        let contract_class = contract_class();
+
+        let handles: Vec<_> = (0..4u8)
+            .map(|i| {
+                let cache = cache.clone();
+                let contract_class = contract_class.clone();
+                thread::spawn(move || cache.insert([i; 32], contract_class))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache, cache.clone());
+        assert_ne!(cache, SharedContractClassCache::new(4));
+    }
+}
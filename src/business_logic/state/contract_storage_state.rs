@@ -1,10 +1,10 @@
 use super::state_api::{State, StateReader};
 use crate::{
     core::errors::state_errors::StateError,
-    utils::{Address, ClassHash},
+    utils::{Address, ClassHash, StorageKey},
 };
 use felt::Felt252;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub(crate) struct ContractStorageState<'a, T: State + StateReader> {
@@ -13,6 +13,8 @@ pub(crate) struct ContractStorageState<'a, T: State + StateReader> {
     /// Maintain all read request values in chronological order
     pub(crate) read_values: Vec<Felt252>,
     pub(crate) accessed_keys: HashSet<ClassHash>,
+    /// The last value written to each key.
+    pub(crate) written_values: HashMap<StorageKey, Felt252>,
 }
 
 impl<'a, T: State + StateReader> ContractStorageState<'a, T> {
@@ -22,6 +24,7 @@ impl<'a, T: State + StateReader> ContractStorageState<'a, T> {
             contract_address,
             read_values: Vec::new(),
             accessed_keys: HashSet::new(),
+            written_values: HashMap::new(),
         }
     }
 
@@ -37,6 +40,7 @@ impl<'a, T: State + StateReader> ContractStorageState<'a, T> {
 
     pub(crate) fn write(&mut self, address: &ClassHash, value: Felt252) {
         self.accessed_keys.insert(*address);
+        self.written_values.insert(*address, value.clone());
         self.state
             .set_storage_at(&(self.contract_address.clone(), *address), value);
     }
@@ -1,5 +1,8 @@
 pub mod cached_state;
+pub mod contract_class_cache;
 pub(crate) mod contract_storage_state;
+pub(crate) mod state_accesses;
 pub mod state_api;
 pub mod state_api_objects;
 pub mod state_cache;
+pub mod state_journal;
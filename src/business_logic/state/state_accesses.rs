@@ -0,0 +1,110 @@
+use super::state_cache::StorageEntry;
+use crate::utils::{Address, ClassHash, CompiledClassHash};
+use std::collections::HashSet;
+
+/// Keys of the state, by kind.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct AccessedKeys {
+    pub(crate) class_hashes: HashSet<Address>,
+    pub(crate) nonces: HashSet<Address>,
+    pub(crate) storage: HashSet<StorageEntry>,
+    pub(crate) contract_classes: HashSet<ClassHash>,
+    pub(crate) compiled_class_hashes: HashSet<ClassHash>,
+    pub(crate) compiled_classes: HashSet<CompiledClassHash>,
+}
+
+impl AccessedKeys {
+    fn is_disjoint(&self, other: &AccessedKeys) -> bool {
+        self.class_hashes.is_disjoint(&other.class_hashes)
+            && self.nonces.is_disjoint(&other.nonces)
+            && self.storage.is_disjoint(&other.storage)
+            && self.contract_classes.is_disjoint(&other.contract_classes)
+            && self
+                .compiled_class_hashes
+                .is_disjoint(&other.compiled_class_hashes)
+            && self.compiled_classes.is_disjoint(&other.compiled_classes)
+    }
+
+    fn extend(&mut self, other: AccessedKeys) {
+        self.class_hashes.extend(other.class_hashes);
+        self.nonces.extend(other.nonces);
+        self.storage.extend(other.storage);
+        self.contract_classes.extend(other.contract_classes);
+        self.compiled_class_hashes
+            .extend(other.compiled_class_hashes);
+        self.compiled_classes.extend(other.compiled_classes);
+    }
+}
+
+/// The keys read and written on a [`CachedState`](super::cached_state::CachedState) while it
+/// tracks its accesses.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct StateAccesses {
+    pub(crate) reads: AccessedKeys,
+    pub(crate) writes: AccessedKeys,
+}
+
+impl StateAccesses {
+    /// Whether running after `earlier` could change what was read or written: any key written
+    /// by one and accessed by the other. Reading the same keys is fine.
+    pub(crate) fn conflicts_with(&self, earlier: &StateAccesses) -> bool {
+        !(self.reads.is_disjoint(&earlier.writes)
+            && self.writes.is_disjoint(&earlier.reads)
+            && self.writes.is_disjoint(&earlier.writes))
+    }
+
+    pub(crate) fn extend(&mut self, other: StateAccesses) {
+        self.reads.extend(other.reads);
+        self.writes.extend(other.writes);
+    }
+
+    /// Only the reads of these accesses, for a run whose changes were undone.
+    pub(crate) fn into_reads(self) -> StateAccesses {
+        StateAccesses {
+            reads: self.reads,
+            writes: AccessedKeys::default(),
+        }
+    }
+
+    /// Every key read or written.
+    pub(crate) fn keys(&self) -> AccessedKeys {
+        let mut keys = self.reads.clone();
+        keys.extend(self.writes.clone());
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coverage_helper::test;
+
+    #[test]
+    fn only_read_read_accesses_dont_conflict() {
+        let mut reads_storage = StateAccesses::default();
+        reads_storage
+            .reads
+            .storage
+            .insert((Address(1.into()), [1; 32]));
+        let mut writes_storage = StateAccesses::default();
+        writes_storage
+            .writes
+            .storage
+            .insert((Address(1.into()), [1; 32]));
+        let mut writes_nonce = StateAccesses::default();
+        writes_nonce.writes.nonces.insert(Address(1.into()));
+
+        assert!(!reads_storage.conflicts_with(&reads_storage));
+        assert!(reads_storage.conflicts_with(&writes_storage));
+        assert!(writes_storage.conflicts_with(&reads_storage));
+        assert!(writes_storage.conflicts_with(&writes_storage));
+        // Same address, but a different kind of key.
+        assert!(!writes_nonce.conflicts_with(&writes_storage));
+
+        let mut accesses = reads_storage.clone();
+        accesses.extend(writes_nonce);
+        assert!(accesses.conflicts_with(&writes_storage));
+        assert_eq!(accesses.keys().nonces.len(), 1);
+        assert_eq!(accesses.keys().storage.len(), 1);
+    }
+}
@@ -1,22 +1,40 @@
-use super::state_cache::StorageEntry;
+use super::{
+    state_api_objects::StateChanges, state_cache::StorageEntry, state_journal::Checkpoint,
+};
 use crate::{
     core::errors::state_errors::StateError,
-    services::api::contract_class::ContractClass,
-    utils::{Address, ClassHash},
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    utils::{Address, ClassHash, CompiledClassHash},
 };
 use felt::Felt252;
+use std::sync::Arc;
 
 pub trait StateReader {
-    /// Returns the contract class of the given class hash.
-    fn get_contract_class(&mut self, class_hash: &ClassHash) -> Result<ContractClass, StateError>;
+    /// Returns a shared handle to the contract class of the given class hash.
+    fn get_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Arc<ContractClass>, StateError>;
     /// Returns the class hash of the contract class at the given address.
     fn get_class_hash_at(&mut self, contract_address: &Address) -> Result<ClassHash, StateError>;
     /// Returns the nonce of the given contract instance.
     fn get_nonce_at(&mut self, contract_address: &Address) -> Result<Felt252, StateError>;
     /// Returns the storage value under the given key in the given contract instance.
     fn get_storage_at(&mut self, storage_entry: &StorageEntry) -> Result<Felt252, StateError>;
-    /// Counts the amount of modified contracts and the updates to the storage
-    fn count_actual_storage_changes(&mut self) -> (usize, usize);
+    /// Returns the compiled class hash of the given Cairo 1 class hash, zero if it wasn't
+    /// declared.
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StateError>;
+    /// Returns a shared handle to the CASM class of the given compiled class hash, the compiled
+    /// version of a declared Cairo 1 class.
+    fn get_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<Arc<CasmContractClass>, StateError>;
+    /// Counts the changes made to the state that aren't committed to the underlying storage yet.
+    fn count_actual_state_changes(&mut self) -> StateChanges;
 }
 
 pub trait State {
@@ -25,6 +43,9 @@ pub trait State {
         class_hash: &ClassHash,
         contract_class: &ContractClass,
     ) -> Result<(), StateError>;
+    /// Keeps the JSON the class of `class_hash` was loaded from. `Program` can't be serialized
+    /// back, so that's what gets written if the class is persisted.
+    fn set_contract_class_source(&mut self, class_hash: &ClassHash, source: Arc<str>);
     fn deploy_contract(
         &mut self,
         contract_address: Address,
@@ -32,4 +53,34 @@ pub trait State {
     ) -> Result<(), StateError>;
     fn increment_nonce(&mut self, contract_address: &Address) -> Result<(), StateError>;
     fn set_storage_at(&mut self, storage_entry: &StorageEntry, value: Felt252);
+    /// Records the compiled class hash of a Cairo 1 class being declared.
+    fn set_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<(), StateError>;
+    /// Stores the CASM class of a Cairo 1 class being declared, under its compiled class hash.
+    fn set_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+        casm_class: CasmContractClass,
+    ) -> Result<(), StateError>;
+    /// Counts the changes like [`StateReader::count_actual_state_changes`], as if the nonces of
+    /// `nonces` were bumped and the keys of `storage` written. Used to charge for the writes a
+    /// transaction does after its resources are computed, like the fee transfer.
+    fn count_actual_state_changes_with(
+        &mut self,
+        nonces: &[Address],
+        storage: &[StorageEntry],
+    ) -> StateChanges;
+    /// Marks the current point of the state history. Every write done from now on can be
+    /// discarded with `revert_to` or kept with `commit`.
+    ///
+    /// Checkpoints nest: reverting or committing one also closes every checkpoint taken after
+    /// it, and writes committed into an enclosing checkpoint are still reverted along with it.
+    fn checkpoint(&mut self) -> Checkpoint;
+    /// Undoes every write done since `checkpoint` was taken.
+    fn revert_to(&mut self, checkpoint: Checkpoint) -> Result<(), StateError>;
+    /// Keeps the writes done since `checkpoint` was taken.
+    fn commit(&mut self, checkpoint: Checkpoint) -> Result<(), StateError>;
 }
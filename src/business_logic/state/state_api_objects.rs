@@ -53,6 +53,23 @@ impl Default for BlockInfo {
     }
 }
 
+/// Summary of the changes a transaction made to the state, used to estimate the L1 gas needed to
+/// publish them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StateChanges {
+    /// Contracts whose storage, nonce or class hash changed, each counted once.
+    pub n_modified_contracts: usize,
+    pub n_storage_updates: usize,
+    /// Contracts whose class hash changed, deployments included.
+    pub n_class_hash_updates: usize,
+    /// Contracts whose class hash changed from the uninitialized one.
+    pub n_deployments: usize,
+    /// Contract classes added to the state that the state reader doesn't know about.
+    pub n_declared_classes: usize,
+    /// Cairo 1 classes whose compiled class hash was set, i.e. declared.
+    pub n_compiled_class_hash_updates: usize,
+}
+
 #[test]
 fn test_validate_legal_progress() {
     let first_block = BlockInfo::default();
@@ -1,6 +1,6 @@
 use crate::{
     core::errors::state_errors::StateError,
-    utils::{Address, ClassHash},
+    utils::{Address, ClassHash, CompiledClassHash, StorageKey},
 };
 use felt::Felt252;
 use getset::{Getters, MutGetters};
@@ -8,7 +8,7 @@ use std::collections::{HashMap, HashSet};
 
 /// (contract_address, key)
 // TODO: Change [u8; 32] to Felt252.
-pub type StorageEntry = (Address, [u8; 32]);
+pub type StorageEntry = (Address, StorageKey);
 
 #[derive(Debug, Default, Clone, Eq, Getters, MutGetters, PartialEq)]
 pub struct StateCache {
@@ -19,6 +19,8 @@ pub struct StateCache {
     pub(crate) nonce_initial_values: HashMap<Address, Felt252>,
     #[get_mut = "pub"]
     pub(crate) storage_initial_values: HashMap<StorageEntry, Felt252>,
+    #[get_mut = "pub"]
+    pub(crate) compiled_class_hash_initial_values: HashMap<ClassHash, CompiledClassHash>,
 
     // Writer's cached information.
     #[get_mut = "pub"]
@@ -27,6 +29,8 @@ pub struct StateCache {
     pub(crate) nonce_writes: HashMap<Address, Felt252>,
     #[getset(get = "pub", get_mut = "pub")]
     pub(crate) storage_writes: HashMap<StorageEntry, Felt252>,
+    #[getset(get = "pub", get_mut = "pub")]
+    pub(crate) compiled_class_hash_writes: HashMap<ClassHash, CompiledClassHash>,
 }
 
 impl StateCache {
@@ -45,6 +49,8 @@ impl StateCache {
             class_hash_writes,
             nonce_writes,
             storage_writes,
+            compiled_class_hash_initial_values: HashMap::new(),
+            compiled_class_hash_writes: HashMap::new(),
         }
     }
 
@@ -56,6 +62,8 @@ impl StateCache {
             class_hash_writes: HashMap::new(),
             nonce_writes: HashMap::new(),
             storage_writes: HashMap::new(),
+            compiled_class_hash_initial_values: HashMap::new(),
+            compiled_class_hash_writes: HashMap::new(),
         }
     }
 
@@ -74,6 +82,8 @@ impl StateCache {
             class_hash_writes,
             nonce_writes,
             storage_writes,
+            compiled_class_hash_initial_values: HashMap::new(),
+            compiled_class_hash_writes: HashMap::new(),
         }
     }
 
@@ -98,11 +108,23 @@ impl StateCache {
         self.storage_initial_values.get(storage_entry)
     }
 
+    pub(crate) fn get_compiled_class_hash(
+        &self,
+        class_hash: &ClassHash,
+    ) -> Option<&CompiledClassHash> {
+        if self.compiled_class_hash_writes.contains_key(class_hash) {
+            return self.compiled_class_hash_writes.get(class_hash);
+        }
+        self.compiled_class_hash_initial_values.get(class_hash)
+    }
+
     pub(crate) fn update_writes_from_other(&mut self, other: &Self) {
         self.class_hash_writes
             .extend(other.class_hash_writes.clone());
         self.nonce_writes.extend(other.nonce_writes.clone());
         self.storage_writes.extend(other.storage_writes.clone());
+        self.compiled_class_hash_writes
+            .extend(other.compiled_class_hash_writes.clone());
     }
 
     pub(crate) fn update_writes(
@@ -127,7 +149,9 @@ impl StateCache {
             && self.nonce_initial_values.is_empty()
             && self.nonce_writes.is_empty()
             && self.storage_initial_values.is_empty()
-            && self.storage_writes.is_empty())
+            && self.storage_writes.is_empty()
+            && self.compiled_class_hash_initial_values.is_empty()
+            && self.compiled_class_hash_writes.is_empty())
         {
             return Err(StateError::StateCacheAlreadyInitialized);
         }
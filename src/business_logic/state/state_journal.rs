@@ -0,0 +1,94 @@
+use super::state_cache::StorageEntry;
+use crate::{
+    core::errors::state_errors::StateError,
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    utils::{Address, ClassHash, CompiledClassHash},
+};
+use felt::Felt252;
+use std::sync::Arc;
+
+/// Handle to a point in the history of a [`CachedState`](super::cached_state::CachedState),
+/// returned by `checkpoint()`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Checkpoint(u64);
+
+/// The value a write replaced, so it can be restored if the write is reverted. `None` means
+/// there was no write for that key before.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum JournalEntry {
+    ClassHash(Address, Option<ClassHash>),
+    Nonce(Address, Option<Felt252>),
+    Storage(StorageEntry, Option<Felt252>),
+    ContractClass(ClassHash, Option<Arc<ContractClass>>),
+    CompiledClassHash(ClassHash, Option<CompiledClassHash>),
+    CompiledClass(CompiledClassHash, Option<Arc<CasmContractClass>>),
+    /// A class declared on the state, which wasn't declared before.
+    DeclaredClass(ClassHash),
+}
+
+/// Undo log of the writes done while there are open checkpoints.
+///
+/// Nothing is recorded while no checkpoint is open, so states that don't use checkpoints don't
+/// pay for them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct StateJournal {
+    entries: Vec<JournalEntry>,
+    // (checkpoint id, journal length when it was taken), from outermost to innermost.
+    checkpoints: Vec<(u64, usize)>,
+    next_id: u64,
+}
+
+impl StateJournal {
+    pub(crate) fn record(&mut self, entry: JournalEntry) {
+        if !self.checkpoints.is_empty() {
+            self.entries.push(entry);
+        }
+    }
+
+    pub(crate) fn checkpoint(&mut self) -> Checkpoint {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.checkpoints.push((id, self.entries.len()));
+        Checkpoint(id)
+    }
+
+    /// Closes `checkpoint` and every checkpoint opened after it, returning the entries to undo,
+    /// most recent first.
+    pub(crate) fn revert_to(
+        &mut self,
+        checkpoint: Checkpoint,
+    ) -> Result<Vec<JournalEntry>, StateError> {
+        let depth = self.depth(checkpoint)?;
+        let (_, start) = self.checkpoints[depth];
+        self.checkpoints.truncate(depth);
+
+        let mut undo = self.entries.split_off(start);
+        undo.reverse();
+        Ok(undo)
+    }
+
+    /// Closes `checkpoint` and every checkpoint opened after it, keeping their writes. The
+    /// writes can still be reverted through an enclosing checkpoint.
+    pub(crate) fn commit(&mut self, checkpoint: Checkpoint) -> Result<(), StateError> {
+        let depth = self.depth(checkpoint)?;
+        self.checkpoints.truncate(depth);
+        if self.checkpoints.is_empty() {
+            self.entries.clear();
+        }
+        Ok(())
+    }
+
+    /// The writes done since the innermost open checkpoint, oldest first, or `None` if no
+    /// checkpoint is open.
+    pub(crate) fn innermost_entries(&self) -> Option<&[JournalEntry]> {
+        let (_, start) = self.checkpoints.last()?;
+        Some(&self.entries[*start..])
+    }
+
+    fn depth(&self, checkpoint: Checkpoint) -> Result<usize, StateError> {
+        self.checkpoints
+            .iter()
+            .position(|(id, _)| *id == checkpoint.0)
+            .ok_or(StateError::InvalidCheckpoint(checkpoint.0))
+    }
+}
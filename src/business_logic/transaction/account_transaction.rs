@@ -0,0 +1,245 @@
+use super::{
+    error::TransactionError,
+    fee::{
+        calculate_tx_fee, check_fee_balance, execute_fee_transfer, execute_reverted_fee_transfer,
+        simulate_fee_charge, FeeInfo,
+    },
+    nonce::increment_nonce,
+    simulation::SimulationFlags,
+};
+use crate::{
+    business_logic::{
+        execution::{
+            objects::{TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
+        },
+        state::{
+            state_api::{State, StateReader},
+            state_api_objects::StateChanges,
+        },
+    },
+    definitions::general_config::StarknetGeneralConfig,
+    utils::{get_erc20_balance_var_addresses, Address},
+};
+use num_traits::Zero;
+use std::collections::HashMap;
+
+/// A transaction sent from an account: an invoke, a declare or a deploy_account.
+///
+/// They all go through the same steps: the account must be able to pay the max fee, the
+/// transaction is applied, the account nonce is bumped and the fee is charged. Each transaction
+/// type only provides how it's validated and executed, in
+/// [`apply_with_validation`](Self::apply_with_validation).
+pub trait AccountTransaction {
+    /// The account that sends the transaction and pays its fee.
+    fn account_address(&self) -> &Address;
+
+    fn version(&self) -> u64;
+
+    fn max_fee(&self) -> u64;
+
+    /// Whether the transaction was built with a query version, in which case it can only be
+    /// simulated.
+    fn is_query(&self) -> bool {
+        false
+    }
+
+    fn get_execution_context(
+        &self,
+        n_steps: u64,
+    ) -> Result<TransactionExecutionContext, TransactionError>;
+
+    /// Verifies the nonce, runs the account's validate entry point if `validate` is set, and
+    /// executes the transaction. The fee isn't charged and the nonce isn't bumped. The entry
+    /// points run are profiled into `profiler`, if given.
+    fn apply_with_validation<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        validate: bool,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError>;
+
+    /// Validates and executes the transaction, then returns the transaction execution info of
+    /// the run.
+    fn apply<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.apply_with_validation(state, general_config, true, None)
+    }
+
+    /// Applies the transaction, bumps the account nonce and charges the actual fee.
+    ///
+    /// The state is only modified if the whole transaction goes through: a failing validation,
+    /// nonce check or fee transfer leaves it untouched. A reverted execution still bumps the
+    /// nonce and pays the fee, up to the max fee.
+    fn execute<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.execute_with_profiler(state, general_config, None)
+    }
+
+    /// Executes the transaction like [`execute`](Self::execute), profiling the entry points run,
+    /// the fee transfer included, into `profiler` if given.
+    fn execute_with_profiler<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        if self.is_query() {
+            return Err(TransactionError::QueryTransactionExecution);
+        }
+
+        let checkpoint = state.checkpoint();
+        match apply_and_charge_fee(self, state, general_config, profiler) {
+            Ok(tx_execution_info) => {
+                state.commit(checkpoint)?;
+                Ok(tx_execution_info)
+            }
+            Err(error) => {
+                state.revert_to(checkpoint)?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Runs the transaction like [`execute`](Self::execute), optionally skipping the validation
+    /// and the fee transfer, and always computing the fee. Query transactions can be simulated.
+    /// The changes are left in `state`, it's up to the caller to discard them.
+    fn simulate<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        flags: SimulationFlags,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        let concurrent_exec_info =
+            self.apply_with_validation(state, general_config, !flags.skip_validate, None)?;
+        increment_nonce(state, self.account_address(), self.version())?;
+
+        let (fee_transfer_info, actual_fee) = simulate_fee_charge(
+            state,
+            &concurrent_exec_info.actual_resources,
+            general_config,
+            &self.get_execution_context(general_config.invoke_tx_max_n_steps)?,
+            flags.skip_fee_charge,
+        )?;
+
+        Ok(
+            TransactionExecutionInfo::from_concurrent_state_execution_info(
+                concurrent_exec_info,
+                actual_fee,
+                fee_transfer_info,
+            ),
+        )
+    }
+}
+
+/// Counts the state changes of `tx`, including the writes done once it's applied: the bump of
+/// the account nonce and, if the transaction pays a fee, the update of the account balance in the
+/// fee token.
+pub(crate) fn count_account_tx_state_changes<T, S>(
+    tx: &T,
+    state: &mut S,
+    general_config: &StarknetGeneralConfig,
+) -> Result<StateChanges, TransactionError>
+where
+    T: AccountTransaction + ?Sized,
+    S: State,
+{
+    let account = tx.account_address();
+    let nonces = match tx.version() {
+        0 => Vec::new(),
+        _ => vec![account.clone()],
+    };
+    let storage = match tx.max_fee() {
+        0 => Vec::new(),
+        _ => {
+            let (balance_key, _) = get_erc20_balance_var_addresses(account)?;
+            vec![(
+                general_config.starknet_os_config.fee_token_address.clone(),
+                balance_key,
+            )]
+        }
+    };
+
+    Ok(state.count_actual_state_changes_with(&nonces, &storage))
+}
+
+fn apply_and_charge_fee<T, S>(
+    tx: &T,
+    state: &mut S,
+    general_config: &StarknetGeneralConfig,
+    profiler: Option<&Profiler>,
+) -> Result<TransactionExecutionInfo, TransactionError>
+where
+    T: AccountTransaction + ?Sized,
+    S: Default + State + StateReader + Clone,
+{
+    check_fee_balance(state, general_config, tx.account_address(), tx.max_fee())?;
+    let concurrent_exec_info = tx.apply_with_validation(state, general_config, true, profiler)?;
+    increment_nonce(state, tx.account_address(), tx.version())?;
+
+    let (fee_transfer_info, actual_fee) = charge_fee(
+        tx,
+        state,
+        &concurrent_exec_info.actual_resources,
+        general_config,
+        concurrent_exec_info.is_reverted(),
+        profiler,
+    )?;
+
+    Ok(
+        TransactionExecutionInfo::from_concurrent_state_execution_info(
+            concurrent_exec_info,
+            actual_fee,
+            fee_transfer_info,
+        ),
+    )
+}
+
+/// Calculates and charges the actual fee. Transactions without a max fee aren't charged.
+///
+/// A transaction whose actual fee exceeds its max fee is rejected, unless its execution was
+/// reverted: it then pays its max fee and is included.
+fn charge_fee<T, S>(
+    tx: &T,
+    state: &mut S,
+    resources: &HashMap<String, usize>,
+    general_config: &StarknetGeneralConfig,
+    reverted: bool,
+    profiler: Option<&Profiler>,
+) -> Result<FeeInfo, TransactionError>
+where
+    T: AccountTransaction + ?Sized,
+    S: Default + State + StateReader + Clone,
+{
+    if tx.max_fee().is_zero() {
+        return Ok((None, 0));
+    }
+
+    let actual_fee = calculate_tx_fee(
+        resources,
+        general_config.starknet_os_config.gas_price,
+        general_config,
+    )?;
+
+    let tx_context = tx.get_execution_context(general_config.invoke_tx_max_n_steps)?;
+    if reverted {
+        return execute_reverted_fee_transfer(
+            state,
+            general_config,
+            &tx_context,
+            actual_fee,
+            profiler,
+        );
+    }
+    let fee_transfer_info =
+        execute_fee_transfer(state, general_config, &tx_context, actual_fee, profiler)?;
+
+    Ok((Some(fee_transfer_info), actual_fee))
+}
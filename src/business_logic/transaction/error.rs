@@ -1,5 +1,5 @@
 use crate::{
-    business_logic::execution::os_usage::OsResources,
+    business_logic::execution::{error_stack::ErrorStack, os_usage::OsResources},
     core::errors::{
         contract_address_errors::ContractAddressError, state_errors::StateError,
         syscall_handler_errors::SyscallHandlerError,
@@ -16,22 +16,31 @@ use cairo_rs::{
         trace_errors::TraceError, vm_errors::VirtualMachineError,
     },
 };
+use num_bigint::BigUint;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum TransactionError {
-    #[error("Nonce is None")]
+    #[error("Transactions of version 1 and above must have a nonce")]
     MissingNonce,
     #[error("An InvokeFunction transaction (version != 0) must have a nonce.")]
     InvokeFunctionNonZeroMissingNonce,
     #[error("An InvokeFunction transaction (version = 0) cannot have a nonce.")]
     InvokeFunctionZeroHasNonce,
+    #[error("Transaction version {0} is not supported")]
+    UnsupportedVersion(String),
+    #[error("Transactions with a query version can only be simulated")]
+    QueryTransactionExecution,
+    #[error("Could not sign the transaction: {0}")]
+    SignatureError(String),
     #[error("Invalid transaction nonce. Expected: {0} got {1}")]
     InvalidTransactionNonce(String, String),
     #[error("{0}")]
     StarknetError(String),
     #[error("{0}")]
     FeeError(String),
+    #[error("Account balance {balance} is lower than the max fee {max_fee}")]
+    InsufficientAccountBalance { balance: BigUint, max_fee: u64 },
     #[error("Cairo resource names must be contained in fee weights dict")]
     ResourcesError,
     #[error("Could not calculate resources")]
@@ -46,6 +55,8 @@ pub enum TransactionError {
     UnauthorizedActionOnValidate,
     #[error("Class hash {0:?} already declared")]
     ClassAlreadyDeclared(ClassHash),
+    #[error("Compiled class hash {0} doesn't match the compiled class, whose hash is {1}")]
+    InvalidCompiledClassHash(String, String),
     #[error(transparent)]
     Starkware(#[from] StarkwareError),
     #[error("Expected a relocatable value but got an integer")]
@@ -88,6 +99,8 @@ pub enum TransactionError {
     OutOfBound,
     #[error("Call to another contract has been done")]
     InvalidContractCall,
+    #[error("Ran out of the Cairo steps allowed for the transaction")]
+    OutOfResources,
     #[error(transparent)]
     TraceException(#[from] TraceError),
     #[error(transparent)]
@@ -100,12 +113,27 @@ pub enum TransactionError {
     Vm(#[from] VirtualMachineError),
     #[error(transparent)]
     CairoRunner(#[from] CairoRunError),
+    #[error("{0}")]
+    EntryPointFailed(#[source] ErrorStack),
     #[error(transparent)]
     Runner(#[from] RunnerError),
     #[error(transparent)]
     StarknetRunner(#[from] StarknetRunnerError),
     #[error("Transaction type {0:?} not found in OsResources: {1:?}")]
     NoneTransactionType(TransactionType, OsResources),
+    #[error("Syscall {0} not found in OsResources")]
+    NoneSyscall(String),
     #[error(transparent)]
     MathError(#[from] MathError),
 }
+
+impl TransactionError {
+    /// The error that made the transaction fail: the one the innermost entry point failed with
+    /// if an entry point failed, or the error itself otherwise.
+    pub fn root_cause(&self) -> &TransactionError {
+        match self {
+            TransactionError::EntryPointFailed(error_stack) => error_stack.root_cause(),
+            error => error,
+        }
+    }
+}
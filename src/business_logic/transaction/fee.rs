@@ -4,6 +4,7 @@ use crate::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
             objects::{CallInfo, TransactionExecutionContext},
+            profiler::Profiler,
         },
         fact_state::state::ExecutionResourcesManager,
         state::state_api::{State, StateReader},
@@ -12,14 +13,51 @@ use crate::{
         constants::TRANSFER_ENTRY_POINT_SELECTOR, general_config::StarknetGeneralConfig,
     },
     services::api::contract_class::EntryPointType,
+    utils::{get_erc20_balance_var_addresses, Address},
 };
 use felt::Felt252;
-use num_traits::ToPrimitive;
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
 use std::collections::HashMap;
 
 // second element is the actual fee that the transaction uses
 pub type FeeInfo = (Option<CallInfo>, u64);
 
+/// Reads the fee token balance of `account` straight from the storage of the fee token
+/// contract, the low and high halves of its `Uint256` combined.
+pub fn get_fee_token_balance<S: StateReader>(
+    state: &mut S,
+    general_config: &StarknetGeneralConfig,
+    account: &Address,
+) -> Result<BigUint, TransactionError> {
+    let fee_token_address = &general_config.starknet_os_config.fee_token_address;
+    let (low_key, high_key) = get_erc20_balance_var_addresses(account)?;
+
+    let low = state.get_storage_at(&(fee_token_address.clone(), low_key))?;
+    let high = state.get_storage_at(&(fee_token_address.clone(), high_key))?;
+    Ok((high.to_biguint() << 128) + low.to_biguint())
+}
+
+/// Checks that `account` can pay `max_fee` before running the transaction, so a transaction
+/// that can't pay for itself is rejected without executing it.
+pub(crate) fn check_fee_balance<S: StateReader>(
+    state: &mut S,
+    general_config: &StarknetGeneralConfig,
+    account: &Address,
+    max_fee: u64,
+) -> Result<(), TransactionError> {
+    // Transactions without a max fee aren't charged.
+    if max_fee.is_zero() {
+        return Ok(());
+    }
+
+    let balance = get_fee_token_balance(state, general_config, account)?;
+    if balance < BigUint::from(max_fee) {
+        return Err(TransactionError::InsufficientAccountBalance { balance, max_fee });
+    }
+    Ok(())
+}
+
 /// Transfers the amount actual_fee from the caller account to the sequencer.
 /// Returns the resulting CallInfo of the transfer call.
 pub(crate) fn execute_fee_transfer<S: Default + State + StateReader + Clone>(
@@ -27,6 +65,7 @@ pub(crate) fn execute_fee_transfer<S: Default + State + StateReader + Clone>(
     general_config: &StarknetGeneralConfig,
     tx_context: &TransactionExecutionContext,
     actual_fee: u64,
+    profiler: Option<&Profiler>,
 ) -> Result<CallInfo, TransactionError> {
     if actual_fee > tx_context.max_fee {
         return Err(TransactionError::FeeError(
@@ -34,6 +73,33 @@ pub(crate) fn execute_fee_transfer<S: Default + State + StateReader + Clone>(
         ));
     }
 
+    transfer_fee(state, general_config, tx_context, actual_fee, profiler)
+}
+
+/// Charges a reverted transaction for the resources it used, up to its max fee, so it's included
+/// as reverted instead of being rejected when they cost more than that. Returns the transfer
+/// along with the fee charged.
+pub(crate) fn execute_reverted_fee_transfer<S: Default + State + StateReader + Clone>(
+    state: &mut S,
+    general_config: &StarknetGeneralConfig,
+    tx_context: &TransactionExecutionContext,
+    actual_fee: u64,
+    profiler: Option<&Profiler>,
+) -> Result<FeeInfo, TransactionError> {
+    let charged_fee = actual_fee.min(tx_context.max_fee);
+    let fee_transfer_info = transfer_fee(state, general_config, tx_context, charged_fee, profiler)?;
+    Ok((Some(fee_transfer_info), charged_fee))
+}
+
+/// Transfers the amount actual_fee from the caller account to the sequencer, whatever the max
+/// fee of the transaction. The transfer is profiled into `profiler`, if given.
+fn transfer_fee<S: Default + State + StateReader + Clone>(
+    state: &mut S,
+    general_config: &StarknetGeneralConfig,
+    tx_context: &TransactionExecutionContext,
+    actual_fee: u64,
+    profiler: Option<&Profiler>,
+) -> Result<CallInfo, TransactionError> {
     let fee_token_address = general_config.starknet_os_config.fee_token_address.clone();
 
     let calldata = [
@@ -53,12 +119,35 @@ pub(crate) fn execute_fee_transfer<S: Default + State + StateReader + Clone>(
         None,
     );
 
-    let mut resources_manager = ExecutionResourcesManager::default();
+    let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
     fee_transfer_call
         .execute(state, general_config, &mut resources_manager, tx_context)
         .map_err(|_| TransactionError::FeeError("Fee transfer failure".to_string()))
 }
 
+/// Calculates the fee of a simulated transaction and, unless `skip_fee_charge` is set, transfers
+/// it to the sequencer. Unlike a regular execution the fee is charged even if it exceeds the max
+/// fee of the transaction, or if the max fee is zero, so it can be reported.
+pub(crate) fn simulate_fee_charge<S: Default + State + StateReader + Clone>(
+    state: &mut S,
+    resources: &HashMap<String, usize>,
+    general_config: &StarknetGeneralConfig,
+    tx_context: &TransactionExecutionContext,
+    skip_fee_charge: bool,
+) -> Result<FeeInfo, TransactionError> {
+    let actual_fee = calculate_tx_fee(
+        resources,
+        general_config.starknet_os_config.gas_price,
+        general_config,
+    )?;
+    if skip_fee_charge {
+        return Ok((None, actual_fee));
+    }
+
+    let fee_transfer_info = transfer_fee(state, general_config, tx_context, actual_fee, None)?;
+    Ok((Some(fee_transfer_info), actual_fee))
+}
+
 // ----------------------------------------------------------------------------------------
 /// Calculates the fee of a transaction given its execution resources.
 /// We add the l1_gas_usage (which may include, for example, the direct cost of L2-to-L1
@@ -68,6 +157,17 @@ pub fn calculate_tx_fee(
     resources: &HashMap<String, usize>,
     gas_price: u64,
     general_config: &StarknetGeneralConfig,
+) -> Result<u64, TransactionError> {
+    Ok(calculate_tx_l1_gas_usage(resources, general_config)? * gas_price)
+}
+
+// ----------------------------------------------------------------------------------------
+/// Calculates the total L1 gas consumed by a transaction given its execution resources, the
+/// l1_gas_usage plus the gas consumed by Cairo resources, rounded up.
+
+pub fn calculate_tx_l1_gas_usage(
+    resources: &HashMap<String, usize>,
+    general_config: &StarknetGeneralConfig,
 ) -> Result<u64, TransactionError> {
     let gas_usage = resources
         .get(&"l1_gas_usage".to_string())
@@ -77,7 +177,7 @@ pub fn calculate_tx_fee(
     let l1_gas_by_cairo_usage = calculate_l1_gas_by_cairo_usage(general_config, resources)?;
     let total_l1_gas_usage = gas_usage.to_f64().unwrap() + l1_gas_by_cairo_usage;
 
-    Ok(total_l1_gas_usage.ceil() as u64 * gas_price)
+    Ok(total_l1_gas_usage.ceil() as u64)
 }
 
 // ----------------------------------------------------------------------------------------
@@ -1,4 +1,8 @@
+pub mod account_transaction;
 pub mod error;
 pub mod fee;
+pub mod nonce;
 pub mod objects;
+pub mod signer;
+pub mod simulation;
 pub mod transactions;
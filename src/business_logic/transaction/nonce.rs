@@ -0,0 +1,227 @@
+use super::{error::TransactionError, transactions::Transaction};
+use crate::{
+    business_logic::state::state_api::{State, StateReader},
+    core::errors::state_errors::StateError,
+    utils::{validate_contract_deployed, Address},
+};
+use felt::Felt252;
+use num_traits::Zero;
+use std::collections::HashMap;
+
+// The nonce policy shared by the account transactions (invoke, declare and deploy_account).
+//
+// Version 0 transactions don't use nonces. From version 1 on, a transaction must carry the
+// current nonce of its account. The nonce is verified before the account's validate entry point
+// runs, and bumped once the transaction went through.
+
+/// Checks that `nonce` is the current nonce of `account`.
+pub(crate) fn verify_nonce<S: StateReader>(
+    state: &mut S,
+    account: &Address,
+    version: u64,
+    nonce: Option<&Felt252>,
+) -> Result<(), TransactionError> {
+    if version == 0 {
+        return Ok(());
+    }
+
+    let nonce = nonce.ok_or(TransactionError::MissingNonce)?;
+    validate_contract_deployed(state, account)?;
+    let current_nonce = state.get_nonce_at(account)?;
+    if *nonce != current_nonce {
+        return Err(TransactionError::InvalidTransactionNonce(
+            current_nonce.to_string(),
+            nonce.to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Bumps the nonce of `account` after a transaction verified with [`verify_nonce`].
+pub(crate) fn increment_nonce<S: State>(
+    state: &mut S,
+    account: &Address,
+    version: u64,
+) -> Result<(), TransactionError> {
+    if version == 0 {
+        return Ok(());
+    }
+
+    state.increment_nonce(account)?;
+    Ok(())
+}
+
+/// The nonces of accounts with transactions accepted but not executed yet.
+///
+/// A sequencer receiving transactions can't check their nonces against the state alone: once a
+/// transaction with nonce `n` is accepted, the next one from the same account comes with `n + 1`
+/// while the state still says `n`. `PendingNonces` keeps track of the accepted transactions so
+/// they can be accepted in order, each with the nonce following the previous one.
+#[derive(Clone, Debug, Default)]
+pub struct PendingNonces {
+    next_nonces: HashMap<Address, Felt252>,
+}
+
+impl PendingNonces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The nonce the next transaction of `account` must have: the nonce following its last
+    /// accepted transaction, or its current nonce in `state` if the state is ahead. Accounts
+    /// that aren't deployed yet start at 0.
+    pub fn next_nonce<S: StateReader>(
+        &self,
+        state: &mut S,
+        account: &Address,
+    ) -> Result<Felt252, TransactionError> {
+        let current_nonce = match state.get_nonce_at(account) {
+            Ok(nonce) => nonce,
+            Err(StateError::NoneContractState(_)) => Felt252::zero(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(match self.next_nonces.get(account) {
+            Some(next_nonce) if *next_nonce > current_nonce => next_nonce.clone(),
+            _ => current_nonce,
+        })
+    }
+
+    /// Accepts `tx` if it has the next nonce of its account, recording it as pending.
+    ///
+    /// Transactions without a nonce, the ones that don't come from an account or have version 0,
+    /// are always accepted.
+    pub fn accept<S: StateReader>(
+        &mut self,
+        state: &mut S,
+        tx: &Transaction,
+    ) -> Result<(), TransactionError> {
+        let (Some(account), Some(nonce)) = (tx.sender_address(), tx.nonce()) else {
+            return Ok(());
+        };
+
+        let expected_nonce = self.next_nonce(state, account)?;
+        if *nonce != expected_nonce {
+            return Err(TransactionError::InvalidTransactionNonce(
+                expected_nonce.to_string(),
+                nonce.to_string(),
+            ));
+        }
+        self.next_nonces.insert(account.clone(), nonce + 1);
+        Ok(())
+    }
+
+    /// Forgets the pending transactions of `account`, e.g. after they were dropped. Its next
+    /// nonce goes back to the one in the state.
+    pub fn remove(&mut self, account: &Address) {
+        self.next_nonces.remove(account);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            fact_state::in_memory_state_reader::InMemoryStateReader,
+            state::cached_state::CachedState,
+            transaction::objects::internal_invoke_function::InternalInvokeFunction,
+        },
+        definitions::{constants::TRANSACTION_VERSION, general_config::StarknetChainId},
+    };
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+
+    fn account_state() -> CachedState<InMemoryStateReader> {
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(Address(1.into()), [1; 32]);
+        state_reader
+            .address_to_nonce_mut()
+            .insert(Address(1.into()), 3.into());
+        CachedState::new(state_reader, None)
+    }
+
+    fn invoke(nonce: u64) -> Transaction {
+        Transaction::InvokeFunction(
+            InternalInvokeFunction::new(
+                Address(1.into()),
+                2.into(),
+                0,
+                TRANSACTION_VERSION.into(),
+                Vec::new(),
+                Vec::new(),
+                StarknetChainId::TestNet.to_felt(),
+                Some(nonce.into()),
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn verify_nonce_policy() {
+        let mut state = account_state();
+        let account = Address(1.into());
+
+        assert!(verify_nonce(&mut state, &account, 1, Some(&3.into())).is_ok());
+        assert_matches!(
+            verify_nonce(&mut state, &account, 1, Some(&4.into())),
+            Err(TransactionError::InvalidTransactionNonce(expected, got))
+                if expected == "3" && got == "4"
+        );
+        assert_matches!(
+            verify_nonce(&mut state, &account, 1, None),
+            Err(TransactionError::MissingNonce)
+        );
+        assert_matches!(
+            verify_nonce(&mut state, &Address(2.into()), 1, Some(&0.into())),
+            Err(TransactionError::NotDeployedContract(..))
+        );
+        // Version 0 transactions have no nonce to check.
+        assert!(verify_nonce(&mut state, &Address(2.into()), 0, None).is_ok());
+
+        increment_nonce(&mut state, &account, 0).unwrap();
+        assert_eq!(state.get_nonce_at(&account).unwrap(), Felt252::new(3));
+        increment_nonce(&mut state, &account, 1).unwrap();
+        assert_eq!(state.get_nonce_at(&account).unwrap(), Felt252::new(4));
+    }
+
+    #[test]
+    fn pending_nonces_accept_transactions_in_order() {
+        let mut state = account_state();
+        let account = Address(1.into());
+        let mut pending_nonces = PendingNonces::new();
+
+        pending_nonces.accept(&mut state, &invoke(3)).unwrap();
+        pending_nonces.accept(&mut state, &invoke(4)).unwrap();
+        assert_matches!(
+            pending_nonces.accept(&mut state, &invoke(6)),
+            Err(TransactionError::InvalidTransactionNonce(expected, _)) if expected == "5"
+        );
+        // A nonce can't be used twice either.
+        assert!(pending_nonces.accept(&mut state, &invoke(4)).is_err());
+        assert_eq!(
+            pending_nonces.next_nonce(&mut state, &account).unwrap(),
+            Felt252::new(5)
+        );
+
+        // Once the state catches up, it's the state that counts.
+        for _ in 0..3 {
+            state.increment_nonce(&account).unwrap();
+        }
+        assert_eq!(
+            pending_nonces.next_nonce(&mut state, &account).unwrap(),
+            Felt252::new(6)
+        );
+
+        pending_nonces.remove(&account);
+        assert!(pending_nonces.accept(&mut state, &invoke(6)).is_ok());
+
+        assert_eq!(
+            pending_nonces
+                .next_nonce(&mut state, &Address(2.into()))
+                .unwrap(),
+            Felt252::zero()
+        );
+    }
+}
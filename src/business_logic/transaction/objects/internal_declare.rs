@@ -3,12 +3,15 @@ use crate::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
             objects::{CallInfo, TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
         },
         fact_state::state::ExecutionResourcesManager,
         state::state_api::{State, StateReader},
         transaction::{
+            account_transaction::{count_account_tx_state_changes, AccountTransaction},
             error::TransactionError,
-            fee::{calculate_tx_fee, execute_fee_transfer, FeeInfo},
+            nonce::verify_nonce,
+            signer::{SignedTransaction, StarkSigner},
         },
     },
     core::{
@@ -20,17 +23,17 @@ use crate::{
         constants::VALIDATE_DECLARE_ENTRY_POINT_SELECTOR, general_config::StarknetGeneralConfig,
         transaction_type::TransactionType,
     },
-    services::api::contract_class::{ContractClass, EntryPointType},
+    services::api::contract_class::{ContractClassWithSource, EntryPointType},
     utils::{calculate_tx_resources, verify_no_calls_to_other_contracts, Address, ClassHash},
 };
 use felt::Felt252;
 use num_traits::Zero;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 ///  Represents an internal transaction in the StarkNet network that is a declaration of a Cairo
 ///  contract class.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct InternalDeclare {
     pub class_hash: ClassHash,
     pub sender_address: Address,
@@ -41,7 +44,9 @@ pub struct InternalDeclare {
     pub signature: Vec<Felt252>,
     pub nonce: Felt252,
     pub hash_value: Felt252,
-    pub contract_class: ContractClass,
+    /// Serialized as the JSON the class was loaded from, so only declares of classes loaded with
+    /// it can be serialized.
+    pub contract_class: ContractClassWithSource,
 }
 
 // ------------------------------------------------------------
@@ -49,7 +54,7 @@ pub struct InternalDeclare {
 // ------------------------------------------------------------
 impl InternalDeclare {
     pub fn new(
-        contract_class: ContractClass,
+        contract_class: impl Into<ContractClassWithSource>,
         chain_id: Felt252,
         sender_address: Address,
         max_fee: u64,
@@ -57,11 +62,12 @@ impl InternalDeclare {
         signature: Vec<Felt252>,
         nonce: Felt252,
     ) -> Result<Self, TransactionError> {
-        let hash = compute_class_hash(&contract_class)?;
+        let contract_class = contract_class.into();
+        let hash = compute_class_hash(&contract_class.contract_class)?;
         let class_hash = hash.to_be_bytes();
 
         let hash_value = calculate_declare_transaction_hash(
-            &contract_class,
+            &contract_class.contract_class,
             chain_id,
             &sender_address,
             max_fee,
@@ -89,6 +95,30 @@ impl InternalDeclare {
         Ok(internal_declare)
     }
 
+    /// Like [`new`](Self::new), signed by `signer`: the signature is `[r, s]` over the declare
+    /// hash, which the sender checks in `__validate_declare__`. Version 0 declares must have an
+    /// empty signature, build them with [`new`](Self::new).
+    pub fn new_signed(
+        contract_class: impl Into<ContractClassWithSource>,
+        chain_id: Felt252,
+        sender_address: Address,
+        max_fee: u64,
+        version: u64,
+        signer: &StarkSigner,
+        nonce: Felt252,
+    ) -> Result<Self, TransactionError> {
+        Self::new(
+            contract_class,
+            chain_id,
+            sender_address,
+            max_fee,
+            version,
+            Vec::new(),
+            nonce,
+        )?
+        .signed_by(signer)
+    }
+
     pub fn get_calldata(&self) -> Vec<Felt252> {
         let bytes = Felt252::from_bytes_be(&self.class_hash);
         Vec::from([bytes])
@@ -107,65 +137,20 @@ impl InternalDeclare {
                     "The nonce field in Declare transactions of version 0 must be 0.".to_string(),
                 ));
             }
-        }
 
-        if !self.signature.len().is_zero() {
-            return Err(TransactionError::StarknetError(
-                "The signature field in Declare transactions must be an empty list.".to_string(),
-            ));
+            if !self.signature.len().is_zero() {
+                return Err(TransactionError::StarknetError(
+                    "The signature field in Declare transactions of version 0 must be an empty list."
+                        .to_string(),
+                ));
+            }
         }
         Ok(())
     }
 
-    /// Executes a call to the cairo-vm using the accounts_validation.cairo contract to validate
-    /// the contract that is being declared. Then it returns the transaction execution info of the run.
-    pub fn apply<S: Default + State + StateReader + Clone>(
-        &self,
-        state: &mut S,
-        general_config: &StarknetGeneralConfig,
-    ) -> Result<TransactionExecutionInfo, TransactionError> {
-        self.verify_version()?;
-
-        // validate transaction
-        let mut resources_manager = ExecutionResourcesManager::default();
-        let validate_info =
-            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?;
-
-        let changes = state.count_actual_storage_changes();
-        let actual_resources = calculate_tx_resources(
-            resources_manager,
-            &vec![validate_info.clone()],
-            TransactionType::Declare,
-            changes,
-            None,
-        )
-        .map_err(|_| TransactionError::ResourcesCalculation)?;
-
-        Ok(
-            TransactionExecutionInfo::create_concurrent_stage_execution_info(
-                validate_info,
-                None,
-                actual_resources,
-                Some(self.tx_type),
-            ),
-        )
-    }
-
     // ~~~~~~~~~~~~~~~~~~~~~~~~~~~
     // Internal Account Functions
     // ~~~~~~~~~~~~~~~~~~~~~~~~~~~
-    pub fn get_execution_context(&self, n_steps: u64) -> TransactionExecutionContext {
-        TransactionExecutionContext::new(
-            self.sender_address.clone(),
-            self.hash_value.clone(),
-            self.signature.clone(),
-            self.max_fee,
-            self.nonce.clone(),
-            n_steps,
-            self.version,
-        )
-    }
-
     pub fn run_validate_entrypoint<S: Default + State + StateReader>(
         &self,
         state: &mut S,
@@ -192,7 +177,7 @@ impl InternalDeclare {
             state,
             general_config,
             resources_manager,
-            &self.get_execution_context(general_config.invoke_tx_max_n_steps),
+            &self.get_execution_context(general_config.validate_max_n_steps)?,
         )?;
 
         verify_no_calls_to_other_contracts(&call_info)
@@ -201,90 +186,105 @@ impl InternalDeclare {
         Ok(Some(call_info))
     }
 
-    /// Calculates and charges the actual fee.
-    pub fn charge_fee<S: Default + State + StateReader + Clone>(
+    fn declare_class<S: Default + State + StateReader + Clone>(
         &self,
         state: &mut S,
-        resources: &HashMap<String, usize>,
-        general_config: &StarknetGeneralConfig,
-    ) -> Result<FeeInfo, TransactionError> {
-        if self.max_fee.is_zero() {
-            return Ok((None, 0));
+    ) -> Result<(), TransactionError> {
+        match state.get_contract_class(&self.class_hash) {
+            Err(StateError::MissingClassHash()) => {
+                // Class is undeclared; declare it.
+                state.set_contract_class(&self.class_hash, &self.contract_class.contract_class)?;
+                if let Some(source) = &self.contract_class.source {
+                    state.set_contract_class_source(&self.class_hash, source.clone());
+                }
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
+            // Class is already declared; cannot redeclare.
+            Ok(_) => Err(TransactionError::ClassAlreadyDeclared(self.class_hash)),
         }
+    }
+}
 
-        let actual_fee = calculate_tx_fee(
-            resources,
-            general_config.starknet_os_config.gas_price,
-            general_config,
-        )?;
+impl AccountTransaction for InternalDeclare {
+    fn account_address(&self) -> &Address {
+        &self.sender_address
+    }
 
-        let tx_context = self.get_execution_context(general_config.invoke_tx_max_n_steps);
-        let fee_transfer_info =
-            execute_fee_transfer(state, general_config, &tx_context, actual_fee)?;
+    fn version(&self) -> u64 {
+        self.version
+    }
 
-        Ok((Some(fee_transfer_info), actual_fee))
+    fn max_fee(&self) -> u64 {
+        self.max_fee
     }
 
-    fn handle_nonce<S: Default + State + StateReader + Clone>(
+    fn get_execution_context(
         &self,
-        state: &mut S,
-    ) -> Result<(), TransactionError> {
-        if self.version == 0 {
-            return Ok(());
-        }
-
-        let contract_address = &self.sender_address;
-        let current_nonce = state.get_nonce_at(contract_address)?;
-        if current_nonce != self.nonce {
-            return Err(TransactionError::InvalidTransactionNonce(
-                current_nonce.to_string(),
-                self.nonce.to_string(),
-            ));
-        }
-
-        state.increment_nonce(contract_address)?;
-
-        Ok(())
+        n_steps: u64,
+    ) -> Result<TransactionExecutionContext, TransactionError> {
+        Ok(TransactionExecutionContext::new(
+            self.sender_address.clone(),
+            self.hash_value.clone(),
+            self.signature.clone(),
+            self.max_fee,
+            self.nonce.clone(),
+            n_steps,
+            self.version,
+        ))
     }
 
-    /// Calculates actual fee used by the transaction using the execution
-    /// info returned by apply(), then updates the transaction execution info with the data of the fee.
-    pub fn execute<S: Default + State + StateReader + Clone>(
+    fn apply_with_validation<S: Default + State + StateReader + Clone>(
         &self,
         state: &mut S,
         general_config: &StarknetGeneralConfig,
+        validate: bool,
+        profiler: Option<&Profiler>,
     ) -> Result<TransactionExecutionInfo, TransactionError> {
-        let concurrent_exec_info = self.apply(state, general_config)?;
-        self.handle_nonce(state)?;
-        // Set contract class
-        match state.get_contract_class(&self.class_hash) {
-            Err(StateError::MissingClassHash()) => {
-                // Class is undeclared; declare it.
-                state.set_contract_class(&self.class_hash, &self.contract_class)?;
-            }
-            Err(error) => return Err(error.into()),
-            Ok(_) => {
-                // Class is already declared; cannot redeclare.
-                return Err(TransactionError::ClassAlreadyDeclared(self.class_hash));
-            }
-        }
+        self.verify_version()?;
+        verify_nonce(state, &self.sender_address, self.version, Some(&self.nonce))?;
 
-        let (fee_transfer_info, actual_fee) = self.charge_fee(
-            state,
-            &concurrent_exec_info.actual_resources,
-            general_config,
-        )?;
+        // validate transaction
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
+        let validate_info = if validate {
+            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?
+        } else {
+            None
+        };
+
+        let changes = count_account_tx_state_changes(self, state, general_config)?;
+        let actual_resources = calculate_tx_resources(
+            resources_manager,
+            &vec![validate_info.clone()],
+            TransactionType::Declare,
+            changes,
+            None,
+        )
+        .map_err(|_| TransactionError::ResourcesCalculation)?;
+        // Declaring the class is not charged as a state change.
+        self.declare_class(state)?;
 
         Ok(
-            TransactionExecutionInfo::from_concurrent_state_execution_info(
-                concurrent_exec_info,
-                actual_fee,
-                fee_transfer_info,
+            TransactionExecutionInfo::create_concurrent_stage_execution_info(
+                validate_info,
+                None,
+                actual_resources,
+                Some(self.tx_type),
             ),
         )
     }
 }
 
+impl SignedTransaction for InternalDeclare {
+    fn transaction_hash(&self) -> &Felt252 {
+        &self.hash_value
+    }
+
+    fn set_signature(&mut self, signature: Vec<Felt252>) {
+        self.signature = signature;
+    }
+}
+
 // ---------------
 //     Tests
 // ---------------
@@ -295,13 +295,14 @@ mod tests {
     use coverage_helper::test;
     use felt::{felt_str, Felt252};
     use num_traits::{One, Zero};
-    use std::{collections::HashMap, path::PathBuf};
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
     use crate::{
         business_logic::{
             execution::objects::{CallInfo, CallType, TransactionExecutionInfo},
             fact_state::in_memory_state_reader::InMemoryStateReader,
             state::cached_state::CachedState,
+            transaction::signer::StarkSigner,
         },
         definitions::{
             constants::VALIDATE_DECLARE_ENTRY_POINT_SELECTOR,
@@ -309,7 +310,7 @@ mod tests {
             transaction_type::TransactionType,
         },
         services::api::contract_class::{ContractClass, EntryPointType},
-        utils::Address,
+        utils::{get_erc20_balance_var_addresses, Address},
     };
 
     use super::InternalDeclare;
@@ -327,7 +328,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class.clone());
+        contract_class_cache.insert(class_hash, Arc::new(contract_class.clone()));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -341,7 +342,7 @@ mod tests {
             .insert(sender_address.clone(), class_hash);
         state_reader
             .address_to_nonce_mut()
-            .insert(sender_address, Felt252::new(1));
+            .insert(sender_address, Felt252::zero());
 
         let mut state = CachedState::new(state_reader, Some(contract_class_cache));
 
@@ -393,8 +394,9 @@ mod tests {
             ..Default::default()
         });
 
+        // The nonce bump of the sender is counted as a state change.
         let actual_resources = HashMap::from([
-            ("l1_gas_usage".to_string(), 0),
+            ("l1_gas_usage".to_string(), 1224),
             ("range_check_builtin".to_string(), 57),
             ("pedersen_builtin".to_string(), 15),
         ]);
@@ -405,6 +407,7 @@ mod tests {
             actual_fee: 0,
             actual_resources,
             tx_type: Some(TransactionType::Declare),
+            revert_error: None,
         };
 
         // ---------------------
@@ -431,7 +434,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -494,7 +497,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -557,7 +560,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -606,6 +609,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn verify_version_accepts_signed_declare_v1() {
+        let fib_path = PathBuf::from("starknet_programs/fibonacci.json");
+        let fib_contract_class = ContractClass::try_from(fib_path).unwrap();
+
+        let internal_declare = InternalDeclare::new_signed(
+            fib_contract_class,
+            StarknetChainId::TestNet.to_felt(),
+            Address(Felt252::one()),
+            0,
+            1,
+            &StarkSigner::new(12345.into()),
+            Felt252::zero(),
+        )
+        .unwrap();
+
+        assert_eq!(internal_declare.signature.len(), 2);
+        assert!(internal_declare.verify_version().is_ok());
+    }
+
     #[test]
     fn execute_class_already_declared_should_fail() {
         // accounts contract class must be stored before running declaration of fibonacci
@@ -619,7 +642,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -684,6 +707,11 @@ mod tests {
             expected_error.unwrap_err(),
             TransactionError::ClassAlreadyDeclared(..)
         );
+        // The failed declaration doesn't use up the nonce.
+        assert_eq!(
+            state.get_nonce_at(&Address(Felt252::one())).unwrap(),
+            Felt252::one()
+        );
     }
 
     #[test]
@@ -699,7 +727,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -805,7 +833,7 @@ mod tests {
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
 
-        contract_class_cache.insert(class_hash, contract_class);
+        contract_class_cache.insert(class_hash, Arc::new(contract_class));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -844,9 +872,19 @@ mod tests {
         )
         .unwrap();
 
-        // We expect a fee transfer failure because the fee token contract is not set up
+        // The account can pay the max fee, but we expect a fee transfer failure because the fee
+        // token contract is not set up
+        let general_config = StarknetGeneralConfig::default();
+        let (balance_key, _) = get_erc20_balance_var_addresses(&Address(1.into())).unwrap();
+        state.set_storage_at(
+            &(
+                general_config.starknet_os_config.fee_token_address.clone(),
+                balance_key,
+            ),
+            10.into(),
+        );
         assert_matches!(
-            internal_declare.execute(&mut state, &StarknetGeneralConfig::default()),
+            internal_declare.execute(&mut state, &general_config),
             Err(TransactionError::FeeError(e)) if e == "Fee transfer failure"
         );
     }
@@ -0,0 +1,475 @@
+use crate::{
+    business_logic::{
+        execution::{
+            execution_entry_point::ExecutionEntryPoint,
+            objects::{CallInfo, TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
+        },
+        fact_state::state::ExecutionResourcesManager,
+        state::state_api::{State, StateReader},
+        transaction::{
+            account_transaction::{count_account_tx_state_changes, AccountTransaction},
+            error::TransactionError,
+            nonce::verify_nonce,
+            signer::{SignedTransaction, StarkSigner},
+        },
+    },
+    core::{
+        contract_address::starknet_contract_address::{
+            compute_compiled_class_hash, compute_sierra_class_hash,
+        },
+        transaction_hash::starknet_transaction_hash::calculate_declare_v2_transaction_hash,
+    },
+    definitions::{
+        constants::{DECLARE_VERSION, VALIDATE_DECLARE_ENTRY_POINT_SELECTOR},
+        general_config::StarknetGeneralConfig,
+        transaction_type::TransactionType,
+    },
+    services::api::{
+        contract_class::EntryPointType,
+        sierra_contract_class::{CasmContractClass, SierraContractClass},
+    },
+    utils::{
+        calculate_tx_resources, verify_no_calls_to_other_contracts, Address, ClassHash,
+        CompiledClassHash,
+    },
+};
+use felt::Felt252;
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+///  Represents an internal transaction in the StarkNet network that is a declaration of a Cairo 1
+///  contract class (a version 2 declare).
+///
+///  The class hash is computed from the Sierra class, and declaring it records the hash of its
+///  compiled class in the state, along with the compiled class itself.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InternalDeclareV2 {
+    pub class_hash: ClassHash,
+    pub compiled_class_hash: CompiledClassHash,
+    pub sender_address: Address,
+    pub tx_type: TransactionType,
+    pub validate_entry_point_selector: Felt252,
+    pub version: u64,
+    pub max_fee: u64,
+    pub signature: Vec<Felt252>,
+    pub nonce: Felt252,
+    pub hash_value: Felt252,
+    pub sierra_contract_class: SierraContractClass,
+    pub casm_contract_class: CasmContractClass,
+}
+
+// ------------------------------------------------------------
+//                        Functions
+// ------------------------------------------------------------
+impl InternalDeclareV2 {
+    /// Builds a declare v2 transaction for `sierra_contract_class`.
+    ///
+    /// `compiled_class_hash` is the hash the sender commits to for the compiled class, it must
+    /// be the hash of `casm_contract_class`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sierra_contract_class: SierraContractClass,
+        casm_contract_class: CasmContractClass,
+        compiled_class_hash: Felt252,
+        chain_id: Felt252,
+        sender_address: Address,
+        max_fee: u64,
+        version: u64,
+        signature: Vec<Felt252>,
+        nonce: Felt252,
+    ) -> Result<Self, TransactionError> {
+        let casm_class_hash = compute_compiled_class_hash(&casm_contract_class)?;
+        if casm_class_hash != compiled_class_hash {
+            return Err(TransactionError::InvalidCompiledClassHash(
+                compiled_class_hash.to_string(),
+                casm_class_hash.to_string(),
+            ));
+        }
+        let sierra_class_hash = compute_sierra_class_hash(&sierra_contract_class)?;
+
+        let hash_value = calculate_declare_v2_transaction_hash(
+            &sierra_class_hash,
+            &compiled_class_hash,
+            chain_id,
+            &sender_address,
+            max_fee,
+            version,
+            nonce.clone(),
+        )?;
+
+        let internal_declare = InternalDeclareV2 {
+            class_hash: sierra_class_hash.to_be_bytes(),
+            compiled_class_hash: compiled_class_hash.to_be_bytes(),
+            sender_address,
+            tx_type: TransactionType::Declare,
+            validate_entry_point_selector: VALIDATE_DECLARE_ENTRY_POINT_SELECTOR.clone(),
+            version,
+            max_fee,
+            signature,
+            nonce,
+            hash_value,
+            sierra_contract_class,
+            casm_contract_class,
+        };
+
+        internal_declare.verify_version()?;
+
+        Ok(internal_declare)
+    }
+
+    /// Like [`new`](Self::new), signed by `signer`: the signature is `[r, s]` over the declare
+    /// hash, which commits to the compiled class hash, and the sender checks it in
+    /// `__validate_declare__`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_signed(
+        sierra_contract_class: SierraContractClass,
+        casm_contract_class: CasmContractClass,
+        compiled_class_hash: Felt252,
+        chain_id: Felt252,
+        sender_address: Address,
+        max_fee: u64,
+        version: u64,
+        signer: &StarkSigner,
+        nonce: Felt252,
+    ) -> Result<Self, TransactionError> {
+        Self::new(
+            sierra_contract_class,
+            casm_contract_class,
+            compiled_class_hash,
+            chain_id,
+            sender_address,
+            max_fee,
+            version,
+            Vec::new(),
+            nonce,
+        )?
+        .signed_by(signer)
+    }
+
+    pub fn get_calldata(&self) -> Vec<Felt252> {
+        let bytes = Felt252::from_bytes_be(&self.class_hash);
+        Vec::from([bytes])
+    }
+
+    pub fn verify_version(&self) -> Result<(), TransactionError> {
+        if self.version != DECLARE_VERSION {
+            return Err(TransactionError::UnsupportedVersion(
+                self.version.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // ~~~~~~~~~~~~~~~~~~~~~~~~~~~
+    // Internal Account Functions
+    // ~~~~~~~~~~~~~~~~~~~~~~~~~~~
+    pub fn run_validate_entrypoint<S: Default + State + StateReader>(
+        &self,
+        state: &mut S,
+        resources_manager: &mut ExecutionResourcesManager,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<Option<CallInfo>, TransactionError> {
+        let calldata = self.get_calldata();
+
+        let entry_point = ExecutionEntryPoint::new(
+            self.sender_address.clone(),
+            calldata,
+            self.validate_entry_point_selector.clone(),
+            Address(Felt252::zero()),
+            EntryPointType::External,
+            None,
+            None,
+        );
+
+        let call_info = entry_point.execute(
+            state,
+            general_config,
+            resources_manager,
+            &self.get_execution_context(general_config.validate_max_n_steps)?,
+        )?;
+
+        verify_no_calls_to_other_contracts(&call_info)
+            .map_err(|_| TransactionError::UnauthorizedActionOnValidate)?;
+
+        Ok(Some(call_info))
+    }
+
+    fn declare_class<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+    ) -> Result<(), TransactionError> {
+        // Classes that aren't declared have a compiled class hash of zero.
+        if state.get_compiled_class_hash(&self.class_hash)? != [0; 32] {
+            return Err(TransactionError::ClassAlreadyDeclared(self.class_hash));
+        }
+        state.set_compiled_class_hash(&self.class_hash, &self.compiled_class_hash)?;
+        state.set_compiled_class(&self.compiled_class_hash, self.casm_contract_class.clone())?;
+        Ok(())
+    }
+}
+
+impl AccountTransaction for InternalDeclareV2 {
+    fn account_address(&self) -> &Address {
+        &self.sender_address
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn max_fee(&self) -> u64 {
+        self.max_fee
+    }
+
+    fn get_execution_context(
+        &self,
+        n_steps: u64,
+    ) -> Result<TransactionExecutionContext, TransactionError> {
+        Ok(TransactionExecutionContext::new(
+            self.sender_address.clone(),
+            self.hash_value.clone(),
+            self.signature.clone(),
+            self.max_fee,
+            self.nonce.clone(),
+            n_steps,
+            self.version,
+        ))
+    }
+
+    /// Validates the declaration with the account's validate entry point and records the
+    /// compiled class hash of the declared class.
+    fn apply_with_validation<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        validate: bool,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.verify_version()?;
+        verify_nonce(state, &self.sender_address, self.version, Some(&self.nonce))?;
+
+        // validate transaction
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
+        let validate_info = if validate {
+            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?
+        } else {
+            None
+        };
+
+        // The compiled class hash update is paid for like any other state change, so the class
+        // is declared before counting them.
+        self.declare_class(state)?;
+
+        let changes = count_account_tx_state_changes(self, state, general_config)?;
+        let actual_resources = calculate_tx_resources(
+            resources_manager,
+            &vec![validate_info.clone()],
+            TransactionType::Declare,
+            changes,
+            None,
+        )
+        .map_err(|_| TransactionError::ResourcesCalculation)?;
+
+        Ok(
+            TransactionExecutionInfo::create_concurrent_stage_execution_info(
+                validate_info,
+                None,
+                actual_resources,
+                Some(self.tx_type),
+            ),
+        )
+    }
+}
+
+impl SignedTransaction for InternalDeclareV2 {
+    fn transaction_hash(&self) -> &Felt252 {
+        &self.hash_value
+    }
+
+    fn set_signature(&mut self, signature: Vec<Felt252>) {
+        self.signature = signature;
+    }
+}
+
+// ---------------
+//     Tests
+// ---------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            fact_state::in_memory_state_reader::InMemoryStateReader,
+            state::cached_state::CachedState,
+        },
+        core::contract_address::starknet_contract_address::compute_class_hash,
+        definitions::general_config::StarknetChainId,
+        services::api::contract_class::ContractClass,
+    };
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+    use felt::felt_str;
+    use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+    fn sierra_and_casm_classes() -> (SierraContractClass, CasmContractClass) {
+        (
+            SierraContractClass::try_from(&PathBuf::from(
+                "starknet_programs/cairo1/minimal_contract.sierra",
+            ))
+            .unwrap(),
+            CasmContractClass::try_from(&PathBuf::from(
+                "starknet_programs/cairo1/minimal_contract.casm",
+            ))
+            .unwrap(),
+        )
+    }
+
+    fn compiled_class_hash() -> Felt252 {
+        felt_str!("3290118810276346746729866995389780649861005566623131667705232161295028017180")
+    }
+
+    fn declare_v2(nonce: u64) -> InternalDeclareV2 {
+        let (sierra_contract_class, casm_contract_class) = sierra_and_casm_classes();
+        InternalDeclareV2::new(
+            sierra_contract_class,
+            casm_contract_class,
+            compiled_class_hash(),
+            StarknetChainId::TestNet.to_felt(),
+            Address(1.into()),
+            0,
+            DECLARE_VERSION,
+            Vec::new(),
+            nonce.into(),
+        )
+        .unwrap()
+    }
+
+    /// A state with an account at address 1 that accepts every declaration.
+    fn account_state() -> CachedState<InMemoryStateReader> {
+        let path = PathBuf::from("starknet_programs/account_without_validation.json");
+        let contract_class = ContractClass::try_from(path).unwrap();
+        let class_hash = compute_class_hash(&contract_class).unwrap().to_be_bytes();
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(Address(1.into()), class_hash);
+        state_reader
+            .address_to_nonce_mut()
+            .insert(Address(1.into()), Felt252::zero());
+
+        CachedState::new(
+            state_reader,
+            Some(HashMap::from([(class_hash, Arc::new(contract_class))])),
+        )
+    }
+
+    #[test]
+    fn new_computes_the_hashes() {
+        let tx = declare_v2(0);
+
+        assert_eq!(
+            Felt252::from_bytes_be(&tx.class_hash),
+            felt_str!(
+                "390758098962163324708665802167666814817552703839323949595722360813047737906"
+            )
+        );
+        assert_eq!(
+            Felt252::from_bytes_be(&tx.compiled_class_hash),
+            compiled_class_hash()
+        );
+        assert_eq!(tx.tx_type, TransactionType::Declare);
+        assert_eq!(
+            tx.get_calldata(),
+            vec![Felt252::from_bytes_be(&tx.class_hash)]
+        );
+    }
+
+    #[test]
+    fn new_with_wrong_compiled_class_hash_should_fail() {
+        let (sierra_contract_class, casm_contract_class) = sierra_and_casm_classes();
+        let result = InternalDeclareV2::new(
+            sierra_contract_class,
+            casm_contract_class,
+            1.into(),
+            StarknetChainId::TestNet.to_felt(),
+            Address(1.into()),
+            0,
+            DECLARE_VERSION,
+            Vec::new(),
+            0.into(),
+        );
+
+        assert_matches!(
+            result,
+            Err(TransactionError::InvalidCompiledClassHash(given, _)) if given == "1"
+        );
+    }
+
+    #[test]
+    fn new_with_unsupported_version_should_fail() {
+        let (sierra_contract_class, casm_contract_class) = sierra_and_casm_classes();
+        let result = InternalDeclareV2::new(
+            sierra_contract_class,
+            casm_contract_class,
+            compiled_class_hash(),
+            StarknetChainId::TestNet.to_felt(),
+            Address(1.into()),
+            0,
+            1,
+            Vec::new(),
+            0.into(),
+        );
+
+        assert_matches!(
+            result,
+            Err(TransactionError::UnsupportedVersion(version)) if version == "1"
+        );
+    }
+
+    #[test]
+    fn execute_records_the_compiled_class_hash() {
+        let mut state = account_state();
+        let tx = declare_v2(0);
+
+        let execution_info = tx
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .unwrap();
+
+        assert_eq!(execution_info.tx_type, Some(TransactionType::Declare));
+        assert!(execution_info.validate_info.is_some());
+        assert_eq!(
+            state.get_compiled_class_hash(&tx.class_hash).unwrap(),
+            tx.compiled_class_hash
+        );
+        assert_eq!(
+            state.get_compiled_class(&tx.compiled_class_hash).unwrap(),
+            Arc::new(tx.casm_contract_class.clone())
+        );
+        assert_eq!(
+            state.get_nonce_at(&Address(1.into())).unwrap(),
+            Felt252::new(1)
+        );
+    }
+
+    #[test]
+    fn execute_class_already_declared_should_fail() {
+        let mut state = account_state();
+        declare_v2(0)
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .unwrap();
+
+        assert_matches!(
+            declare_v2(1).execute(&mut state, &StarknetGeneralConfig::default()),
+            Err(TransactionError::ClassAlreadyDeclared(..))
+        );
+        // The failed declaration doesn't use up the nonce.
+        assert_eq!(
+            state.get_nonce_at(&Address(1.into())).unwrap(),
+            Felt252::new(1)
+        );
+    }
+}
@@ -3,6 +3,7 @@ use crate::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
             objects::{CallInfo, TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
         },
         fact_state::state::ExecutionResourcesManager,
         state::state_api::{State, StateReader},
@@ -24,8 +25,9 @@ use crate::{
 };
 use felt::Felt252;
 use num_traits::Zero;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct InternalDeploy {
     pub hash_value: Felt252,
     pub version: u64,
@@ -81,6 +83,15 @@ impl InternalDeploy {
         &self,
         state: &mut S,
         general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.apply_with_profiler(state, general_config, None)
+    }
+
+    fn apply_with_profiler<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
     ) -> Result<TransactionExecutionInfo, TransactionError> {
         state.deploy_contract(self.contract_address.clone(), self.contract_hash)?;
         let class_hash: ClassHash = self.contract_hash;
@@ -94,7 +105,7 @@ impl InternalDeploy {
             // Contract has no constructors
             Ok(self.handle_empty_constructor(state)?)
         } else {
-            self.invoke_constructor(state, general_config)
+            self.invoke_constructor(state, general_config, profiler)
         }
     }
 
@@ -115,7 +126,7 @@ impl InternalDeploy {
 
         let resources_manager = ExecutionResourcesManager::default();
 
-        let changes = state.count_actual_storage_changes();
+        let changes = state.count_actual_state_changes();
         let actual_resources = calculate_tx_resources(
             resources_manager,
             &[Some(call_info.clone())],
@@ -135,10 +146,12 @@ impl InternalDeploy {
         )
     }
 
+    /// Runs the constructor of the deployed contract, profiling it into `profiler` if given.
     pub fn invoke_constructor<S: Default + State + StateReader + Clone>(
         &self,
         state: &mut S,
         general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
     ) -> Result<TransactionExecutionInfo, TransactionError> {
         let call = ExecutionEntryPoint::new(
             self.contract_address.clone(),
@@ -160,7 +173,7 @@ impl InternalDeploy {
             self.version,
         );
 
-        let mut resources_manager = ExecutionResourcesManager::default();
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
         let call_info = call.execute(
             state,
             general_config,
@@ -168,7 +181,7 @@ impl InternalDeploy {
             &tx_execution_context,
         )?;
 
-        let changes = state.count_actual_storage_changes();
+        let changes = state.count_actual_state_changes();
         let actual_resources = calculate_tx_resources(
             resources_manager,
             &[Some(call_info.clone())],
@@ -194,7 +207,18 @@ impl InternalDeploy {
         state: &mut S,
         general_config: &StarknetGeneralConfig,
     ) -> Result<TransactionExecutionInfo, TransactionError> {
-        let concurrent_exec_info = self.apply(state, general_config)?;
+        self.execute_with_profiler(state, general_config, None)
+    }
+
+    /// Executes the transaction like [`execute`](Self::execute), profiling the constructor into
+    /// `profiler` if given.
+    pub fn execute_with_profiler<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        let concurrent_exec_info = self.apply_with_profiler(state, general_config, profiler)?;
         let (fee_transfer_info, actual_fee) = (None, 0);
 
         Ok(
@@ -295,7 +319,11 @@ mod tests {
         let config = Default::default();
 
         let result = internal_deploy.execute(&mut state, &config);
-        assert_matches!(result.unwrap_err(), TransactionError::CairoRunner(..))
+        assert_matches!(
+            result.unwrap_err(),
+            TransactionError::EntryPointFailed(stack)
+                if matches!(*stack.error, TransactionError::CairoRunner(..))
+        )
     }
 
     #[test]
@@ -4,12 +4,15 @@ use crate::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
             objects::{CallInfo, TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
         },
         fact_state::state::ExecutionResourcesManager,
         state::state_api::{State, StateReader},
         transaction::{
+            account_transaction::{count_account_tx_state_changes, AccountTransaction},
             error::TransactionError,
-            fee::{calculate_tx_fee, execute_fee_transfer, FeeInfo},
+            nonce::verify_nonce,
+            signer::{SignedTransaction, StarkSigner},
         },
     },
     core::{
@@ -27,9 +30,9 @@ use crate::{
     utils::{calculate_tx_resources, Address, ClassHash},
 };
 use felt::Felt252;
-use getset::Getters;
+use getset::{CopyGetters, Getters};
 use num_traits::Zero;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct StateSelector {
@@ -37,7 +40,7 @@ pub struct StateSelector {
     pub class_hashes: Vec<ClassHash>,
 }
 
-#[derive(Clone, Debug, Getters)]
+#[derive(Clone, Debug, Getters, CopyGetters, Deserialize, Serialize)]
 pub struct InternalDeployAccount {
     #[getset(get = "pub")]
     contract_address: Address,
@@ -47,7 +50,9 @@ pub struct InternalDeployAccount {
     class_hash: ClassHash,
     #[getset(get = "pub")]
     constructor_calldata: Vec<Felt252>,
+    #[getset(get_copy = "pub")]
     version: u64,
+    #[getset(get = "pub")]
     nonce: Felt252,
     max_fee: u64,
     #[getset(get = "pub")]
@@ -101,6 +106,34 @@ impl InternalDeployAccount {
         })
     }
 
+    /// Like [`new`](Self::new), signed by `signer`: the signature is `[r, s]` over the
+    /// deploy_account hash, which the deployed account checks in `__validate_deploy__`. `signer`
+    /// holds the private key of the new account, whose public key usually goes in the
+    /// constructor calldata.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_signed(
+        class_hash: ClassHash,
+        max_fee: u64,
+        version: u64,
+        nonce: Felt252,
+        constructor_calldata: Vec<Felt252>,
+        signer: &StarkSigner,
+        contract_address_salt: Address,
+        chain_id: StarknetChainId,
+    ) -> Result<Self, TransactionError> {
+        Self::new(
+            class_hash,
+            max_fee,
+            version,
+            nonce,
+            constructor_calldata,
+            Vec::new(),
+            contract_address_salt,
+            chain_id,
+        )?
+        .signed_by(signer)
+    }
+
     pub fn get_state_selector(&self, _general_config: StarknetGeneralConfig) -> StateSelector {
         StateSelector {
             contract_addresses: vec![self.contract_address.clone()],
@@ -108,76 +141,9 @@ impl InternalDeployAccount {
         }
     }
 
-    pub fn execute<S>(
-        &self,
-        state: &mut S,
-        general_config: &StarknetGeneralConfig,
-    ) -> Result<TransactionExecutionInfo, TransactionError>
-    where
-        S: Clone + Default + State + StateReader,
-    {
-        let tx_info = self.apply(state, general_config)?;
-
-        self.handle_nonce(state)?;
-        let (fee_transfer_info, actual_fee) =
-            self.charge_fee(state, &tx_info.actual_resources, general_config)?;
-
-        Ok(
-            TransactionExecutionInfo::from_concurrent_state_execution_info(
-                tx_info,
-                actual_fee,
-                fee_transfer_info,
-            ),
-        )
-    }
-
-    /// Execute a call to the cairo-vm using the accounts_validation.cairo contract to validate
-    /// the contract that is being declared. Then it returns the transaction execution info of the run.
-    fn apply<S>(
-        &self,
-        state: &mut S,
-        general_config: &StarknetGeneralConfig,
-    ) -> Result<TransactionExecutionInfo, TransactionError>
-    where
-        S: Default + State + StateReader,
-    {
-        let contract_class = state.get_contract_class(&self.class_hash)?;
-
-        state.deploy_contract(self.contract_address.clone(), self.class_hash)?;
-
-        let mut resources_manager = ExecutionResourcesManager::default();
-        let constructor_call_info = self.handle_constructor(
-            contract_class,
-            state,
-            general_config,
-            &mut resources_manager,
-        )?;
-
-        let validate_info =
-            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?;
-
-        let actual_resources = calculate_tx_resources(
-            resources_manager,
-            &[Some(constructor_call_info.clone()), validate_info.clone()],
-            TransactionType::DeployAccount,
-            state.count_actual_storage_changes(),
-            None,
-        )
-        .map_err::<TransactionError, _>(|_| TransactionError::ResourcesCalculation)?;
-
-        Ok(
-            TransactionExecutionInfo::create_concurrent_stage_execution_info(
-                validate_info,
-                Some(constructor_call_info),
-                actual_resources,
-                Some(TransactionType::DeployAccount),
-            ),
-        )
-    }
-
     pub fn handle_constructor<S>(
         &self,
-        contract_class: ContractClass,
+        contract_class: &ContractClass,
         state: &mut S,
         general_config: &StarknetGeneralConfig,
         resources_manager: &mut ExecutionResourcesManager,
@@ -209,27 +175,6 @@ impl InternalDeployAccount {
         }
     }
 
-    fn handle_nonce<S: Default + State + StateReader + Clone>(
-        &self,
-        state: &mut S,
-    ) -> Result<(), TransactionError> {
-        if self.version == 0 {
-            return Ok(());
-        }
-
-        let current_nonce = state.get_nonce_at(&self.contract_address)?;
-        if current_nonce != self.nonce {
-            return Err(TransactionError::InvalidTransactionNonce(
-                current_nonce.to_string(),
-                self.nonce.to_string(),
-            ));
-        }
-
-        state.increment_nonce(&self.contract_address)?;
-
-        Ok(())
-    }
-
     pub fn run_constructor_entrypoint<S>(
         &self,
         state: &mut S,
@@ -253,7 +198,7 @@ impl InternalDeployAccount {
             state,
             general_config,
             resources_manager,
-            &self.get_execution_context(general_config.validate_max_n_steps),
+            &self.get_execution_context(general_config.validate_max_n_steps)?,
         )?;
 
         verify_no_calls_to_other_contracts(&call_info)
@@ -261,28 +206,6 @@ impl InternalDeployAccount {
         Ok(call_info)
     }
 
-    pub fn get_execution_context(&self, n_steps: u64) -> TransactionExecutionContext {
-        TransactionExecutionContext::new(
-            self.contract_address.clone(),
-            calculate_deploy_account_transaction_hash(
-                self.version,
-                &self.contract_address,
-                Felt252::from_bytes_be(&self.class_hash),
-                &self.constructor_calldata,
-                self.max_fee,
-                self.nonce.clone(),
-                self.contract_address_salt.0.clone(),
-                self.chain_id.to_felt(),
-            )
-            .unwrap(),
-            self.signature.clone(),
-            self.max_fee,
-            self.nonce.clone(),
-            n_steps,
-            self.version,
-        )
-    }
-
     pub fn run_validate_entrypoint<S>(
         &self,
         state: &mut S,
@@ -316,7 +239,7 @@ impl InternalDeployAccount {
             state,
             general_config,
             resources_manager,
-            &self.get_execution_context(general_config.validate_max_n_steps),
+            &self.get_execution_context(general_config.validate_max_n_steps)?,
         )?;
 
         verify_no_calls_to_other_contracts(&call_info)
@@ -324,31 +247,106 @@ impl InternalDeployAccount {
 
         Ok(Some(call_info))
     }
+}
+
+impl AccountTransaction for InternalDeployAccount {
+    fn account_address(&self) -> &Address {
+        &self.contract_address
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn max_fee(&self) -> u64 {
+        self.max_fee
+    }
+
+    fn get_execution_context(
+        &self,
+        n_steps: u64,
+    ) -> Result<TransactionExecutionContext, TransactionError> {
+        Ok(TransactionExecutionContext::new(
+            self.contract_address.clone(),
+            calculate_deploy_account_transaction_hash(
+                self.version,
+                &self.contract_address,
+                Felt252::from_bytes_be(&self.class_hash),
+                &self.constructor_calldata,
+                self.max_fee,
+                self.nonce.clone(),
+                self.contract_address_salt.0.clone(),
+                self.chain_id.to_felt(),
+            )?,
+            self.signature.clone(),
+            self.max_fee,
+            self.nonce.clone(),
+            n_steps,
+            self.version,
+        ))
+    }
 
-    fn charge_fee<S>(
+    /// Deploys the account, runs its constructor and validates the deployment.
+    fn apply_with_validation<S: Default + State + StateReader + Clone>(
         &self,
         state: &mut S,
-        resources: &HashMap<String, usize>,
         general_config: &StarknetGeneralConfig,
-    ) -> Result<FeeInfo, TransactionError>
-    where
-        S: Clone + Default + State + StateReader,
-    {
-        if self.max_fee.is_zero() {
-            return Ok((None, 0));
-        }
+        validate: bool,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        let contract_class = state.get_contract_class(&self.class_hash)?;
 
-        let actual_fee = calculate_tx_fee(
-            resources,
-            general_config.starknet_os_config.gas_price,
+        state.deploy_contract(self.contract_address.clone(), self.class_hash)?;
+        // The account exists from here on, with a zero nonce.
+        verify_nonce(
+            state,
+            &self.contract_address,
+            self.version,
+            Some(&self.nonce),
+        )?;
+
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
+        let constructor_call_info = self.handle_constructor(
+            &contract_class,
+            state,
             general_config,
+            &mut resources_manager,
         )?;
 
-        let tx_context = self.get_execution_context(general_config.invoke_tx_max_n_steps);
-        let fee_transfer_info =
-            execute_fee_transfer(state, general_config, &tx_context, actual_fee)?;
+        let validate_info = if validate {
+            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?
+        } else {
+            None
+        };
+
+        let changes = count_account_tx_state_changes(self, state, general_config)?;
+        let actual_resources = calculate_tx_resources(
+            resources_manager,
+            &[Some(constructor_call_info.clone()), validate_info.clone()],
+            TransactionType::DeployAccount,
+            changes,
+            None,
+        )
+        .map_err::<TransactionError, _>(|_| TransactionError::ResourcesCalculation)?;
+
+        Ok(
+            TransactionExecutionInfo::create_concurrent_stage_execution_info(
+                validate_info,
+                Some(constructor_call_info),
+                actual_resources,
+                Some(TransactionType::DeployAccount),
+            ),
+        )
+    }
+}
+
+impl SignedTransaction for InternalDeployAccount {
+    fn transaction_hash(&self) -> &Felt252 {
+        &self.hash_value
+    }
 
-        Ok((Some(fee_transfer_info), actual_fee))
+    fn set_signature(&mut self, signature: Vec<Felt252>) {
+        self.signature = signature;
     }
 }
 
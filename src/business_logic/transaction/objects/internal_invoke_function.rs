@@ -1,23 +1,24 @@
-use std::collections::HashMap;
-
 use crate::{
     business_logic::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
             objects::{CallInfo, TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
         },
         fact_state::state::ExecutionResourcesManager,
         state::state_api::{State, StateReader},
         transaction::{
+            account_transaction::{count_account_tx_state_changes, AccountTransaction},
             error::TransactionError,
-            fee::{calculate_tx_fee, execute_fee_transfer, FeeInfo},
+            nonce::verify_nonce,
+            signer::{SignedTransaction, StarkSigner},
         },
     },
     core::transaction_hash::starknet_transaction_hash::{
         calculate_transaction_hash_common, TransactionHashPrefix,
     },
     definitions::{
-        constants::{EXECUTE_ENTRY_POINT_SELECTOR, TRANSACTION_VERSION},
+        constants::{EXECUTE_ENTRY_POINT_SELECTOR, QUERY_VERSION_BASE, TRANSACTION_VERSION},
         general_config::StarknetGeneralConfig,
         transaction_type::TransactionType,
     },
@@ -26,43 +27,66 @@ use crate::{
     utils::{calculate_tx_resources, Address},
 };
 use felt::Felt252;
-use getset::Getters;
-use num_traits::Zero;
+use getset::{CopyGetters, Getters};
+use num_traits::{ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Getters)]
+#[derive(Debug, Getters, CopyGetters, Deserialize, Serialize)]
 pub struct InternalInvokeFunction {
     #[getset(get = "pub")]
     contract_address: Address,
     entry_point_selector: Felt252,
     #[allow(dead_code)]
+    #[serde(skip, default = "external_entry_point_type")]
     entry_point_type: EntryPointType,
     calldata: Vec<Felt252>,
     tx_type: TransactionType,
+    /// The version without the query offset.
+    #[getset(get_copy = "pub")]
     version: u64,
+    /// Whether the transaction was built with a query version, in which case it can only be
+    /// simulated.
+    #[getset(get_copy = "pub")]
+    #[serde(default)]
+    is_query: bool,
     validate_entry_point_selector: Felt252,
     #[getset(get = "pub")]
     hash_value: Felt252,
     #[getset(get = "pub")]
     signature: Vec<Felt252>,
     max_fee: u64,
+    #[getset(get = "pub")]
     nonce: Option<Felt252>,
 }
 
 impl InternalInvokeFunction {
+    /// Builds an invoke transaction of the given version, 0 or 1, or of their query versions
+    /// (`QUERY_VERSION_BASE + version`).
+    ///
+    /// Version 0 transactions call `entry_point_selector` directly and have no nonce. Version 1
+    /// transactions go through the account's `__validate__` and `__execute__` entry points.
+    /// Query transactions are hashed with the query version, so they can't be replayed as
+    /// regular ones, and are otherwise handled like their base version.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         contract_address: Address,
         entry_point_selector: Felt252,
         max_fee: u64,
+        version: Felt252,
         calldata: Vec<Felt252>,
         signature: Vec<Felt252>,
         chain_id: Felt252,
         nonce: Option<Felt252>,
     ) -> Result<Self, TransactionError> {
-        let version = TRANSACTION_VERSION;
+        let (base_version, is_query) = split_query_version(&version);
+        let base_version = match base_version.to_u64() {
+            Some(base_version @ (0 | TRANSACTION_VERSION)) => base_version,
+            _ => return Err(TransactionError::UnsupportedVersion(version.to_string())),
+        };
         let (entry_point_selector_field, additional_data) = preprocess_invoke_function_fields(
             entry_point_selector.clone(),
             nonce.clone(),
-            version,
+            base_version,
         )?;
         let hash_value = calculate_transaction_hash_common(
             TransactionHashPrefix::Invoke,
@@ -82,7 +106,8 @@ impl InternalInvokeFunction {
             entry_point_type: EntryPointType::External,
             calldata,
             tx_type: TransactionType::InvokeFunction,
-            version,
+            version: base_version,
+            is_query,
             max_fee,
             signature,
             validate_entry_point_selector,
@@ -91,19 +116,31 @@ impl InternalInvokeFunction {
         })
     }
 
-    fn get_execution_context(
-        &self,
-        n_steps: u64,
-    ) -> Result<TransactionExecutionContext, TransactionError> {
-        Ok(TransactionExecutionContext::new(
-            self.contract_address.clone(),
-            self.hash_value.clone(),
-            self.signature.clone(),
-            self.max_fee,
-            self.nonce.clone().ok_or(TransactionError::MissingNonce)?,
-            n_steps,
-            self.version,
-        ))
+    /// Like [`new`](Self::new), signed by `signer`: the signature is `[r, s]` over the invoke
+    /// hash, which the account checks in `__validate__`. Version 0 invokes aren't validated, so
+    /// their signature is only passed along to the called entry point.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_signed(
+        contract_address: Address,
+        entry_point_selector: Felt252,
+        max_fee: u64,
+        version: Felt252,
+        calldata: Vec<Felt252>,
+        signer: &StarkSigner,
+        chain_id: Felt252,
+        nonce: Option<Felt252>,
+    ) -> Result<Self, TransactionError> {
+        Self::new(
+            contract_address,
+            entry_point_selector,
+            max_fee,
+            version,
+            calldata,
+            Vec::new(),
+            chain_id,
+            nonce,
+        )?
+        .signed_by(signer)
     }
 
     fn run_validate_entrypoint<T>(
@@ -147,13 +184,14 @@ impl InternalInvokeFunction {
         Ok(Some(call_info))
     }
 
-    /// Builds the transaction execution context and executes the entry point.
+    /// Executes the entry point within the given transaction execution context.
     /// Returns the CallInfo.
     fn run_execute_entrypoint<T>(
         &self,
         state: &mut T,
         general_config: &StarknetGeneralConfig,
         resources_manager: &mut ExecutionResourcesManager,
+        tx_execution_context: &TransactionExecutionContext,
     ) -> Result<CallInfo, TransactionError>
     where
         T: Default + State + StateReader,
@@ -168,131 +206,137 @@ impl InternalInvokeFunction {
             None,
         );
 
-        call.execute(
+        // Execute in a child checkpoint, so a failing call doesn't leave half of its writes
+        // behind.
+        let checkpoint = state.checkpoint();
+        match call.execute(
             state,
             general_config,
             resources_manager,
-            &self
-                .get_execution_context(general_config.invoke_tx_max_n_steps)
-                .map_err(|_| TransactionError::InvalidTxContext)?,
-        )
+            tx_execution_context,
+        ) {
+            Ok(call_info) => {
+                state.commit(checkpoint)?;
+                Ok(call_info)
+            }
+            Err(error) => {
+                state.revert_to(checkpoint)?;
+                Err(error)
+            }
+        }
+    }
+}
+
+impl AccountTransaction for InternalInvokeFunction {
+    fn account_address(&self) -> &Address {
+        &self.contract_address
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn max_fee(&self) -> u64 {
+        self.max_fee
     }
 
-    /// Execute a call to the cairo-vm using the accounts_validation.cairo contract to validate
-    /// the contract that is being declared. Then it returns the transaction execution info of the run.
-    pub fn apply<T>(
+    fn is_query(&self) -> bool {
+        self.is_query
+    }
+
+    fn get_execution_context(
         &self,
-        state: &mut T,
+        n_steps: u64,
+    ) -> Result<TransactionExecutionContext, TransactionError> {
+        let mut tx_execution_context = TransactionExecutionContext::new(
+            self.contract_address.clone(),
+            self.hash_value.clone(),
+            self.signature.clone(),
+            self.max_fee,
+            // Version 0 transactions have no nonce, the account sees a zero one.
+            match (self.version, &self.nonce) {
+                (0, _) => Felt252::zero(),
+                (_, nonce) => nonce.clone().ok_or(TransactionError::MissingNonce)?,
+            },
+            n_steps,
+            self.version,
+        );
+        // The account sees the version the transaction was signed with.
+        if self.is_query {
+            tx_execution_context.version += &*QUERY_VERSION_BASE;
+        }
+        Ok(tx_execution_context)
+    }
+
+    /// If the execution fails its changes are discarded and the returned info is marked as
+    /// reverted, with the error as the revert reason.
+    fn apply_with_validation<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
         general_config: &StarknetGeneralConfig,
-    ) -> Result<TransactionExecutionInfo, TransactionError>
-    where
-        T: Default + State + StateReader + Clone,
-    {
-        let mut resources_manager = ExecutionResourcesManager::default();
+        validate: bool,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        verify_nonce(
+            state,
+            &self.contract_address,
+            self.version,
+            self.nonce.as_ref(),
+        )?;
 
-        let validate_info =
-            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?;
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
+
+        let validate_info = if validate {
+            self.run_validate_entrypoint(state, &mut resources_manager, general_config)?
+        } else {
+            None
+        };
+        let tx_execution_context = self
+            .get_execution_context(general_config.invoke_tx_max_n_steps)
+            .map_err(|_| TransactionError::InvalidTxContext)?;
         // Execute transaction
-        let call_info =
-            self.run_execute_entrypoint(state, general_config, &mut resources_manager)?;
-        let changes = state.count_actual_storage_changes();
+        let call_info = self
+            .run_execute_entrypoint(
+                state,
+                general_config,
+                &mut resources_manager,
+                &tx_execution_context,
+            )
+            .map_err(|error| error.to_string());
+        let changes = count_account_tx_state_changes(self, state, general_config)?;
         let actual_resources = calculate_tx_resources(
             resources_manager,
-            &vec![Some(call_info.clone()), validate_info.clone()],
+            &vec![call_info.clone().ok(), validate_info.clone()],
             self.tx_type,
             changes,
             None,
         )?;
 
-        let transaction_execution_info =
-            TransactionExecutionInfo::create_concurrent_stage_execution_info(
+        let transaction_execution_info = match call_info {
+            Ok(call_info) => TransactionExecutionInfo::create_concurrent_stage_execution_info(
                 validate_info,
                 Some(call_info),
                 actual_resources,
                 Some(self.tx_type),
-            );
+            ),
+            Err(revert_error) => TransactionExecutionInfo::create_reverted_execution_info(
+                validate_info,
+                revert_error,
+                actual_resources,
+                Some(self.tx_type),
+            ),
+        };
         Ok(transaction_execution_info)
     }
+}
 
-    fn charge_fee<S>(
-        &self,
-        state: &mut S,
-        resources: &HashMap<String, usize>,
-        general_config: &StarknetGeneralConfig,
-    ) -> Result<FeeInfo, TransactionError>
-    where
-        S: Clone + Default + State + StateReader,
-    {
-        if self.max_fee.is_zero() {
-            return Ok((None, 0));
-        }
-
-        let actual_fee = calculate_tx_fee(
-            resources,
-            general_config.starknet_os_config.gas_price,
-            general_config,
-        )?;
-
-        let tx_context = self.get_execution_context(general_config.invoke_tx_max_n_steps)?;
-        let fee_transfer_info =
-            execute_fee_transfer(state, general_config, &tx_context, actual_fee)?;
-
-        Ok((Some(fee_transfer_info), actual_fee))
-    }
-
-    /// Calculates actual fee used by the transaction using the execution info returned by apply(),
-    /// then updates the transaction execution info with the data of the fee.
-    pub fn execute<S: Default + State + StateReader + Clone>(
-        &self,
-        state: &mut S,
-        general_config: &StarknetGeneralConfig,
-    ) -> Result<TransactionExecutionInfo, TransactionError> {
-        let concurrent_exec_info = self.apply(state, general_config)?;
-        self.handle_nonce(state)?;
-
-        let (fee_transfer_info, actual_fee) = self.charge_fee(
-            state,
-            &concurrent_exec_info.actual_resources,
-            general_config,
-        )?;
-
-        Ok(
-            TransactionExecutionInfo::from_concurrent_state_execution_info(
-                concurrent_exec_info,
-                actual_fee,
-                fee_transfer_info,
-            ),
-        )
+impl SignedTransaction for InternalInvokeFunction {
+    fn transaction_hash(&self) -> &Felt252 {
+        &self.hash_value
     }
 
-    fn handle_nonce<S: Default + State + StateReader + Clone>(
-        &self,
-        state: &mut S,
-    ) -> Result<(), TransactionError> {
-        if self.version == 0 {
-            return Ok(());
-        }
-
-        let contract_address = self.contract_address();
-
-        let current_nonce = state.get_nonce_at(contract_address)?;
-
-        match &self.nonce {
-            None => {
-                // TODO: Remove this once we have a better way to handle the nonce.
-                Ok(())
-            }
-            Some(nonce) => {
-                if *nonce != current_nonce {
-                    return Err(TransactionError::InvalidTransactionNonce(
-                        current_nonce.to_string(),
-                        nonce.to_string(),
-                    ));
-                }
-                state.increment_nonce(contract_address)?;
-                Ok(())
-            }
-        }
+    fn set_signature(&mut self, signature: Vec<Felt252>) {
+        self.signature = signature;
     }
 }
 
@@ -300,6 +344,11 @@ impl InternalInvokeFunction {
 //  Invoke internal functions utils
 // ------------------------------------
 
+// Invoke transactions always call external entry points.
+fn external_entry_point_type() -> EntryPointType {
+    EntryPointType::External
+}
+
 pub fn verify_no_calls_to_other_contracts(call_info: &CallInfo) -> Result<(), TransactionError> {
     let invoked_contract_address = call_info.contract_address.clone();
     for internal_call in call_info.gen_call_topology() {
@@ -310,6 +359,15 @@ pub fn verify_no_calls_to_other_contracts(call_info: &CallInfo) -> Result<(), Tr
     Ok(())
 }
 
+/// Splits a transaction version into its base version and whether it's a query version.
+pub(crate) fn split_query_version(version: &Felt252) -> (Felt252, bool) {
+    if version >= &*QUERY_VERSION_BASE {
+        (version - &*QUERY_VERSION_BASE, true)
+    } else {
+        (version.clone(), false)
+    }
+}
+
 // Performs validation on fields related to function invocation transaction.
 // InvokeFunction transaction.
 // Deduces and returns fields required for hash calculation of
@@ -319,7 +377,7 @@ pub(crate) fn preprocess_invoke_function_fields(
     nonce: Option<Felt252>,
     version: u64,
 ) -> Result<(Felt252, Vec<Felt252>), TransactionError> {
-    if version == 0 || version == u64::MAX {
+    if version == 0 {
         match nonce {
             Some(_) => Err(TransactionError::InvokeFunctionZeroHasNonce),
             None => {
@@ -348,9 +406,12 @@ mod tests {
             fact_state::in_memory_state_reader::InMemoryStateReader,
             state::cached_state::CachedState,
         },
+        definitions::general_config::StarknetChainId,
         services::api::contract_class::ContractClass,
+        utils::{calculate_sn_keccak, get_erc20_balance_var_addresses},
     };
     use coverage_helper::test;
+    use num_bigint::BigUint;
     use num_traits::Num;
     use std::{collections::HashMap, path::PathBuf};
 
@@ -367,6 +428,7 @@ mod tests {
             calldata: vec![1.into(), 1.into(), 10.into()],
             tx_type: TransactionType::InvokeFunction,
             version: 0,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -433,6 +495,7 @@ mod tests {
             calldata: vec![1.into(), 1.into(), 10.into()],
             tx_type: TransactionType::InvokeFunction,
             version: 0,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -487,7 +550,7 @@ mod tests {
     }
 
     #[test]
-    fn test_apply_invoke_entrypoint_not_found_should_fail() {
+    fn test_apply_invoke_entrypoint_not_found_should_revert() {
         let internal_invoke_function = InternalInvokeFunction {
             contract_address: Address(0.into()),
             entry_point_selector: (*EXECUTE_ENTRY_POINT_SELECTOR).clone(),
@@ -495,6 +558,7 @@ mod tests {
             calldata: Vec::new(),
             tx_type: TransactionType::InvokeFunction,
             version: 0,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -528,13 +592,207 @@ mod tests {
             .set_contract_class(&class_hash, &contract_class)
             .unwrap();
 
-        let expected_error =
-            internal_invoke_function.apply(&mut state, &StarknetGeneralConfig::default());
+        let tx_execution_info = internal_invoke_function
+            .apply(&mut state, &StarknetGeneralConfig::default())
+            .unwrap();
+
+        assert!(tx_execution_info.is_reverted());
+        assert_eq!(
+            tx_execution_info.revert_error,
+            Some(TransactionError::EntryPointNotFound.to_string())
+        );
+        assert_eq!(tx_execution_info.call_info, None);
+    }
+
+    #[test]
+    fn test_apply_invoke_failing_midway_charges_the_failed_execution() {
+        let class_hash = [1; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/amm.json")).unwrap();
+        let contract_address = Address(0.into());
+        let apply_reverted = |entry_point_selector: Felt252, calldata: Vec<Felt252>| {
+            let internal_invoke_function = InternalInvokeFunction {
+                contract_address: contract_address.clone(),
+                entry_point_selector,
+                entry_point_type: EntryPointType::External,
+                calldata,
+                tx_type: TransactionType::InvokeFunction,
+                version: 0,
+                is_query: false,
+                validate_entry_point_selector: 0.into(),
+                hash_value: 0.into(),
+                signature: Vec::new(),
+                max_fee: 0,
+                nonce: Some(0.into()),
+            };
+
+            let mut state_reader = InMemoryStateReader::default();
+            state_reader
+                .address_to_class_hash_mut()
+                .insert(contract_address.clone(), class_hash);
+            state_reader
+                .address_to_nonce
+                .insert(contract_address.clone(), Felt252::zero());
+            let mut state = CachedState::new(state_reader, Some(HashMap::new()));
+            state
+                .set_contract_class(&class_hash, &contract_class)
+                .unwrap();
+
+            let tx_execution_info = internal_invoke_function
+                .apply(&mut state, &StarknetGeneralConfig::default())
+                .unwrap();
+            assert!(tx_execution_info.is_reverted());
+            tx_execution_info.actual_resources
+        };
+
+        // The caller has no tokens, so swapping one hashes the key of its balance and fails
+        // after range checking the amount.
+        let failed_swap_resources = apply_reverted(
+            Felt252::from_bytes_be(&calculate_sn_keccak(b"swap")),
+            vec![1.into(), 1.into()],
+        );
+        let not_run_resources = apply_reverted(Felt252::new(404), Vec::new());
+
+        assert!(
+            failed_swap_resources.get("pedersen_builtin")
+                > not_run_resources.get("pedersen_builtin")
+        );
+        assert!(
+            failed_swap_resources["range_check_builtin"] > not_run_resources["range_check_builtin"]
+        );
+    }
+
+    #[test]
+    fn test_execute_reverted_invoke_still_increments_nonce() {
+        let internal_invoke_function = InternalInvokeFunction {
+            contract_address: Address(0.into()),
+            entry_point_selector: Felt252::new(404),
+            entry_point_type: EntryPointType::External,
+            calldata: Vec::new(),
+            tx_type: TransactionType::InvokeFunction,
+            version: 1,
+            is_query: false,
+            validate_entry_point_selector: 0.into(),
+            hash_value: 0.into(),
+            signature: Vec::new(),
+            max_fee: 0,
+            nonce: Some(0.into()),
+        };
+
+        let mut state_reader = InMemoryStateReader::default();
+        let class_hash = [1; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap();
+        let contract_address = Address(0.into());
+
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(contract_address.clone(), class_hash);
+        state_reader
+            .address_to_nonce
+            .insert(contract_address.clone(), Felt252::zero());
+
+        let mut state = CachedState::new(state_reader, Some(HashMap::new()));
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+
+        let tx_execution_info = internal_invoke_function
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .unwrap();
+
+        assert_eq!(
+            tx_execution_info.revert_error,
+            Some(TransactionError::EntryPointNotFound.to_string())
+        );
+        assert_eq!(state.get_nonce_at(&contract_address), Ok(Felt252::new(1)));
+    }
+
+    #[test]
+    fn test_execute_invoke_failed_validation_leaves_state_untouched() {
+        // The fibonacci contract has no __validate__ entry point.
+        let internal_invoke_function = InternalInvokeFunction {
+            contract_address: Address(0.into()),
+            entry_point_selector: (*EXECUTE_ENTRY_POINT_SELECTOR).clone(),
+            entry_point_type: EntryPointType::External,
+            calldata: Vec::new(),
+            tx_type: TransactionType::InvokeFunction,
+            version: 1,
+            is_query: false,
+            validate_entry_point_selector: VALIDATE_ENTRY_POINT_SELECTOR.clone(),
+            hash_value: 0.into(),
+            signature: Vec::new(),
+            max_fee: 0,
+            nonce: Some(0.into()),
+        };
+
+        let mut state_reader = InMemoryStateReader::default();
+        let class_hash = [1; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap();
+        let contract_address = Address(0.into());
+
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(contract_address.clone(), class_hash);
+        state_reader
+            .address_to_nonce
+            .insert(contract_address.clone(), Felt252::zero());
+
+        let mut state = CachedState::new(state_reader, Some(HashMap::new()));
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+        let cache_before = state.cache().clone();
 
-        assert!(expected_error.is_err());
         assert_matches!(
-            expected_error.unwrap_err(),
-            TransactionError::EntryPointNotFound
+            internal_invoke_function.execute(&mut state, &StarknetGeneralConfig::default()),
+            Err(TransactionError::EntryPointNotFound)
+        );
+        assert_eq!(state.cache().storage_writes, cache_before.storage_writes);
+        assert_eq!(state.cache().nonce_writes, cache_before.nonce_writes);
+        assert_eq!(state.get_nonce_at(&contract_address), Ok(Felt252::zero()));
+    }
+
+    #[test]
+    fn test_invoke_wrong_nonce_fails_before_validation() {
+        // The fibonacci contract has no __validate__ entry point, reaching it would fail with
+        // EntryPointNotFound.
+        let internal_invoke_function = InternalInvokeFunction {
+            contract_address: Address(0.into()),
+            entry_point_selector: (*EXECUTE_ENTRY_POINT_SELECTOR).clone(),
+            entry_point_type: EntryPointType::External,
+            calldata: Vec::new(),
+            tx_type: TransactionType::InvokeFunction,
+            version: 1,
+            is_query: false,
+            validate_entry_point_selector: VALIDATE_ENTRY_POINT_SELECTOR.clone(),
+            hash_value: 0.into(),
+            signature: Vec::new(),
+            max_fee: 0,
+            nonce: Some(1.into()),
+        };
+
+        let mut state_reader = InMemoryStateReader::default();
+        let class_hash = [1; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap();
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(Address(0.into()), class_hash);
+        state_reader
+            .address_to_nonce
+            .insert(Address(0.into()), Felt252::zero());
+
+        let mut state = CachedState::new(state_reader, Some(HashMap::new()));
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+
+        assert_matches!(
+            internal_invoke_function.apply(&mut state, &StarknetGeneralConfig::default()),
+            Err(TransactionError::InvalidTransactionNonce(expected, got))
+                if expected == "0" && got == "1"
         );
     }
 
@@ -547,6 +805,7 @@ mod tests {
             calldata: Vec::new(),
             tx_type: TransactionType::InvokeFunction,
             version: 1,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -584,10 +843,7 @@ mod tests {
             internal_invoke_function.apply(&mut state, &StarknetGeneralConfig::default());
 
         assert!(expected_error.is_err());
-        assert_matches!(
-            expected_error.unwrap_err(),
-            TransactionError::InvalidTxContext
-        );
+        assert_matches!(expected_error.unwrap_err(), TransactionError::MissingNonce);
     }
 
     #[test]
@@ -604,6 +860,7 @@ mod tests {
             calldata: vec![1.into(), 1.into(), 10.into()],
             tx_type: TransactionType::InvokeFunction,
             version: 1,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -644,6 +901,16 @@ mod tests {
             (String::from("range_check_builtin"), 70.into()),
         ]);
 
+        // Fund the account, so the transaction gets through the balance check.
+        let (balance_key, _) = get_erc20_balance_var_addresses(&Address(0.into())).unwrap();
+        state.set_storage_at(
+            &(
+                config.starknet_os_config.fee_token_address.clone(),
+                balance_key,
+            ),
+            1000.into(),
+        );
+
         let expected_error = internal_invoke_function.execute(&mut state, &config);
         let error_msg = "Fee transfer failure".to_string();
         assert!(expected_error.is_err());
@@ -663,6 +930,7 @@ mod tests {
             calldata: vec![1.into(), 1.into(), 10.into()],
             tx_type: TransactionType::InvokeFunction,
             version: 1,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -704,12 +972,62 @@ mod tests {
         ]);
         config.starknet_os_config.gas_price = 1;
 
+        // Fund the account, so the transaction gets through the balance check.
+        let (balance_key, _) = get_erc20_balance_var_addresses(&Address(0.into())).unwrap();
+        state.set_storage_at(
+            &(
+                config.starknet_os_config.fee_token_address.clone(),
+                balance_key,
+            ),
+            1000.into(),
+        );
+
         let expected_error = internal_invoke_function.execute(&mut state, &config);
         let error_msg = "Actual fee exceeded max fee.".to_string();
         assert!(expected_error.is_err());
         assert_matches!(expected_error.unwrap_err(), TransactionError::FeeError(actual_error_msg) if actual_error_msg == error_msg);
     }
 
+    #[test]
+    fn test_execute_invoke_insufficient_balance_should_fail() {
+        let mut state = fibonacci_state();
+        let invoke = InternalInvokeFunction::new(
+            Address(0.into()),
+            Felt252::from_str_radix(
+                "112e35f48499939272000bd72eb840e502ca4c3aefa8800992e8defb746e0c9",
+                16,
+            )
+            .unwrap(),
+            1000,
+            TRANSACTION_VERSION.into(),
+            vec![1.into(), 1.into(), 10.into()],
+            Vec::new(),
+            StarknetChainId::TestNet.to_felt(),
+            Some(0.into()),
+        )
+        .unwrap();
+
+        let config = StarknetGeneralConfig::default();
+        let (low_key, high_key) = get_erc20_balance_var_addresses(&Address(0.into())).unwrap();
+        let fee_token_address = config.starknet_os_config.fee_token_address.clone();
+        state.set_storage_at(&(fee_token_address.clone(), low_key), 999.into());
+
+        assert_matches!(
+            invoke.execute(&mut state, &config),
+            Err(TransactionError::InsufficientAccountBalance { balance, max_fee: 1000 })
+                if balance == BigUint::from(999_u32)
+        );
+        assert_eq!(state.get_nonce_at(&Address(0.into())), Ok(Felt252::zero()));
+
+        // The high half of the balance counts too, the account is rich enough now and the
+        // transaction only fails when transferring the fee.
+        state.set_storage_at(&(fee_token_address, high_key), 1.into());
+        assert_matches!(
+            invoke.execute(&mut state, &config),
+            Err(TransactionError::FeeError(..))
+        );
+    }
+
     #[test]
     fn test_execute_invoke_twice_should_fail() {
         let internal_invoke_function = InternalInvokeFunction {
@@ -723,6 +1041,7 @@ mod tests {
             calldata: vec![1.into(), 1.into(), 10.into()],
             tx_type: TransactionType::InvokeFunction,
             version: 1,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -783,6 +1102,7 @@ mod tests {
             calldata: vec![1.into(), 1.into(), 10.into()],
             tx_type: TransactionType::InvokeFunction,
             version: 1,
+            is_query: false,
             validate_entry_point_selector: 0.into(),
             hash_value: 0.into(),
             signature: Vec::new(),
@@ -820,10 +1140,7 @@ mod tests {
             internal_invoke_function.execute(&mut state, &StarknetGeneralConfig::default());
 
         assert!(expected_error.is_err());
-        assert_matches!(
-            expected_error.unwrap_err(),
-            TransactionError::InvalidTxContext
-        )
+        assert_matches!(expected_error.unwrap_err(), TransactionError::MissingNonce)
     }
 
     #[test]
@@ -899,4 +1216,138 @@ mod tests {
             TransactionError::InvokeFunctionNonZeroMissingNonce
         )
     }
+
+    fn fibonacci_state() -> CachedState<InMemoryStateReader> {
+        let class_hash = [1; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/fibonacci.json")).unwrap();
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(Address(0.into()), class_hash);
+        state_reader
+            .address_to_nonce
+            .insert(Address(0.into()), Felt252::zero());
+
+        let mut state = CachedState::new(state_reader, Some(HashMap::new()));
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+        state
+    }
+
+    fn fib_invoke(version: Felt252, nonce: Option<Felt252>) -> InternalInvokeFunction {
+        InternalInvokeFunction::new(
+            Address(0.into()),
+            Felt252::from_str_radix(
+                "112e35f48499939272000bd72eb840e502ca4c3aefa8800992e8defb746e0c9",
+                16,
+            )
+            .unwrap(),
+            0,
+            version,
+            vec![1.into(), 1.into(), 10.into()],
+            Vec::new(),
+            StarknetChainId::TestNet.to_felt(),
+            nonce,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn execute_invoke_version_zero() {
+        let mut state = fibonacci_state();
+        let internal_invoke_function = fib_invoke(0.into(), None);
+        assert_eq!(internal_invoke_function.version(), 0);
+
+        let result = internal_invoke_function
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .unwrap();
+
+        // The entry point is called directly, without going through __validate__, and the
+        // nonce isn't touched.
+        assert_eq!(result.validate_info, None);
+        assert_eq!(result.call_info.unwrap().retdata, vec![Felt252::new(144)]);
+        assert_eq!(state.get_nonce_at(&Address(0.into())), Ok(Felt252::zero()));
+    }
+
+    #[test]
+    fn invoke_query_version() {
+        let query_version = QUERY_VERSION_BASE.clone() + Felt252::new(TRANSACTION_VERSION);
+        let query = fib_invoke(query_version.clone(), Some(0.into()));
+        let regular = fib_invoke(TRANSACTION_VERSION.into(), Some(0.into()));
+
+        assert!(query.is_query());
+        assert!(!regular.is_query());
+        assert_eq!(query.version(), TRANSACTION_VERSION);
+        assert_ne!(query.hash_value(), regular.hash_value());
+        assert_eq!(
+            query.hash_value(),
+            &calculate_transaction_hash_common(
+                TransactionHashPrefix::Invoke,
+                query_version,
+                &Address(0.into()),
+                Felt252::zero(),
+                &[1.into(), 1.into(), 10.into()],
+                0,
+                StarknetChainId::TestNet.to_felt(),
+                &[0.into()],
+            )
+            .unwrap()
+        );
+
+        // The account sees the version the transaction was signed with.
+        assert_eq!(
+            query.get_execution_context(0).unwrap().version,
+            query_version
+        );
+        assert_eq!(
+            regular.get_execution_context(0).unwrap().version,
+            Felt252::new(TRANSACTION_VERSION)
+        );
+
+        // Query transactions can't be applied to a state.
+        let mut state = fibonacci_state();
+        assert_matches!(
+            query.execute(&mut state, &StarknetGeneralConfig::default()),
+            Err(TransactionError::QueryTransactionExecution)
+        );
+    }
+
+    #[test]
+    fn invoke_without_is_query_deserializes_as_regular() {
+        let invoke = fib_invoke(TRANSACTION_VERSION.into(), Some(0.into()));
+        let mut json = serde_json::to_value(&invoke).unwrap();
+        json.as_object_mut().unwrap().remove("is_query");
+
+        let deserialized: InternalInvokeFunction = serde_json::from_value(json).unwrap();
+        assert!(!deserialized.is_query());
+        assert_eq!(deserialized.hash_value(), invoke.hash_value());
+    }
+
+    #[test]
+    fn invoke_query_version_zero_has_no_nonce() {
+        let query = fib_invoke(QUERY_VERSION_BASE.clone(), None);
+
+        assert!(query.is_query());
+        assert_eq!(query.version(), 0);
+    }
+
+    #[test]
+    fn invoke_unsupported_version_should_fail() {
+        let error = InternalInvokeFunction::new(
+            Address(0.into()),
+            0.into(),
+            0,
+            2.into(),
+            Vec::new(),
+            Vec::new(),
+            StarknetChainId::TestNet.to_felt(),
+            Some(0.into()),
+        )
+        .unwrap_err();
+
+        assert_matches!(error, TransactionError::UnsupportedVersion(version) if version == "2");
+    }
 }
@@ -0,0 +1,292 @@
+use crate::{
+    business_logic::{
+        execution::{
+            execution_entry_point::ExecutionEntryPoint,
+            objects::{TransactionExecutionContext, TransactionExecutionInfo},
+            profiler::Profiler,
+        },
+        fact_state::state::ExecutionResourcesManager,
+        state::state_api::{State, StateReader},
+        transaction::error::TransactionError,
+    },
+    core::transaction_hash::starknet_transaction_hash::{
+        calculate_transaction_hash_common, TransactionHashPrefix,
+    },
+    definitions::{general_config::StarknetGeneralConfig, transaction_type::TransactionType},
+    services::api::contract_class::EntryPointType,
+    utils::{calculate_tx_resources, Address},
+};
+use felt::Felt252;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+
+const L1_HANDLER_VERSION: u64 = 0;
+
+/// A message sent from L1 to the `@l1_handler` entry point of an L2 contract.
+///
+/// The handler is called with the L1 sender address followed by the message payload. There is no
+/// account involved, so nothing is validated and no fee is charged on L2.
+#[derive(Debug, Getters, Deserialize, Serialize)]
+pub struct InternalL1Handler {
+    #[getset(get = "pub")]
+    hash_value: Felt252,
+    #[getset(get = "pub")]
+    contract_address: Address,
+    #[getset(get = "pub")]
+    entry_point_selector: Felt252,
+    #[getset(get = "pub")]
+    calldata: Vec<Felt252>,
+    #[getset(get = "pub")]
+    nonce: Felt252,
+    tx_type: TransactionType,
+}
+
+impl InternalL1Handler {
+    pub fn new(
+        contract_address: Address,
+        entry_point_selector: Felt252,
+        from_address: Felt252,
+        payload: Vec<Felt252>,
+        nonce: Felt252,
+        chain_id: Felt252,
+    ) -> Result<Self, TransactionError> {
+        let calldata: Vec<Felt252> = [from_address].into_iter().chain(payload).collect();
+        let hash_value = calculate_transaction_hash_common(
+            TransactionHashPrefix::L1Handler,
+            L1_HANDLER_VERSION,
+            &contract_address,
+            entry_point_selector.clone(),
+            &calldata,
+            0, // L1 handlers don't pay fees on L2.
+            chain_id,
+            &[nonce.clone()],
+        )?;
+
+        Ok(InternalL1Handler {
+            hash_value,
+            contract_address,
+            entry_point_selector,
+            calldata,
+            nonce,
+            tx_type: TransactionType::L1Handler,
+        })
+    }
+
+    /// The L1 address that sent the message.
+    pub fn from_address(&self) -> &Felt252 {
+        &self.calldata[0]
+    }
+
+    pub fn payload(&self) -> &[Felt252] {
+        &self.calldata[1..]
+    }
+
+    pub fn version(&self) -> u64 {
+        L1_HANDLER_VERSION
+    }
+
+    /// Runs the handler and returns the execution info.
+    pub fn apply<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.apply_with_profiler(state, general_config, None)
+    }
+
+    fn apply_with_profiler<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        let call = ExecutionEntryPoint::new(
+            self.contract_address.clone(),
+            self.calldata.clone(),
+            self.entry_point_selector.clone(),
+            Address(0.into()),
+            EntryPointType::L1Handler,
+            None,
+            None,
+        );
+
+        let mut resources_manager = ExecutionResourcesManager::with_profiler(profiler);
+        let call_info = call.execute(
+            state,
+            general_config,
+            &mut resources_manager,
+            &self.get_execution_context(general_config.invoke_tx_max_n_steps),
+        )?;
+
+        let changes = state.count_actual_state_changes();
+        let actual_resources = calculate_tx_resources(
+            resources_manager,
+            &[Some(call_info.clone())],
+            self.tx_type,
+            changes,
+            Some(self.payload().len()),
+        )?;
+
+        Ok(
+            TransactionExecutionInfo::create_concurrent_stage_execution_info(
+                None,
+                Some(call_info),
+                actual_resources,
+                Some(self.tx_type),
+            ),
+        )
+    }
+
+    /// Runs the handler. If it fails, the state is left untouched.
+    pub fn execute<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.execute_with_profiler(state, general_config, None)
+    }
+
+    /// Runs the handler like [`execute`](Self::execute), profiling it into `profiler` if given.
+    pub fn execute_with_profiler<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        let checkpoint = state.checkpoint();
+        match self.apply_with_profiler(state, general_config, profiler) {
+            Ok(tx_execution_info) => {
+                state.commit(checkpoint)?;
+                Ok(tx_execution_info)
+            }
+            Err(error) => {
+                state.revert_to(checkpoint)?;
+                Err(error)
+            }
+        }
+    }
+
+    fn get_execution_context(&self, n_steps: u64) -> TransactionExecutionContext {
+        TransactionExecutionContext::new(
+            self.contract_address.clone(),
+            self.hash_value.clone(),
+            Vec::new(),
+            0,
+            self.nonce.clone(),
+            n_steps,
+            L1_HANDLER_VERSION,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            execution::objects::CallType, fact_state::in_memory_state_reader::InMemoryStateReader,
+            state::cached_state::CachedState,
+        },
+        definitions::general_config::StarknetChainId,
+        services::api::contract_class::ContractClass,
+        utils::calculate_sn_keccak,
+    };
+    use coverage_helper::test;
+    use felt::felt_str;
+    use num_traits::Num;
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn l1l2_state() -> CachedState<InMemoryStateReader> {
+        let class_hash = [1; 32];
+        let contract_class =
+            ContractClass::try_from(PathBuf::from("starknet_programs/l1l2.json")).unwrap();
+
+        let mut state_reader = InMemoryStateReader::default();
+        state_reader
+            .address_to_class_hash_mut()
+            .insert(Address(1.into()), class_hash);
+
+        let mut state = CachedState::new(state_reader, Some(HashMap::new()));
+        state
+            .set_contract_class(&class_hash, &contract_class)
+            .unwrap();
+        state
+    }
+
+    fn deposit(from_address: Felt252) -> InternalL1Handler {
+        InternalL1Handler::new(
+            Address(1.into()),
+            Felt252::from_bytes_be(&calculate_sn_keccak(b"deposit")),
+            from_address,
+            vec![1.into(), 10.into()],
+            0.into(),
+            StarknetChainId::TestNet.to_felt(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn execute_l1_handler() {
+        let mut state = l1l2_state();
+        let l1_handler = deposit(
+            Felt252::from_str_radix("8359E4B0152ed5A731162D3c7B0D8D56edB165A0", 16).unwrap(),
+        );
+
+        let tx_execution_info = l1_handler
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .unwrap();
+
+        let call_info = tx_execution_info.call_info.unwrap();
+        assert_eq!(call_info.entry_point_type, Some(EntryPointType::L1Handler));
+        assert_eq!(call_info.call_type, Some(CallType::Call));
+        assert_eq!(call_info.calldata, l1_handler.calldata);
+        assert_eq!(tx_execution_info.validate_info, None);
+        assert_eq!(tx_execution_info.fee_transfer_info, None);
+        assert_eq!(tx_execution_info.actual_fee, 0);
+        assert_eq!(tx_execution_info.tx_type, Some(TransactionType::L1Handler));
+
+        // The message segment holds the header and the 2 felts of payload, the data segment the
+        // updated balance. Consuming the message costs a counter decrease and an event.
+        assert_eq!(tx_execution_info.actual_resources["l1_gas_usage"], 18471);
+
+        let storage_writes: Vec<_> = state.cache().storage_writes().values().collect();
+        assert_eq!(storage_writes, vec![&Felt252::new(10)]);
+    }
+
+    #[test]
+    fn l1_handler_fields() {
+        let l1_handler = deposit(2.into());
+
+        assert_eq!(l1_handler.from_address(), &Felt252::new(2));
+        assert_eq!(l1_handler.payload(), &[Felt252::new(1), Felt252::new(10)]);
+        assert_eq!(
+            l1_handler.hash_value,
+            calculate_transaction_hash_common(
+                TransactionHashPrefix::L1Handler,
+                0,
+                &Address(1.into()),
+                l1_handler.entry_point_selector.clone(),
+                &[2.into(), 1.into(), 10.into()],
+                0,
+                felt_str!("1536727068981429685321"),
+                &[0.into()],
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn failing_l1_handler_leaves_state_untouched() {
+        let mut state = l1l2_state();
+        let cache_before = state.cache().clone();
+
+        // The handler only accepts messages from its L1 contract.
+        assert!(deposit(2.into())
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .is_err());
+        assert_eq!(
+            state.cache().storage_writes(),
+            cache_before.storage_writes()
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub mod internal_declare;
+pub mod internal_declare_v2;
 pub mod internal_deploy;
 pub mod internal_deploy_account;
 pub mod internal_invoke_function;
+pub mod internal_l1_handler;
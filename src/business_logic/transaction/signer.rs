@@ -0,0 +1,92 @@
+use super::error::TransactionError;
+use crate::utils::{felt_to_field_element, field_element_to_felt};
+use felt::Felt252;
+use starknet_crypto::{get_public_key, rfc6979_generate_k, sign};
+
+/// Signs transaction hashes with a Stark private key, the way the OpenZeppelin account expects
+/// them: a signature is `[r, s]`.
+///
+/// Signatures are deterministic (RFC 6979), signing the same hash twice gives the same
+/// signature.
+// Not Debug on purpose, so the private key doesn't end up in logs.
+#[derive(Clone)]
+pub struct StarkSigner {
+    private_key: Felt252,
+}
+
+impl StarkSigner {
+    pub fn new(private_key: Felt252) -> Self {
+        Self { private_key }
+    }
+
+    pub fn public_key(&self) -> Result<Felt252, TransactionError> {
+        let private_key = felt_to_field_element(&self.private_key)?;
+        Ok(field_element_to_felt(&get_public_key(&private_key)))
+    }
+
+    /// Signs `hash`, returning `(r, s)`.
+    pub fn sign_hash(&self, hash: &Felt252) -> Result<(Felt252, Felt252), TransactionError> {
+        let private_key = felt_to_field_element(&self.private_key)?;
+        let message = felt_to_field_element(hash)?;
+        let k = rfc6979_generate_k(&message, &private_key, None);
+
+        let signature = sign(&private_key, &message, &k)
+            .map_err(|error| TransactionError::SignatureError(format!("{error:?}")))?;
+        Ok((
+            field_element_to_felt(&signature.r),
+            field_element_to_felt(&signature.s),
+        ))
+    }
+
+    /// Signs `hash`, returning the signature as it goes in a transaction.
+    pub fn sign_transaction_hash(&self, hash: &Felt252) -> Result<Vec<Felt252>, TransactionError> {
+        let (r, s) = self.sign_hash(hash)?;
+        Ok(vec![r, s])
+    }
+}
+
+/// A transaction whose signature is computed from its own hash, once it's built.
+pub(crate) trait SignedTransaction: Sized {
+    fn transaction_hash(&self) -> &Felt252;
+
+    fn set_signature(&mut self, signature: Vec<Felt252>);
+
+    /// Replaces the signature of the transaction with the one `signer` computes from its hash.
+    fn signed_by(mut self, signer: &StarkSigner) -> Result<Self, TransactionError> {
+        let signature = signer.sign_transaction_hash(self.transaction_hash())?;
+        self.set_signature(signature);
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coverage_helper::test;
+    use starknet_crypto::verify;
+
+    #[test]
+    fn sign_and_verify() {
+        let signer = StarkSigner::new(12345.into());
+        let hash = Felt252::new(999);
+
+        let (r, s) = signer.sign_hash(&hash).unwrap();
+        assert!(verify(
+            &felt_to_field_element(&signer.public_key().unwrap()).unwrap(),
+            &felt_to_field_element(&hash).unwrap(),
+            &felt_to_field_element(&r).unwrap(),
+            &felt_to_field_element(&s).unwrap(),
+        )
+        .unwrap());
+
+        // Signing is deterministic.
+        assert_eq!(
+            signer.sign_transaction_hash(&hash).unwrap(),
+            vec![r.clone(), s.clone()]
+        );
+        assert_ne!(
+            StarkSigner::new(54321.into()).sign_hash(&hash).unwrap(),
+            (r, s)
+        );
+    }
+}
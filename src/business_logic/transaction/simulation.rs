@@ -0,0 +1,84 @@
+use super::{
+    account_transaction::AccountTransaction, error::TransactionError,
+    fee::calculate_tx_l1_gas_usage, transactions::Transaction,
+};
+use crate::{
+    business_logic::{
+        execution::objects::TransactionExecutionInfo,
+        state::state_api::{State, StateReader},
+    },
+    definitions::general_config::StarknetGeneralConfig,
+};
+
+/// Parts of a transaction to leave out when simulating it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimulationFlags {
+    /// Don't run the account's validate entry point.
+    pub skip_validate: bool,
+    /// Compute the fee but don't transfer it to the sequencer.
+    pub skip_fee_charge: bool,
+}
+
+/// The outcome of a simulated transaction.
+#[derive(Debug)]
+pub struct TransactionSimulation {
+    /// The fee the transaction would pay, `gas_consumed * gas_price`.
+    pub overall_fee: u64,
+    pub gas_consumed: u64,
+    pub gas_price: u64,
+    /// The execution info, with `actual_fee` set to the fee charged. Transactions that don't
+    /// pay fees on L2, deploys and L1 handlers, have an `actual_fee` of 0.
+    pub execution_info: TransactionExecutionInfo,
+}
+
+impl Transaction {
+    /// Runs the transaction on `state` and discards its changes, returning what the transaction
+    /// would have done and cost.
+    ///
+    /// Unlike [`execute`](Self::execute), the fee is computed even if the max fee of the
+    /// transaction is too low to cover it, and transactions with a query version are accepted.
+    pub fn simulate<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        flags: SimulationFlags,
+    ) -> Result<TransactionSimulation, TransactionError> {
+        let checkpoint = state.checkpoint();
+        let execution_info = match self {
+            Transaction::Declare(tx) => tx.simulate(state, general_config, flags),
+            Transaction::DeclareV2(tx) => tx.simulate(state, general_config, flags),
+            Transaction::Deploy(tx) => tx.apply(state, general_config),
+            Transaction::DeployAccount(tx) => tx.simulate(state, general_config, flags),
+            Transaction::InvokeFunction(tx) => tx.simulate(state, general_config, flags),
+            Transaction::L1Handler(tx) => tx.apply(state, general_config),
+        };
+        state.revert_to(checkpoint)?;
+        let execution_info = execution_info?;
+
+        let gas_consumed =
+            calculate_tx_l1_gas_usage(&execution_info.actual_resources, general_config)?;
+        let gas_price = general_config.starknet_os_config.gas_price;
+        Ok(TransactionSimulation {
+            overall_fee: gas_consumed * gas_price,
+            gas_consumed,
+            gas_price,
+            execution_info,
+        })
+    }
+
+    /// Estimates the fee of the transaction by simulating it without the fee transfer.
+    pub fn estimate_fee<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionSimulation, TransactionError> {
+        self.simulate(
+            state,
+            general_config,
+            SimulationFlags {
+                skip_validate: false,
+                skip_fee_charge: true,
+            },
+        )
+    }
+}
@@ -1,34 +1,116 @@
 use crate::{
     business_logic::{
-        execution::objects::TransactionExecutionInfo,
+        execution::{objects::TransactionExecutionInfo, profiler::Profiler},
         state::state_api::{State, StateReader},
     },
-    definitions::general_config::StarknetGeneralConfig,
+    definitions::{general_config::StarknetGeneralConfig, transaction_type::TransactionType},
     utils::{Address, ClassHash},
 };
+use felt::Felt252;
+use serde::{Deserialize, Serialize};
 
 use super::{
+    account_transaction::AccountTransaction,
     error::TransactionError,
-    objects::{internal_deploy::InternalDeploy, internal_invoke_function::InternalInvokeFunction},
+    objects::{
+        internal_declare::InternalDeclare, internal_declare_v2::InternalDeclareV2,
+        internal_deploy::InternalDeploy, internal_deploy_account::InternalDeployAccount,
+        internal_invoke_function::InternalInvokeFunction, internal_l1_handler::InternalL1Handler,
+    },
 };
 
+/// Any transaction that can be executed on a StarkNet state.
+#[derive(Debug, Deserialize, Serialize)]
 pub enum Transaction {
+    Declare(InternalDeclare),
+    DeclareV2(InternalDeclareV2),
     Deploy(InternalDeploy),
+    DeployAccount(InternalDeployAccount),
     InvokeFunction(InternalInvokeFunction),
+    L1Handler(InternalL1Handler),
 }
 
 impl Transaction {
-    pub fn contract_hash(&self) -> ClassHash {
+    pub fn hash(&self) -> &Felt252 {
+        match self {
+            Transaction::Declare(tx) => &tx.hash_value,
+            Transaction::DeclareV2(tx) => &tx.hash_value,
+            Transaction::Deploy(tx) => &tx.hash_value,
+            Transaction::DeployAccount(tx) => tx.hash_value(),
+            Transaction::InvokeFunction(tx) => tx.hash_value(),
+            Transaction::L1Handler(tx) => tx.hash_value(),
+        }
+    }
+
+    pub fn tx_type(&self) -> TransactionType {
+        match self {
+            Transaction::Declare(_) | Transaction::DeclareV2(_) => TransactionType::Declare,
+            Transaction::Deploy(_) => TransactionType::Deploy,
+            Transaction::DeployAccount(_) => TransactionType::DeployAccount,
+            Transaction::InvokeFunction(_) => TransactionType::InvokeFunction,
+            Transaction::L1Handler(_) => TransactionType::L1Handler,
+        }
+    }
+
+    pub fn version(&self) -> u64 {
         match self {
-            Transaction::Deploy(tx) => tx.contract_hash,
-            _ => [0; 32],
+            Transaction::Declare(tx) => tx.version,
+            Transaction::DeclareV2(tx) => tx.version,
+            Transaction::Deploy(tx) => tx.version,
+            Transaction::DeployAccount(tx) => tx.version(),
+            Transaction::InvokeFunction(tx) => tx.version(),
+            Transaction::L1Handler(tx) => tx.version(),
         }
     }
 
+    /// The account that sent the transaction. Deploy and L1 handler transactions don't come from
+    /// an account.
+    pub fn sender_address(&self) -> Option<&Address> {
+        match self {
+            Transaction::Declare(tx) => Some(&tx.sender_address),
+            Transaction::DeclareV2(tx) => Some(&tx.sender_address),
+            Transaction::DeployAccount(tx) => Some(tx.contract_address()),
+            Transaction::InvokeFunction(tx) => Some(tx.contract_address()),
+            Transaction::Deploy(_) | Transaction::L1Handler(_) => None,
+        }
+    }
+
+    /// The account nonce the transaction uses. Transactions that don't come from an account, and
+    /// version 0 ones, have none.
+    pub fn nonce(&self) -> Option<&Felt252> {
+        if self.version() == 0 {
+            return None;
+        }
+        match self {
+            Transaction::Declare(tx) => Some(&tx.nonce),
+            Transaction::DeclareV2(tx) => Some(&tx.nonce),
+            Transaction::DeployAccount(tx) => Some(tx.nonce()),
+            Transaction::InvokeFunction(tx) => tx.nonce().as_ref(),
+            Transaction::Deploy(_) | Transaction::L1Handler(_) => None,
+        }
+    }
+
+    /// The contract the transaction is executed on: the declaring account for declare
+    /// transactions, the deployed contract for deployments.
     pub fn contract_address(&self) -> Address {
         match self {
+            Transaction::Declare(tx) => tx.sender_address.clone(),
+            Transaction::DeclareV2(tx) => tx.sender_address.clone(),
             Transaction::Deploy(tx) => tx.contract_address.clone(),
+            Transaction::DeployAccount(tx) => tx.contract_address().clone(),
             Transaction::InvokeFunction(tx) => tx.contract_address().clone(),
+            Transaction::L1Handler(tx) => tx.contract_address().clone(),
+        }
+    }
+
+    /// The class declared or deployed by the transaction, if any.
+    pub fn class_hash(&self) -> Option<ClassHash> {
+        match self {
+            Transaction::Declare(tx) => Some(tx.class_hash),
+            Transaction::DeclareV2(tx) => Some(tx.class_hash),
+            Transaction::Deploy(tx) => Some(tx.contract_hash),
+            Transaction::DeployAccount(tx) => Some(*tx.class_hash()),
+            Transaction::InvokeFunction(_) | Transaction::L1Handler(_) => None,
         }
     }
 
@@ -36,10 +118,206 @@ impl Transaction {
         &self,
         state: &mut S,
         general_config: &StarknetGeneralConfig,
+    ) -> Result<TransactionExecutionInfo, TransactionError> {
+        self.execute_with_profiler(state, general_config, None)
+    }
+
+    /// Executes the transaction, profiling the entry points it runs into `profiler` if given.
+    pub fn execute_with_profiler<S: Default + State + StateReader + Clone>(
+        &self,
+        state: &mut S,
+        general_config: &StarknetGeneralConfig,
+        profiler: Option<&Profiler>,
     ) -> Result<TransactionExecutionInfo, TransactionError> {
         match self {
-            Transaction::Deploy(tx) => tx.execute(state, general_config),
-            Transaction::InvokeFunction(tx) => tx.execute(state, general_config),
+            Transaction::Declare(tx) => tx.execute_with_profiler(state, general_config, profiler),
+            Transaction::DeclareV2(tx) => tx.execute_with_profiler(state, general_config, profiler),
+            Transaction::Deploy(tx) => tx.execute_with_profiler(state, general_config, profiler),
+            Transaction::DeployAccount(tx) => {
+                tx.execute_with_profiler(state, general_config, profiler)
+            }
+            Transaction::InvokeFunction(tx) => {
+                tx.execute_with_profiler(state, general_config, profiler)
+            }
+            Transaction::L1Handler(tx) => tx.execute_with_profiler(state, general_config, profiler),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        business_logic::{
+            fact_state::in_memory_state_reader::InMemoryStateReader,
+            state::cached_state::CachedState,
+        },
+        core::contract_address::starknet_contract_address::compute_compiled_class_hash,
+        definitions::{constants::DECLARE_VERSION, general_config::StarknetChainId},
+        services::api::{
+            contract_class::ContractClassWithSource,
+            sierra_contract_class::{CasmContractClass, SierraContractClass},
+        },
+    };
+    use coverage_helper::test;
+    use std::{collections::HashMap, path::PathBuf};
+
+    fn invoke() -> Transaction {
+        Transaction::InvokeFunction(
+            InternalInvokeFunction::new(
+                Address(1.into()),
+                2.into(),
+                0,
+                1.into(),
+                vec![3.into()],
+                Vec::new(),
+                StarknetChainId::TestNet.to_felt(),
+                Some(0.into()),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn deploy_account() -> Transaction {
+        Transaction::DeployAccount(
+            InternalDeployAccount::new(
+                [1; 32],
+                0,
+                1,
+                0.into(),
+                Vec::new(),
+                Vec::new(),
+                Address(2.into()),
+                StarknetChainId::TestNet,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn l1_handler() -> Transaction {
+        Transaction::L1Handler(
+            InternalL1Handler::new(
+                Address(1.into()),
+                2.into(),
+                3.into(),
+                vec![4.into()],
+                0.into(),
+                StarknetChainId::TestNet.to_felt(),
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn transaction_accessors() {
+        let invoke = invoke();
+        assert_eq!(invoke.tx_type(), TransactionType::InvokeFunction);
+        assert_eq!(invoke.version(), 1);
+        assert_eq!(invoke.sender_address(), Some(&Address(1.into())));
+        assert_eq!(invoke.class_hash(), None);
+        assert_eq!(invoke.nonce(), Some(&Felt252::new(0)));
+
+        let deploy_account = deploy_account();
+        let Transaction::DeployAccount(tx) = &deploy_account else { unreachable!() };
+        assert_eq!(deploy_account.hash(), tx.hash_value());
+        assert_eq!(deploy_account.tx_type(), TransactionType::DeployAccount);
+        assert_eq!(deploy_account.sender_address(), Some(tx.contract_address()));
+        assert_eq!(deploy_account.class_hash(), Some([1; 32]));
+
+        let l1_handler = l1_handler();
+        assert_eq!(l1_handler.tx_type(), TransactionType::L1Handler);
+        assert_eq!(l1_handler.version(), 0);
+        assert_eq!(l1_handler.sender_address(), None);
+        assert_eq!(l1_handler.contract_address(), Address(1.into()));
+        assert_eq!(l1_handler.nonce(), None);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        for tx in [invoke(), deploy_account(), l1_handler()] {
+            let deserialized: Transaction =
+                serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+
+            assert_eq!(deserialized.hash(), tx.hash());
+            assert_eq!(deserialized.tx_type(), tx.tx_type());
+            assert_eq!(deserialized.version(), tx.version());
+            assert_eq!(deserialized.contract_address(), tx.contract_address());
         }
     }
+
+    #[test]
+    fn serde_round_trip_declare() {
+        let contract_class =
+            ContractClassWithSource::try_from(&PathBuf::from("starknet_programs/fibonacci.json"))
+                .unwrap();
+        let tx = Transaction::Declare(
+            InternalDeclare::new(
+                contract_class.clone(),
+                StarknetChainId::TestNet.to_felt(),
+                Address(1.into()),
+                0,
+                1,
+                Vec::new(),
+                0.into(),
+            )
+            .unwrap(),
+        );
+
+        let deserialized: Transaction =
+            serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+        let Transaction::Declare(declare) = &deserialized else { unreachable!() };
+        assert_eq!(declare.contract_class, contract_class);
+        assert_eq!(deserialized.hash(), tx.hash());
+        assert_eq!(deserialized.class_hash(), tx.class_hash());
+    }
+
+    #[test]
+    fn serde_round_trip_declare_v2() {
+        let sierra_contract_class = SierraContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.sierra",
+        ))
+        .unwrap();
+        let casm_contract_class = CasmContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.casm",
+        ))
+        .unwrap();
+        let compiled_class_hash = compute_compiled_class_hash(&casm_contract_class).unwrap();
+        let tx = Transaction::DeclareV2(
+            InternalDeclareV2::new(
+                sierra_contract_class,
+                casm_contract_class,
+                compiled_class_hash,
+                StarknetChainId::TestNet.to_felt(),
+                Address(1.into()),
+                0,
+                DECLARE_VERSION,
+                Vec::new(),
+                3.into(),
+            )
+            .unwrap(),
+        );
+        assert_eq!(tx.tx_type(), TransactionType::Declare);
+        assert_eq!(tx.nonce(), Some(&Felt252::new(3)));
+
+        let deserialized: Transaction =
+            serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+        assert_eq!(deserialized.hash(), tx.hash());
+        assert_eq!(deserialized.version(), DECLARE_VERSION);
+        assert_eq!(deserialized.sender_address(), Some(&Address(1.into())));
+        assert_eq!(deserialized.class_hash(), tx.class_hash());
+    }
+
+    #[test]
+    fn execute_dispatches_to_the_transaction() {
+        let mut state = CachedState::new(InMemoryStateReader::default(), Some(HashMap::new()));
+
+        // Nothing is deployed at the target address, so both fail without touching the state.
+        assert!(invoke()
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .is_err());
+        assert!(l1_handler()
+            .execute(&mut state, &StarknetGeneralConfig::default())
+            .is_err());
+        assert!(state.cache().storage_writes().is_empty());
+    }
 }
@@ -1,5 +1,7 @@
 use crate::{
-    core::errors::syscall_handler_errors::SyscallHandlerError, hash_utils::compute_hash_on_elements,
+    core::errors::syscall_handler_errors::SyscallHandlerError,
+    hash_utils::compute_hash_on_elements,
+    starkware_utils::{patricia_tree::PatriciaTree, starkware_errors::StarkwareError},
 };
 use felt::Felt252;
 use starknet_crypto::{pedersen_hash, FieldElement};
@@ -8,10 +10,25 @@ use std::iter::zip;
 // --------------------------------------------------------------
 // TODO:
 //  * calculate_block_hash
-//  * calculate_patricia_root
-//  there are missing structures to implement this functions yet
+//  there are missing structures to implement this function yet
 // -------------------------------------------------------------
 
+/// Calculates the root of a Patricia tree of the given height, with the leaves indexed by their
+/// position in `leaves`.
+pub fn calculate_patricia_root(
+    leaves: Vec<Felt252>,
+    height: u64,
+) -> Result<Felt252, StarkwareError> {
+    let tree = PatriciaTree::new(
+        height,
+        leaves
+            .into_iter()
+            .enumerate()
+            .map(|(index, leaf)| (Felt252::from(index), leaf)),
+    )?;
+    Ok(tree.root())
+}
+
 pub fn calculate_tx_hashes_with_signatures(
     tx_hashes: Vec<Felt252>,
     tx_signatures: Vec<Vec<Felt252>>,
@@ -57,6 +74,28 @@ mod tests {
     use super::*;
     use coverage_helper::test;
 
+    #[test]
+    fn calculate_patricia_root_test() {
+        let leaves: Vec<Felt252> = vec![1.into(), 2.into(), 3.into()];
+
+        let root = calculate_patricia_root(leaves.clone(), 64).unwrap();
+        assert_eq!(
+            root,
+            PatriciaTree::new(
+                64,
+                [
+                    (0.into(), 1.into()),
+                    (1.into(), 2.into()),
+                    (2.into(), 3.into())
+                ]
+            )
+            .unwrap()
+            .root()
+        );
+        assert_eq!(calculate_patricia_root(vec![], 64), Ok(0.into()));
+        assert!(calculate_patricia_root(leaves, 1).is_err());
+    }
+
     #[test]
     fn calculate_event_hash_test() {
         let from_address = 1.into();
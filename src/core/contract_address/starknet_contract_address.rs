@@ -1,6 +1,14 @@
 use crate::{
-    core::errors::contract_address_errors::ContractAddressError,
-    services::api::contract_class::{ContractClass, ContractEntryPoint, EntryPointType},
+    core::errors::{
+        contract_address_errors::ContractAddressError, syscall_handler_errors::SyscallHandlerError,
+    },
+    hash_utils::compute_poseidon_hash_on_elements,
+    services::api::{
+        contract_class::{ContractClass, ContractEntryPoint, EntryPointType},
+        sierra_contract_class::{
+            CasmContractClass, CasmEntryPoint, SierraContractClass, SierraEntryPoint,
+        },
+    },
 };
 use cairo_rs::{
     hint_processor::builtin_hint_processor::builtin_hint_processor_definition::BuiltinHintProcessor,
@@ -201,12 +209,75 @@ pub fn compute_class_hash(contract_class: &ContractClass) -> Result<Felt252, Con
     }
 }
 
+/// Computes the class hash of a Cairo 1 contract class from its Sierra program.
+pub fn compute_sierra_class_hash(
+    contract_class: &SierraContractClass,
+) -> Result<Felt252, SyscallHandlerError> {
+    let api_version = Felt252::from_bytes_be(
+        format!("CONTRACT_CLASS_V{}", contract_class.contract_class_version).as_bytes(),
+    );
+
+    let hash_entry_points = |entry_points: &[SierraEntryPoint]| {
+        let elements: Vec<Felt252> = entry_points
+            .iter()
+            .flat_map(|entry_point| {
+                [
+                    entry_point.selector.clone(),
+                    entry_point.function_idx.into(),
+                ]
+            })
+            .collect();
+        compute_poseidon_hash_on_elements(&elements)
+    };
+    let entry_points = &contract_class.entry_points_by_type;
+
+    compute_poseidon_hash_on_elements(&[
+        api_version,
+        hash_entry_points(&entry_points.external)?,
+        hash_entry_points(&entry_points.l1_handler)?,
+        hash_entry_points(&entry_points.constructor)?,
+        starknet_keccak(contract_class.abi.as_bytes()),
+        compute_poseidon_hash_on_elements(&contract_class.sierra_program)?,
+    ])
+}
+
+/// Computes the compiled class hash of a CASM contract class, the hash declare v2 transactions
+/// commit to along with the Sierra class hash.
+pub fn compute_compiled_class_hash(
+    contract_class: &CasmContractClass,
+) -> Result<Felt252, SyscallHandlerError> {
+    let hash_entry_points = |entry_points: &[CasmEntryPoint]| {
+        let mut elements = Vec::with_capacity(entry_points.len() * 3);
+        for entry_point in entry_points {
+            let builtins: Vec<Felt252> = entry_point
+                .builtins
+                .iter()
+                .map(|builtin| Felt252::from_bytes_be(builtin.as_bytes()))
+                .collect();
+            elements.push(entry_point.selector.clone());
+            elements.push(entry_point.offset.into());
+            elements.push(compute_poseidon_hash_on_elements(&builtins)?);
+        }
+        compute_poseidon_hash_on_elements(&elements)
+    };
+    let entry_points = &contract_class.entry_points_by_type;
+
+    compute_poseidon_hash_on_elements(&[
+        Felt252::from_bytes_be(b"COMPILED_CLASS_V1"),
+        hash_entry_points(&entry_points.external)?,
+        hash_entry_points(&entry_points.l1_handler)?,
+        hash_entry_points(&entry_points.constructor)?,
+        compute_poseidon_hash_on_elements(&contract_class.bytecode)?,
+    ])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use coverage_helper::test;
-    use felt::Felt252;
+    use felt::{felt_str, Felt252};
     use num_traits::Num;
+    use std::path::PathBuf;
 
     #[test]
     fn test_starknet_keccak() {
@@ -292,6 +363,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compute_sierra_and_compiled_class_hashes() {
+        let sierra_class = SierraContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.sierra",
+        ))
+        .unwrap();
+        let casm_class = CasmContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.casm",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            compute_sierra_class_hash(&sierra_class).unwrap(),
+            felt_str!(
+                "390758098962163324708665802167666814817552703839323949595722360813047737906"
+            )
+        );
+        // A class with its ABI already serialized, as sent to the gateway, has the same hash.
+        let gateway_sierra_class =
+            SierraContractClass::try_from(serde_json::to_string(&sierra_class).unwrap().as_str())
+                .unwrap();
+        assert_eq!(
+            compute_sierra_class_hash(&gateway_sierra_class).unwrap(),
+            compute_sierra_class_hash(&sierra_class).unwrap()
+        );
+        assert_eq!(
+            compute_compiled_class_hash(&casm_class).unwrap(),
+            felt_str!(
+                "3290118810276346746729866995389780649861005566623131667705232161295028017180"
+            )
+        );
+    }
+
     #[test]
     fn test_compute_hinted_class_hash() {
         let mut entry_points_by_type = HashMap::new();
@@ -2,7 +2,7 @@ use crate::{
     business_logic::state::state_cache::StorageEntry,
     services::api::contract_class_errors::ContractClassError,
     starknet_storage::errors::storage_errors::StorageError,
-    utils::{Address, ClassHash},
+    utils::{Address, ClassHash, CompiledClassHash},
 };
 use thiserror::Error;
 
@@ -42,4 +42,12 @@ pub enum StateError {
     ExecutionEntryPoint(),
     #[error("No class hash declared in class_hash_to_contract_class")]
     MissingClassHash(),
+    #[error("Checkpoint {0} was already reverted or committed")]
+    InvalidCheckpoint(u64),
+    #[error("JSON-RPC request failed: {0}")]
+    Rpc(String),
+    #[error("The compiled class hash of class 0x{} is unknown", hex::encode(.0))]
+    UnknownCompiledClassHash(ClassHash),
+    #[error("Missing compiled class 0x{}", hex::encode(.0))]
+    MissingCompiledClass(CompiledClassHash),
 }
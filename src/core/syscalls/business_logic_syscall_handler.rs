@@ -49,6 +49,9 @@ pub struct BusinessLogicSyscallHandler<'a, T: State + StateReader> {
     pub(crate) starknet_storage_state: ContractStorageState<'a, T>,
     pub(crate) internal_calls: Vec<CallInfo>,
     pub(crate) expected_syscall_ptr: Relocatable,
+    /// The error of the last call made by the contract that failed, to be added to the error
+    /// stack if the contract fails because of it.
+    pub(crate) failed_call: Option<TransactionError>,
 }
 
 impl<'a, T: Default + State + StateReader> BusinessLogicSyscallHandler<'a, T> {
@@ -82,6 +85,7 @@ impl<'a, T: Default + State + StateReader> BusinessLogicSyscallHandler<'a, T> {
             starknet_storage_state,
             internal_calls,
             expected_syscall_ptr: syscall_ptr,
+            failed_call: None,
         }
     }
 
@@ -143,6 +147,7 @@ impl<'a, T: Default + State + StateReader> BusinessLogicSyscallHandler<'a, T> {
             starknet_storage_state,
             internal_calls,
             expected_syscall_ptr,
+            failed_call: None,
         }
     }
 
@@ -216,7 +221,10 @@ impl<'a, T: Default + State + StateReader> BusinessLogicSyscallHandler<'a, T> {
                 &mut self.resources_manager,
                 &self.tx_execution_context,
             )
-            .map_err(|_| StateError::ExecutionEntryPoint())?;
+            .map_err(|error| {
+                self.failed_call = Some(error);
+                StateError::ExecutionEntryPoint()
+            })?;
         Ok(())
     }
 }
@@ -419,7 +427,11 @@ where
 
                 retdata
             })
-            .map_err(|e| SyscallHandlerError::ExecutionError(e.to_string()))
+            .map_err(|error| {
+                let message = error.to_string();
+                self.failed_call = Some(error);
+                SyscallHandlerError::ExecutionError(message)
+            })
     }
 
     fn get_block_info(&self) -> &BlockInfo {
@@ -543,9 +543,15 @@ mod tests {
                 cached_state::CachedState,
                 state_api::{State, StateReader},
             },
-            transaction::objects::internal_invoke_function::InternalInvokeFunction,
+            transaction::{
+                account_transaction::AccountTransaction,
+                objects::internal_invoke_function::InternalInvokeFunction,
+            },
+        },
+        definitions::{
+            constants::TRANSACTION_VERSION, general_config::StarknetGeneralConfig,
+            transaction_type::TransactionType,
         },
-        definitions::{general_config::StarknetGeneralConfig, transaction_type::TransactionType},
         memory_insert,
         services::api::contract_class::{ContractClass, EntryPointType},
         utils::{
@@ -790,7 +796,7 @@ mod tests {
             signature: vec![300.into(), 301.into()],
             nonce: 263.into(),
             n_sent_messages: 52,
-            _n_steps: 100000,
+            ..Default::default()
         };
         syscall_handler_hint_processor
             .syscall_handler
@@ -819,8 +825,8 @@ mod tests {
 
         // TxInfoStruct
         assert_matches!(
-            get_integer(&vm, relocatable!(4, 0)),
-            Ok(field) if field == tx_execution_context.version as usize
+            get_big_int(&vm, relocatable!(4, 0)),
+            Ok(field) if field == tx_execution_context.version
         );
         assert_matches!(
             get_big_int(&vm, relocatable!(4, 1)),
@@ -1097,7 +1103,7 @@ mod tests {
             signature: vec![300.into(), 301.into()],
             nonce: 263.into(),
             n_sent_messages: 52,
-            _n_steps: 10000,
+            ..Default::default()
         };
         syscall_handler_hint_processor
             .syscall_handler
@@ -1442,6 +1448,7 @@ mod tests {
             )
             .unwrap(),
             0,
+            TRANSACTION_VERSION.into(),
             vec![10.into()],
             Vec::new(),
             0.into(),
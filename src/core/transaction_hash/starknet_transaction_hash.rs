@@ -49,10 +49,11 @@ impl TransactionHashPrefix {
 /// ```txt
 ///     H([x,y,z]) = h(h(x,y),z) = H([w, z]) where w = h(x,y)
 /// ```
+/// The version is taken as a felt so query versions, which don't fit in a `u64`, can be hashed.
 #[allow(clippy::too_many_arguments)]
 pub fn calculate_transaction_hash_common(
     tx_hash_prefix: TransactionHashPrefix,
-    version: u64,
+    version: impl Into<Felt252>,
     contract_address: &Address,
     entry_point_selector: Felt252,
     calldata: &[Felt252],
@@ -150,12 +151,36 @@ pub fn calculate_declare_transaction_hash(
     )
 }
 
+/// Calculates the hash of a declare transaction of version 2, which declares a Cairo 1 class
+/// by its Sierra class hash and commits to the hash of its compiled class.
+pub fn calculate_declare_v2_transaction_hash(
+    sierra_class_hash: &Felt252,
+    compiled_class_hash: &Felt252,
+    chain_id: Felt252,
+    sender_address: &Address,
+    max_fee: u64,
+    version: impl Into<Felt252>,
+    nonce: Felt252,
+) -> Result<Felt252, SyscallHandlerError> {
+    calculate_transaction_hash_common(
+        TransactionHashPrefix::Declare,
+        version,
+        sender_address,
+        Felt252::zero(),
+        &[sierra_class_hash.clone()],
+        max_fee,
+        chain_id,
+        &[nonce, compiled_class_hash.clone()],
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use coverage_helper::test;
     use felt::felt_str;
 
     use super::*;
+    use crate::definitions::general_config::StarknetChainId;
 
     #[test]
     fn calculate_transaction_hash_common_test() {
@@ -187,4 +212,32 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn calculate_declare_v2_transaction_hash_test() {
+        let sierra_class_hash = felt_str!(
+            "390758098962163324708665802167666814817552703839323949595722360813047737906"
+        );
+        let compiled_class_hash = felt_str!(
+            "3290118810276346746729866995389780649861005566623131667705232161295028017180"
+        );
+
+        let result = calculate_declare_v2_transaction_hash(
+            &sierra_class_hash,
+            &compiled_class_hash,
+            StarknetChainId::TestNet.to_felt(),
+            &Address(1.into()),
+            10,
+            2,
+            3.into(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            felt_str!(
+                "1552162836419305565037316074353050635761642177076766490851266453012057338832"
+            )
+        );
+    }
 }
@@ -7,7 +7,7 @@ use std::collections::HashMap;
 
 pub(crate) const L2_TO_L1_MSG_HEADER_SIZE: usize = 3;
 pub(crate) const L1_TO_L2_MSG_HEADER_SIZE: usize = 5;
-pub(crate) const DEPLOYMENT_INFO_SIZE: usize = 2;
+pub(crate) const CLASS_UPDATE_SIZE: usize = 1;
 pub(crate) const CONSUMED_MSG_TO_L2_N_TOPICS: usize = 3;
 pub(crate) const LOG_MSG_TO_L1_N_TOPICS: usize = 2;
 pub(crate) const N_DEFAULT_TOPICS: usize = 1; // Events have one default topic.
@@ -58,6 +58,11 @@ pub const DECLARE_VERSION: u64 = 2;
 pub const TRANSACTION_VERSION: u64 = 1;
 
 lazy_static! {
+    /// Added to the version of a transaction to get its query version, 2**128. Transactions
+    /// with a query version can only be simulated.
+    pub static ref QUERY_VERSION_BASE: Felt252 =
+        felt_str!("340282366920938463463374607431768211456");
+
     /// Value generated from `get_selector_from_name('constructor')`.
     pub static ref CONSTRUCTOR_ENTRY_POINT_SELECTOR: Felt252 =
         felt_str!("1159040026212278395030414237414753050475174923702621880048416706425641521556");
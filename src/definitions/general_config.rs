@@ -1,6 +1,7 @@
 use crate::{business_logic::state::state_api_objects::BlockInfo, utils::Address};
 use felt::Felt252;
 use getset::{CopyGetters, Getters, MutGetters};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use super::constants::{
@@ -9,7 +10,7 @@ use super::constants::{
     DEFAULT_SEQUENCER_ADDRESS, DEFAULT_STARKNET_OS_CONFIG, DEFAULT_VALIDATE_MAX_N_STEPS,
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub enum StarknetChainId {
     MainNet,
     TestNet,
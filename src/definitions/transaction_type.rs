@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Deserialize, Serialize)]
 pub enum TransactionType {
     Declare,
     Deploy,
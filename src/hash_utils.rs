@@ -2,7 +2,7 @@ use crate::{core::errors::syscall_handler_errors::SyscallHandlerError, utils::Ad
 use felt::Felt252;
 use num_integer::Integer;
 use num_traits::Pow;
-use starknet_crypto::{pedersen_hash, FieldElement};
+use starknet_crypto::{pedersen_hash, poseidon_hash_many, FieldElement};
 use std::vec;
 
 pub fn calculate_contract_address(
@@ -49,6 +49,23 @@ pub(crate) fn compute_hash_on_elements(vec: &[Felt252]) -> Result<Felt252, Sysca
     Ok(result)
 }
 
+/// Poseidon hash of a sequence of elements, the hash used by Cairo 1 class hashes. Unlike
+/// [`compute_hash_on_elements`], the length isn't hashed along with the elements.
+pub(crate) fn compute_poseidon_hash_on_elements(
+    vec: &[Felt252],
+) -> Result<Felt252, SyscallHandlerError> {
+    let felt_vec = vec
+        .iter()
+        .map(|num| {
+            FieldElement::from_dec_str(&num.to_str_radix(10))
+                .map_err(|_| SyscallHandlerError::FailToComputeHash)
+        })
+        .collect::<Result<Vec<FieldElement>, SyscallHandlerError>>()?;
+
+    let felt_result = poseidon_hash_many(&felt_vec);
+    Ok(Felt252::from_bytes_be(&felt_result.to_bytes_be()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,6 +111,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compute_poseidon_hash_on_elements() {
+        assert_matches!(
+            compute_poseidon_hash_on_elements(&[1.into(), 2.into()]),
+            Ok(x) if x == felt_str!(
+                "1557996165160500454210437319447297236715335099509187222888255133199463084263"
+            )
+        );
+    }
+
     #[test]
     fn test_calculate_contract_address_from_hash() {
         let result_1 = calculate_contract_address(
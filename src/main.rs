@@ -15,7 +15,10 @@ use starknet_rs::{
             cached_state::CachedState,
             state_api::{State, StateReader},
         },
-        transaction::objects::internal_invoke_function::InternalInvokeFunction,
+        transaction::{
+            account_transaction::AccountTransaction,
+            objects::internal_invoke_function::InternalInvokeFunction,
+        },
     },
     core::{
         contract_address::starknet_contract_address::compute_class_hash,
@@ -193,6 +196,7 @@ fn invoke_parser(
         contract_address.clone(),
         entrypoint_selector.clone(),
         0,
+        TRANSACTION_VERSION.into(),
         calldata.clone(),
         vec![],
         Felt252::zero(),
@@ -251,11 +255,21 @@ fn call_parser(
         None,
         None,
     );
+    let general_config = StarknetGeneralConfig::default();
+    let tx_execution_context = TransactionExecutionContext::new(
+        Address(0.into()),
+        0.into(),
+        Vec::new(),
+        0,
+        0.into(),
+        general_config.invoke_tx_max_n_steps(),
+        0,
+    );
     let call_info = execution_entry_point.execute(
         cached_state,
-        &StarknetGeneralConfig::default(),
+        &general_config,
         &mut ExecutionResourcesManager::default(),
-        &TransactionExecutionContext::default(),
+        &tx_execution_context,
     )?;
     Ok(call_info.retdata)
 }
@@ -9,9 +9,9 @@ use cairo_rs::{
     utils::is_subsequence,
 };
 use getset::Getters;
-use serde::Deserialize;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
 use starknet_contract_class::ParsedContractClass;
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 const SUPPORTED_BUILTINS: [BuiltinName; 5] = [
     BuiltinName::pedersen,
@@ -111,6 +111,65 @@ impl TryFrom<&PathBuf> for ContractClass {
     }
 }
 
+// -------------------------------
+//   Contract Class with source
+// -------------------------------
+
+/// A contract class along with the JSON it was loaded from, if it was.
+///
+/// `Program` can't be serialized back, so that JSON is what gets written wherever the class has
+/// to be sent or stored. Only what does that keeps it, plain [`ContractClass`]es don't.
+/// Serializes as the JSON, which fails for classes built in memory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContractClassWithSource {
+    pub contract_class: ContractClass,
+    pub source: Option<Arc<str>>,
+}
+
+impl From<ContractClass> for ContractClassWithSource {
+    fn from(contract_class: ContractClass) -> Self {
+        Self {
+            contract_class,
+            source: None,
+        }
+    }
+}
+
+impl TryFrom<&str> for ContractClassWithSource {
+    type Error = ProgramError;
+
+    fn try_from(s: &str) -> Result<Self, ProgramError> {
+        Ok(Self {
+            contract_class: ContractClass::try_from(s)?,
+            source: Some(Arc::from(s)),
+        })
+    }
+}
+
+impl TryFrom<&PathBuf> for ContractClassWithSource {
+    type Error = ProgramError;
+
+    fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
+        Self::try_from(std::fs::read_to_string(path)?.as_str())
+    }
+}
+
+impl Serialize for ContractClassWithSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let source = self.source.as_deref().ok_or_else(|| {
+            ser::Error::custom("the contract class wasn't loaded from JSON, it can't be serialized")
+        })?;
+        serializer.serialize_str(source)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContractClassWithSource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        Self::try_from(source.as_str()).map_err(de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
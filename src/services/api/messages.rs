@@ -1,10 +1,12 @@
 use crate::utils::Address;
 use felt::Felt252;
+use getset::Getters;
 use num_traits::ToPrimitive;
 use sha3::{Digest, Keccak256};
 
 /// A StarkNet Message from L2 to L1.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Getters, PartialEq, Eq)]
+#[getset(get = "pub")]
 pub struct StarknetMessageToL1 {
     from_address: Address,
     to_address: Address,
@@ -1,3 +1,4 @@
 pub mod contract_class;
 pub mod contract_class_errors;
 pub mod messages;
+pub mod sierra_contract_class;
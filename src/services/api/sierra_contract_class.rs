@@ -0,0 +1,350 @@
+use felt::Felt252;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// -------------------------------
+//      Sierra Contract Class
+// -------------------------------
+
+/// The entry points of a Cairo 1 class, by type. Sierra and CASM classes only differ in what an
+/// entry point points to.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct EntryPointsByType<E> {
+    pub external: Vec<E>,
+    pub l1_handler: Vec<E>,
+    pub constructor: Vec<E>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SierraEntryPoint {
+    #[serde(with = "felt_hex")]
+    pub selector: Felt252,
+    pub function_idx: usize,
+}
+
+/// A Cairo 1 contract class, as it's declared: the Sierra program of the contract.
+///
+/// This is the class the class hash is computed from, the class that is executed is its
+/// compiled (CASM) version, [`CasmContractClass`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SierraContractClass {
+    #[serde(with = "felt_hex::vec")]
+    pub sierra_program: Vec<Felt252>,
+    pub contract_class_version: String,
+    pub entry_points_by_type: EntryPointsByType<SierraEntryPoint>,
+    /// The ABI serialized without whitespace, its keys in the order the compiler wrote them,
+    /// which is the string the class hash is computed from. Compilers output the ABI as a JSON
+    /// array, declarations sent to the gateway carry it already serialized.
+    #[serde(default, deserialize_with = "abi_json::deserialize")]
+    pub abi: String,
+}
+
+// -------------------------------
+//       CASM Contract Class
+// -------------------------------
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CasmEntryPoint {
+    #[serde(with = "felt_hex")]
+    pub selector: Felt252,
+    pub offset: usize,
+    pub builtins: Vec<String>,
+}
+
+/// The compiled version of a [`SierraContractClass`], in Cairo assembly.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CasmContractClass {
+    pub compiler_version: String,
+    #[serde(with = "felt_hex::vec")]
+    pub bytecode: Vec<Felt252>,
+    /// The hints of the program, kept as they come from the compiler.
+    #[serde(default)]
+    pub hints: serde_json::Value,
+    pub entry_points_by_type: EntryPointsByType<CasmEntryPoint>,
+}
+
+// -------------------
+//  TryFrom traits
+// -------------------
+
+impl TryFrom<&str> for SierraContractClass {
+    type Error = serde_json::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+impl TryFrom<&PathBuf> for SierraContractClass {
+    type Error = std::io::Error;
+
+    fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
+        Ok(Self::try_from(std::fs::read_to_string(path)?.as_str())?)
+    }
+}
+
+impl TryFrom<&str> for CasmContractClass {
+    type Error = serde_json::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+impl TryFrom<&PathBuf> for CasmContractClass {
+    type Error = std::io::Error;
+
+    fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
+        Ok(Self::try_from(std::fs::read_to_string(path)?.as_str())?)
+    }
+}
+
+/// Felts as `0x`-prefixed hex strings, the way Cairo 1 classes encode them.
+mod felt_hex {
+    use crate::utils::{decode_felt, encode_felt};
+    use felt::Felt252;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        felt: &Felt252,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_felt(felt))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Felt252, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        parse(&hex).map_err(de::Error::custom)
+    }
+
+    fn parse(hex: &str) -> Result<Felt252, String> {
+        if !hex.starts_with("0x") {
+            return Err(format!("expected a 0x-prefixed hex string, got {hex:?}"));
+        }
+        decode_felt(hex)
+    }
+
+    pub(super) mod vec {
+        use super::*;
+        use serde::ser::SerializeSeq;
+
+        pub(in super::super) fn serialize<S: Serializer>(
+            felts: &[Felt252],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(felts.len()))?;
+            for felt in felts {
+                seq.serialize_element(&encode_felt(felt))?;
+            }
+            seq.end()
+        }
+
+        pub(in super::super) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Felt252>, D::Error> {
+            Vec::<String>::deserialize(deserializer)?
+                .iter()
+                .map(|hex| parse(hex).map_err(de::Error::custom))
+                .collect()
+        }
+    }
+}
+
+/// The ABI of a Sierra class, from either its JSON or a string holding it.
+///
+/// `serde_json::Value` sorts the keys of objects, so the JSON is serialized as it's read.
+mod abi_json {
+    use serde::{
+        de::{self, MapAccess, SeqAccess, Visitor},
+        Deserialize, Deserializer,
+    };
+    use std::fmt;
+
+    /// The key of the map `serde_json` reads numbers as with its `arbitrary_precision` feature.
+    const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<String, D::Error> {
+        deserializer.deserialize_any(AbiVisitor)
+    }
+
+    struct AbiVisitor;
+
+    impl<'de> Visitor<'de> for AbiVisitor {
+        type Value = String;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a JSON ABI or a string holding it")
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<String, E> {
+            Ok(String::new())
+        }
+
+        fn visit_str<E: de::Error>(self, abi: &str) -> Result<String, E> {
+            Ok(abi.to_string())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<String, A::Error> {
+            CompactJsonVisitor.visit_seq(seq).map(|json| json.0)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<String, A::Error> {
+            CompactJsonVisitor.visit_map(map).map(|json| json.0)
+        }
+    }
+
+    /// A JSON value serialized without whitespace, keeping the order of its keys.
+    struct CompactJson(String);
+
+    impl<'de> Deserialize<'de> for CompactJson {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(CompactJsonVisitor)
+        }
+    }
+
+    struct CompactJsonVisitor;
+
+    impl CompactJsonVisitor {
+        fn serialize<T: serde::Serialize + ?Sized, E: de::Error>(
+            value: &T,
+        ) -> Result<CompactJson, E> {
+            serde_json::to_string(value)
+                .map(CompactJson)
+                .map_err(E::custom)
+        }
+    }
+
+    impl<'de> Visitor<'de> for CompactJsonVisitor {
+        type Value = CompactJson;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a JSON value")
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<CompactJson, E> {
+            Ok(CompactJson("null".to_string()))
+        }
+
+        fn visit_bool<E: de::Error>(self, value: bool) -> Result<CompactJson, E> {
+            Self::serialize(&value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<CompactJson, E> {
+            Self::serialize(&value)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<CompactJson, E> {
+            Self::serialize(&value)
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<CompactJson, E> {
+            Self::serialize(&value)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<CompactJson, E> {
+            Self::serialize(value)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<CompactJson, A::Error> {
+            let mut elements = Vec::new();
+            while let Some(CompactJson(element)) = seq.next_element()? {
+                elements.push(element);
+            }
+            Ok(CompactJson(format!("[{}]", elements.join(","))))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<CompactJson, A::Error> {
+            let mut entries = Vec::new();
+            while let Some(key) = map.next_key::<String>()? {
+                if key == NUMBER_TOKEN {
+                    return Ok(CompactJson(map.next_value::<String>()?));
+                }
+                let CompactJson(value) = map.next_value()?;
+                entries.push(format!(
+                    "{}:{value}",
+                    Self::serialize::<_, A::Error>(&key)?.0
+                ));
+            }
+            Ok(CompactJson(format!("{{{}}}", entries.join(","))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coverage_helper::test;
+    use felt::felt_str;
+
+    #[test]
+    fn deserialize_sierra_and_casm_classes() {
+        let sierra_class = SierraContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.sierra",
+        ))
+        .unwrap();
+        let casm_class = CasmContractClass::try_from(&PathBuf::from(
+            "starknet_programs/cairo1/minimal_contract.casm",
+        ))
+        .unwrap();
+
+        let selector = felt_str!(
+            "1fc3f77ebc090777f567969ad9823cf6334ab888acb385ca72668ec5adbde80",
+            16
+        );
+        assert_eq!(sierra_class.contract_class_version, "0.1.0");
+        assert_eq!(
+            sierra_class.entry_points_by_type.external,
+            vec![SierraEntryPoint {
+                selector: selector.clone(),
+                function_idx: 0
+            }]
+        );
+        assert!(sierra_class.entry_points_by_type.constructor.is_empty());
+        assert_eq!(
+            sierra_class.sierra_program[..2],
+            [Felt252::new(1), Felt252::new(5)]
+        );
+
+        assert_eq!(
+            sierra_class.abi,
+            r#"[{"type":"function","name":"empty","inputs":[],"outputs":[],"state_mutability":"external"},{"type":"event","name":"cairo_level_tests::contracts::minimal_contract::minimal_contract::Event","kind":"enum","variants":[]}]"#
+        );
+
+        assert_eq!(casm_class.bytecode.len(), 89);
+        assert_eq!(
+            casm_class.entry_points_by_type.external,
+            vec![CasmEntryPoint {
+                selector,
+                offset: 0,
+                builtins: vec!["range_check".to_string()]
+            }]
+        );
+
+        // Round trip through the JSON encoding.
+        let serialized = serde_json::to_string(&sierra_class).unwrap();
+        assert_eq!(
+            SierraContractClass::try_from(serialized.as_str()).unwrap(),
+            sierra_class
+        );
+        let serialized = serde_json::to_string(&casm_class).unwrap();
+        assert_eq!(
+            CasmContractClass::try_from(serialized.as_str()).unwrap(),
+            casm_class
+        );
+    }
+
+    #[test]
+    fn felts_must_be_hex_strings() {
+        let sierra_class = r#"{
+            "sierra_program": ["12"],
+            "contract_class_version": "0.1.0",
+            "entry_points_by_type": {"EXTERNAL": [], "L1_HANDLER": [], "CONSTRUCTOR": []}
+        }"#;
+        assert!(SierraContractClass::try_from(sierra_class).is_err());
+    }
+}
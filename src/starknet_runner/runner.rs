@@ -1,6 +1,6 @@
 use super::starknet_runner_error::StarknetRunnerError;
 use crate::{
-    business_logic::transaction::error::TransactionError,
+    business_logic::{execution::objects::RunResources, transaction::error::TransactionError},
     core::syscalls::syscall_handler::{
         SyscallHandler, SyscallHandlerPostRun, SyscallHintProcessor,
     },
@@ -8,10 +8,15 @@ use crate::{
 use cairo_rs::{
     types::relocatable::{MaybeRelocatable, Relocatable},
     vm::{
+        errors::{
+            cairo_run_errors::CairoRunError, vm_errors::VirtualMachineError,
+            vm_exception::VmException,
+        },
         runners::{
             builtin_runner::BuiltinRunner,
             cairo_runner::{CairoArg, CairoRunner, ExecutionResources},
         },
+        security::verify_secure_runner,
         vm_core::VirtualMachine,
     },
 };
@@ -58,21 +63,57 @@ where
         }
     }
 
+    /// Runs the entry point at `entrypoint`, taking the steps it runs from `run_resources`.
+    ///
+    /// The calls the entry point makes take their steps from `run_resources` too, so the entry
+    /// point can only run the steps they left. Fails with [`TransactionError::OutOfResources`]
+    /// once there are no steps left.
     pub fn run_from_entrypoint(
         &mut self,
         entrypoint: usize,
         args: &[&CairoArg],
+        run_resources: &RunResources,
     ) -> Result<(), TransactionError> {
         let verify_secure = true;
-        let args: Vec<&CairoArg> = args.iter().map(ToOwned::to_owned).collect();
-
-        self.cairo_runner.run_from_entrypoint(
+        let stack = args
+            .iter()
+            .map(|arg| self.vm.segments.gen_cairo_arg(arg))
+            .collect::<Result<Vec<MaybeRelocatable>, _>>()?;
+        let return_fp = MaybeRelocatable::from(0);
+        self.cairo_runner.initialize_function_entrypoint(
+            &mut self.vm,
             entrypoint,
-            &args,
-            verify_secure,
+            stack,
+            return_fp,
+        )?;
+        self.cairo_runner.initialize_vm(&mut self.vm)?;
+
+        // Running for one step more than there are left tells running out of steps apart from
+        // ending on the last one.
+        let max_steps = run_resources.n_steps().saturating_add(1);
+        let n_steps = match self.cairo_runner.run_for_steps(
+            max_steps as usize,
             &mut self.vm,
             &mut self.hint_processor,
-        )?;
+        ) {
+            Ok(()) => max_steps,
+            Err(VirtualMachineError::EndOfProgram(steps_left)) => max_steps - steps_left as u64,
+            Err(err) => {
+                return Err(CairoRunError::VmException(VmException::from_vm_error(
+                    &self.cairo_runner,
+                    &self.vm,
+                    err,
+                ))
+                .into())
+            }
+        };
+        run_resources.consume(n_steps)?;
+
+        self.cairo_runner
+            .end_run(true, false, &mut self.vm, &mut self.hint_processor)?;
+        if verify_secure {
+            verify_secure_runner(&self.cairo_runner, false, &mut self.vm)?;
+        }
         Ok(())
     }
 
@@ -89,6 +130,16 @@ where
         })
     }
 
+    /// The resources used by a run that failed before ending, up to where it failed.
+    pub(crate) fn get_failed_execution_resources(
+        &mut self,
+    ) -> Result<ExecutionResources, TransactionError> {
+        // Ending the run computes the sizes of the segments the builtins and memory holes are
+        // counted from.
+        self.vm.segments.compute_effective_sizes();
+        self.get_execution_resources()
+    }
+
     pub fn get_return_values(&self) -> Result<Vec<Felt252>, StarknetRunnerError> {
         let ret_data = self.vm.get_return_values(2)?;
 
@@ -237,6 +288,7 @@ mod test {
     use super::StarknetRunner;
     use crate::{
         business_logic::{
+            execution::objects::RunResources,
             fact_state::in_memory_state_reader::InMemoryStateReader,
             state::cached_state::CachedState, transaction::error::TransactionError,
         },
@@ -283,7 +335,9 @@ mod test {
             SyscallHintProcessor::new(BusinessLogicSyscallHandler::default_with(&mut state));
 
         let mut runner = StarknetRunner::new(cairo_runner, vm, hint_processor);
-        assert!(runner.run_from_entrypoint(1, &[]).is_err())
+        assert!(runner
+            .run_from_entrypoint(1, &[], &RunResources::new(1000))
+            .is_err())
     }
 
     #[test]
@@ -1,8 +1,9 @@
-use super::{errors::storage_errors::StorageError, storage::Storage};
+use super::{
+    errors::storage_errors::StorageError,
+    storage::{Storage, StorageKey},
+};
 use std::collections::HashMap;
 
-pub type StorageKey = (Prefix, ClassHash);
-
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct DictStorage {
     storage: HashMap<StorageKey, Vec<u8>>,
@@ -16,15 +17,6 @@ impl DictStorage {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Copy)]
-pub enum Prefix {
-    Int,
-    Float,
-    Str,
-    ContractState,
-    ContractClass,
-}
-
 impl Storage for DictStorage {
     fn set_value(&mut self, key: &StorageKey, value: Vec<u8>) -> Result<(), StorageError> {
         self.storage.insert(*key, value);
@@ -42,7 +34,7 @@ impl Storage for DictStorage {
 
 #[cfg(test)]
 mod test {
-    use crate::utils::test_utils::storage_key;
+    use crate::{starknet_storage::storage::Prefix, utils::test_utils::storage_key};
     use coverage_helper::test;
 
     use super::*;
@@ -134,4 +126,24 @@ mod test {
             Err(StorageError::RemoveMissingKey)
         );
     }
+
+    #[test]
+    fn typed_values_under_the_same_key_dont_collide() {
+        let mut storage = DictStorage::new();
+
+        let key = storage_key!("0000000000000000000000000000000000000000000000000000000000000000");
+
+        storage
+            .write_batch(vec![
+                ((Prefix::Int, key), 7_i32.to_be_bytes().to_vec()),
+                ((Prefix::Bytes, key), vec![1, 2, 3]),
+            ])
+            .unwrap();
+        storage.set_str(&key, "value").unwrap();
+
+        assert_eq!(storage.get_int(&key), Ok(7));
+        assert_eq!(storage.get_bytes(&key), Ok(vec![1, 2, 3]));
+        assert_eq!(storage.delete_str(&key), Ok(String::from("value")));
+        assert_eq!(storage.get_str(&key), Err(StorageError::ErrorFetchingData));
+    }
 }
@@ -12,6 +12,12 @@ pub enum StorageError {
     RemoveMissingKey,
     #[error("Serde error: {0}")]
     SerdeError(String),
+    #[error("IO error: {0}")]
+    IoError(String),
+    #[error("Contract class {0} has no JSON representation and can't be persisted")]
+    UnserializableContractClass(String),
+    #[error("The state has no directory to be persisted to")]
+    MissingStateDirectory,
 }
 
 impl From<serde_json::Error> for StorageError {
@@ -20,6 +26,12 @@ impl From<serde_json::Error> for StorageError {
     }
 }
 
+impl From<std::io::Error> for StorageError {
+    fn from(error: std::io::Error) -> Self {
+        StorageError::IoError(error.to_string())
+    }
+}
+
 #[test]
 fn test_from_serde_json_error_for_storage_error() {
     let bugged_json: Result<starknet_api::state::ContractClass, serde_json::Error> =
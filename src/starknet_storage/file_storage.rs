@@ -0,0 +1,151 @@
+use super::{
+    errors::storage_errors::StorageError,
+    storage::{Prefix, Storage, StorageKey},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Deserialize, Serialize)]
+struct StoredValue {
+    prefix: Prefix,
+    key: String,
+    value: String,
+}
+
+/// A [`Storage`] kept in memory and written through to a JSON file on every change.
+///
+/// Batches are written to the file once, after every value has been set.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FileStorage {
+    path: PathBuf,
+    storage: HashMap<StorageKey, Vec<u8>>,
+}
+
+impl FileStorage {
+    /// Opens the storage kept at `path`, starting with an empty one if the file doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
+        let path = path.as_ref().to_path_buf();
+        let mut storage = HashMap::new();
+
+        if path.exists() {
+            let stored_values: Vec<StoredValue> = serde_json::from_slice(&fs::read(&path)?)?;
+            for StoredValue { prefix, key, value } in stored_values {
+                let key = hex::decode(key)
+                    .map_err(|error| StorageError::SerdeError(error.to_string()))?
+                    .try_into()
+                    .map_err(|_| StorageError::IncorrectDataSize)?;
+                let value = hex::decode(value)
+                    .map_err(|error| StorageError::SerdeError(error.to_string()))?;
+                storage.insert((prefix, key), value);
+            }
+        }
+
+        Ok(Self { path, storage })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        let mut stored_values: Vec<_> = self
+            .storage
+            .iter()
+            .map(|((prefix, key), value)| StoredValue {
+                prefix: *prefix,
+                key: hex::encode(key),
+                value: hex::encode(value),
+            })
+            .collect();
+        stored_values.sort_by(|a, b| a.key.cmp(&b.key));
+
+        // Write to a temporary file first so a crash never leaves a truncated storage behind.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&stored_values)?)?;
+        fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn set_value(&mut self, key: &StorageKey, value: Vec<u8>) -> Result<(), StorageError> {
+        self.storage.insert(*key, value);
+        self.flush()
+    }
+
+    fn get_value(&self, key: &StorageKey) -> Option<Vec<u8>> {
+        self.storage.get(key).cloned()
+    }
+
+    fn delete_value(&mut self, key: &StorageKey) -> Result<Vec<u8>, StorageError> {
+        let value = self
+            .storage
+            .remove(key)
+            .ok_or(StorageError::RemoveMissingKey)?;
+        self.flush()?;
+        Ok(value)
+    }
+
+    fn write_batch(&mut self, batch: Vec<(StorageKey, Vec<u8>)>) -> Result<(), StorageError> {
+        self.storage.extend(batch);
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::storage_key;
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+
+    fn temp_storage_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "starknet_rs_file_storage_{name}_{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn values_survive_reopening() {
+        let path = temp_storage_path("values_survive_reopening");
+        let ikey = storage_key!("0000000000000000000000000000000000000000000000000000000000000000");
+        let skey = storage_key!("0000000000000000000000000000000000000000000000000000000000000001");
+
+        let mut storage = FileStorage::open(&path).unwrap();
+        storage.set_int(&ikey, 12).unwrap();
+        storage
+            .write_batch(vec![
+                ((Prefix::Str, skey), b"value".to_vec()),
+                ((Prefix::Bytes, skey), vec![0, 1]),
+            ])
+            .unwrap();
+        storage.delete_bytes(&skey).unwrap();
+
+        let storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.get_int(&ikey), Ok(12));
+        assert_eq!(storage.get_str(&skey), Ok(String::from("value")));
+        assert_eq!(
+            storage.get_bytes(&skey),
+            Err(StorageError::ErrorFetchingData)
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn open_corrupted_file() {
+        let path = temp_storage_path("open_corrupted_file");
+        fs::write(&path, "{").unwrap();
+
+        assert_matches!(FileStorage::open(&path), Err(StorageError::SerdeError(_)));
+
+        fs::remove_file(path).unwrap();
+    }
+}
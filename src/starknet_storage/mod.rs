@@ -1 +1,4 @@
+pub mod dict_storage;
 pub mod errors;
+pub mod file_storage;
+pub mod storage;
@@ -0,0 +1,225 @@
+use super::errors::storage_errors::StorageError;
+use crate::{
+    business_logic::fact_state::contract_state::ContractState,
+    services::api::{contract_class::ContractClass, sierra_contract_class::CasmContractClass},
+    utils::{ClassHash, CompiledClassHash},
+};
+use serde::{Deserialize, Serialize};
+
+pub type StorageKey = (Prefix, ClassHash);
+
+/// Namespaces of the values kept in a [`Storage`], so that typed values stored under the same
+/// key don't overwrite each other.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Prefix {
+    Int,
+    Float,
+    Str,
+    Bytes,
+    ContractState,
+    ContractClass,
+    CompiledClassHash,
+    CompiledClass,
+}
+
+/// A key-value store of raw bytes, with typed accessors built on top of it.
+///
+/// Implementors only need to provide `set_value`, `get_value` and `delete_value`.
+pub trait Storage {
+    fn set_value(&mut self, key: &StorageKey, value: Vec<u8>) -> Result<(), StorageError>;
+    fn get_value(&self, key: &StorageKey) -> Option<Vec<u8>>;
+    fn delete_value(&mut self, key: &StorageKey) -> Result<Vec<u8>, StorageError>;
+
+    /// Sets every value of `batch`. Backends where each write is expensive should override it
+    /// to store the whole batch at once.
+    fn write_batch(&mut self, batch: Vec<(StorageKey, Vec<u8>)>) -> Result<(), StorageError> {
+        for (key, value) in batch {
+            self.set_value(&key, value)?;
+        }
+        Ok(())
+    }
+
+    // ---------------
+    //  Integers
+    // ---------------
+
+    fn set_int(&mut self, key: &ClassHash, value: i32) -> Result<(), StorageError> {
+        self.set_value(&(Prefix::Int, *key), value.to_be_bytes().to_vec())
+    }
+
+    fn get_int(&self, key: &ClassHash) -> Result<i32, StorageError> {
+        let value = self
+            .get_value(&(Prefix::Int, *key))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        Ok(i32::from_be_bytes(to_array(&value)?))
+    }
+
+    fn get_int_or_default(&self, key: &ClassHash, default: i32) -> Result<i32, StorageError> {
+        match self.get_int(key) {
+            Err(StorageError::ErrorFetchingData) => Ok(default),
+            result => result,
+        }
+    }
+
+    fn delete_int(&mut self, key: &ClassHash) -> Result<i32, StorageError> {
+        let value = self.delete_value(&(Prefix::Int, *key))?;
+        Ok(i32::from_be_bytes(to_array(&value)?))
+    }
+
+    // ---------------
+    //  Floats
+    // ---------------
+
+    fn set_float(&mut self, key: &ClassHash, value: f64) -> Result<(), StorageError> {
+        self.set_value(&(Prefix::Float, *key), value.to_be_bytes().to_vec())
+    }
+
+    fn get_float(&self, key: &ClassHash) -> Result<f64, StorageError> {
+        let value = self
+            .get_value(&(Prefix::Float, *key))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        Ok(f64::from_be_bytes(to_array(&value)?))
+    }
+
+    fn get_float_or_default(&self, key: &ClassHash, default: f64) -> Result<f64, StorageError> {
+        match self.get_float(key) {
+            Err(StorageError::ErrorFetchingData) => Ok(default),
+            result => result,
+        }
+    }
+
+    fn delete_float(&mut self, key: &ClassHash) -> Result<f64, StorageError> {
+        let value = self.delete_value(&(Prefix::Float, *key))?;
+        Ok(f64::from_be_bytes(to_array(&value)?))
+    }
+
+    // ---------------
+    //  Strings
+    // ---------------
+
+    fn set_str(&mut self, key: &ClassHash, value: &str) -> Result<(), StorageError> {
+        self.set_value(&(Prefix::Str, *key), value.as_bytes().to_vec())
+    }
+
+    fn get_str(&self, key: &ClassHash) -> Result<String, StorageError> {
+        let value = self
+            .get_value(&(Prefix::Str, *key))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        String::from_utf8(value).map_err(|_| StorageError::IncorrectUtf8Enconding)
+    }
+
+    fn delete_str(&mut self, key: &ClassHash) -> Result<String, StorageError> {
+        let value = self.delete_value(&(Prefix::Str, *key))?;
+        String::from_utf8(value).map_err(|_| StorageError::IncorrectUtf8Enconding)
+    }
+
+    // ---------------
+    //  Bytes
+    // ---------------
+
+    fn set_bytes(&mut self, key: &ClassHash, value: &[u8]) -> Result<(), StorageError> {
+        self.set_value(&(Prefix::Bytes, *key), value.to_vec())
+    }
+
+    fn get_bytes(&self, key: &ClassHash) -> Result<Vec<u8>, StorageError> {
+        self.get_value(&(Prefix::Bytes, *key))
+            .ok_or(StorageError::ErrorFetchingData)
+    }
+
+    fn delete_bytes(&mut self, key: &ClassHash) -> Result<Vec<u8>, StorageError> {
+        self.delete_value(&(Prefix::Bytes, *key))
+    }
+
+    // ---------------
+    //  State objects
+    // ---------------
+
+    fn set_contract_state(
+        &mut self,
+        address: &ClassHash,
+        contract_state: &ContractState,
+    ) -> Result<(), StorageError> {
+        self.set_value(
+            &(Prefix::ContractState, *address),
+            serde_json::to_vec(contract_state)?,
+        )
+    }
+
+    fn get_contract_state(&self, address: &ClassHash) -> Result<ContractState, StorageError> {
+        let value = self
+            .get_value(&(Prefix::ContractState, *address))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        Ok(serde_json::from_slice(&value)?)
+    }
+
+    /// Stores a contract class as the JSON it was loaded from, since `Program` can't be
+    /// serialized back.
+    fn set_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+        source: &str,
+    ) -> Result<(), StorageError> {
+        self.set_value(
+            &(Prefix::ContractClass, *class_hash),
+            source.as_bytes().to_vec(),
+        )
+    }
+
+    fn get_contract_class(&self, class_hash: &ClassHash) -> Result<ContractClass, StorageError> {
+        let value = self
+            .get_value(&(Prefix::ContractClass, *class_hash))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        let source_json =
+            std::str::from_utf8(&value).map_err(|_| StorageError::IncorrectUtf8Enconding)?;
+        ContractClass::try_from(source_json)
+            .map_err(|error| StorageError::SerdeError(error.to_string()))
+    }
+
+    fn set_compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<(), StorageError> {
+        self.set_value(
+            &(Prefix::CompiledClassHash, *class_hash),
+            compiled_class_hash.to_vec(),
+        )
+    }
+
+    fn get_compiled_class_hash(
+        &self,
+        class_hash: &ClassHash,
+    ) -> Result<CompiledClassHash, StorageError> {
+        let value = self
+            .get_value(&(Prefix::CompiledClassHash, *class_hash))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        to_array(&value)
+    }
+
+    fn set_compiled_class(
+        &mut self,
+        compiled_class_hash: &CompiledClassHash,
+        casm_class: &CasmContractClass,
+    ) -> Result<(), StorageError> {
+        self.set_value(
+            &(Prefix::CompiledClass, *compiled_class_hash),
+            serde_json::to_vec(casm_class)?,
+        )
+    }
+
+    fn get_compiled_class(
+        &self,
+        compiled_class_hash: &CompiledClassHash,
+    ) -> Result<CasmContractClass, StorageError> {
+        let value = self
+            .get_value(&(Prefix::CompiledClass, *compiled_class_hash))
+            .ok_or(StorageError::ErrorFetchingData)?;
+        Ok(serde_json::from_slice(&value)?)
+    }
+}
+
+fn to_array<const N: usize>(value: &[u8]) -> Result<[u8; N], StorageError> {
+    value
+        .try_into()
+        .map_err(|_| StorageError::IncorrectDataSize)
+}
@@ -1 +1,2 @@
+pub mod patricia_tree;
 pub mod starkware_errors;
@@ -0,0 +1,381 @@
+use super::starkware_errors::StarkwareError;
+use felt::Felt252;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use starknet_crypto::{pedersen_hash, FieldElement};
+use std::collections::BTreeMap;
+
+// ---------------------------------
+//  Binary Merkle-Patricia tree
+// ---------------------------------
+
+/// A node of an inclusion proof, as seen from the root down to the leaf.
+///
+/// Binary nodes hold the hashes of both children, edge nodes the hash of their only child along
+/// with the path (the key bits skipped by the edge) and its length.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProofNode {
+    Binary {
+        left: Felt252,
+        right: Felt252,
+    },
+    Edge {
+        child: Felt252,
+        path: Felt252,
+        length: u64,
+    },
+}
+
+impl ProofNode {
+    pub fn hash(&self) -> Result<Felt252, StarkwareError> {
+        match self {
+            ProofNode::Binary { left, right } => pedersen(left, right),
+            ProofNode::Edge {
+                child,
+                path,
+                length,
+            } => edge_hash(child, path, *length),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Node {
+    Leaf(Felt252),
+    Binary {
+        left: Box<Node>,
+        right: Box<Node>,
+        hash: Felt252,
+    },
+    Edge {
+        child: Box<Node>,
+        path: BigUint,
+        length: u64,
+        hash: Felt252,
+    },
+}
+
+impl Node {
+    fn hash(&self) -> &Felt252 {
+        match self {
+            Node::Leaf(hash) | Node::Binary { hash, .. } | Node::Edge { hash, .. } => hash,
+        }
+    }
+}
+
+/// The binary Merkle-Patricia tree used for the Starknet commitments.
+///
+/// Leaves are indexed by `height`-bit keys and zero leaves are considered empty, so they are
+/// left out of the tree. Chains of nodes with a single non-empty child are compressed into edge
+/// nodes, whose hash is `H(child, path) + length`. Binary nodes hash to `H(left, right)` and the
+/// root of an empty tree is zero.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PatriciaTree {
+    height: u64,
+    root: Option<Node>,
+}
+
+impl PatriciaTree {
+    pub fn new<I>(height: u64, leaves: I) -> Result<Self, StarkwareError>
+    where
+        I: IntoIterator<Item = (Felt252, Felt252)>,
+    {
+        let mut sorted_leaves = BTreeMap::new();
+        for (key, value) in leaves {
+            let key = check_key(&key, height)?;
+            if value.is_zero() {
+                sorted_leaves.remove(&key);
+            } else {
+                sorted_leaves.insert(key, value);
+            }
+        }
+
+        let sorted_leaves: Vec<_> = sorted_leaves.into_iter().collect();
+        let root = match sorted_leaves.is_empty() {
+            true => None,
+            false => Some(build_node(&sorted_leaves, height)?),
+        };
+
+        Ok(Self { height, root })
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn root(&self) -> Felt252 {
+        self.root
+            .as_ref()
+            .map(|root| root.hash().clone())
+            .unwrap_or_else(Felt252::zero)
+    }
+
+    /// Returns the nodes on the path from the root to the leaf at `key`.
+    ///
+    /// If the leaf is empty the proof stops at the node where the path diverges from the key,
+    /// which proves that no value is stored there.
+    pub fn proof(&self, key: &Felt252) -> Result<Vec<ProofNode>, StarkwareError> {
+        let key = check_key(key, self.height)?;
+        let mut proof = Vec::new();
+        let mut height = self.height;
+        let mut node = self.root.as_ref();
+
+        while let Some(current) = node {
+            node = match current {
+                Node::Leaf(_) => None,
+                Node::Binary { left, right, .. } => {
+                    proof.push(ProofNode::Binary {
+                        left: left.hash().clone(),
+                        right: right.hash().clone(),
+                    });
+                    height -= 1;
+                    Some(if key.bit(height) { right } else { left })
+                }
+                Node::Edge {
+                    child,
+                    path,
+                    length,
+                    ..
+                } => {
+                    proof.push(ProofNode::Edge {
+                        child: child.hash().clone(),
+                        path: biguint_to_felt(path),
+                        length: *length,
+                    });
+                    height -= length;
+                    match &key_bits(&key, height, *length) == path {
+                        true => Some(child),
+                        false => None,
+                    }
+                }
+            };
+        }
+
+        Ok(proof)
+    }
+}
+
+/// Checks that `proof` proves `value` to be the leaf at `key` in the tree with the given `root`.
+/// Zero values are checked to be absent from the tree.
+pub fn verify_proof(
+    root: &Felt252,
+    height: u64,
+    key: &Felt252,
+    value: &Felt252,
+    proof: &[ProofNode],
+) -> Result<bool, StarkwareError> {
+    let key = check_key(key, height)?;
+    if proof.is_empty() {
+        return Ok(root.is_zero() && value.is_zero());
+    }
+
+    let mut expected_hash = root.clone();
+    let mut height = height;
+    for node in proof {
+        if node.hash()? != expected_hash {
+            return Ok(false);
+        }
+        expected_hash = match node {
+            ProofNode::Binary { left, right } => {
+                height = match height.checked_sub(1) {
+                    Some(height) => height,
+                    None => return Ok(false),
+                };
+                match key.bit(height) {
+                    true => right.clone(),
+                    false => left.clone(),
+                }
+            }
+            ProofNode::Edge {
+                child,
+                path,
+                length,
+            } => {
+                height = match height.checked_sub(*length) {
+                    Some(height) => height,
+                    None => return Ok(false),
+                };
+                if key_bits(&key, height, *length) != path.to_biguint() {
+                    // The key isn't in the tree.
+                    return Ok(value.is_zero());
+                }
+                child.clone()
+            }
+        };
+    }
+
+    Ok(height == 0 && &expected_hash == value)
+}
+
+fn build_node(leaves: &[(BigUint, Felt252)], height: u64) -> Result<Node, StarkwareError> {
+    if height == 0 {
+        // Keys are unique, so there's a single leaf left once every bit was used.
+        return Ok(Node::Leaf(leaves[0].1.clone()));
+    }
+
+    let bit = height - 1;
+    let (left, right) = leaves.split_at(leaves.partition_point(|(key, _)| !key.bit(bit)));
+
+    if !left.is_empty() && !right.is_empty() {
+        let left = build_node(left, bit)?;
+        let right = build_node(right, bit)?;
+        let hash = pedersen(left.hash(), right.hash())?;
+        return Ok(Node::Binary {
+            left: Box::new(left),
+            right: Box::new(right),
+            hash,
+        });
+    }
+
+    // A single non-empty child: extend its edge (or start a new one) by one bit.
+    let (child, path, length) = match build_node(if left.is_empty() { right } else { left }, bit)? {
+        Node::Edge {
+            child,
+            path,
+            length,
+            ..
+        } => (child, path, length),
+        child => (Box::new(child), BigUint::zero(), 0),
+    };
+    let path = match left.is_empty() {
+        true => path | (BigUint::one() << length),
+        false => path,
+    };
+    let length = length + 1;
+    let hash = edge_hash(child.hash(), &biguint_to_felt(&path), length)?;
+
+    Ok(Node::Edge {
+        child,
+        path,
+        length,
+        hash,
+    })
+}
+
+fn check_key(key: &Felt252, height: u64) -> Result<BigUint, StarkwareError> {
+    let key = key.to_biguint();
+    if key.bits() > height {
+        return Err(StarkwareError::PatriciaKeyOutOfRange(
+            key.to_str_radix(16),
+            height,
+        ));
+    }
+    Ok(key)
+}
+
+/// The `length` bits of `key` right above the lowest `height` bits.
+fn key_bits(key: &BigUint, height: u64, length: u64) -> BigUint {
+    (key >> height) & ((BigUint::one() << length) - BigUint::one())
+}
+
+fn biguint_to_felt(value: &BigUint) -> Felt252 {
+    Felt252::from_bytes_be(&value.to_bytes_be())
+}
+
+fn edge_hash(child: &Felt252, path: &Felt252, length: u64) -> Result<Felt252, StarkwareError> {
+    Ok(pedersen(child, path)? + Felt252::from(length))
+}
+
+pub(crate) fn pedersen(a: &Felt252, b: &Felt252) -> Result<Felt252, StarkwareError> {
+    let a = FieldElement::from_bytes_be(&a.to_be_bytes())
+        .map_err(|_| StarkwareError::FailToComputeHash)?;
+    let b = FieldElement::from_bytes_be(&b.to_be_bytes())
+        .map_err(|_| StarkwareError::FailToComputeHash)?;
+    Ok(Felt252::from_bytes_be(&pedersen_hash(&a, &b).to_bytes_be()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use coverage_helper::test;
+
+    fn leaves(values: &[(u64, u64)]) -> Vec<(Felt252, Felt252)> {
+        values
+            .iter()
+            .map(|(key, value)| (Felt252::from(*key), Felt252::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn empty_tree() {
+        let tree = PatriciaTree::new(251, leaves(&[(1, 0), (2, 0)])).unwrap();
+
+        assert_eq!(tree.root(), Felt252::zero());
+        assert_eq!(tree.proof(&1.into()), Ok(vec![]));
+        assert_eq!(
+            verify_proof(&tree.root(), 251, &1.into(), &Felt252::zero(), &[]),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn single_leaf_is_an_edge_from_the_root() {
+        let tree = PatriciaTree::new(3, leaves(&[(5, 7)])).unwrap();
+
+        assert_eq!(
+            tree.root(),
+            pedersen(&7.into(), &5.into()).unwrap() + Felt252::from(3)
+        );
+    }
+
+    #[test]
+    fn binary_and_edge_nodes() {
+        // Keys 0b00 and 0b11 split at the root, each child is an edge of length 1.
+        let tree = PatriciaTree::new(2, leaves(&[(0, 10), (3, 11)])).unwrap();
+
+        let left = pedersen(&10.into(), &0.into()).unwrap() + Felt252::from(1);
+        let right = pedersen(&11.into(), &1.into()).unwrap() + Felt252::from(1);
+        assert_eq!(tree.root(), pedersen(&left, &right).unwrap());
+    }
+
+    #[test]
+    fn leaf_order_doesnt_matter() {
+        let tree_a = PatriciaTree::new(251, leaves(&[(1, 2), (3, 4), (1000, 5)])).unwrap();
+        let tree_b = PatriciaTree::new(251, leaves(&[(1000, 5), (1, 2), (3, 4)])).unwrap();
+
+        assert_eq!(tree_a.root(), tree_b.root());
+        assert_ne!(tree_a.root(), Felt252::zero());
+    }
+
+    #[test]
+    fn inclusion_proofs() {
+        let values = [(1, 2), (3, 4), (1000, 5), (1001, 6)];
+        let tree = PatriciaTree::new(251, leaves(&values)).unwrap();
+
+        for (key, value) in values {
+            let proof = tree.proof(&key.into()).unwrap();
+            assert_eq!(
+                verify_proof(&tree.root(), 251, &key.into(), &value.into(), &proof),
+                Ok(true)
+            );
+            assert_eq!(
+                verify_proof(&tree.root(), 251, &key.into(), &(value + 1).into(), &proof),
+                Ok(false)
+            );
+        }
+    }
+
+    #[test]
+    fn non_membership_proof() {
+        let tree = PatriciaTree::new(251, leaves(&[(1, 2), (3, 4)])).unwrap();
+        let proof = tree.proof(&2.into()).unwrap();
+
+        assert_eq!(
+            verify_proof(&tree.root(), 251, &2.into(), &Felt252::zero(), &proof),
+            Ok(true)
+        );
+        assert_eq!(
+            verify_proof(&tree.root(), 251, &2.into(), &1.into(), &proof),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn key_out_of_range() {
+        assert_matches!(
+            PatriciaTree::new(2, leaves(&[(4, 1)])),
+            Err(StarkwareError::PatriciaKeyOutOfRange(_, 2))
+        );
+    }
+}
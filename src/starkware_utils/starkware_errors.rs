@@ -11,6 +11,10 @@ pub enum StarkwareError {
     InvalidBlockNumber,
     #[error("Invalid Block Timestamp.")]
     InvalidBlockTimestamp,
+    #[error("Key 0x{0} doesn't fit in a Patricia tree of height {1}.")]
+    PatriciaKeyOutOfRange(String, u64),
+    #[error("Failed to compute hash.")]
+    FailToComputeHash,
     #[error(transparent)]
     StateError(#[from] StateError),
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use felt::{felt_str, Felt};
 use num_traits::Zero;
@@ -15,9 +15,9 @@ use lazy_static::lazy_static;
 
 lazy_static! {
     // include_str! doesn't seem to work in CI
-    static ref CONTRACT_CLASS: ContractClass = ContractClass::try_from(PathBuf::from(
+    static ref CONTRACT_CLASS: Arc<ContractClass> = Arc::new(ContractClass::try_from(PathBuf::from(
         "starknet_programs/first_contract.json",
-    )).unwrap();
+    )).unwrap());
 
     static ref CONTRACT_PATH: PathBuf = PathBuf::from("starknet_programs/first_contract.json");
 
@@ -1,3 +1,4 @@
 pub mod starknet_state;
 pub mod starknet_state_error;
+mod starknet_state_snapshot;
 pub mod type_utils;
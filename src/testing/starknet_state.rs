@@ -1,10 +1,13 @@
-use super::starknet_state_error::StarknetStateError;
+use super::{
+    starknet_state_error::StarknetStateError,
+    starknet_state_snapshot::{SnapshotVersion, StarknetStateSnapshot, SNAPSHOT_VERSION},
+};
 use crate::{
     business_logic::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
             objects::{
-                CallInfo, Event, L2toL1MessageInfo, TransactionExecutionContext,
+                CallInfo, Event, L2toL1MessageInfo, RunResources, TransactionExecutionContext,
                 TransactionExecutionInfo,
             },
         },
@@ -16,6 +19,7 @@ use crate::{
             state_api::{State, StateReader},
         },
         transaction::{
+            account_transaction::AccountTransaction,
             error::TransactionError,
             objects::{
                 internal_declare::InternalDeclare, internal_deploy::InternalDeploy,
@@ -26,14 +30,14 @@ use crate::{
     },
     definitions::{constants::TRANSACTION_VERSION, general_config::StarknetGeneralConfig},
     services::api::{
-        contract_class::{ContractClass, EntryPointType},
+        contract_class::{ContractClassWithSource, EntryPointType},
         messages::StarknetMessageToL1,
     },
     utils::{Address, ClassHash},
 };
 use felt::Felt252;
 use num_traits::{One, Zero};
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
 
 // ---------------------------------------------------------------------
 /// StarkNet testing object. Represents a state of a StarkNet network.
@@ -41,9 +45,9 @@ use std::collections::HashMap;
 pub struct StarknetState {
     pub state: CachedState<InMemoryStateReader>,
     pub general_config: StarknetGeneralConfig,
-    l2_to_l1_messages: HashMap<Vec<u8>, usize>,
-    l2_to_l1_messages_log: Vec<StarknetMessageToL1>,
-    events: Vec<Event>,
+    pub(crate) l2_to_l1_messages: HashMap<Vec<u8>, usize>,
+    pub(crate) l2_to_l1_messages_log: Vec<StarknetMessageToL1>,
+    pub(crate) events: Vec<Event>,
 }
 
 impl StarknetState {
@@ -84,14 +88,39 @@ impl StarknetState {
         }
     }
 
+    /// Writes the whole state to a snapshot file at `path`: contract states, contract classes,
+    /// the general config, the pending L2 to L1 messages and the events. Open checkpoints
+    /// aren't saved.
+    ///
+    /// Every contract class needs the JSON it was loaded from, kept by the state when it's
+    /// declared or deployed with it, so classes built in memory can't be dumped.
+    pub fn dump<P: AsRef<Path>>(&self, path: P) -> Result<(), StarknetStateError> {
+        let snapshot = StarknetStateSnapshot::try_from(self)?;
+        fs::write(path, serde_json::to_vec(&snapshot)?)?;
+        Ok(())
+    }
+
+    /// Loads a state from a snapshot file written by [`dump`](Self::dump).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, StarknetStateError> {
+        let bytes = fs::read(path)?;
+
+        let SnapshotVersion { version } = serde_json::from_slice(&bytes)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(StarknetStateError::UnsupportedSnapshotVersion(version));
+        }
+
+        serde_json::from_slice::<StarknetStateSnapshot>(&bytes)?.try_into()
+    }
+
     // ------------------------------------------------------------------------------------
     /// Declares a contract class.
     /// Returns the class hash and the execution info.
     /// Args:
-    /// contract_class - a compiled StarkNet contract
+    /// contract_class - a compiled StarkNet contract, with the JSON it was loaded from to be
+    /// able to dump the state
     pub fn declare(
         &mut self,
-        contract_class: ContractClass,
+        contract_class: impl Into<ContractClassWithSource>,
     ) -> Result<(ClassHash, TransactionExecutionInfo), TransactionError> {
         let tx = InternalDeclare::new(
             contract_class,
@@ -154,7 +183,10 @@ impl StarknetState {
         let mut state_copy = self.state.apply_to_copy();
         let mut resources_manager = ExecutionResourcesManager::default();
 
-        let tx_execution_context = TransactionExecutionContext::default();
+        let tx_execution_context = TransactionExecutionContext {
+            run_resources: RunResources::new(self.general_config.invoke_tx_max_n_steps),
+            ..Default::default()
+        };
         let call_info = call.execute(
             &mut state_copy,
             &self.general_config,
@@ -172,27 +204,37 @@ impl StarknetState {
 
     /// Deploys a contract. Returns the contract address and the execution info.
     /// Args:
-    /// contract_class - a compiled StarkNet contract
+    /// contract_class - a compiled StarkNet contract, with the JSON it was loaded from to be
+    /// able to dump the state
     /// contract_address_salt
     /// the salt to use for deploying. Otherwise, the salt is randomized.
     pub fn deploy(
         &mut self,
-        contract_class: ContractClass,
+        contract_class: impl Into<ContractClassWithSource>,
         constructor_calldata: Vec<Felt252>,
         contract_address_salt: Address,
     ) -> Result<(Address, TransactionExecutionInfo), StarknetStateError> {
+        let ContractClassWithSource {
+            contract_class,
+            source,
+        } = contract_class.into();
         let chain_id = self.general_config.starknet_os_config.chain_id.to_felt();
-        let mut tx = Transaction::Deploy(InternalDeploy::new(
+        let tx = InternalDeploy::new(
             contract_address_salt,
             contract_class.clone(),
             constructor_calldata,
             chain_id,
             TRANSACTION_VERSION,
-        )?);
+        )?;
 
         self.state
-            .set_contract_class(&tx.contract_hash(), &contract_class)?;
+            .set_contract_class(&tx.class_hash(), &contract_class)?;
+        if let Some(source) = source {
+            self.state
+                .set_contract_class_source(&tx.class_hash(), source);
+        }
 
+        let mut tx = Transaction::Deploy(tx);
         let tx_execution_info = self.execute_tx(&mut tx)?;
         Ok((tx.contract_address(), tx_execution_info))
     }
@@ -286,6 +328,7 @@ impl StarknetState {
             contract_address,
             entry_point_selector,
             max_fee,
+            TRANSACTION_VERSION.into(),
             calldata,
             signature,
             self.chain_id(),
@@ -297,15 +340,16 @@ impl StarknetState {
 #[cfg(test)]
 mod tests {
     use coverage_helper::test;
-    use std::path::PathBuf;
+    use std::{path::PathBuf, sync::Arc};
 
+    use cairo_rs::types::program::Program;
     use felt::felt_str;
     use num_traits::Num;
 
     use super::*;
     use crate::{
         business_logic::{
-            execution::objects::{CallType, OrderedL2ToL1Message},
+            execution::objects::{CallType, OrderedEvent, OrderedL2ToL1Message},
             state::state_cache::StorageEntry,
         },
         core::contract_address::starknet_contract_address::compute_class_hash,
@@ -313,6 +357,8 @@ mod tests {
         definitions::{
             constants::CONSTRUCTOR_ENTRY_POINT_SELECTOR, transaction_type::TransactionType,
         },
+        services::api::contract_class::ContractClass,
+        starknet_storage::errors::storage_errors::StorageError,
         testing::type_utils::ExecutionInfo,
         utils::calculate_sn_keccak,
     };
@@ -335,7 +381,7 @@ mod tests {
         ));
 
         let mut actual_resources = HashMap::new();
-        actual_resources.insert("l1_gas_usage".to_string(), 1224);
+        actual_resources.insert("l1_gas_usage".to_string(), 1836);
 
         let transaction_exec_info = TransactionExecutionInfo {
             validate_info: None,
@@ -353,6 +399,7 @@ mod tests {
             actual_fee: 0,
             actual_resources,
             tx_type: Some(TransactionType::Deploy),
+            revert_error: None,
         };
 
         // check result is correct
@@ -372,8 +419,8 @@ mod tests {
                 .unwrap()
                 .get(&class_hash)
                 .unwrap()
-                .to_owned(),
-            contract_class
+                .as_ref(),
+            &contract_class
         );
     }
 
@@ -389,7 +436,7 @@ mod tests {
         // hack store account contract
         let hash = compute_class_hash(&contract_class).unwrap();
         let class_hash = hash.to_be_bytes();
-        contract_class_cache.insert(class_hash, contract_class.clone());
+        contract_class_cache.insert(class_hash, Arc::new(contract_class.clone()));
 
         // store sender_address
         let sender_address = Address(1.into());
@@ -412,7 +459,7 @@ mod tests {
             .insert(storage_entry.clone(), storage.clone());
         state_reader
             .class_hash_to_contract_class_mut()
-            .insert(class_hash, contract_class.clone());
+            .insert(class_hash, Arc::new(contract_class.clone()));
 
         let state = CachedState::new(state_reader, Some(contract_class_cache));
 
@@ -443,7 +490,7 @@ mod tests {
             .state
             .state_reader
             .class_hash_to_contract_class_mut()
-            .insert(class_hash, contract_class);
+            .insert(class_hash, Arc::new(contract_class));
 
         // --------------------------------------------
         //      Test declare with starknet state
@@ -479,8 +526,9 @@ mod tests {
             starknet_state
                 .state
                 .get_contract_class(&fib_class_hash)
-                .unwrap(),
-            fib_contract_class
+                .unwrap()
+                .as_ref(),
+            &fib_contract_class
         );
     }
 
@@ -532,8 +580,9 @@ mod tests {
         let address = felt_str!(
             "2066790681318687707025847340457605657642478884993868155391041767964612021885"
         );
+        // The nonce bump of the account is counted as a state change.
         let actual_resources = HashMap::from([
-            ("l1_gas_usage".to_string(), 0),
+            ("l1_gas_usage".to_string(), 1224),
             ("range_check_builtin".to_string(), 70),
             ("pedersen_builtin".to_string(), 16),
         ]);
@@ -701,4 +750,118 @@ mod tests {
             TransactionError::State(StateError::NoneContractState(_))
         );
     }
+
+    fn temp_snapshot_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "starknet_rs_snapshot_{name}_{}.json",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_dump_and_load() {
+        let mut starknet_state = StarknetState::new(None);
+        starknet_state.general_config.block_info_mut().block_number = 7;
+        let contract_class =
+            ContractClassWithSource::try_from(&PathBuf::from("starknet_programs/fibonacci.json"))
+                .unwrap();
+        let (contract_address, _) = starknet_state
+            .deploy(contract_class, vec![], Address(1.into()))
+            .unwrap();
+        starknet_state
+            .state
+            .set_storage_at(&(contract_address.clone(), [1; 32]), Felt252::new(3));
+        starknet_state
+            .state
+            .state_reader
+            .class_hash_to_compiled_class_hash
+            .insert([2; 32], [3; 32]);
+        starknet_state
+            .state
+            .set_compiled_class_hash(&[4; 32], &[5; 32])
+            .unwrap();
+
+        let exec_info = ExecutionInfo::Call(Box::new(CallInfo {
+            contract_address,
+            events: vec![OrderedEvent::new(0, vec![1.into()], vec![2.into()])],
+            l2_to_l1_messages: vec![OrderedL2ToL1Message {
+                order: 1,
+                to_address: Address(0.into()),
+                payload: vec![0.into()],
+            }],
+            ..Default::default()
+        }));
+        starknet_state
+            .add_messages_and_events(
+                &exec_info.get_sorted_events().unwrap(),
+                &exec_info.get_sorted_l2_to_l1_messages().unwrap(),
+            )
+            .unwrap();
+
+        let path = temp_snapshot_path("dump_and_load");
+        starknet_state.dump(&path).unwrap();
+        let loaded_state = StarknetState::load(&path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(
+            loaded_state.state.state_reader(),
+            starknet_state.state.state_reader()
+        );
+        assert_eq!(loaded_state.state.cache(), starknet_state.state.cache());
+        assert_eq!(
+            loaded_state.state.contract_classes(),
+            starknet_state.state.contract_classes()
+        );
+        assert_eq!(
+            loaded_state.state.declared_classes(),
+            starknet_state.state.declared_classes()
+        );
+        assert!(!loaded_state.state.declared_classes().is_empty());
+        assert_eq!(
+            loaded_state.state.cache().compiled_class_hash_writes,
+            HashMap::from([([4; 32], [5; 32])])
+        );
+        assert_eq!(loaded_state.general_config.block_info().block_number, 7);
+        assert_eq!(
+            loaded_state.l2_to_l1_messages,
+            starknet_state.l2_to_l1_messages
+        );
+        assert_eq!(
+            loaded_state.l2_to_l1_messages_log,
+            starknet_state.l2_to_l1_messages_log
+        );
+        assert_eq!(loaded_state.events, starknet_state.events);
+    }
+
+    #[test]
+    fn test_load_unsupported_snapshot_version() {
+        let path = temp_snapshot_path("unsupported_version");
+        std::fs::write(&path, r#"{"version": 0}"#).unwrap();
+        let result = StarknetState::load(&path);
+        let _ = std::fs::remove_file(path);
+
+        assert_matches!(
+            result,
+            Err(StarknetStateError::UnsupportedSnapshotVersion(0))
+        );
+    }
+
+    #[test]
+    fn test_dump_contract_class_without_source_should_fail() {
+        let mut starknet_state = StarknetState::new(None);
+        starknet_state
+            .state
+            .set_contract_class(
+                &[1; 32],
+                &ContractClass::new(Program::default(), HashMap::new(), None).unwrap(),
+            )
+            .unwrap();
+
+        assert_matches!(
+            starknet_state.dump(temp_snapshot_path("without_source")),
+            Err(StarknetStateError::State(StateError::Storage(
+                StorageError::UnserializableContractClass(_)
+            )))
+        );
+    }
 }
@@ -9,6 +9,14 @@ use crate::{
 pub enum StarknetStateError {
     #[error("Invalid message hash key passed to l2 messages")]
     InvalidMessageHash,
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedSnapshotVersion(u32),
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
     #[error(transparent)]
     Syscall(#[from] SyscallHandlerError),
     #[error(transparent)]
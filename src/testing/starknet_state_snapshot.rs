@@ -0,0 +1,490 @@
+use super::{starknet_state::StarknetState, starknet_state_error::StarknetStateError};
+use crate::{
+    business_logic::{
+        execution::objects::Event,
+        fact_state::in_memory_state_reader::InMemoryStateReader,
+        state::{
+            cached_state::{CachedState, ContractClassSources},
+            state_api_objects::BlockInfo,
+            state_cache::{StateCache, StorageEntry},
+        },
+    },
+    core::errors::state_errors::StateError,
+    definitions::general_config::{StarknetChainId, StarknetGeneralConfig, StarknetOsConfig},
+    services::api::{contract_class::ContractClass, messages::StarknetMessageToL1},
+    starknet_storage::errors::storage_errors::StorageError,
+    utils::{
+        decode_felt, decode_hash, encode_felt, encode_hash, Address, ClassHash, CompiledClassHash,
+    },
+};
+use felt::Felt252;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
+
+/// Version of the snapshot format. Bump it on every change that older versions can't read.
+pub(crate) const SNAPSHOT_VERSION: u32 = 3;
+
+/// Just the version of a snapshot, read before the rest so snapshots in another format are
+/// rejected with a clear error.
+#[derive(Deserialize)]
+pub(crate) struct SnapshotVersion {
+    pub(crate) version: u32,
+}
+
+/// On-disk representation of a [`StarknetState`]. Felts, addresses and hashes are `0x` prefixed
+/// hex strings, and the maps are ordered so the same state is always dumped the same way.
+///
+/// Contract classes are stored once, as their source JSON, and referenced by class hash from
+/// the state reader and the cached state.
+#[derive(Deserialize, Serialize)]
+pub(crate) struct StarknetStateSnapshot {
+    version: u32,
+    state_reader: ContractStatesSnapshot,
+    state_reader_classes: BTreeSet<String>,
+    initial_values: ContractStatesSnapshot,
+    writes: ContractStatesSnapshot,
+    // None if the cached state has no contract class cache.
+    cached_classes: Option<BTreeSet<String>>,
+    declared_classes: BTreeSet<String>,
+    contract_classes: BTreeMap<String, String>,
+    general_config: GeneralConfigSnapshot,
+    l2_to_l1_messages: BTreeMap<String, usize>,
+    l2_to_l1_messages_log: Vec<MessageSnapshot>,
+    events: Vec<EventSnapshot>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct ContractStatesSnapshot {
+    class_hashes: BTreeMap<String, String>,
+    nonces: BTreeMap<String, String>,
+    storage: BTreeMap<String, BTreeMap<String, String>>,
+    compiled_class_hashes: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct GeneralConfigSnapshot {
+    chain_id: String,
+    fee_token_address: String,
+    gas_price: u64,
+    contract_storage_commitment_tree_height: u64,
+    global_state_commitment_tree_height: u64,
+    cairo_resource_fee_weights: BTreeMap<String, f64>,
+    invoke_tx_max_n_steps: u64,
+    validate_max_n_steps: u64,
+    block_info: BlockInfoSnapshot,
+}
+
+#[derive(Deserialize, Serialize)]
+struct BlockInfoSnapshot {
+    block_number: u64,
+    block_timestamp: u64,
+    gas_price: u64,
+    sequencer_address: String,
+    starknet_version: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MessageSnapshot {
+    from_address: String,
+    to_address: String,
+    payload: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct EventSnapshot {
+    from_address: String,
+    keys: Vec<String>,
+    data: Vec<String>,
+}
+
+type ContractStates = (
+    HashMap<Address, ClassHash>,
+    HashMap<Address, Felt252>,
+    HashMap<StorageEntry, Felt252>,
+    HashMap<ClassHash, CompiledClassHash>,
+);
+
+impl ContractStatesSnapshot {
+    fn new(
+        class_hashes: &HashMap<Address, ClassHash>,
+        nonces: &HashMap<Address, Felt252>,
+        storage: &HashMap<StorageEntry, Felt252>,
+        compiled_class_hashes: &HashMap<ClassHash, CompiledClassHash>,
+    ) -> Self {
+        let mut snapshot = ContractStatesSnapshot::default();
+        for (address, class_hash) in class_hashes {
+            snapshot
+                .class_hashes
+                .insert(encode_felt(&address.0), encode_hash(class_hash));
+        }
+        for (address, nonce) in nonces {
+            snapshot
+                .nonces
+                .insert(encode_felt(&address.0), encode_felt(nonce));
+        }
+        for ((address, key), value) in storage {
+            snapshot
+                .storage
+                .entry(encode_felt(&address.0))
+                .or_default()
+                .insert(encode_hash(key), encode_felt(value));
+        }
+        for (class_hash, compiled_class_hash) in compiled_class_hashes {
+            snapshot
+                .compiled_class_hashes
+                .insert(encode_hash(class_hash), encode_hash(compiled_class_hash));
+        }
+        snapshot
+    }
+
+    fn decode(self) -> Result<ContractStates, StarknetStateError> {
+        let mut class_hashes = HashMap::new();
+        for (address, class_hash) in self.class_hashes {
+            let address = decode_felt(&address).map_err(invalid_snapshot)?;
+            let class_hash = decode_hash(&class_hash).map_err(invalid_snapshot)?;
+            class_hashes.insert(Address(address), class_hash);
+        }
+
+        let mut nonces = HashMap::new();
+        for (address, nonce) in self.nonces {
+            let address = decode_felt(&address).map_err(invalid_snapshot)?;
+            let nonce = decode_felt(&nonce).map_err(invalid_snapshot)?;
+            nonces.insert(Address(address), nonce);
+        }
+
+        let mut storage = HashMap::new();
+        for (address, contract_storage) in self.storage {
+            let address = Address(decode_felt(&address).map_err(invalid_snapshot)?);
+            for (key, value) in contract_storage {
+                let key = decode_hash(&key).map_err(invalid_snapshot)?;
+                let value = decode_felt(&value).map_err(invalid_snapshot)?;
+                storage.insert((address.clone(), key), value);
+            }
+        }
+
+        let mut compiled_class_hashes = HashMap::new();
+        for (class_hash, compiled_class_hash) in self.compiled_class_hashes {
+            compiled_class_hashes.insert(
+                decode_hash(&class_hash).map_err(invalid_snapshot)?,
+                decode_hash(&compiled_class_hash).map_err(invalid_snapshot)?,
+            );
+        }
+
+        Ok((class_hashes, nonces, storage, compiled_class_hashes))
+    }
+}
+
+impl TryFrom<&StarknetState> for StarknetStateSnapshot {
+    type Error = StarknetStateError;
+
+    fn try_from(starknet_state: &StarknetState) -> Result<Self, Self::Error> {
+        let state_reader = starknet_state.state.state_reader();
+        let cache = starknet_state.state.cache();
+        let contract_class_sources = starknet_state.state.contract_class_sources();
+        let mut contract_classes = BTreeMap::new();
+
+        let state_reader_classes: BTreeSet<String> = state_reader
+            .class_hash_to_contract_class
+            .keys()
+            .map(|class_hash| {
+                add_contract_class(&mut contract_classes, class_hash, contract_class_sources)
+            })
+            .collect::<Result<_, _>>()?;
+        let cached_classes: Option<BTreeSet<String>> = starknet_state
+            .state
+            .contract_classes()
+            .as_ref()
+            .map(|cached_classes| {
+                cached_classes
+                    .keys()
+                    .map(|class_hash| {
+                        add_contract_class(
+                            &mut contract_classes,
+                            class_hash,
+                            contract_class_sources,
+                        )
+                    })
+                    .collect::<Result<_, _>>()
+            })
+            .transpose()?;
+
+        Ok(StarknetStateSnapshot {
+            version: SNAPSHOT_VERSION,
+            state_reader: ContractStatesSnapshot::new(
+                &state_reader.address_to_class_hash,
+                &state_reader.address_to_nonce,
+                &state_reader.address_to_storage,
+                &state_reader.class_hash_to_compiled_class_hash,
+            ),
+            state_reader_classes,
+            initial_values: ContractStatesSnapshot::new(
+                &cache.class_hash_initial_values,
+                &cache.nonce_initial_values,
+                &cache.storage_initial_values,
+                &cache.compiled_class_hash_initial_values,
+            ),
+            writes: ContractStatesSnapshot::new(
+                &cache.class_hash_writes,
+                &cache.nonce_writes,
+                &cache.storage_writes,
+                &cache.compiled_class_hash_writes,
+            ),
+            cached_classes,
+            declared_classes: starknet_state
+                .state
+                .declared_classes()
+                .iter()
+                .map(encode_hash)
+                .collect(),
+            contract_classes,
+            general_config: (&starknet_state.general_config).into(),
+            l2_to_l1_messages: starknet_state
+                .l2_to_l1_messages
+                .iter()
+                .map(|(message_hash, count)| (format!("0x{}", hex::encode(message_hash)), *count))
+                .collect(),
+            l2_to_l1_messages_log: starknet_state
+                .l2_to_l1_messages_log
+                .iter()
+                .map(|message| MessageSnapshot {
+                    from_address: encode_felt(&message.from_address().0),
+                    to_address: encode_felt(&message.to_address().0),
+                    payload: message.payload().iter().map(encode_felt).collect(),
+                })
+                .collect(),
+            events: starknet_state
+                .events
+                .iter()
+                .map(|event| EventSnapshot {
+                    from_address: encode_felt(&event.from_address.0),
+                    keys: event.keys.iter().map(encode_felt).collect(),
+                    data: event.data.iter().map(encode_felt).collect(),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl TryFrom<StarknetStateSnapshot> for StarknetState {
+    type Error = StarknetStateError;
+
+    fn try_from(snapshot: StarknetStateSnapshot) -> Result<Self, Self::Error> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(StarknetStateError::UnsupportedSnapshotVersion(
+                snapshot.version,
+            ));
+        }
+
+        let mut contract_classes = HashMap::new();
+        let mut contract_class_sources = HashMap::new();
+        for (class_hash, source) in snapshot.contract_classes {
+            let contract_class = ContractClass::try_from(source.as_str())
+                .map_err(|error| invalid_snapshot(format!("Invalid contract class: {error}")))?;
+            contract_class_sources.insert(
+                decode_hash(&class_hash).map_err(invalid_snapshot)?,
+                Arc::from(source),
+            );
+            contract_classes.insert(class_hash, Arc::new(contract_class));
+        }
+        let get_contract_class = |class_hash: &String| {
+            contract_classes
+                .get(class_hash)
+                .cloned()
+                .ok_or_else(|| invalid_snapshot(format!("Missing contract class: {class_hash}")))
+        };
+
+        let (
+            address_to_class_hash,
+            address_to_nonce,
+            address_to_storage,
+            class_hash_to_compiled_class_hash,
+        ) = snapshot.state_reader.decode()?;
+        let mut class_hash_to_contract_class = HashMap::new();
+        for class_hash in snapshot.state_reader_classes.iter() {
+            class_hash_to_contract_class.insert(
+                decode_hash(class_hash).map_err(invalid_snapshot)?,
+                get_contract_class(class_hash)?,
+            );
+        }
+        let mut state_reader = InMemoryStateReader::new(
+            address_to_class_hash,
+            address_to_nonce,
+            address_to_storage,
+            class_hash_to_contract_class,
+        );
+        state_reader.class_hash_to_compiled_class_hash = class_hash_to_compiled_class_hash;
+
+        let (
+            class_hash_initial_values,
+            nonce_initial_values,
+            storage_initial_values,
+            compiled_class_hash_initial_values,
+        ) = snapshot.initial_values.decode()?;
+        let (class_hash_writes, nonce_writes, storage_writes, compiled_class_hash_writes) =
+            snapshot.writes.decode()?;
+        let mut cache = StateCache::new(
+            class_hash_initial_values,
+            nonce_initial_values,
+            storage_initial_values,
+            class_hash_writes,
+            nonce_writes,
+            storage_writes,
+        );
+        cache.compiled_class_hash_initial_values = compiled_class_hash_initial_values;
+        cache.compiled_class_hash_writes = compiled_class_hash_writes;
+
+        let cached_classes = match snapshot.cached_classes {
+            Some(class_hashes) => {
+                let mut cached_classes = HashMap::new();
+                for class_hash in class_hashes.iter() {
+                    cached_classes.insert(
+                        decode_hash(class_hash).map_err(invalid_snapshot)?,
+                        get_contract_class(class_hash)?,
+                    );
+                }
+                Some(cached_classes)
+            }
+            None => None,
+        };
+
+        let mut l2_to_l1_messages = HashMap::new();
+        for (message_hash, count) in snapshot.l2_to_l1_messages {
+            let message_hash = hex::decode(message_hash.trim_start_matches("0x"))
+                .map_err(|_| invalid_snapshot(format!("Invalid message hash: {message_hash}")))?;
+            l2_to_l1_messages.insert(message_hash, count);
+        }
+
+        let mut l2_to_l1_messages_log = Vec::new();
+        for message in snapshot.l2_to_l1_messages_log {
+            l2_to_l1_messages_log.push(StarknetMessageToL1::new(
+                Address(decode_felt(&message.from_address).map_err(invalid_snapshot)?),
+                Address(decode_felt(&message.to_address).map_err(invalid_snapshot)?),
+                decode_felts(&message.payload)?,
+            ));
+        }
+
+        let mut events = Vec::new();
+        for event in snapshot.events {
+            events.push(Event {
+                from_address: Address(decode_felt(&event.from_address).map_err(invalid_snapshot)?),
+                keys: decode_felts(&event.keys)?,
+                data: decode_felts(&event.data)?,
+            });
+        }
+
+        let mut state = CachedState::new_for_testing(state_reader, cached_classes, cache);
+        state.contract_class_sources = contract_class_sources;
+        for class_hash in snapshot.declared_classes.iter() {
+            state
+                .declared_classes
+                .insert(decode_hash(class_hash).map_err(invalid_snapshot)?);
+        }
+
+        Ok(StarknetState {
+            state,
+            general_config: snapshot.general_config.try_into()?,
+            l2_to_l1_messages,
+            l2_to_l1_messages_log,
+            events,
+        })
+    }
+}
+
+impl From<&StarknetGeneralConfig> for GeneralConfigSnapshot {
+    fn from(general_config: &StarknetGeneralConfig) -> Self {
+        let os_config = &general_config.starknet_os_config;
+        let block_info = &general_config.block_info;
+        GeneralConfigSnapshot {
+            chain_id: os_config.chain_id.to_string(),
+            fee_token_address: encode_felt(&os_config.fee_token_address.0),
+            gas_price: os_config.gas_price,
+            contract_storage_commitment_tree_height: general_config
+                .contract_storage_commitment_tree_height,
+            global_state_commitment_tree_height: general_config
+                .global_state_commitment_tree_height(),
+            cairo_resource_fee_weights: general_config
+                .cairo_resource_fee_weights
+                .iter()
+                .map(|(resource, weight)| (resource.clone(), *weight))
+                .collect(),
+            invoke_tx_max_n_steps: general_config.invoke_tx_max_n_steps,
+            validate_max_n_steps: general_config.validate_max_n_steps,
+            block_info: BlockInfoSnapshot {
+                block_number: block_info.block_number,
+                block_timestamp: block_info.block_timestamp,
+                gas_price: block_info.gas_price,
+                sequencer_address: encode_felt(&block_info.sequencer_address.0),
+                starknet_version: block_info.starknet_version.clone(),
+            },
+        }
+    }
+}
+
+impl TryFrom<GeneralConfigSnapshot> for StarknetGeneralConfig {
+    type Error = StarknetStateError;
+
+    fn try_from(snapshot: GeneralConfigSnapshot) -> Result<Self, Self::Error> {
+        let chain_id = [
+            StarknetChainId::MainNet,
+            StarknetChainId::TestNet,
+            StarknetChainId::TestNet2,
+        ]
+        .into_iter()
+        .find(|chain_id| chain_id.to_string() == snapshot.chain_id)
+        .ok_or_else(|| invalid_snapshot(format!("Invalid chain id: {}", snapshot.chain_id)))?;
+        let block_info = snapshot.block_info;
+
+        Ok(StarknetGeneralConfig::new(
+            StarknetOsConfig::new(
+                chain_id,
+                Address(decode_felt(&snapshot.fee_token_address).map_err(invalid_snapshot)?),
+                snapshot.gas_price,
+            ),
+            snapshot.contract_storage_commitment_tree_height,
+            snapshot.global_state_commitment_tree_height,
+            snapshot.cairo_resource_fee_weights.into_iter().collect(),
+            snapshot.invoke_tx_max_n_steps,
+            snapshot.validate_max_n_steps,
+            BlockInfo {
+                block_number: block_info.block_number,
+                block_timestamp: block_info.block_timestamp,
+                gas_price: block_info.gas_price,
+                sequencer_address: Address(
+                    decode_felt(&block_info.sequencer_address).map_err(invalid_snapshot)?,
+                ),
+                starknet_version: block_info.starknet_version,
+            },
+        ))
+    }
+}
+
+/// Adds the source JSON of the class to `contract_classes` and returns its encoded class hash.
+fn add_contract_class(
+    contract_classes: &mut BTreeMap<String, String>,
+    class_hash: &ClassHash,
+    contract_class_sources: &ContractClassSources,
+) -> Result<String, StarknetStateError> {
+    let encoded_class_hash = encode_hash(class_hash);
+    // Classes built in memory have no JSON they came from, so there's nothing to save.
+    let source = contract_class_sources.get(class_hash).ok_or_else(|| {
+        StateError::from(StorageError::UnserializableContractClass(
+            encoded_class_hash.clone(),
+        ))
+    })?;
+    contract_classes.insert(encoded_class_hash.clone(), source.to_string());
+    Ok(encoded_class_hash)
+}
+
+fn invalid_snapshot(message: String) -> StarknetStateError {
+    StarknetStateError::InvalidSnapshot(message)
+}
+
+fn decode_felts(values: &[String]) -> Result<Vec<Felt252>, StarknetStateError> {
+    values
+        .iter()
+        .map(|value| decode_felt(value).map_err(invalid_snapshot))
+        .collect()
+}
@@ -7,7 +7,7 @@ use crate::{
         fact_state::state::ExecutionResourcesManager,
         state::{
             cached_state::UNINITIALIZED_CLASS_HASH, state_api::StateReader,
-            state_cache::StorageEntry,
+            state_api_objects::StateChanges, state_cache::StorageEntry,
         },
         transaction::error::TransactionError,
     },
@@ -17,16 +17,21 @@ use crate::{
 };
 use cairo_rs::{types::relocatable::Relocatable, vm::vm_core::VirtualMachine};
 use felt::{Felt252, ParseFeltError};
-use num_traits::{Num, ToPrimitive};
+use num_integer::Integer;
+use num_traits::{Num, Pow, ToPrimitive};
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
-use starknet_crypto::FieldElement;
+use starknet_crypto::{pedersen_hash, FieldElement};
 use std::{
     collections::{HashMap, HashSet},
     hash::Hash,
 };
 
 pub type ClassHash = [u8; 32];
+/// The hash of the compiled (CASM) version of a Cairo 1 class.
+pub type CompiledClassHash = [u8; 32];
+/// A key in the storage of a contract.
+pub type StorageKey = [u8; 32];
 
 //* -------------------
 //*      Address
@@ -92,6 +97,40 @@ pub fn string_to_hash(class_string: &String) -> Result<ClassHash, ParseFeltError
     Ok(parsed_felt)
 }
 
+/// Encodes a felt as a `0x`-prefixed hexadecimal string.
+pub fn encode_felt(value: &Felt252) -> String {
+    format!("0x{}", value.to_str_radix(16))
+}
+
+/// Parses a felt from a hexadecimal string, with or without the `0x` prefix.
+pub fn decode_felt(value: &str) -> Result<Felt252, String> {
+    Felt252::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16)
+        .map_err(|_| format!("Invalid felt: {value}"))
+}
+
+/// Encodes a hash the same way [`encode_felt`] encodes felts, without leading zeros. The bytes are
+/// encoded directly, so hashes above the field prime aren't reduced.
+pub fn encode_hash(value: &[u8; 32]) -> String {
+    let encoded = hex::encode(value);
+    match encoded.trim_start_matches('0') {
+        "" => "0x0".to_string(),
+        digits => format!("0x{digits}"),
+    }
+}
+
+/// Parses a hash encoded by [`encode_hash`], also accepting zero-padded strings.
+pub fn decode_hash(value: &str) -> Result<[u8; 32], String> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    let mut hash = [0; 32];
+    if digits.is_empty() || digits.len() > 64 {
+        return Err(format!("Invalid hash: {value}"));
+    }
+    hex::decode_to_slice(format!("{digits:0>64}"), &mut hash)
+        .map_err(|_| format!("Invalid hash: {value}"))?;
+
+    Ok(hash)
+}
+
 // -------------------
 //    STATE UTILS
 // -------------------
@@ -101,12 +140,13 @@ pub fn string_to_hash(class_string: &String) -> Result<ClassHash, ParseFeltError
 
 pub fn to_state_diff_storage_mapping(
     storage_writes: HashMap<StorageEntry, Felt252>,
-) -> HashMap<Felt252, HashMap<ClassHash, Address>> {
-    let mut storage_updates: HashMap<Felt252, HashMap<ClassHash, Address>> = HashMap::new();
+) -> HashMap<Address, HashMap<[u8; 32], Felt252>> {
+    let mut storage_updates: HashMap<Address, HashMap<[u8; 32], Felt252>> = HashMap::new();
     for ((address, key), value) in storage_writes {
-        let mut map = storage_updates.get(&address.0).cloned().unwrap_or_default();
-        map.insert(key, Address(value));
-        storage_updates.insert(address.0, map);
+        storage_updates
+            .entry(address)
+            .or_default()
+            .insert(key, value);
     }
     storage_updates
 }
@@ -130,13 +170,10 @@ pub fn calculate_tx_resources(
     resources_manager: ExecutionResourcesManager,
     call_info: &[Option<CallInfo>],
     tx_type: TransactionType,
-    storage_changes: (usize, usize),
+    state_changes: StateChanges,
     l1_handler_payload_size: Option<usize>,
 ) -> Result<HashMap<String, usize>, TransactionError> {
-    let (n_modified_contracts, n_storage_changes) = storage_changes;
-
     let non_optional_calls: Vec<CallInfo> = call_info.iter().flatten().cloned().collect();
-    let n_deployments = non_optional_calls.iter().map(get_call_n_deployments).sum();
 
     let mut l2_to_l1_messages = Vec::new();
 
@@ -144,13 +181,8 @@ pub fn calculate_tx_resources(
         l2_to_l1_messages.extend(call_info.get_sorted_l2_to_l1_messages()?)
     }
 
-    let l1_gas_usage = calculate_tx_gas_usage(
-        l2_to_l1_messages,
-        n_modified_contracts,
-        n_storage_changes,
-        l1_handler_payload_size,
-        n_deployments,
-    );
+    let l1_gas_usage =
+        calculate_tx_gas_usage(l2_to_l1_messages, &state_changes, l1_handler_payload_size);
 
     let cairo_usage = resources_manager.cairo_usage.clone();
     let tx_syscall_counter = resources_manager.syscall_counter;
@@ -197,12 +229,12 @@ where
 /// storage mapping (Tuple of address and key map to the associated value).
 
 pub fn to_cache_state_storage_mapping(
-    map: HashMap<Felt252, HashMap<ClassHash, Address>>,
+    map: HashMap<Address, HashMap<[u8; 32], Felt252>>,
 ) -> HashMap<StorageEntry, Felt252> {
     let mut storage_writes = HashMap::new();
     for (address, contract_storage) in map {
         for (key, value) in contract_storage {
-            storage_writes.insert((Address(address.clone()), key), value.0);
+            storage_writes.insert((address.clone(), key), value);
         }
     }
     storage_writes
@@ -272,6 +304,32 @@ pub fn calculate_sn_keccak(data: &[u8]) -> ClassHash {
     result
 }
 
+/// Computes the address of the storage variable `storage_var_name` at the key `args`, the way
+/// `get_storage_var_address` does in cairo-lang.
+pub fn get_storage_var_address(
+    storage_var_name: &str,
+    args: &[Felt252],
+) -> Result<Felt252, SyscallHandlerError> {
+    let base = felt_to_field_element(&Felt252::from_bytes_be(&calculate_sn_keccak(
+        storage_var_name.as_bytes(),
+    )))?;
+    let address = args.iter().try_fold(base, |address, arg| {
+        Ok::<_, SyscallHandlerError>(pedersen_hash(&address, &felt_to_field_element(arg)?))
+    })?;
+
+    let addr_bound = Felt252::new(2).pow(251) - Felt252::new(256);
+    Ok(field_element_to_felt(&address).mod_floor(&addr_bound))
+}
+
+/// Returns the storage keys of the low and high halves of the ERC20 balance of `account`.
+pub fn get_erc20_balance_var_addresses(
+    account: &Address,
+) -> Result<(StorageKey, StorageKey), SyscallHandlerError> {
+    let low_key = get_storage_var_address("ERC20_balances", &[account.0.clone()])?;
+    let high_key = &low_key + 1;
+    Ok((low_key.to_be_bytes(), high_key.to_be_bytes()))
+}
+
 //* -------------------
 //*      Macros
 //* -------------------
@@ -462,6 +520,21 @@ pub mod test_utils {
         }};
     }
     pub(crate) use run_syscall_hint;
+
+    macro_rules! storage_key {
+        ( $key:literal ) => {{
+            assert_eq!($key.len(), 64, "keys must be 64 nibbles in length.");
+            let key: [u8; 32] = $key
+                .as_bytes()
+                .chunks_exact(2)
+                .map(|x| u8::from_str_radix(std::str::from_utf8(x).unwrap(), 16).unwrap())
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap();
+            key
+        }};
+    }
+    pub(crate) use storage_key;
 }
 
 #[cfg(test)]
@@ -489,14 +562,8 @@ mod test {
 
         let map = to_state_diff_storage_mapping(storage);
 
-        assert_eq!(
-            *map.get(&address1.0).unwrap().get(&key1).unwrap(),
-            Address(value1)
-        );
-        assert_eq!(
-            *map.get(&address2.0).unwrap().get(&key2).unwrap(),
-            Address(value2)
-        );
+        assert_eq!(*map.get(&address1).unwrap().get(&key1).unwrap(), value1);
+        assert_eq!(*map.get(&address2).unwrap().get(&key2).unwrap(), value2);
     }
 
     #[test]
@@ -668,6 +735,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_get_erc20_balance_var_addresses() {
+        let (low_key, high_key) = get_erc20_balance_var_addresses(&Address(257.into())).unwrap();
+
+        assert_eq!(
+            low_key,
+            felt_str!(
+                "1192211877881866289306604115402199097887041303917861778777990838480655617515"
+            )
+            .to_be_bytes()
+        );
+        assert_eq!(
+            high_key,
+            felt_str!(
+                "1192211877881866289306604115402199097887041303917861778777990838480655617516"
+            )
+            .to_be_bytes()
+        );
+    }
+
     #[test]
     fn test_string_to_hash() {
         assert_eq!(
@@ -702,4 +789,31 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn encode_and_decode_felt() {
+        let value = felt_str!("1234567890abcdef", 16);
+
+        assert_eq!(encode_felt(&value), "0x1234567890abcdef");
+        assert_eq!(decode_felt("0x1234567890abcdef"), Ok(value.clone()));
+        assert_eq!(decode_felt("1234567890abcdef"), Ok(value));
+        assert!(decode_felt("0xzz").is_err());
+    }
+
+    #[test]
+    fn encode_and_decode_hash() {
+        let mut hash = [0; 32];
+        hash[30..].copy_from_slice(&[0x01, 0x2a]);
+
+        assert_eq!(encode_hash(&hash), "0x12a");
+        assert_eq!(encode_hash(&[0; 32]), "0x0");
+        assert_eq!(decode_hash("0x12a"), Ok(hash));
+        assert_eq!(decode_hash(&format!("0x{}", hex::encode(hash))), Ok(hash));
+
+        // Hashes above the field prime are kept as they are.
+        assert_eq!(decode_hash(&encode_hash(&[0xff; 32])), Ok([0xff; 32]));
+
+        assert!(decode_hash("0x").is_err());
+        assert!(decode_hash(&format!("0x1{}", "0".repeat(64))).is_err());
+    }
 }
@@ -10,7 +10,7 @@ use starknet_rs::{
             in_memory_state_reader::InMemoryStateReader, state::ExecutionResourcesManager,
         },
         state::{cached_state::CachedState, state_api::StateReader},
-        transaction::error::TransactionError,
+        transaction::{error::TransactionError, signer::StarkSigner},
     },
     definitions::general_config::StarknetGeneralConfig,
     services::api::contract_class::EntryPointType,
@@ -90,6 +90,13 @@ fn amm_init_pool_test() {
         execution_resources: ExecutionResources::default(),
         class_hash: Some(class_hash),
         accessed_storage_keys,
+        storage_writes: get_storage_writes(
+            "pool_balance",
+            vec![
+                (vec![1_u8.into()], 10000.into()),
+                (vec![2_u8.into()], 10000.into()),
+            ],
+        ),
         ..Default::default()
     };
 
@@ -168,6 +175,13 @@ fn amm_add_demo_tokens_test() {
         class_hash: Some(class_hash),
         accessed_storage_keys: accessed_storage_keys_add_demo_token,
         storage_read_values: vec![Felt252::zero(), Felt252::zero()],
+        storage_writes: get_storage_writes(
+            "account_balance",
+            vec![
+                (vec![0_u8.into(), 1_u8.into()], 100.into()),
+                (vec![0_u8.into(), 2_u8.into()], 100.into()),
+            ],
+        ),
         ..Default::default()
     };
 
@@ -322,6 +336,22 @@ fn amm_swap_test() {
             100.into(),
         ]
         .to_vec(),
+        storage_writes: get_storage_writes(
+            "pool_balance",
+            vec![
+                (vec![1_u8.into()], 10010.into()),
+                (vec![2_u8.into()], 9991.into()),
+            ],
+        )
+        .into_iter()
+        .chain(get_storage_writes(
+            "account_balance",
+            vec![
+                (vec![0_u8.into(), 1_u8.into()], 90.into()),
+                (vec![0_u8.into(), 2_u8.into()], 109.into()),
+            ],
+        ))
+        .collect(),
         ..Default::default()
     };
 
@@ -542,3 +572,69 @@ fn amm_get_account_token_balance_test() {
         expected_call_info_get_account_token_balance
     );
 }
+
+#[test]
+fn amm_init_pool_from_signed_account() {
+    let general_config = StarknetGeneralConfig::default();
+    let mut state = CachedState::new(InMemoryStateReader::default(), Some(Default::default()));
+    let signer = StarkSigner::new(1234567890.into());
+    let account = deploy_signed_account(&mut state, &signer, &general_config).unwrap();
+    let (contract_address, class_hash) = deploy(
+        &mut state,
+        "starknet_programs/amm.json",
+        &[],
+        &general_config,
+    )
+    .unwrap();
+
+    let init_pool_calldata = [10000.into(), 10000.into()];
+    // The account only accepts transactions signed with its key.
+    assert!(invoke_from_account(
+        &mut state,
+        &account,
+        &StarkSigner::new(987654321.into()),
+        0.into(),
+        &contract_address,
+        "init_pool",
+        &init_pool_calldata,
+        &general_config,
+    )
+    .is_err());
+
+    let tx_info = invoke_from_account(
+        &mut state,
+        &account,
+        &signer,
+        0.into(),
+        &contract_address,
+        "init_pool",
+        &init_pool_calldata,
+        &general_config,
+    )
+    .unwrap();
+    assert!(tx_info.validate_info.is_some());
+    assert_eq!(tx_info.revert_error, None);
+
+    let entry_points_by_type = state
+        .get_contract_class(&class_hash)
+        .unwrap()
+        .entry_points_by_type()
+        .clone();
+    let mut resources_manager = ExecutionResourcesManager::default();
+    let mut call_config = CallConfig {
+        state: &mut state,
+        caller_address: &account,
+        address: &contract_address,
+        class_hash: &class_hash,
+        entry_points_by_type: &entry_points_by_type,
+        entry_point_type: &EntryPointType::External,
+        general_config: &general_config,
+        resources_manager: &mut resources_manager,
+    };
+    assert_eq!(
+        get_pool_token_balance(&[1.into()], &mut call_config)
+            .unwrap()
+            .retdata,
+        vec![10000.into()]
+    );
+}
@@ -1,5 +1,8 @@
 use crate::complex_contracts::utils::*;
-use cairo_rs::vm::runners::cairo_runner::ExecutionResources;
+use assert_matches::assert_matches;
+use cairo_rs::vm::{
+    errors::cairo_run_errors::CairoRunError, runners::cairo_runner::ExecutionResources,
+};
 use felt::Felt252;
 use starknet_crypto::FieldElement;
 use starknet_rs::{
@@ -9,6 +12,7 @@ use starknet_rs::{
             in_memory_state_reader::InMemoryStateReader, state::ExecutionResourcesManager,
         },
         state::{cached_state::CachedState, state_api::StateReader},
+        transaction::error::TransactionError,
     },
     definitions::general_config::StarknetGeneralConfig,
     services::api::contract_class::EntryPointType,
@@ -77,6 +81,13 @@ fn amm_proxy_init_pool_test() {
         execution_resources: ExecutionResources::default(),
         class_hash: Some(contract_class_hash),
         accessed_storage_keys,
+        storage_writes: get_storage_writes(
+            "pool_balance",
+            vec![
+                (vec![1_u8.into()], 555.into()),
+                (vec![2_u8.into()], 666.into()),
+            ],
+        ),
         ..Default::default()
     }];
 
@@ -274,6 +285,13 @@ fn amm_proxy_add_demo_token_test() {
         execution_resources: ExecutionResources::default(),
         class_hash: Some(contract_class_hash),
         accessed_storage_keys,
+        storage_writes: get_storage_writes(
+            "account_balance",
+            vec![
+                (vec![proxy_addres_felt, 1_u32.into()], 55.into()),
+                (vec![proxy_addres_felt, 2_u32.into()], 66.into()),
+            ],
+        ),
         ..Default::default()
     }];
 
@@ -508,6 +526,22 @@ fn amm_proxy_swap() {
         execution_resources: ExecutionResources::default(),
         class_hash: Some(contract_class_hash),
         accessed_storage_keys,
+        storage_writes: get_storage_writes(
+            "pool_balance",
+            vec![
+                (vec![1_u8.into()], 1100.into()),
+                (vec![2_u8.into()], 910.into()),
+            ],
+        )
+        .into_iter()
+        .chain(get_storage_writes(
+            "account_balance",
+            vec![
+                (vec![proxy_addres_felt, 1_u8.into()], 0.into()),
+                (vec![proxy_addres_felt, 2_u8.into()], 290.into()),
+            ],
+        ))
+        .collect(),
         ..Default::default()
     }];
 
@@ -534,3 +568,88 @@ fn amm_proxy_swap() {
 
     assert_eq!(result, expected_call_info);
 }
+
+#[test]
+fn amm_proxy_swap_of_unknown_token_fails_in_the_amm() {
+    let general_config = StarknetGeneralConfig::default();
+    let mut state = CachedState::new(InMemoryStateReader::default(), Some(Default::default()));
+    // Deploy contract
+    let (contract_address, contract_class_hash) = deploy(
+        &mut state,
+        "starknet_programs/amm.json",
+        &[],
+        &general_config,
+    )
+    .unwrap();
+    // Deploy proxy
+    let (proxy_address, proxy_class_hash) = deploy(
+        &mut state,
+        "starknet_programs/amm_proxy.json",
+        &[],
+        &general_config,
+    )
+    .unwrap();
+
+    let proxy_entry_points_by_type = state
+        .get_contract_class(&proxy_class_hash)
+        .unwrap()
+        .entry_points_by_type()
+        .clone();
+
+    let caller_address = Address(1000000.into());
+    let mut resources_manager = ExecutionResourcesManager::default();
+
+    let mut call_config = CallConfig {
+        state: &mut state,
+        caller_address: &caller_address,
+        address: &proxy_address,
+        class_hash: &proxy_class_hash,
+        entry_points_by_type: &proxy_entry_points_by_type,
+        entry_point_type: &EntryPointType::External,
+        general_config: &general_config,
+        resources_manager: &mut resources_manager,
+    };
+
+    // The amm only has the tokens of type 1 and 2, it asserts the token to swap is one of them
+    let calldata = [contract_address.0.clone(), 3.into(), 10.into()].to_vec();
+    let error = execute_entry_point("proxy_swap", &calldata, &mut call_config).unwrap_err();
+
+    let stack = match &error {
+        TransactionError::EntryPointFailed(stack) => stack,
+        error => panic!("Expected the error stack of the failed call, got {error:?}"),
+    };
+    assert_eq!(stack.frames.len(), 2);
+
+    // The proxy failed in the call_contract syscall
+    let proxy_frame = &stack.frames[0];
+    assert_eq!(proxy_frame.contract_address, proxy_address);
+    assert_eq!(proxy_frame.class_hash, proxy_class_hash);
+    assert_eq!(
+        proxy_frame.entry_point_selector,
+        Felt252::from_bytes_be(&calculate_sn_keccak(b"proxy_swap"))
+    );
+    assert!(proxy_frame.pc.is_some());
+    assert!(proxy_frame.location.is_some());
+
+    // The amm failed at the assertion on the token type
+    let amm_frame = &stack.frames[1];
+    assert_eq!(amm_frame.contract_address, contract_address);
+    assert_eq!(amm_frame.class_hash, contract_class_hash);
+    assert_eq!(
+        amm_frame.entry_point_selector,
+        Felt252::from_bytes_be(&calculate_sn_keccak(b"swap"))
+    );
+    let location = amm_frame.location.as_ref().unwrap();
+    assert!(location.input_file.filename.ends_with("amm.cairo"));
+    assert_eq!(location.start_line, 111);
+
+    assert_matches!(
+        error.root_cause(),
+        TransactionError::CairoRunner(CairoRunError::VmException(vm_exception))
+            if Some(vm_exception.pc) == amm_frame.pc
+    );
+    assert!(error.to_string().contains(&format!(
+        "amm.cairo:111:{}: Error at pc=0:",
+        location.start_col
+    )));
+}
@@ -7,6 +7,7 @@ use num_traits::Zero;
 use starknet_crypto::FieldElement;
 use starknet_rs::business_logic::state::cached_state::CachedState;
 use starknet_rs::business_logic::transaction::error::TransactionError;
+use starknet_rs::business_logic::transaction::signer::StarkSigner;
 use starknet_rs::{
     business_logic::{
         execution::objects::{CallInfo, CallType, OrderedEvent},
@@ -477,6 +478,10 @@ fn erc721_test_approve() {
         class_hash: Some(class_hash),
         accessed_storage_keys,
         storage_read_values,
+        storage_writes: get_storage_writes(
+            "ERC721_token_approvals",
+            vec![(vec![1_u32.into(), 0_u32.into()], 777.into())],
+        ),
         events: expected_events,
         ..Default::default()
     };
@@ -564,6 +569,10 @@ fn erc721_set_approval_for_all() {
         class_hash: Some(class_hash),
         accessed_storage_keys,
         storage_read_values,
+        storage_writes: get_storage_writes(
+            "ERC721_operator_approvals",
+            vec![(vec![666_u32.into(), 777_u32.into()], 1.into())],
+        ),
         events: expected_events,
         ..Default::default()
     };
@@ -633,19 +642,34 @@ fn erc721_transfer_from_test() {
         vec![vec![FieldElement::from(1_u8), FieldElement::from(0_u8)]],
     ));
 
+    // The token approval is cleared and the owner is updated
+    let mut storage_writes = get_storage_writes(
+        "ERC721_token_approvals",
+        vec![(vec![1_u32.into(), 0_u32.into()], Felt252::zero())],
+    );
+    storage_writes.extend(get_storage_writes(
+        "ERC721_owners",
+        vec![(vec![1_u32.into(), 0_u32.into()], 777.into())],
+    ));
+
+    // The balances are uint256, with the high part in the key after the low one
     let mut balance_from = get_accessed_keys("ERC721_balances", vec![vec![666_u32.into()]])
         .drain()
         .collect::<Vec<[u8; 32]>>()[0];
     accessed_storage_keys.insert(balance_from);
+    storage_writes.insert(balance_from, Felt252::zero());
     balance_from[31] += 1;
     accessed_storage_keys.insert(balance_from);
+    storage_writes.insert(balance_from, Felt252::zero());
 
     let mut balance_to = get_accessed_keys("ERC721_balances", vec![vec![777_u32.into()]])
         .drain()
         .collect::<Vec<[u8; 32]>>()[0];
     accessed_storage_keys.insert(balance_to);
+    storage_writes.insert(balance_to, Felt252::from(1));
     balance_to[31] += 1;
     accessed_storage_keys.insert(balance_to);
+    storage_writes.insert(balance_to, Felt252::zero());
 
     let expected_read_values = vec![
         Felt252::from(666),
@@ -692,6 +716,7 @@ fn erc721_transfer_from_test() {
         class_hash: Some(class_hash),
         accessed_storage_keys,
         storage_read_values: expected_read_values,
+        storage_writes,
         events: expected_events,
         ..Default::default()
     };
@@ -908,7 +933,8 @@ fn erc721_constructor_should_fail_with_to_equal_zero() {
             &general_config,
         )
         .unwrap_err(),
-        TransactionError::CairoRunner(..)
+        TransactionError::EntryPointFailed(stack)
+            if matches!(*stack.error, TransactionError::CairoRunner(..))
     );
 }
 
@@ -1012,3 +1038,63 @@ fn erc721_transfer_fail_not_owner() {
 
     assert!(execute_entry_point("transferFrom", &calldata, &mut call_config).is_err());
 }
+
+#[test]
+fn erc721_approve_from_signed_account() {
+    let general_config = StarknetGeneralConfig::default();
+    let mut state = CachedState::new(InMemoryStateReader::default(), Some(Default::default()));
+    let signer = StarkSigner::new(1234567890.into());
+    let account = deploy_signed_account(&mut state, &signer, &general_config).unwrap();
+
+    // The token is minted to the account.
+    let collection_name = Felt252::from_bytes_be(b"some-nft");
+    let collection_symbol = Felt252::from(555);
+    let (contract_address, class_hash) = deploy(
+        &mut state,
+        "starknet_programs/ERC721.json",
+        &[collection_name, collection_symbol, account.0.clone()],
+        &general_config,
+    )
+    .unwrap();
+
+    let tx_info = invoke_from_account(
+        &mut state,
+        &account,
+        &signer,
+        0.into(),
+        &contract_address,
+        "approve",
+        &[Felt252::from(777), Felt252::from(1), Felt252::zero()],
+        &general_config,
+    )
+    .unwrap();
+    assert!(tx_info.validate_info.is_some());
+    assert_eq!(tx_info.revert_error, None);
+
+    let entry_points_by_type = state
+        .get_contract_class(&class_hash)
+        .unwrap()
+        .entry_points_by_type()
+        .clone();
+    let mut resources_manager = ExecutionResourcesManager::default();
+    let mut call_config = CallConfig {
+        state: &mut state,
+        caller_address: &account,
+        address: &contract_address,
+        class_hash: &class_hash,
+        entry_points_by_type: &entry_points_by_type,
+        entry_point_type: &EntryPointType::External,
+        general_config: &general_config,
+        resources_manager: &mut resources_manager,
+    };
+    assert_eq!(
+        execute_entry_point(
+            "getApproved",
+            &[Felt252::from(1), Felt252::zero()],
+            &mut call_config
+        )
+        .unwrap()
+        .retdata,
+        vec![Felt252::from(777)]
+    );
+}
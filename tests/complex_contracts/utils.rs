@@ -7,15 +7,27 @@ use starknet_rs::{
     business_logic::{
         execution::{
             execution_entry_point::ExecutionEntryPoint,
-            objects::{CallInfo, CallType, TransactionExecutionContext},
+            objects::{CallInfo, CallType, TransactionExecutionContext, TransactionExecutionInfo},
         },
         fact_state::{
             in_memory_state_reader::InMemoryStateReader, state::ExecutionResourcesManager,
         },
         state::{cached_state::CachedState, state_api::State},
-        transaction::{error::TransactionError, objects::internal_deploy::InternalDeploy},
+        transaction::{
+            account_transaction::AccountTransaction,
+            error::TransactionError,
+            objects::{
+                internal_deploy::InternalDeploy, internal_deploy_account::InternalDeployAccount,
+                internal_invoke_function::InternalInvokeFunction,
+            },
+            signer::StarkSigner,
+        },
+    },
+    core::contract_address::starknet_contract_address::compute_class_hash,
+    definitions::{
+        constants::{EXECUTE_ENTRY_POINT_SELECTOR, TRANSACTION_VERSION},
+        general_config::{StarknetChainId, StarknetGeneralConfig},
     },
-    definitions::{constants::TRANSACTION_VERSION, general_config::StarknetGeneralConfig},
     services::api::contract_class::{ContractClass, EntryPointType},
     utils::{calculate_sn_keccak, Address},
 };
@@ -63,6 +75,22 @@ pub fn get_accessed_keys(variable_name: &str, fields: Vec<Vec<FieldElement>>) ->
     accessed_storage_keys
 }
 
+/// The storage writes of a call that set the `variable_name` entry of each list of fields to
+/// its value.
+pub fn get_storage_writes(
+    variable_name: &str,
+    writes: Vec<(Vec<FieldElement>, Felt252)>,
+) -> HashMap<[u8; 32], Felt252> {
+    writes
+        .into_iter()
+        .flat_map(|(fields, value)| {
+            get_accessed_keys(variable_name, vec![fields])
+                .into_iter()
+                .map(move |key| (key, value.clone()))
+        })
+        .collect()
+}
+
 pub fn get_entry_points(
     function_name: &str,
     entry_point_type: &EntryPointType,
@@ -158,3 +186,72 @@ pub fn deploy(
 
     Ok((contract_address, class_hash))
 }
+
+/// Deploys an account holding the public key of `signer`, with a deploy_account transaction
+/// signed by it.
+pub fn deploy_signed_account(
+    state: &mut CachedState<InMemoryStateReader>,
+    signer: &StarkSigner,
+    config: &StarknetGeneralConfig,
+) -> Result<Address, TransactionError> {
+    let contract_class =
+        ContractClass::try_from(PathBuf::from("starknet_programs/Account.json")).unwrap();
+    let class_hash = compute_class_hash(&contract_class).unwrap().to_be_bytes();
+    state.set_contract_class(&class_hash, &contract_class)?;
+
+    let deploy_account = InternalDeployAccount::new_signed(
+        class_hash,
+        0,
+        TRANSACTION_VERSION,
+        Felt252::zero(),
+        vec![signer.public_key()?],
+        signer,
+        Address(1.into()),
+        StarknetChainId::TestNet,
+    )?;
+    deploy_account.execute(state, config)?;
+
+    Ok(deploy_account.contract_address().clone())
+}
+
+/// Calls `function_name` of `contract_address` from `account`, with an invoke of the account's
+/// `__execute__` signed by `signer`.
+#[allow(clippy::too_many_arguments)]
+pub fn invoke_from_account(
+    state: &mut CachedState<InMemoryStateReader>,
+    account: &Address,
+    signer: &StarkSigner,
+    nonce: Felt252,
+    contract_address: &Address,
+    function_name: &str,
+    calldata: &[Felt252],
+    config: &StarknetGeneralConfig,
+) -> Result<TransactionExecutionInfo, TransactionError> {
+    let selector = Felt252::from_bytes_be(&calculate_sn_keccak(function_name.as_bytes()));
+    // A single call: its (to, selector, data_offset, data_len) entry in the call array, and then
+    // its calldata.
+    let execute_calldata = [
+        vec![
+            1.into(),
+            contract_address.0.clone(),
+            selector,
+            0.into(),
+            calldata.len().into(),
+            calldata.len().into(),
+        ],
+        calldata.to_vec(),
+    ]
+    .concat();
+
+    InternalInvokeFunction::new_signed(
+        account.clone(),
+        EXECUTE_ENTRY_POINT_SELECTOR.clone(),
+        0,
+        TRANSACTION_VERSION.into(),
+        execute_calldata,
+        signer,
+        StarknetChainId::TestNet.to_felt(),
+        Some(nonce),
+    )?
+    .execute(state, config)
+}
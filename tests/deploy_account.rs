@@ -7,11 +7,15 @@ use starknet_rs::{
         execution::objects::{CallInfo, CallType, TransactionExecutionInfo},
         fact_state::in_memory_state_reader::InMemoryStateReader,
         state::{cached_state::CachedState, state_api::State},
-        transaction::objects::internal_deploy_account::InternalDeployAccount,
+        transaction::{
+            account_transaction::AccountTransaction,
+            objects::internal_deploy_account::InternalDeployAccount, signer::StarkSigner,
+        },
     },
     core::contract_address::starknet_contract_address::compute_class_hash,
     definitions::{
-        constants::CONSTRUCTOR_ENTRY_POINT_SELECTOR, general_config::StarknetChainId,
+        constants::{CONSTRUCTOR_ENTRY_POINT_SELECTOR, TRANSACTION_VERSION},
+        general_config::StarknetChainId,
         transaction_type::TransactionType,
     },
     services::api::contract_class::{ContractClass, EntryPointType},
@@ -37,20 +41,13 @@ fn internal_deploy_account() {
         .set_contract_class(&class_hash, &contract_class)
         .unwrap();
 
-    let internal_deploy_account = InternalDeployAccount::new(
+    let internal_deploy_account = InternalDeployAccount::new_signed(
         class_hash,
         0,
         0,
         Felt252::zero(),
         vec![],
-        vec![
-            felt_str!(
-                "3233776396904427614006684968846859029149676045084089832563834729503047027074"
-            ),
-            felt_str!(
-                "707039245213420890976709143988743108543645298941971188668773816813012281203"
-            ),
-        ],
+        &StarkSigner::new(felt_str!("1234567890")),
         Address(felt_str!(
             "2669425616857739096022668060305620640217901643963991674344872184515580705509"
         )),
@@ -81,7 +78,7 @@ fn internal_deploy_account() {
             [
                 ("pedersen_builtin", 23),
                 ("range_check_builtin", 74),
-                ("l1_gas_usage", 1224)
+                ("l1_gas_usage", 1836)
             ]
             .into_iter()
             .map(|(k, v)| (k.to_string(), v))
@@ -90,3 +87,43 @@ fn internal_deploy_account() {
         ),
     );
 }
+
+#[test]
+fn internal_deploy_signed_account() {
+    let mut state = CachedState::new(InMemoryStateReader::default(), None);
+    state.set_contract_classes(Default::default()).unwrap();
+
+    let contract_class =
+        ContractClass::try_from(PathBuf::from("starknet_programs/Account.json")).unwrap();
+    let class_hash = compute_class_hash(&contract_class).unwrap().to_be_bytes();
+    state
+        .set_contract_class(&class_hash, &contract_class)
+        .unwrap();
+
+    let signer = StarkSigner::new(felt_str!("1234567890"));
+    let public_key = signer.public_key().unwrap();
+    let deploy_account = |signer: &StarkSigner| {
+        InternalDeployAccount::new_signed(
+            class_hash,
+            0,
+            TRANSACTION_VERSION,
+            Felt252::zero(),
+            vec![public_key.clone()],
+            signer,
+            Address(1.into()),
+            StarknetChainId::TestNet,
+        )
+        .unwrap()
+    };
+
+    // The account checks the signature against the public key it's deployed with. The failed
+    // deployment leaves the address free.
+    assert!(deploy_account(&StarkSigner::new(felt_str!("987654321")))
+        .execute(&mut state, &Default::default())
+        .is_err());
+
+    let tx_info = deploy_account(&signer)
+        .execute(&mut state, &Default::default())
+        .unwrap();
+    assert!(tx_info.validate_info.is_some());
+}
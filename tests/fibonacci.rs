@@ -18,7 +18,7 @@ use starknet_rs::{
     services::api::contract_class::{ContractClass, EntryPointType},
     utils::{Address, ClassHash},
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 #[test]
 fn integration_test() {
@@ -50,7 +50,7 @@ fn integration_test() {
     let class_hash: ClassHash = [1; 32];
     let nonce = Felt252::zero();
 
-    contract_class_cache.insert(class_hash, contract_class);
+    contract_class_cache.insert(class_hash, Arc::new(contract_class));
     let mut state_reader = InMemoryStateReader::default();
     state_reader
         .address_to_class_hash_mut()
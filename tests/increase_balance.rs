@@ -21,6 +21,7 @@ use starknet_rs::{
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
+    sync::Arc,
 };
 
 #[test]
@@ -56,7 +57,7 @@ fn hello_starknet_increase_balance() {
     let storage_entry: StorageEntry = (address.clone(), [1; 32]);
     let storage = Felt252::zero();
 
-    contract_class_cache.insert(class_hash, contract_class);
+    contract_class_cache.insert(class_hash, Arc::new(contract_class));
     let mut state_reader = InMemoryStateReader::default();
     state_reader
         .address_to_class_hash_mut()
@@ -124,6 +125,7 @@ fn hello_starknet_increase_balance() {
         class_hash: Some(class_hash),
         accessed_storage_keys: expected_accessed_storage_keys,
         storage_read_values: expected_storage_read_values,
+        storage_writes: HashMap::from([(expected_key, 1.into())]),
         ..Default::default()
     };
 
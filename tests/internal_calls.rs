@@ -17,7 +17,7 @@ use starknet_rs::{
     services::api::contract_class::{ContractClass, EntryPointType},
     utils::{calculate_sn_keccak, Address, ClassHash},
 };
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 #[test]
 fn test_internal_calls() {
@@ -51,7 +51,11 @@ fn test_internal_calls() {
 
     let mut state = CachedState::new(
         state_reader,
-        Some([([0x01; 32], contract_class)].into_iter().collect()),
+        Some(
+            [([0x01; 32], Arc::new(contract_class))]
+                .into_iter()
+                .collect(),
+        ),
     );
 
     let entry_point_selector = Felt252::from_bytes_be(&calculate_sn_keccak(b"a"));
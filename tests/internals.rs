@@ -21,6 +21,7 @@ use starknet_rs::{
             state_cache::StorageEntry,
         },
         transaction::{
+            account_transaction::AccountTransaction,
             error::TransactionError,
             objects::{
                 internal_deploy_account::InternalDeployAccount,
@@ -29,12 +30,14 @@ use starknet_rs::{
                     internal_invoke_function::InternalInvokeFunction,
                 },
             },
+            simulation::SimulationFlags,
+            transactions::Transaction,
         },
     },
     definitions::{
         constants::{
-            CONSTRUCTOR_ENTRY_POINT_SELECTOR, EXECUTE_ENTRY_POINT_SELECTOR, TRANSACTION_VERSION,
-            TRANSFER_ENTRY_POINT_SELECTOR, TRANSFER_EVENT_SELECTOR,
+            CONSTRUCTOR_ENTRY_POINT_SELECTOR, EXECUTE_ENTRY_POINT_SELECTOR, QUERY_VERSION_BASE,
+            TRANSACTION_VERSION, TRANSFER_ENTRY_POINT_SELECTOR, TRANSFER_EVENT_SELECTOR,
             VALIDATE_DECLARE_ENTRY_POINT_SELECTOR, VALIDATE_DEPLOY_ENTRY_POINT_SELECTOR,
         },
         general_config::{StarknetChainId, StarknetGeneralConfig, StarknetOsConfig},
@@ -42,7 +45,7 @@ use starknet_rs::{
     },
     public::abi::VALIDATE_ENTRY_POINT_SELECTOR,
     services::api::contract_class::{ContractClass, EntryPointType},
-    utils::{calculate_sn_keccak, Address, ClassHash},
+    utils::{calculate_sn_keccak, get_erc20_balance_var_addresses, Address, ClassHash},
 };
 use starknet_rs::{
     core::errors::state_errors::StateError,
@@ -51,6 +54,7 @@ use starknet_rs::{
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
+    sync::Arc,
 };
 
 const ACCOUNT_CONTRACT_PATH: &str = "starknet_programs/account_without_validation.json";
@@ -90,6 +94,9 @@ lazy_static! {
     // Others.
     // Blockifier had this value hardcoded to 2.
     static ref ACTUAL_FEE: Felt252 = Felt252::zero();
+    // The fee token balance of the test accounts, enough to cover the max fee of the test
+    // transactions.
+    static ref INITIAL_BALANCE: Felt252 = Felt252::from(2);
 }
 
 fn get_contract_class<P>(path: P) -> Result<ContractClass, Box<dyn std::error::Error>>
@@ -110,7 +117,7 @@ pub fn new_starknet_general_config_for_testing() -> StarknetGeneralConfig {
         0,
         DEFAULT_CAIRO_RESOURCE_FEE_WEIGHTS.clone(),
         1_000_000,
-        0,
+        1_000_000,
         BlockInfo::empty(TEST_SEQUENCER_ADDRESS.clone()),
     )
 }
@@ -156,7 +163,7 @@ fn create_account_tx_test_state(
 
     let storage_view = HashMap::from([(
         (test_erc20_address, test_erc20_account_balance_key),
-        ACTUAL_FEE.clone(),
+        INITIAL_BALANCE.clone(),
     )]);
 
     let cached_state = CachedState::new(
@@ -187,7 +194,7 @@ fn create_account_tx_test_state(
             for (class_hash, contract_class) in class_hash_to_class {
                 state_reader
                     .class_hash_to_contract_class_mut()
-                    .insert(class_hash, contract_class);
+                    .insert(class_hash, Arc::new(contract_class));
             }
             state_reader
         },
@@ -211,15 +218,15 @@ fn expected_state_after_tx() -> CachedState<InMemoryStateReader> {
     let contract_classes_cache = ContractClassCache::from([
         (
             TEST_CLASS_HASH.clone().to_be_bytes(),
-            get_contract_class(TEST_CONTRACT_PATH).unwrap(),
+            Arc::new(get_contract_class(TEST_CONTRACT_PATH).unwrap()),
         ),
         (
             TEST_ACCOUNT_CONTRACT_CLASS_HASH.clone().to_be_bytes(),
-            get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap(),
+            Arc::new(get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap()),
         ),
         (
             TEST_ERC20_CONTRACT_CLASS_HASH.clone().to_be_bytes(),
-            get_contract_class(ERC20_CONTRACT_PATH).unwrap(),
+            Arc::new(get_contract_class(ERC20_CONTRACT_PATH).unwrap()),
         ),
     ]);
 
@@ -262,7 +269,7 @@ fn state_cache_after_invoke_tx() -> StateCache {
                 TEST_ERC20_CONTRACT_ADDRESS.clone(),
                 TEST_ERC20_ACCOUNT_BALANCE_KEY.clone().to_be_bytes(),
             ),
-            Felt252::zero(),
+            INITIAL_BALANCE.clone(),
         ),
         (
             (
@@ -297,7 +304,7 @@ fn state_cache_after_invoke_tx() -> StateCache {
                 TEST_ERC20_CONTRACT_ADDRESS.clone(),
                 TEST_ERC20_ACCOUNT_BALANCE_KEY.clone().to_be_bytes(),
             ),
-            Felt252::from(0),
+            INITIAL_BALANCE.clone(),
         ),
         (
             (
@@ -348,20 +355,20 @@ fn initial_in_memory_state_reader() -> InMemoryStateReader {
                 TEST_ERC20_CONTRACT_ADDRESS.clone(),
                 TEST_ERC20_ACCOUNT_BALANCE_KEY.clone().to_be_bytes(),
             ),
-            Felt252::from(0),
+            INITIAL_BALANCE.clone(),
         )]),
         HashMap::from([
             (
                 TEST_ERC20_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(ERC20_CONTRACT_PATH).unwrap(),
+                Arc::new(get_contract_class(ERC20_CONTRACT_PATH).unwrap()),
             ),
             (
                 TEST_ACCOUNT_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap(),
+                Arc::new(get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap()),
             ),
             (
                 TEST_CLASS_HASH.to_be_bytes(),
-                get_contract_class(TEST_CONTRACT_PATH).unwrap(),
+                Arc::new(get_contract_class(TEST_CONTRACT_PATH).unwrap()),
             ),
         ]),
     )
@@ -386,6 +393,26 @@ fn expected_validate_call_info(
     }
 }
 
+/// The balances a fee transfer of `actual_fee` from `account` to a sequencer without funds
+/// leaves in the fee token storage.
+fn expected_fee_transfer_storage_writes(
+    account: &Address,
+    sequencer: &Address,
+    actual_fee: u64,
+) -> HashMap<ClassHash, Felt252> {
+    let (account_low, account_high) = get_erc20_balance_var_addresses(account).unwrap();
+    let (sequencer_low, sequencer_high) = get_erc20_balance_var_addresses(sequencer).unwrap();
+    HashMap::from([
+        (
+            account_low,
+            INITIAL_BALANCE.clone() - Felt252::from(actual_fee),
+        ),
+        (account_high, Felt252::zero()),
+        (sequencer_low, Felt252::from(actual_fee)),
+        (sequencer_high, Felt252::zero()),
+    ])
+}
+
 fn expected_fee_transfer_call_info(
     general_config: &StarknetGeneralConfig,
     account_address: &Address,
@@ -438,11 +465,16 @@ fn expected_fee_transfer_call_info(
             ],
         ]),
         storage_read_values: vec![
-            Felt252::zero(),
+            INITIAL_BALANCE.clone(),
             Felt252::zero(),
             Felt252::zero(),
             Felt252::zero(),
         ],
+        storage_writes: expected_fee_transfer_storage_writes(
+            account_address,
+            &general_config.block_info().sequencer_address,
+            actual_fee,
+        ),
 
         ..Default::default()
     }
@@ -465,7 +497,10 @@ fn validate_final_balances<S>(
             *erc20_account_balance_storage_key,
         ))
         .unwrap();
-    assert_eq!(account_balance, Felt252::zero());
+    assert_eq!(
+        account_balance,
+        &*INITIAL_BALANCE - expected_sequencer_balance
+    );
 
     let sequencer_balance = state
         .get_storage_at(&(
@@ -494,7 +529,7 @@ fn test_create_account_tx_test_state() {
             TEST_ERC20_ACCOUNT_BALANCE_KEY.to_be_bytes(),
         ))
         .unwrap();
-    assert_eq!(value, *ACTUAL_FEE);
+    assert_eq!(value, *INITIAL_BALANCE);
 
     let class_hash = state.get_class_hash_at(&TEST_CONTRACT_ADDRESS).unwrap();
     assert_eq!(class_hash, TEST_CLASS_HASH.to_be_bytes());
@@ -503,8 +538,8 @@ fn test_create_account_tx_test_state() {
         .get_contract_class(&TEST_ERC20_CONTRACT_CLASS_HASH.to_be_bytes())
         .unwrap();
     assert_eq!(
-        contract_class,
-        get_contract_class(ERC20_CONTRACT_PATH).unwrap()
+        contract_class.as_ref(),
+        &get_contract_class(ERC20_CONTRACT_PATH).unwrap()
     );
 }
 
@@ -513,6 +548,7 @@ fn invoke_tx(calldata: Vec<Felt252>) -> InternalInvokeFunction {
         TEST_ACCOUNT_CONTRACT_ADDRESS.clone(),
         EXECUTE_ENTRY_POINT_SELECTOR.clone(),
         2,
+        TRANSACTION_VERSION.into(),
         calldata,
         vec![],
         StarknetChainId::TestNet.to_felt(),
@@ -546,7 +582,7 @@ fn expected_fee_transfer_info() -> CallInfo {
             ],
         }],
         storage_read_values: vec![
-            Felt252::zero(),
+            INITIAL_BALANCE.clone(),
             Felt252::zero(),
             Felt252::zero(),
             Felt252::zero(),
@@ -569,6 +605,11 @@ fn expected_fee_transfer_info() -> CallInfo {
                 119, 136, 76, 21, 186, 42, 176, 242, 36, 27, 8, 13, 236,
             ],
         ]),
+        storage_writes: expected_fee_transfer_storage_writes(
+            &TEST_ACCOUNT_CONTRACT_ADDRESS,
+            &Address(Felt252::from(4096)),
+            0,
+        ),
     }
 }
 
@@ -614,7 +655,7 @@ fn expected_declare_fee_transfer_info() -> CallInfo {
             ],
         )],
         storage_read_values: vec![
-            Felt252::zero(),
+            INITIAL_BALANCE.clone(),
             Felt252::zero(),
             Felt252::zero(),
             Felt252::zero(),
@@ -637,6 +678,11 @@ fn expected_declare_fee_transfer_info() -> CallInfo {
                 119, 136, 76, 21, 186, 42, 176, 242, 36, 27, 8, 13, 235,
             ],
         ]),
+        storage_writes: expected_fee_transfer_storage_writes(
+            &TEST_ACCOUNT_CONTRACT_ADDRESS,
+            &TEST_SEQUENCER_ADDRESS,
+            0,
+        ),
         ..Default::default()
     }
 }
@@ -669,7 +715,8 @@ fn test_declare_tx() {
         HashMap::from([
             ("range_check_builtin".to_string(), 57),
             ("pedersen_builtin".to_string(), 15),
-            ("l1_gas_usage".to_string(), 0),
+            // The nonce bump and the balance update of the fee transfer.
+            ("l1_gas_usage".to_string(), 3672),
         ]),
         Some(TransactionType::Declare),
     );
@@ -756,8 +803,9 @@ fn expected_transaction_execution_info() -> TransactionExecutionInfo {
         0,
         HashMap::from([
             ("pedersen_builtin".to_string(), 16),
-            ("range_check_builtin".to_string(), 72),
-            ("l1_gas_usage".to_string(), 0),
+            ("range_check_builtin".to_string(), 92),
+            // The nonce bump and the balance update of the fee transfer.
+            ("l1_gas_usage".to_string(), 3672),
         ]),
         Some(TransactionType::InvokeFunction),
     )
@@ -805,6 +853,142 @@ fn test_invoke_tx_state() {
     assert_eq!(*state, expected_final_state);
 }
 
+fn return_result_calldata() -> Vec<Felt252> {
+    let Address(test_contract_address) = TEST_CONTRACT_ADDRESS.clone();
+    vec![
+        test_contract_address, // CONTRACT_ADDRESS
+        Felt252::from_bytes_be(&calculate_sn_keccak(b"return_result")), // CONTRACT FUNCTION SELECTOR
+        Felt252::from(1),                                               // CONTRACT_CALLDATA LEN
+        Felt252::from(2),                                               // CONTRACT_CALLDATA
+    ]
+}
+
+#[test]
+fn test_simulate_invoke_tx() {
+    let (starknet_general_config, state) = &mut create_account_tx_test_state().unwrap();
+    let tx = Transaction::InvokeFunction(invoke_tx(return_result_calldata()));
+
+    let simulation = tx
+        .simulate(state, starknet_general_config, SimulationFlags::default())
+        .unwrap();
+
+    assert_eq!(
+        simulation.execution_info,
+        expected_transaction_execution_info()
+    );
+    // The l1_gas_usage plus the heaviest Cairo resource, the range checks: 92 * 0.16 gas.
+    assert_eq!(simulation.gas_consumed, 3687);
+    assert_eq!(simulation.gas_price, 0);
+    assert_eq!(simulation.overall_fee, 0);
+
+    // Nothing is committed.
+    assert!(state.cache().storage_writes().is_empty());
+    assert_eq!(
+        state.get_nonce_at(&TEST_ACCOUNT_CONTRACT_ADDRESS),
+        Ok(Felt252::zero())
+    );
+
+    let simulation = tx
+        .simulate(
+            state,
+            starknet_general_config,
+            SimulationFlags {
+                skip_validate: true,
+                skip_fee_charge: true,
+            },
+        )
+        .unwrap();
+    assert_eq!(simulation.execution_info.validate_info, None);
+    assert_eq!(simulation.execution_info.fee_transfer_info, None);
+    assert_eq!(
+        simulation.execution_info.call_info,
+        Some(expected_execute_call_info())
+    );
+}
+
+#[test]
+fn test_estimate_fee_above_max_fee() {
+    let (_, state) = &mut create_account_tx_test_state().unwrap();
+    let mut general_config = new_starknet_general_config_for_testing();
+    *general_config.starknet_os_config_mut() = StarknetOsConfig::new(
+        StarknetChainId::TestNet,
+        TEST_ERC20_CONTRACT_ADDRESS.clone(),
+        10,
+    );
+
+    // The max fee of the transaction is 2, execute() would fail.
+    let tx = invoke_tx(return_result_calldata());
+    assert_matches!(
+        tx.execute(state, &general_config),
+        Err(TransactionError::FeeError(_))
+    );
+
+    let estimation = Transaction::InvokeFunction(tx)
+        .estimate_fee(state, &general_config)
+        .unwrap();
+    assert_eq!(estimation.gas_consumed, 3687);
+    assert_eq!(estimation.gas_price, 10);
+    assert_eq!(estimation.overall_fee, 36870);
+    assert_eq!(estimation.execution_info.actual_fee, 36870);
+    assert_eq!(estimation.execution_info.fee_transfer_info, None);
+}
+
+#[test]
+fn test_reverted_invoke_above_max_fee_pays_max_fee() {
+    let (_, state) = &mut create_account_tx_test_state().unwrap();
+    let mut general_config = new_starknet_general_config_for_testing();
+    *general_config.starknet_os_config_mut() = StarknetOsConfig::new(
+        StarknetChainId::TestNet,
+        TEST_ERC20_CONTRACT_ADDRESS.clone(),
+        10,
+    );
+
+    // The account calls an entry point the test contract doesn't have. The resources used cost
+    // more than the max fee of 2.
+    let Address(test_contract_address) = TEST_CONTRACT_ADDRESS.clone();
+    let tx = invoke_tx(vec![
+        test_contract_address,
+        Felt252::from_bytes_be(&calculate_sn_keccak(b"none_function")),
+        Felt252::zero(),
+    ]);
+    let tx_info = tx.execute(state, &general_config).unwrap();
+
+    assert!(tx_info.is_reverted());
+    assert!(tx_info.fee_transfer_info.is_some());
+    assert_eq!(tx_info.actual_fee, 2);
+    assert_eq!(
+        state.get_nonce_at(&TEST_ACCOUNT_CONTRACT_ADDRESS),
+        Ok(Felt252::one())
+    );
+    validate_final_balances(
+        state,
+        &general_config,
+        &Felt252::from(2),
+        &TEST_ERC20_ACCOUNT_BALANCE_KEY.to_be_bytes(),
+    );
+}
+
+#[test]
+fn test_estimate_fee_of_query_invoke_tx() {
+    let (starknet_general_config, state) = &mut create_account_tx_test_state().unwrap();
+    let tx = InternalInvokeFunction::new(
+        TEST_ACCOUNT_CONTRACT_ADDRESS.clone(),
+        EXECUTE_ENTRY_POINT_SELECTOR.clone(),
+        2,
+        QUERY_VERSION_BASE.clone() + Felt252::from(TRANSACTION_VERSION),
+        return_result_calldata(),
+        vec![],
+        StarknetChainId::TestNet.to_felt(),
+        Some(Felt252::zero()),
+    )
+    .unwrap();
+
+    let estimation = Transaction::InvokeFunction(tx)
+        .estimate_fee(state, starknet_general_config)
+        .unwrap();
+    assert_eq!(estimation.gas_consumed, 3687);
+}
+
 #[test]
 fn test_deploy_account() {
     let (general_config, mut state) = create_account_tx_test_state().unwrap();
@@ -829,7 +1013,7 @@ fn test_deploy_account() {
                 .clone(),
             TEST_ERC20_DEPLOYED_ACCOUNT_BALANCE_KEY.to_be_bytes(),
         ),
-        ACTUAL_FEE.clone(),
+        INITIAL_BALANCE.clone(),
     );
 
     let (state_before, state_after) = expected_deploy_account_states();
@@ -886,7 +1070,7 @@ fn test_deploy_account() {
         // Entry **not** in blockifier.
         // Default::default(),
         [
-            ("l1_gas_usage", 3672),
+            ("l1_gas_usage", 4284),
             ("range_check_builtin", 74),
             ("pedersen_builtin", 23),
         ]
@@ -949,13 +1133,13 @@ fn expected_deploy_account_states() -> (
                     (
                         (Address(0x1001.into()),
                         felt_str!("1192211877881866289306604115402199097887041303917861778777990838480655617515").to_be_bytes()),
-                        Felt252::zero(),
+                        INITIAL_BALANCE.clone(),
                     ),
                         ]),
             HashMap::from([
-                (Felt252::new(0x110).to_be_bytes(), ContractClass::try_from(PathBuf::from(TEST_CONTRACT_PATH)).unwrap()),
-                (Felt252::new(0x111).to_be_bytes(), ContractClass::try_from(PathBuf::from(ACCOUNT_CONTRACT_PATH)).unwrap()),
-                (Felt252::new(0x1010).to_be_bytes(), ContractClass::try_from(PathBuf::from(ERC20_CONTRACT_PATH)).unwrap()),
+                (Felt252::new(0x110).to_be_bytes(), Arc::new(ContractClass::try_from(PathBuf::from(TEST_CONTRACT_PATH)).unwrap())),
+                (Felt252::new(0x111).to_be_bytes(), Arc::new(ContractClass::try_from(PathBuf::from(ACCOUNT_CONTRACT_PATH)).unwrap())),
+                (Felt252::new(0x1010).to_be_bytes(), Arc::new(ContractClass::try_from(PathBuf::from(ERC20_CONTRACT_PATH)).unwrap())),
             ]),
         ),
         Some(ContractClassCache::new()),
@@ -968,7 +1152,7 @@ fn expected_deploy_account_states() -> (
             )
             .to_be_bytes(),
         ),
-        0.into(),
+        INITIAL_BALANCE.clone(),
     );
 
     let mut state_after = state_before.clone();
@@ -1048,7 +1232,7 @@ fn expected_deploy_account_states() -> (
             )
             .to_be_bytes(),
         ),
-        Felt252::zero(),
+        INITIAL_BALANCE.clone(),
     );
     state_after.cache_mut().storage_writes_mut().insert(
         (
@@ -1129,7 +1313,7 @@ fn test_state_for_declare_tx() {
                 TEST_ERC20_CONTRACT_ADDRESS.clone(),
                 TEST_ERC20_ACCOUNT_BALANCE_KEY.to_be_bytes()
             ),
-            Felt252::zero()
+            INITIAL_BALANCE.clone()
         ),]),
     );
 
@@ -1138,15 +1322,15 @@ fn test_state_for_declare_tx() {
         &mut HashMap::from([
             (
                 TEST_ERC20_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(ERC20_CONTRACT_PATH).unwrap()
+                Arc::new(get_contract_class(ERC20_CONTRACT_PATH).unwrap())
             ),
             (
                 TEST_CLASS_HASH.to_be_bytes(),
-                get_contract_class(TEST_CONTRACT_PATH).unwrap()
+                Arc::new(get_contract_class(TEST_CONTRACT_PATH).unwrap())
             ),
             (
                 TEST_ACCOUNT_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap()
+                Arc::new(get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap())
             ),
         ])
     );
@@ -1196,7 +1380,7 @@ fn test_state_for_declare_tx() {
                     TEST_ERC20_CONTRACT_ADDRESS.clone(),
                     TEST_ERC20_ACCOUNT_BALANCE_KEY.to_be_bytes()
                     ),
-                    0.into()
+                    INITIAL_BALANCE.clone()
                 )
             ]),
             HashMap::new(),
@@ -1231,7 +1415,7 @@ fn test_state_for_declare_tx() {
                     TEST_ERC20_CONTRACT_ADDRESS.clone(),
                     TEST_ERC20_ACCOUNT_BALANCE_KEY.to_be_bytes()
                     ),
-                    0.into()
+                    INITIAL_BALANCE.clone()
                 ),
             ]),
         )
@@ -1243,15 +1427,15 @@ fn test_state_for_declare_tx() {
         &Some(HashMap::from([
             (
                 TEST_EMPTY_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(TEST_EMPTY_CONTRACT_PATH).unwrap()
+                Arc::new(get_contract_class(TEST_EMPTY_CONTRACT_PATH).unwrap())
             ),
             (
                 TEST_ERC20_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(ERC20_CONTRACT_PATH).unwrap()
+                Arc::new(get_contract_class(ERC20_CONTRACT_PATH).unwrap())
             ),
             (
                 TEST_ACCOUNT_CONTRACT_CLASS_HASH.to_be_bytes(),
-                get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap()
+                Arc::new(get_contract_class(ACCOUNT_CONTRACT_PATH).unwrap())
             ),
         ]))
     );
@@ -1276,12 +1460,14 @@ fn test_invoke_tx_wrong_call_data() {
     // Assert error
     assert_matches!(
         result,
-        Err(TransactionError::CairoRunner(CairoRunError::VmException(
-            VmException {
-                inner_exc: VirtualMachineError::DiffAssertValues(..),
-                ..
-            }
-        )))
+        Err(TransactionError::EntryPointFailed(stack))
+            if matches!(
+                    *stack.error,
+                    TransactionError::CairoRunner(CairoRunError::VmException(VmException {
+                        inner_exc: VirtualMachineError::DiffAssertValues(..),
+                        ..
+                    }))
+                )
     );
 }
 
@@ -1296,6 +1482,7 @@ fn test_invoke_tx_wrong_entrypoint() {
         // Entrypoiont that doesnt exits in the contract
         Felt252::from_bytes_be(&calculate_sn_keccak(b"none_function")),
         1,
+        TRANSACTION_VERSION.into(),
         vec![
             test_contract_address, // CONTRACT_ADDRESS
             Felt252::from_bytes_be(&calculate_sn_keccak(b"return_result")), // CONTRACT FUNCTION SELECTOR
@@ -1309,10 +1496,42 @@ fn test_invoke_tx_wrong_entrypoint() {
     .unwrap();
 
     // Execute transaction
-    let result = invoke_tx.execute(state, starknet_general_config);
+    let result = invoke_tx.execute(state, starknet_general_config).unwrap();
 
-    // Assert error
-    assert_matches!(result, Err(TransactionError::EntryPointNotFound));
+    // The execution fails, so the transaction is reverted
+    assert_eq!(
+        result.revert_error,
+        Some(TransactionError::EntryPointNotFound.to_string())
+    );
+    assert_eq!(result.call_info, None);
+}
+
+#[test]
+fn test_invoke_tx_insufficient_balance() {
+    let (starknet_general_config, state) = &mut create_account_tx_test_state().unwrap();
+    let invoke_tx = InternalInvokeFunction::new(
+        TEST_ACCOUNT_CONTRACT_ADDRESS.clone(),
+        EXECUTE_ENTRY_POINT_SELECTOR.clone(),
+        3,
+        TRANSACTION_VERSION.into(),
+        return_result_calldata(),
+        vec![],
+        StarknetChainId::TestNet.to_felt(),
+        Some(Felt252::zero()),
+    )
+    .unwrap();
+
+    assert_matches!(
+        invoke_tx.execute(state, starknet_general_config),
+        Err(TransactionError::InsufficientAccountBalance { balance, max_fee: 3 })
+            if balance == INITIAL_BALANCE.to_biguint()
+    );
+    // The transaction is rejected before running.
+    assert!(state.cache().storage_writes().is_empty());
+    assert_eq!(
+        state.get_nonce_at(&TEST_ACCOUNT_CONTRACT_ADDRESS),
+        Ok(Felt252::zero())
+    );
 }
 
 #[test]
@@ -1333,6 +1552,20 @@ fn test_deploy_undeclared_account() {
     )
     .unwrap();
 
+    // Fund the account, so the transaction gets past the balance check.
+    let (balance_key, _) =
+        get_erc20_balance_var_addresses(deploy_account_tx.contract_address()).unwrap();
+    state.set_storage_at(
+        &(
+            general_config
+                .starknet_os_config()
+                .fee_token_address()
+                .clone(),
+            balance_key,
+        ),
+        INITIAL_BALANCE.clone(),
+    );
+
     // Check not_deployed_class_hash
     assert!(state.get_contract_class(&not_deployed_class_hash).is_err());
 
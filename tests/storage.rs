@@ -19,6 +19,7 @@ use starknet_rs::{
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
+    sync::Arc,
 };
 
 #[test]
@@ -53,7 +54,7 @@ fn integration_storage_test() {
     let storage_entry = (address.clone(), [90; 32]);
     let storage_value = Felt252::new(10902);
 
-    contract_class_cache.insert(class_hash, contract_class);
+    contract_class_cache.insert(class_hash, Arc::new(contract_class));
     let mut state_reader = InMemoryStateReader::default();
     state_reader
         .address_to_class_hash_mut()
@@ -121,6 +122,7 @@ fn integration_storage_test() {
         class_hash: Some(class_hash),
         storage_read_values: vec![42.into()],
         accessed_storage_keys: expected_accessed_storage_keys,
+        storage_writes: HashMap::from([(expected_key, 42.into())]),
         ..Default::default()
     };
 
@@ -25,7 +25,12 @@ use starknet_rs::{
     services::api::contract_class::{ContractClass, EntryPointType},
     utils::{calculate_sn_keccak, Address, ClassHash},
 };
-use std::{collections::HashSet, iter::empty, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::empty,
+    path::Path,
+    sync::Arc,
+};
 
 #[allow(clippy::too_many_arguments)]
 fn test_contract<'a>(
@@ -40,6 +45,7 @@ fn test_contract<'a>(
     l2_to_l1_messages: impl Into<Vec<OrderedL2ToL1Message>>,
     storage_read_values: impl Into<Vec<Felt252>>,
     accessed_storage_keys: impl Iterator<Item = ClassHash>,
+    storage_writes: impl Iterator<Item = (ClassHash, Felt252)>,
     extra_contracts: impl Iterator<
         Item = (
             ClassHash,
@@ -75,15 +81,17 @@ fn test_contract<'a>(
         .insert(contract_address.clone(), nonce);
     state_reader
         .class_hash_to_contract_class_mut()
-        .insert(class_hash, contract_class);
+        .insert(class_hash, Arc::new(contract_class));
 
     let mut storage_entries = Vec::new();
     let contract_class_cache = {
         let mut contract_class_cache = ContractClassCache::new();
 
         for (class_hash, contract_path, contract_address) in extra_contracts {
-            let contract_class = ContractClass::try_from(contract_path.to_path_buf())
-                .expect("Could not load extra contract from JSON");
+            let contract_class = Arc::new(
+                ContractClass::try_from(contract_path.to_path_buf())
+                    .expect("Could not load extra contract from JSON"),
+            );
 
             contract_class_cache.insert(class_hash, contract_class.clone());
 
@@ -147,6 +155,7 @@ fn test_contract<'a>(
             l2_to_l1_messages: l2_to_l1_messages.into(),
             storage_read_values: storage_read_values.into(),
             accessed_storage_keys: accessed_storage_keys.collect(),
+            storage_writes: storage_writes.collect(),
             calldata,
             retdata: return_data.into(),
             internal_calls: internal_calls.into(),
@@ -169,6 +178,7 @@ fn call_contract_syscall() {
         [],
         [10.into()],
         [calculate_sn_keccak("lib_state".as_bytes())].into_iter(),
+        [(calculate_sn_keccak("lib_state".as_bytes()), 10.into())].into_iter(),
         [(
             [2u8; 32],
             Path::new("starknet_programs/syscalls-lib.json"),
@@ -206,6 +216,10 @@ fn call_contract_syscall() {
                 ]]
                 .into_iter()
                 .collect(),
+                storage_writes: HashMap::from([(
+                    calculate_sn_keccak("lib_state".as_bytes()),
+                    11.into(),
+                )]),
                 ..Default::default()
             },
             CallInfo {
@@ -270,6 +284,7 @@ fn emit_event_syscall() {
         [],
         empty(),
         empty(),
+        empty(),
         [],
         [],
         [],
@@ -295,6 +310,7 @@ fn get_block_number_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [block_number.into()],
@@ -325,6 +341,7 @@ fn get_block_timestamp_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [block_timestamp.into()],
@@ -352,6 +369,7 @@ fn get_caller_address_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [caller_address],
@@ -379,6 +397,7 @@ fn get_contract_address_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [contract_address],
@@ -409,6 +428,7 @@ fn get_sequencer_address_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [sequencer_address],
@@ -453,6 +473,7 @@ fn get_tx_info_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [
@@ -555,6 +576,7 @@ fn get_tx_signature_syscall() {
             [],
             empty(),
             empty(),
+            empty(),
             [],
             [],
             [
@@ -586,6 +608,7 @@ fn library_call_syscall() {
         [],
         [11.into()],
         [calculate_sn_keccak("lib_state".as_bytes())].into_iter(),
+        [(calculate_sn_keccak("lib_state".as_bytes()), 10.into())].into_iter(),
         [(
             [2; 32],
             Path::new("starknet_programs/syscalls-lib.json"),
@@ -623,6 +646,10 @@ fn library_call_syscall() {
                 ]]
                 .into_iter()
                 .collect(),
+                storage_writes: HashMap::from([(
+                    calculate_sn_keccak("lib_state".as_bytes()),
+                    11.into(),
+                )]),
                 ..Default::default()
             },
             CallInfo {
@@ -657,6 +684,7 @@ fn library_call_l1_handler_syscall() {
         [],
         [5.into()],
         [calculate_sn_keccak("lib_state".as_bytes())].into_iter(),
+        empty(),
         [(
             [2; 32],
             Path::new("starknet_programs/syscalls-lib.json"),
@@ -680,6 +708,10 @@ fn library_call_l1_handler_syscall() {
             ]]
             .into_iter()
             .collect(),
+            storage_writes: HashMap::from([(
+                calculate_sn_keccak("lib_state".as_bytes()),
+                5.into(),
+            )]),
             ..Default::default()
         }],
         [],
@@ -717,6 +749,7 @@ fn send_message_to_l1_syscall() {
         [],
         empty(),
         empty(),
+        empty(),
         [],
         [],
         [],
@@ -741,6 +774,7 @@ fn deploy_syscall() {
         [],
         [],
         [].into_iter(),
+        empty(),
         [(
             deploy_class_hash,
             Path::new("starknet_programs/storage.json"),
@@ -782,6 +816,7 @@ fn deploy_with_constructor_syscall() {
         [],
         [],
         [].into_iter(),
+        empty(),
         [(
             deploy_class_hash,
             Path::new("starknet_programs/storage_var_and_constructor.json"),
@@ -826,6 +861,7 @@ fn test_deploy_and_call_contract_syscall() {
         [],
         [],
         [].into_iter(),
+        empty(),
         [(
             deploy_class_hash,
             Path::new("starknet_programs/storage_var_and_constructor.json"),
@@ -879,6 +915,7 @@ fn test_deploy_and_call_contract_syscall() {
             retdata: vec![],
             storage_read_values: vec![],
             accessed_storage_keys: HashSet::from([constant_storage_key]),
+            storage_writes: HashMap::from([(constant_storage_key, new_constant.clone())]),
             ..Default::default()
         },
         // Invoke storage_var_and_constructor.cairo get_constant function
@@ -19,7 +19,7 @@ use starknet_rs::{
     services::api::contract_class::{ContractClass, EntryPointType},
     utils::{calculate_sn_keccak, Address, ClassHash},
 };
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 use assert_matches::assert_matches;
 
@@ -66,15 +66,17 @@ fn test_contract<'a>(
         .insert(contract_address.clone(), nonce);
     state_reader
         .class_hash_to_contract_class_mut()
-        .insert(class_hash, contract_class);
+        .insert(class_hash, Arc::new(contract_class));
 
     let mut storage_entries = Vec::new();
     let contract_class_cache = {
         let mut contract_class_cache = ContractClassCache::new();
 
         for (class_hash, contract_path, contract_address) in extra_contracts {
-            let contract_class = ContractClass::try_from(contract_path.to_path_buf())
-                .expect("Could not load extra contract from JSON");
+            let contract_class = Arc::new(
+                ContractClass::try_from(contract_path.to_path_buf())
+                    .expect("Could not load extra contract from JSON"),
+            );
 
             contract_class_cache.insert(class_hash, contract_class.clone());
 